@@ -1,8 +1,10 @@
 use std::{env, path::PathBuf};
 
 fn main() {
+    println!("cargo:rerun-if-changed=tests/test_bstats.cpp");
     println!("cargo:rerun-if-changed=tests/test_connection_timeout.cpp");
     println!("cargo:rerun-if-changed=tests/test_enforced_encryption.cpp");
+    println!("cargo:rerun-if-changed=tests/test_epoll.cpp");
     println!("cargo:rerun-if-changed=tests/test_file_transmission.cpp");
     println!("cargo:rerun-if-changed=tests/test_ipv6.cpp");
     println!("cargo:rerun-if-changed=tests/test_listen_callback.cpp");
@@ -41,8 +43,10 @@ fn main() {
         .define("MinInputBWRuntime", "DISABLED_MinInputBWRuntime")
         // files
         .define("SRT_ENABLE_ENCRYPTION", None)
+        .file("tests/test_bstats.cpp")
         .file("tests/test_connection_timeout.cpp")
         .file("tests/test_enforced_encryption.cpp")
+        .file("tests/test_epoll.cpp")
         .file("tests/test_file_transmission.cpp")
         .file("tests/test_ipv6.cpp")
         .file("tests/test_listen_callback.cpp")
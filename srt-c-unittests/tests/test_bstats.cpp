@@ -0,0 +1,161 @@
+/*
+ * SRT - Secure, Reliable, Transport
+ * Copyright (c) 2019 Haivision Systems Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ *
+ * Written by:
+ *             Haivision Systems Inc.
+ */
+
+#include <gtest/gtest.h>
+
+#include <chrono>
+#include <future>
+#include <thread>
+
+#include "srtrs.h"
+
+#ifdef __linux__
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#endif
+
+using namespace std;
+
+class TestBStats : public ::testing::Test {
+ protected:
+  TestBStats() {
+    // initialization code here
+  }
+
+  ~TestBStats() {
+    // cleanup any pending stuff, but no exceptions allowed
+  }
+
+ public:
+  SRTSOCKET EstablishConnection() {
+    sockaddr* psa = (sockaddr*)&m_sa;
+    EXPECT_NE(srt_bind(m_listen_sock, psa, sizeof m_sa), SRT_ERROR);
+    EXPECT_NE(srt_listen(m_listen_sock, 1), SRT_ERROR);
+
+    auto accept_async = [](SRTSOCKET listen_sock) {
+      sockaddr_in client_address;
+      int length = sizeof(sockaddr_in);
+      return srt_accept(listen_sock, (sockaddr*)&client_address, &length);
+    };
+    auto accept_res = async(launch::async, accept_async, m_listen_sock);
+
+    EXPECT_EQ(srt_connect(m_caller_sock, psa, sizeof m_sa), SRT_SUCCESS);
+
+    const SRTSOCKET accepted_sock = accept_res.get();
+    EXPECT_NE(accepted_sock, SRT_INVALID_SOCK);
+
+    return accepted_sock;
+  }
+
+  void SendMessages(int count) {
+    const char message[] = "message";
+    for (int i = 0; i < count; ++i) {
+      ASSERT_EQ(srt_sendmsg(m_caller_sock, message, sizeof message, -1, 1),
+                (int)sizeof message);
+    }
+    // the statistics are updated once per second
+    this_thread::sleep_for(chrono::milliseconds(1500));
+  }
+
+ protected:
+  // SetUp() is run immediately before a test starts.
+  void SetUp() override {
+    ASSERT_GE(srt_startup(), 0);
+    const int yes = 1;
+
+    memset(&m_sa, 0, sizeof m_sa);
+    m_sa.sin_family = AF_INET;
+    m_sa.sin_port = htons(5220);
+    ASSERT_EQ(inet_pton(AF_INET, "127.0.0.1", &m_sa.sin_addr), 1);
+
+    m_caller_sock = srt_create_socket();
+    ASSERT_NE(m_caller_sock, SRT_INVALID_SOCK);
+    ASSERT_EQ(srt_setsockopt(m_caller_sock, 0, SRTO_RCVSYN, &yes, sizeof yes),
+              SRT_SUCCESS);
+
+    m_listen_sock = srt_create_socket();
+    ASSERT_NE(m_listen_sock, SRT_INVALID_SOCK);
+    ASSERT_EQ(srt_setsockopt(m_listen_sock, 0, SRTO_RCVSYN, &yes, sizeof yes),
+              SRT_SUCCESS);
+  }
+
+  void TearDown() override {
+    // Code here will be called just after the test completes.
+    // OK to throw exceptions from here if needed.
+    ASSERT_NE(srt_close(m_caller_sock), SRT_ERROR);
+    ASSERT_NE(srt_close(m_listen_sock), SRT_ERROR);
+    srt_cleanup();
+  }
+
+ protected:
+  sockaddr_in m_sa;
+  SRTSOCKET m_caller_sock = SRT_INVALID_SOCK;
+  SRTSOCKET m_listen_sock = SRT_INVALID_SOCK;
+};
+
+TEST_F(TestBStats, NotConnected) {
+  SRT_TRACEBSTATS stats;
+  EXPECT_EQ(srt_bstats(m_caller_sock, &stats, 0), SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_ENOCONN);
+}
+
+// Clearing the statistics starts a new interval for the local measurements,
+// while the totals keep growing. The unique counts are checked exactly, the
+// others also include retransmissions.
+TEST_F(TestBStats, Clear) {
+  const SRTSOCKET accepted_sock = EstablishConnection();
+  SRT_TRACEBSTATS stats;
+
+  SendMessages(10);
+  ASSERT_EQ(srt_bistats(m_caller_sock, &stats, 1, 1), SRT_SUCCESS);
+  EXPECT_EQ(stats.pktSentUnique, 10);
+  EXPECT_EQ(stats.pktSentUniqueTotal, 10);
+  EXPECT_GE(stats.pktSent, 10);
+  EXPECT_EQ(stats.pktSent, stats.pktSentTotal);
+  EXPECT_EQ(stats.byteSent, stats.byteSentTotal);
+  const int64_t sent_first = stats.pktSentTotal;
+  const uint64_t bytes_first = stats.byteSentTotal;
+
+  ASSERT_EQ(srt_bstats(accepted_sock, &stats, 1), SRT_SUCCESS);
+  EXPECT_EQ(stats.pktRecvUnique, 10);
+  EXPECT_EQ(stats.pktRecvUniqueTotal, 10);
+
+  SendMessages(5);
+  ASSERT_EQ(srt_bistats(m_caller_sock, &stats, 1, 1), SRT_SUCCESS);
+  EXPECT_EQ(stats.pktSentUnique, 5);
+  EXPECT_EQ(stats.pktSentUniqueTotal, 15);
+  EXPECT_GE(stats.pktSent, 5);
+  EXPECT_EQ(stats.pktSent, stats.pktSentTotal - sent_first);
+  EXPECT_EQ(stats.byteSent, stats.byteSentTotal - bytes_first);
+  const int64_t sent_second = stats.pktSentTotal;
+
+  ASSERT_EQ(srt_bstats(accepted_sock, &stats, 1), SRT_SUCCESS);
+  EXPECT_EQ(stats.pktRecvUnique, 5);
+  EXPECT_EQ(stats.pktRecvUniqueTotal, 15);
+
+  // nothing was sent since the last clear
+  ASSERT_EQ(srt_bistats(m_caller_sock, &stats, 1, 1), SRT_SUCCESS);
+  EXPECT_EQ(stats.pktSentUnique, 0);
+  EXPECT_EQ(stats.byteSentUnique, 0u);
+  EXPECT_EQ(stats.pktSentUniqueTotal, 15);
+  EXPECT_GE(stats.pktSentTotal, sent_second);
+
+  // without clear, the interval keeps going
+  SendMessages(3);
+  for (int i = 0; i < 2; ++i) {
+    ASSERT_EQ(srt_bistats(m_caller_sock, &stats, 0, 1), SRT_SUCCESS);
+    EXPECT_EQ(stats.pktSentUnique, 3);
+    EXPECT_EQ(stats.pktSentUniqueTotal, 18);
+  }
+
+  ASSERT_NE(srt_close(accepted_sock), SRT_ERROR);
+}
@@ -0,0 +1,332 @@
+/*
+ * SRT - Secure, Reliable, Transport
+ * Copyright (c) 2019 Haivision Systems Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ *
+ * Written by:
+ *             Haivision Systems Inc.
+ */
+
+#include <gtest/gtest.h>
+
+#include <chrono>
+#include <future>
+#include <thread>
+
+#include "srtrs.h"
+
+#ifdef __linux__
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <sys/socket.h>
+#include <unistd.h>
+#endif
+
+using namespace std;
+
+class TestEPoll : public ::testing::Test {
+ protected:
+  TestEPoll() {
+    // initialization code here
+  }
+
+  ~TestEPoll() {
+    // cleanup any pending stuff, but no exceptions allowed
+  }
+
+ public:
+  SRTSOCKET EstablishConnection() {
+    sockaddr* psa = (sockaddr*)&m_sa;
+    EXPECT_NE(srt_bind(m_listen_sock, psa, sizeof m_sa), SRT_ERROR);
+    EXPECT_NE(srt_listen(m_listen_sock, 1), SRT_ERROR);
+
+    auto accept_async = [](SRTSOCKET listen_sock) {
+      sockaddr_in client_address;
+      int length = sizeof(sockaddr_in);
+      return srt_accept(listen_sock, (sockaddr*)&client_address, &length);
+    };
+    auto accept_res = async(launch::async, accept_async, m_listen_sock);
+
+    EXPECT_EQ(srt_connect(m_caller_sock, psa, sizeof m_sa), SRT_SUCCESS);
+
+    const SRTSOCKET accepted_sock = accept_res.get();
+    EXPECT_NE(accepted_sock, SRT_INVALID_SOCK);
+
+    return accepted_sock;
+  }
+
+ protected:
+  // SetUp() is run immediately before a test starts.
+  void SetUp() override {
+    ASSERT_GE(srt_startup(), 0);
+    const int yes = 1;
+
+    memset(&m_sa, 0, sizeof m_sa);
+    m_sa.sin_family = AF_INET;
+    m_sa.sin_port = htons(5210);
+    ASSERT_EQ(inet_pton(AF_INET, "127.0.0.1", &m_sa.sin_addr), 1);
+
+    m_caller_sock = srt_create_socket();
+    ASSERT_NE(m_caller_sock, SRT_INVALID_SOCK);
+    ASSERT_EQ(srt_setsockopt(m_caller_sock, 0, SRTO_RCVSYN, &yes, sizeof yes),
+              SRT_SUCCESS);
+
+    m_listen_sock = srt_create_socket();
+    ASSERT_NE(m_listen_sock, SRT_INVALID_SOCK);
+    ASSERT_EQ(srt_setsockopt(m_listen_sock, 0, SRTO_RCVSYN, &yes, sizeof yes),
+              SRT_SUCCESS);
+
+    m_pollid = srt_epoll_create();
+    ASSERT_GT(m_pollid, 0);
+  }
+
+  void TearDown() override {
+    // Code here will be called just after the test completes.
+    // Some tests close the sockets themselves, so failures are expected
+    srt_close(m_caller_sock);
+    srt_close(m_listen_sock);
+    EXPECT_EQ(srt_epoll_release(m_pollid), SRT_SUCCESS);
+    srt_cleanup();
+  }
+
+ protected:
+  sockaddr_in m_sa;
+  SRTSOCKET m_caller_sock = SRT_INVALID_SOCK;
+  SRTSOCKET m_listen_sock = SRT_INVALID_SOCK;
+
+  int m_pollid = 0;
+};
+
+TEST_F(TestEPoll, ReleaseTwice) {
+  const int eid = srt_epoll_create();
+  ASSERT_GT(eid, 0);
+  EXPECT_NE(eid, m_pollid);
+
+  EXPECT_EQ(srt_epoll_release(eid), SRT_SUCCESS);
+  EXPECT_EQ(srt_epoll_release(eid), SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVPOLLID);
+}
+
+// Waiting on an empty container fails, unless SRT_EPOLL_ENABLE_EMPTY is set
+TEST_F(TestEPoll, WaitEmpty) {
+  SRTSOCKET read[2];
+  int rlen = 2;
+  SRT_EPOLL_EVENT events[2];
+
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 100, NULL, NULL,
+                           NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EPOLLEMPTY);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EPOLLEMPTY);
+
+  EXPECT_EQ(srt_epoll_set(m_pollid, SRT_EPOLL_ENABLE_EMPTY), 0);
+  EXPECT_EQ(srt_epoll_set(m_pollid, -1), SRT_EPOLL_ENABLE_EMPTY);
+
+  rlen = 2;
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 100, NULL, NULL,
+                           NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_ETIMEOUT);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+}
+
+// With SRT_EPOLL_ENABLE_OUTPUTCHECK, waiting without any room for the ready
+// sockets is an error
+TEST_F(TestEPoll, OutputCheck) {
+  const int epoll_in = SRT_EPOLL_IN;
+  ASSERT_EQ(srt_epoll_add_usock(m_pollid, m_caller_sock, &epoll_in),
+            SRT_SUCCESS);
+  EXPECT_EQ(srt_epoll_set(m_pollid, SRT_EPOLL_ENABLE_OUTPUTCHECK), 0);
+
+  EXPECT_EQ(srt_epoll_wait(m_pollid, NULL, NULL, NULL, NULL, 100, NULL, NULL,
+                           NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVPARAM);
+
+  SRTSOCKET read[2];
+  int rlen = 0;
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 100, NULL, NULL,
+                           NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVPARAM);
+
+  // the unconnected socket is never ready
+  rlen = 2;
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 100, NULL, NULL,
+                           NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_ETIMEOUT);
+}
+
+// Level triggered subscriptions report the socket as long as it is ready
+TEST_F(TestEPoll, LevelTriggered) {
+  const SRTSOCKET accepted_sock = EstablishConnection();
+  const int epoll_in = SRT_EPOLL_IN;
+  ASSERT_EQ(srt_epoll_add_usock(m_pollid, accepted_sock, &epoll_in),
+            SRT_SUCCESS);
+
+  SRT_EPOLL_EVENT events[2];
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+
+  const char message[] = "message";
+  ASSERT_EQ(srt_sendmsg(m_caller_sock, message, sizeof message, -1, 1),
+            (int)sizeof message);
+
+  for (int i = 0; i < 2; ++i) {
+    ASSERT_EQ(srt_epoll_uwait(m_pollid, events, 2, 1000), 1);
+    EXPECT_EQ(events[0].fd, accepted_sock);
+    EXPECT_EQ(events[0].events, SRT_EPOLL_IN);
+  }
+
+  char buffer[1316];
+  EXPECT_EQ(srt_recvmsg(accepted_sock, buffer, sizeof buffer),
+            (int)sizeof message);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+
+  ASSERT_NE(srt_close(accepted_sock), SRT_ERROR);
+}
+
+// Edge triggered subscriptions report the socket once, and again after a
+// receive consumed the data, even if it was ready all along
+TEST_F(TestEPoll, EdgeTriggered) {
+  const SRTSOCKET accepted_sock = EstablishConnection();
+  const int epoll_in_et = SRT_EPOLL_IN | SRT_EPOLL_ET;
+  ASSERT_EQ(srt_epoll_add_usock(m_pollid, accepted_sock, &epoll_in_et),
+            SRT_SUCCESS);
+
+  const char message[] = "message";
+  ASSERT_EQ(srt_sendmsg(m_caller_sock, message, sizeof message, -1, 1),
+            (int)sizeof message);
+  ASSERT_EQ(srt_sendmsg(m_caller_sock, message, sizeof message, -1, 1),
+            (int)sizeof message);
+  // both messages are past their latency and ready to be received
+  this_thread::sleep_for(chrono::milliseconds(500));
+
+  SRT_EPOLL_EVENT events[2];
+  ASSERT_EQ(srt_epoll_uwait(m_pollid, events, 2, 1000), 1);
+  EXPECT_EQ(events[0].fd, accepted_sock);
+  EXPECT_EQ(events[0].events, SRT_EPOLL_IN);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0)
+      << "An edge triggered event is only reported once";
+
+  char buffer[1316];
+  EXPECT_EQ(srt_recvmsg(accepted_sock, buffer, sizeof buffer),
+            (int)sizeof message);
+  ASSERT_EQ(srt_epoll_uwait(m_pollid, events, 2, 1000), 1)
+      << "Receiving rearms the event for the second message";
+  EXPECT_EQ(events[0].fd, accepted_sock);
+  EXPECT_EQ(events[0].events, SRT_EPOLL_IN);
+
+  EXPECT_EQ(srt_recvmsg(accepted_sock, buffer, sizeof buffer),
+            (int)sizeof message);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+
+  ASSERT_NE(srt_close(accepted_sock), SRT_ERROR);
+}
+
+// System sockets are polled alongside the SRT sockets by srt_epoll_wait
+TEST_F(TestEPoll, SystemSocket) {
+  const int udp_sock = socket(AF_INET, SOCK_DGRAM, IPPROTO_UDP);
+  ASSERT_NE(udp_sock, -1);
+  sockaddr_in sa = m_sa;
+  sa.sin_port = 0;
+  ASSERT_EQ(::bind(udp_sock, (sockaddr*)&sa, sizeof sa), 0);
+  socklen_t sa_len = sizeof sa;
+  ASSERT_EQ(getsockname(udp_sock, (sockaddr*)&sa, &sa_len), 0);
+
+  const int epoll_in = SRT_EPOLL_IN;
+  ASSERT_EQ(srt_epoll_add_ssock(m_pollid, udp_sock, &epoll_in), SRT_SUCCESS);
+
+  SRTSOCKET read[2];
+  int rlen = 2;
+  int lread[2];
+  int lrlen = 2;
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 100, lread,
+                           &lrlen, NULL, NULL),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_ETIMEOUT);
+
+  const char datagram[] = "datagram";
+  ASSERT_EQ(sendto(udp_sock, datagram, sizeof datagram, 0, (sockaddr*)&sa,
+                   sizeof sa),
+            (ssize_t)sizeof datagram);
+
+  rlen = 2;
+  lrlen = 2;
+  EXPECT_EQ(srt_epoll_wait(m_pollid, read, &rlen, NULL, NULL, 1000, lread,
+                           &lrlen, NULL, NULL),
+            1);
+  EXPECT_EQ(rlen, 0);
+  EXPECT_EQ(lrlen, 1);
+  EXPECT_EQ(lread[0], udp_sock);
+
+  SRT_EPOLL_EVENT events[2];
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), SRT_ERROR)
+      << "srt_epoll_uwait does not support system sockets";
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVOP);
+
+  EXPECT_EQ(srt_epoll_remove_ssock(m_pollid, udp_sock), SRT_SUCCESS);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EPOLLEMPTY);
+
+  close(udp_sock);
+}
+
+// Closing a socket removes it from the containers it was added to
+TEST_F(TestEPoll, RemoveClosedSocket) {
+  const int epoll_in = SRT_EPOLL_IN;
+  ASSERT_EQ(srt_epoll_add_usock(m_pollid, m_caller_sock, &epoll_in),
+            SRT_SUCCESS);
+  SRT_EPOLL_EVENT events[2];
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+
+  ASSERT_NE(srt_close(m_caller_sock), SRT_ERROR);
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EPOLLEMPTY);
+
+  EXPECT_EQ(srt_epoll_add_usock(m_pollid, m_caller_sock, &epoll_in),
+            SRT_ERROR);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVSOCK);
+}
+
+// A nonblocking listener can be polled before a caller arrives, accepting
+// without a pending connection does not stop it from accepting later
+TEST_F(TestEPoll, NonblockingAccept) {
+  const int no = 0;
+  ASSERT_EQ(srt_setsockopt(m_listen_sock, 0, SRTO_RCVSYN, &no, sizeof no),
+            SRT_SUCCESS);
+  sockaddr* psa = (sockaddr*)&m_sa;
+  ASSERT_NE(srt_bind(m_listen_sock, psa, sizeof m_sa), SRT_ERROR);
+  ASSERT_NE(srt_listen(m_listen_sock, 1), SRT_ERROR);
+
+  const int epoll_in = SRT_EPOLL_IN;
+  ASSERT_EQ(srt_epoll_add_usock(m_pollid, m_listen_sock, &epoll_in),
+            SRT_SUCCESS);
+
+  SRT_EPOLL_EVENT events[2];
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+  EXPECT_EQ(srt_accept(m_listen_sock, NULL, NULL), SRT_INVALID_SOCK);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EASYNCRCV);
+
+  auto connect_res = async(launch::async, [this, psa]() {
+    return srt_connect(m_caller_sock, psa, sizeof m_sa);
+  });
+
+  ASSERT_EQ(srt_epoll_uwait(m_pollid, events, 2, 3000), 1);
+  EXPECT_EQ(events[0].fd, m_listen_sock);
+  EXPECT_EQ(events[0].events, SRT_EPOLL_IN);
+
+  const SRTSOCKET accepted_sock = srt_accept(m_listen_sock, NULL, NULL);
+  EXPECT_NE(accepted_sock, SRT_INVALID_SOCK);
+  EXPECT_EQ(connect_res.get(), SRT_SUCCESS);
+
+  EXPECT_EQ(srt_epoll_uwait(m_pollid, events, 2, 100), 0);
+  EXPECT_EQ(srt_accept(m_listen_sock, NULL, NULL), SRT_INVALID_SOCK);
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EASYNCRCV);
+
+  ASSERT_NE(srt_close(accepted_sock), SRT_ERROR);
+}
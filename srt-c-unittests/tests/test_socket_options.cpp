@@ -1208,3 +1208,124 @@ TEST_F(TestSocketOptions, StreamIDLenListener) {
 
   ASSERT_NE(srt_close(accepted_sock), SRT_ERROR);
 }
+
+// Setting an out of range value fails and keeps the previous value
+template <class ValueType>
+void CheckRejectedValue(SRTSOCKET sock, SRT_SOCKOPT optid, const char* optname,
+                        ValueType value) {
+  ValueType before;
+  int optlen = (int)(sizeof before);
+  ASSERT_EQ(srt_getsockflag(sock, optid, &before, &optlen), SRT_SUCCESS)
+      << "Getting " << optname << " failed";
+
+  EXPECT_EQ(srt_setsockflag(sock, optid, &value, sizeof value), SRT_ERROR)
+      << "Setting " << optname << " to " << value << " must fail";
+  EXPECT_EQ(srt_getlasterror(NULL), SRT_EINVPARAM);
+
+  ValueType after;
+  optlen = (int)(sizeof after);
+  EXPECT_EQ(srt_getsockflag(sock, optid, &after, &optlen), SRT_SUCCESS);
+  EXPECT_EQ(after, before) << optname << " changed by a rejected value";
+}
+
+TEST_F(TestSocketOptions, OutOfRangeVals) {
+  CheckRejectedValue(m_caller_sock, SRTO_MSS, "SRTO_MSS", 75);
+  CheckRejectedValue(m_caller_sock, SRTO_OHEADBW, "SRTO_OHEADBW", 4);
+  CheckRejectedValue(m_caller_sock, SRTO_OHEADBW, "SRTO_OHEADBW", 101);
+  CheckRejectedValue(m_caller_sock, SRTO_PBKEYLEN, "SRTO_PBKEYLEN", 7);
+  CheckRejectedValue(m_caller_sock, SRTO_SNDBUF, "SRTO_SNDBUF", -1);
+
+  // the payload has to fit into SRTO_MSS, along with the UDP/IP and SRT headers
+  const int mss = 1000;
+  ASSERT_EQ(srt_setsockflag(m_caller_sock, SRTO_MSS, &mss, sizeof mss),
+            SRT_SUCCESS);
+  CheckRejectedValue(m_caller_sock, SRTO_PAYLOADSIZE, "SRTO_PAYLOADSIZE",
+                     mss - 44 + 1);
+  const int payload_size = mss - 44;
+  EXPECT_EQ(srt_setsockflag(m_caller_sock, SRTO_PAYLOADSIZE, &payload_size,
+                            sizeof payload_size),
+            SRT_SUCCESS);
+}
+
+template <class ValueType>
+void CheckRoundTrip(SRTSOCKET sock, SRT_SOCKOPT optid, const char* optname,
+                    ValueType value) {
+  EXPECT_EQ(srt_setsockflag(sock, optid, &value, sizeof value), SRT_SUCCESS)
+      << "Setting " << optname << " to " << value << " failed";
+
+  ValueType opt_val;
+  int optlen = (int)(sizeof opt_val);
+  EXPECT_EQ(srt_getsockflag(sock, optid, &opt_val, &optlen), SRT_SUCCESS)
+      << "Getting " << optname << " failed";
+  EXPECT_EQ(optlen, (int)(sizeof opt_val)) << optname;
+  EXPECT_EQ(opt_val, value) << optname;
+}
+
+void CheckStringRoundTrip(SRTSOCKET sock, SRT_SOCKOPT optid,
+                          const char* optname, const string& value) {
+  EXPECT_EQ(srt_setsockflag(sock, optid, value.c_str(), value.size()),
+            SRT_SUCCESS)
+      << "Setting " << optname << " to " << value << " failed";
+
+  char opt_val[64];
+  int optlen = (int)(sizeof opt_val);
+  EXPECT_EQ(srt_getsockflag(sock, optid, opt_val, &optlen), SRT_SUCCESS)
+      << "Getting " << optname << " failed";
+  EXPECT_EQ(string(opt_val, optlen), value) << optname;
+}
+
+TEST_F(TestSocketOptions, RoundTripVals) {
+  const SRTSOCKET sock = m_caller_sock;
+
+  CheckRoundTrip(sock, SRTO_MSS, "SRTO_MSS", 1400);
+  CheckRoundTrip(sock, SRTO_FC, "SRTO_FC", 10000);
+  CheckRoundTrip(sock, SRTO_SNDBUF, "SRTO_SNDBUF", 4000000);
+  CheckRoundTrip(sock, SRTO_RCVBUF, "SRTO_RCVBUF", 4000000);
+  CheckRoundTrip(sock, SRTO_UDP_SNDBUF, "SRTO_UDP_SNDBUF", 100000);
+  CheckRoundTrip(sock, SRTO_UDP_RCVBUF, "SRTO_UDP_RCVBUF", 100000);
+  CheckRoundTrip(sock, SRTO_MAXBW, "SRTO_MAXBW", (int64_t)1000000);
+  CheckRoundTrip(sock, SRTO_INPUTBW, "SRTO_INPUTBW", (int64_t)2000000);
+  CheckRoundTrip(sock, SRTO_OHEADBW, "SRTO_OHEADBW", 50);
+  CheckRoundTrip(sock, SRTO_MININPUTBW, "SRTO_MININPUTBW", (int64_t)3000000);
+  CheckRoundTrip(sock, SRTO_CONNTIMEO, "SRTO_CONNTIMEO", 5000);
+  CheckRoundTrip(sock, SRTO_LATENCY, "SRTO_LATENCY", 200);
+  CheckRoundTrip(sock, SRTO_RCVLATENCY, "SRTO_RCVLATENCY", 300);
+  CheckRoundTrip(sock, SRTO_PEERLATENCY, "SRTO_PEERLATENCY", 400);
+  CheckRoundTrip(sock, SRTO_PBKEYLEN, "SRTO_PBKEYLEN", 24);
+  CheckRoundTrip(sock, SRTO_KMREFRESHRATE, "SRTO_KMREFRESHRATE", 100000);
+  CheckRoundTrip(sock, SRTO_KMPREANNOUNCE, "SRTO_KMPREANNOUNCE", 2000);
+  CheckRoundTrip(sock, SRTO_ENFORCEDENCRYPTION, "SRTO_ENFORCEDENCRYPTION", 0);
+  CheckRoundTrip(sock, SRTO_CRYPTOMODE, "SRTO_CRYPTOMODE", 2);
+  CheckRoundTrip(sock, SRTO_IPTTL, "SRTO_IPTTL", 32);
+  CheckRoundTrip(sock, SRTO_TLPKTDROP, "SRTO_TLPKTDROP", 0);
+  CheckRoundTrip(sock, SRTO_SNDDROPDELAY, "SRTO_SNDDROPDELAY", 500);
+  CheckRoundTrip(sock, SRTO_NAKREPORT, "SRTO_NAKREPORT", 0);
+  CheckRoundTrip(sock, SRTO_DRIFTTRACER, "SRTO_DRIFTTRACER", 0);
+  CheckRoundTrip(sock, SRTO_LOSSMAXTTL, "SRTO_LOSSMAXTTL", 5);
+  CheckRoundTrip(sock, SRTO_MINVERSION, "SRTO_MINVERSION", 0x010300);
+  CheckRoundTrip(sock, SRTO_PAYLOADSIZE, "SRTO_PAYLOADSIZE", 1000);
+  CheckRoundTrip(sock, SRTO_PEERIDLETIMEO, "SRTO_PEERIDLETIMEO", 10000);
+  CheckRoundTrip(sock, SRTO_RETRANSMITALGO, "SRTO_RETRANSMITALGO", 0);
+  // the stream API is only supported by file transmission
+  CheckStringRoundTrip(sock, SRTO_CONGESTION, "SRTO_CONGESTION", "file");
+  CheckRoundTrip(sock, SRTO_MESSAGEAPI, "SRTO_MESSAGEAPI", 0);
+  // this also resets the options that depend on the transmission type
+  CheckRoundTrip(sock, SRTO_TRANSTYPE, "SRTO_TRANSTYPE", (int)SRTT_LIVE);
+  // packet filters are only supported by live transmission
+  CheckStringRoundTrip(sock, SRTO_PACKETFILTER, "SRTO_PACKETFILTER",
+                       "fec,cols:10");
+
+  linger lin = {1, 30};
+  EXPECT_EQ(srt_setsockflag(sock, SRTO_LINGER, &lin, sizeof lin), SRT_SUCCESS);
+  linger opt_lin = {0, 0};
+  int optlen = (int)(sizeof opt_lin);
+  EXPECT_EQ(srt_getsockflag(sock, SRTO_LINGER, &opt_lin, &optlen), SRT_SUCCESS);
+  EXPECT_EQ(opt_lin.l_onoff, 1);
+  EXPECT_EQ(opt_lin.l_linger, 30);
+
+  // like in libsrt, the passphrase can't be read back
+  const string passphrase = "passphrase1234";
+  EXPECT_EQ(srt_setsockflag(sock, SRTO_PASSPHRASE, passphrase.c_str(),
+                            passphrase.size()),
+            SRT_SUCCESS);
+}
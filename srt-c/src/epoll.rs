@@ -0,0 +1,532 @@
+use std::{
+    cmp::{max, min},
+    collections::BTreeMap,
+    os::raw::c_int,
+    pin::Pin,
+    ptr::NonNull,
+    slice::from_raw_parts_mut,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex, RwLock, TryLockError,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{future::poll_fn, Sink};
+use lazy_static::lazy_static;
+use tokio::time::timeout;
+
+use crate::{
+    catch_panic,
+    errors::{SRT_ERRNO, SRT_ERRNO::*},
+    get_sock, set_error, set_error_fmt, SocketData, SRTSOCKET, SRT_EPOLL_EVENT, SRT_EPOLL_OPT,
+    SRT_ERROR, SRT_SUCCESS, TOKIO_RUNTIME,
+};
+
+// the file descriptor of a system socket, these are only supported on unix
+pub type SYSSOCKET = c_int;
+
+#[repr(C)]
+pub enum SRT_EPOLL_FLAGS {
+    /// This allows the EID container to be empty when calling the waiting
+    /// function with infinite time. This means an infinite hangup, although
+    /// a socket can be added to this EID from a separate thread.
+    SRT_EPOLL_ENABLE_EMPTY = 1,
+
+    /// This makes the waiting function check if there is output container
+    /// passed to it, and report an error if it isn't. By default it is allowed
+    /// that the output container is 0 size or NULL and therefore the readiness
+    /// state is reported only as a number of ready sockets from return value.
+    SRT_EPOLL_ENABLE_OUTPUTCHECK = 2,
+}
+
+const IN: c_int = SRT_EPOLL_OPT::SRT_EPOLL_IN as c_int;
+const OUT: c_int = SRT_EPOLL_OPT::SRT_EPOLL_OUT as c_int;
+const ERR: c_int = SRT_EPOLL_OPT::SRT_EPOLL_ERR as c_int;
+const ET: c_int = SRT_EPOLL_OPT::SRT_EPOLL_ET as c_int;
+const ENABLE_EMPTY: c_int = SRT_EPOLL_FLAGS::SRT_EPOLL_ENABLE_EMPTY as c_int;
+const ENABLE_OUTPUTCHECK: c_int = SRT_EPOLL_FLAGS::SRT_EPOLL_ENABLE_OUTPUTCHECK as c_int;
+
+// changes in readiness that don't wake up the waiting thread, like the end of a non-blocking
+// connect or the state of system sockets, are picked up within this interval
+const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+lazy_static! {
+    static ref EPOLLS: RwLock<BTreeMap<c_int, Arc<Mutex<Epoll>>>> = RwLock::new(BTreeMap::new());
+}
+
+static NEXT_EPOLLID: AtomicI32 = AtomicI32::new(1);
+
+#[derive(Default)]
+struct Epoll {
+    flags: c_int,
+    usocks: BTreeMap<SRTSOCKET, Subscription>,
+    ssocks: BTreeMap<SYSSOCKET, Subscription>,
+}
+
+struct Subscription {
+    events: c_int,
+    edge_triggered: bool,
+    // the subscribed events that were ready at the last check, and have been reported
+    reported: c_int,
+}
+
+#[derive(Default)]
+struct ReadyEvents {
+    usocks: Vec<(SRTSOCKET, c_int)>,
+    ssocks: Vec<(SYSSOCKET, c_int)>,
+}
+
+impl Subscription {
+    fn new(events: Option<c_int>) -> Self {
+        let events = events.unwrap_or(IN | OUT | ERR);
+        Self {
+            events: events & (IN | OUT | ERR),
+            edge_triggered: events & ET != 0,
+            reported: 0,
+        }
+    }
+
+    /// The subscribed events out of the ready events. Edge triggered subscriptions report an
+    /// event once, and again only after it stopped being ready in between
+    fn filter(&mut self, ready: c_int) -> c_int {
+        let ready = ready & self.events;
+        if !self.edge_triggered {
+            return ready;
+        }
+        let new = ready & !self.reported;
+        self.reported = ready;
+        new
+    }
+}
+
+impl Epoll {
+    fn is_empty(&self) -> bool {
+        self.usocks.is_empty() && self.ssocks.is_empty()
+    }
+
+    fn poll_ready(&mut self, cx: &mut Context) -> ReadyEvents {
+        let mut ready = ReadyEvents::default();
+        // sockets closed while waiting are no longer watched
+        self.usocks.retain(|&id, subscription| {
+            let sock = match get_sock(id) {
+                Some(sock) => sock,
+                None => return false,
+            };
+            let events = match sock.try_lock() {
+                Ok(mut sock) => sock.poll_events(cx),
+                // another thread is blocked on the socket, so it can't be ready for this one
+                Err(TryLockError::WouldBlock) => 0,
+                Err(TryLockError::Poisoned(_)) => ERR,
+            };
+            let events = subscription.filter(events);
+            if events != 0 {
+                ready.usocks.push((id, events));
+            }
+            true
+        });
+        ready.ssocks = poll_system_sockets(&mut self.ssocks);
+        ready
+    }
+}
+
+impl ReadyEvents {
+    fn is_empty(&self) -> bool {
+        self.usocks.is_empty() && self.ssocks.is_empty()
+    }
+}
+
+impl SocketData {
+    /// The events the socket is ready for, the waker is notified of most changes
+    fn poll_events(&mut self, cx: &mut Context) -> c_int {
+        use SocketData::*;
+        match self {
+            Established(sock, _, _) => {
+                let mut events = 0;
+                match Pin::new(&mut **sock).poll_peek(cx) {
+                    Poll::Ready(Some(Ok(_))) => events |= IN,
+                    // a broken connection fails any operation
+                    Poll::Ready(Some(Err(_)) | None) => return IN | OUT | ERR,
+                    Poll::Pending => {}
+                }
+                match Pin::new(&mut **sock.get_mut()).poll_ready(cx) {
+                    Poll::Ready(Ok(())) => events | OUT,
+                    Poll::Ready(Err(_)) => IN | OUT | ERR,
+                    Poll::Pending => events,
+                }
+            }
+            Listening(_, Some(incoming), _, _) => match Pin::new(incoming).poll_peek(cx) {
+                Poll::Ready(Some(_)) => IN,
+                Poll::Ready(None) => ERR,
+                Poll::Pending => 0,
+            },
+            ConnectFailed(_) => ERR,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn poll_system_sockets(ssocks: &mut BTreeMap<SYSSOCKET, Subscription>) -> Vec<(SYSSOCKET, c_int)> {
+    if ssocks.is_empty() {
+        return vec![];
+    }
+    let mut fds: Vec<_> = ssocks
+        .iter()
+        .map(|(&fd, subscription)| libc::pollfd {
+            fd,
+            events: if subscription.events & IN != 0 {
+                libc::POLLIN
+            } else {
+                0
+            } | if subscription.events & OUT != 0 {
+                libc::POLLOUT
+            } else {
+                0
+            },
+            revents: 0,
+        })
+        .collect();
+    // only check the current state, waiting happens alongside the SRT sockets
+    if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 0) } < 0 {
+        return vec![];
+    }
+    fds.iter()
+        .zip(ssocks.values_mut())
+        .filter_map(|(fd, subscription)| {
+            let mut events = 0;
+            if fd.revents & libc::POLLIN != 0 {
+                events |= IN;
+            }
+            if fd.revents & libc::POLLOUT != 0 {
+                events |= OUT;
+            }
+            if fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                events |= ERR;
+            }
+            match subscription.filter(events) {
+                0 => None,
+                events => Some((fd.fd, events)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn poll_system_sockets(_ssocks: &mut BTreeMap<SYSSOCKET, Subscription>) -> Vec<(SYSSOCKET, c_int)> {
+    vec![]
+}
+
+/// Waits for any subscribed event, None if the timeout expired first
+fn wait(epoll: &Mutex<Epoll>, ms_timeout: i64) -> Result<Option<ReadyEvents>, SRT_ERRNO> {
+    {
+        let epoll = epoll.lock().unwrap();
+        if epoll.is_empty() && epoll.flags & ENABLE_EMPTY == 0 {
+            return Err(SRT_EPOLLEMPTY);
+        }
+    }
+
+    let deadline = u64::try_from(ms_timeout)
+        .ok()
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+    TOKIO_RUNTIME.block_on(async {
+        loop {
+            let ready = poll_fn(|cx| {
+                let ready = epoll.lock().unwrap().poll_ready(cx);
+                if ready.is_empty() {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(ready)
+                }
+            });
+            let interval = match deadline {
+                Some(deadline) => min(
+                    CHECK_INTERVAL,
+                    deadline.saturating_duration_since(Instant::now()),
+                ),
+                None => CHECK_INTERVAL,
+            };
+            if let Ok(ready) = timeout(interval, ready).await {
+                return Ok(Some(ready));
+            }
+            if matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+                return Ok(None);
+            }
+        }
+    })
+}
+
+fn get_epoll(eid: c_int) -> Option<Arc<Mutex<Epoll>>> {
+    EPOLLS.read().unwrap().get(&eid).cloned()
+}
+
+/// An operation consumed the event, so that edge triggered subscriptions report it again once the
+/// socket is ready again, even if no check saw it not ready in between
+pub(crate) fn rearm(sock: SRTSOCKET, event: SRT_EPOLL_OPT) {
+    let event = event as c_int;
+    for epoll in EPOLLS.read().unwrap().values() {
+        if let Some(subscription) = epoll.lock().unwrap().usocks.get_mut(&sock) {
+            subscription.reported &= !event;
+        }
+    }
+}
+
+/// Closed sockets are removed from all containers
+pub(crate) fn remove_socket(sock: SRTSOCKET) {
+    for epoll in EPOLLS.read().unwrap().values() {
+        epoll.lock().unwrap().usocks.remove(&sock);
+    }
+}
+
+pub(crate) fn release_all() {
+    EPOLLS.write().unwrap().clear();
+}
+
+#[no_mangle]
+pub extern "C" fn srt_epoll_create() -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let eid = NEXT_EPOLLID.fetch_add(1, Ordering::SeqCst);
+        EPOLLS.write().unwrap().insert(eid, Default::default());
+        eid
+    })
+}
+
+/// # Safety
+/// `events` must be null, which subscribes to all events, or point to the events to subscribe to
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_add_usock(
+    eid: c_int,
+    sock: SRTSOCKET,
+    events: *const c_int,
+) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        if get_sock(sock).is_none() {
+            return set_error(SRT_EINVSOCK);
+        }
+
+        let subscription = Subscription::new(events.as_ref().copied());
+        epoll.lock().unwrap().usocks.insert(sock, subscription);
+        SRT_SUCCESS
+    })
+}
+
+/// # Safety
+/// `events` must be null, which subscribes to all events, or point to the events to subscribe to
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_update_usock(
+    eid: c_int,
+    sock: SRTSOCKET,
+    events: *const c_int,
+) -> c_int {
+    srt_epoll_add_usock(eid, sock, events)
+}
+
+#[no_mangle]
+pub extern "C" fn srt_epoll_remove_usock(eid: c_int, sock: SRTSOCKET) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        epoll.lock().unwrap().usocks.remove(&sock);
+        SRT_SUCCESS
+    })
+}
+
+/// # Safety
+/// `events` must be null, which subscribes to all events, or point to the events to subscribe to
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_add_ssock(
+    eid: c_int,
+    sock: SYSSOCKET,
+    events: *const c_int,
+) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        if cfg!(not(unix)) {
+            return set_error_fmt(
+                SRT_EINVOP,
+                "System sockets are not supported on this platform",
+            );
+        }
+
+        let subscription = Subscription::new(events.as_ref().copied());
+        epoll.lock().unwrap().ssocks.insert(sock, subscription);
+        SRT_SUCCESS
+    })
+}
+
+/// # Safety
+/// `events` must be null, which subscribes to all events, or point to the events to subscribe to
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_update_ssock(
+    eid: c_int,
+    sock: SYSSOCKET,
+    events: *const c_int,
+) -> c_int {
+    srt_epoll_add_ssock(eid, sock, events)
+}
+
+#[no_mangle]
+pub extern "C" fn srt_epoll_remove_ssock(eid: c_int, sock: SYSSOCKET) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        epoll.lock().unwrap().ssocks.remove(&sock);
+        SRT_SUCCESS
+    })
+}
+
+/// Sets the flags of the container, or only returns them when `flags` is -1. Returns the flags
+/// from before the call.
+#[no_mangle]
+pub extern "C" fn srt_epoll_set(eid: c_int, flags: c_int) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        let mut epoll = epoll.lock().unwrap();
+        let previous = epoll.flags;
+        if flags != -1 {
+            epoll.flags = flags;
+        }
+        previous
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn srt_epoll_release(eid: c_int) -> c_int {
+    catch_panic(SRT_ERROR, || match EPOLLS.write().unwrap().remove(&eid) {
+        None => set_error(SRT_EINVPOLLID),
+        Some(_) => SRT_SUCCESS,
+    })
+}
+
+/// Writes the sockets that fit into `array`, whose capacity is given by `len`, and sets `len`
+/// to the number written
+unsafe fn fill<T>(
+    array: *mut T,
+    len: Option<&mut c_int>,
+    sockets: impl Iterator<Item = T>,
+) -> c_int {
+    let len = match len {
+        Some(len) => len,
+        None => return 0,
+    };
+    let written = match NonNull::new(array) {
+        Some(array) => from_raw_parts_mut(array.as_ptr(), max(*len, 0) as usize)
+            .iter_mut()
+            .zip(sockets)
+            .map(|(slot, sock)| *slot = sock)
+            .count(),
+        None => 0,
+    };
+    *len = written as c_int;
+    written as c_int
+}
+
+/// Returns the number of ready sockets, the sockets with errors are reported both as ready for
+/// reading and for writing
+///
+/// # Safety
+/// Each array must be null or have room for as many sockets as its length, the lengths must be
+/// null or valid
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_wait(
+    eid: c_int,
+    readfds: *mut SRTSOCKET,
+    rnum: Option<&mut c_int>,
+    writefds: *mut SRTSOCKET,
+    wnum: Option<&mut c_int>,
+    msTimeOut: i64,
+    lrfds: *mut SYSSOCKET,
+    lrnum: Option<&mut c_int>,
+    lwfds: *mut SYSSOCKET,
+    lwnum: Option<&mut c_int>,
+) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        let missing_output = |array: *const (), len: &Option<&mut c_int>| {
+            array.is_null() || !matches!(len, Some(len) if **len > 0)
+        };
+        if epoll.lock().unwrap().flags & ENABLE_OUTPUTCHECK != 0
+            && missing_output(readfds as *const (), &rnum)
+            && missing_output(writefds as *const (), &wnum)
+            && missing_output(lrfds as *const (), &lrnum)
+            && missing_output(lwfds as *const (), &lwnum)
+        {
+            return set_error_fmt(SRT_EINVPARAM, "No output container for the ready sockets");
+        }
+
+        let ready = match wait(&epoll, msTimeOut) {
+            Ok(Some(ready)) => ready,
+            Ok(None) => return set_error(SRT_ETIMEOUT),
+            Err(e) => return set_error(e),
+        };
+
+        fn with<T: Copy>(ready: &[(T, c_int)], events: c_int) -> impl Iterator<Item = T> + '_ {
+            ready
+                .iter()
+                .filter(move |(_, ready)| ready & events != 0)
+                .map(|(sock, _)| *sock)
+        }
+        fill(readfds, rnum, with(&ready.usocks, IN | ERR))
+            + fill(writefds, wnum, with(&ready.usocks, OUT | ERR))
+            + fill(lrfds, lrnum, with(&ready.ssocks, IN | ERR))
+            + fill(lwfds, lwnum, with(&ready.ssocks, OUT | ERR))
+    })
+}
+
+/// Returns the number of ready sockets, which may be more than fit into `fdsSet`, or 0 if the
+/// timeout expired
+///
+/// # Safety
+/// `fdsSet` must be null or have room for `fdsSize` events
+#[no_mangle]
+pub unsafe extern "C" fn srt_epoll_uwait(
+    eid: c_int,
+    fdsSet: *mut SRT_EPOLL_EVENT,
+    fdsSize: c_int,
+    msTimeOut: i64,
+) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let epoll = match get_epoll(eid) {
+            None => return set_error(SRT_EINVPOLLID),
+            Some(epoll) => epoll,
+        };
+        if !epoll.lock().unwrap().ssocks.is_empty() {
+            return set_error_fmt(
+                SRT_EINVOP,
+                "srt_epoll_uwait does not support system sockets",
+            );
+        }
+
+        let ready = match wait(&epoll, msTimeOut) {
+            Ok(Some(ready)) => ready,
+            Ok(None) => return 0,
+            Err(e) => return set_error(e),
+        };
+
+        if let Some(fds) = NonNull::new(fdsSet) {
+            let fds = from_raw_parts_mut(fds.as_ptr(), max(fdsSize, 0) as usize);
+            for (slot, &(fd, events)) in fds.iter_mut().zip(&ready.usocks) {
+                *slot = SRT_EPOLL_EVENT { fd, events };
+            }
+        }
+        ready.usocks.len() as c_int
+    })
+}
@@ -3,95 +3,95 @@ use thiserror::Error;
 #[repr(C)]
 #[derive(Error, Debug, Clone, Copy)]
 pub enum SRT_ERRNO {
-    #[error("")]
+    #[error("Unknown error")]
     SRT_EUNKNOWN = -1,
-    #[error("")]
+    #[error("Success")]
     SRT_SUCCESS = 0,
 
-    #[error("")]
+    #[error("Connection setup failure")]
     SRT_ECONNSETUP = 1000,
-    #[error("")]
+    #[error("Connection setup failure: connection timed out")]
     SRT_ENOSERVER = 1001,
-    #[error("")]
+    #[error("Connection setup failure: connection rejected")]
     SRT_ECONNREJ = 1002,
-    #[error("")]
+    #[error("Connection setup failure: unable to create or configure the SRT socket")]
     SRT_ESOCKFAIL = 1003,
-    #[error("")]
+    #[error("Connection setup failure: aborted for security reasons")]
     SRT_ESECFAIL = 1004,
-    #[error("")]
+    #[error("Connection setup failure: socket closed during the operation")]
     SRT_ESCLOSED = 1005,
 
-    #[error("")]
+    #[error("Connection failure")]
     SRT_ECONNFAIL = 2000,
-    #[error("")]
+    #[error("Connection failure: connection was broken")]
     SRT_ECONNLOST = 2001,
-    #[error("")]
+    #[error("Connection failure: connection does not exist")]
     SRT_ENOCONN = 2002,
 
-    #[error("")]
+    #[error("System resource failure")]
     SRT_ERESOURCE = 3000,
-    #[error("")]
+    #[error("System resource failure: unable to create new threads")]
     SRT_ETHREAD = 3001,
-    #[error("")]
+    #[error("System resource failure: unable to allocate buffers")]
     SRT_ENOBUF = 3002,
-    #[error("")]
+    #[error("System resource failure: unable to allocate a system object")]
     SRT_ESYSOBJ = 3003,
 
-    #[error("")]
+    #[error("File system failure")]
     SRT_EFILE = 4000,
-    #[error("")]
+    #[error("File system failure: cannot seek the read position")]
     SRT_EINVRDOFF = 4001,
-    #[error("")]
+    #[error("File system failure: failure in read")]
     SRT_ERDPERM = 4002,
-    #[error("")]
+    #[error("File system failure: cannot seek the write position")]
     SRT_EINVWROFF = 4003,
-    #[error("")]
+    #[error("File system failure: failure in write")]
     SRT_EWRPERM = 4004,
 
-    #[error("")]
+    #[error("Operation not supported")]
     SRT_EINVOP = 5000,
-    #[error("")]
+    #[error("The socket is already bound")]
     SRT_EBOUNDSOCK = 5001,
     #[error("The socket is already connected")]
     SRT_ECONNSOCK = 5002,
-    #[error("")]
+    #[error("Invalid parameters")]
     SRT_EINVPARAM = 5003,
     #[error("Invalid socket ID")]
     SRT_EINVSOCK = 5004,
-    #[error("")]
+    #[error("The socket is not bound")]
     SRT_EUNBOUNDSOCK = 5005,
     #[error("The socket was not setup as a listener (srt_listen was not called)")]
     SRT_ENOLISTEN = 5006,
-    #[error("")]
+    #[error("The operation is not supported in rendezvous mode")]
     SRT_ERDVNOSERV = 5007,
-    #[error("")]
+    #[error("A rendezvous socket must be bound before connecting")]
     SRT_ERDVUNBOUND = 5008,
-    #[error("")]
+    #[error("Invalid operation for the message API")]
     SRT_EINVALMSGAPI = 5009,
-    #[error("")]
+    #[error("Invalid operation for the buffer API")]
     SRT_EINVALBUFFERAPI = 5010,
-    #[error("")]
+    #[error("Another socket is already listening on the same port")]
     SRT_EDUPLISTEN = 5011,
-    #[error("")]
+    #[error("The message is too large to send")]
     SRT_ELARGEMSG = 5012,
-    #[error("")]
+    #[error("Invalid epoll ID")]
     SRT_EINVPOLLID = 5013,
-    #[error("")]
+    #[error("No sockets are subscribed to the epoll, waiting would block forever")]
     SRT_EPOLLEMPTY = 5014,
-    #[error("")]
+    #[error("Another socket is bound to the port with conflicting settings")]
     SRT_EBINDCONFLICT = 5015,
 
-    #[error("")]
+    #[error("Non-blocking call failure")]
     SRT_EASYNCFAIL = 6000,
-    #[error("")]
+    #[error("Non-blocking call failure: no buffer available for sending")]
     SRT_EASYNCSND = 6001,
-    #[error("")]
+    #[error("Non-blocking call failure: no data available for reading")]
     SRT_EASYNCRCV = 6002,
-    #[error("")]
+    #[error("Non-blocking call failure: the operation timed out")]
     SRT_ETIMEOUT = 6003,
-    #[error("")]
+    #[error("Non-blocking call failure: early congestion notification")]
     SRT_ECONGEST = 6004,
 
-    #[error("")]
+    #[error("The peer side has signaled an error")]
     SRT_EPEERERR = 7000,
 }
@@ -1,38 +1,55 @@
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 
+mod epoll;
 mod errors;
 
+pub use epoll::*;
 use errors::{SRT_ERRNO, SRT_ERRNO::*};
 use os_socketaddr::OsSocketAddr;
 use srt_protocol::{
-    connection::ConnectionSettings,
-    options::{DataRate, KeySize, LiveBandwidthMode, Percent, Sender},
-    settings::KeySettings,
+    connection::{ConnectionSettings, MessageControl, SequencedMessage},
+    options::{
+        ByteCount, Connect, CryptoMode, DataRate, KeySize, LiveBandwidthMode, PacketCount,
+        PacketSize, Percent, Sender, SrtVersion, TransmissionType,
+    },
+    settings::{AcceptParameters, KeyMaterialState, KeySettings},
+    statistics::SocketStatistics,
 };
 
 use std::{
-    cell::RefCell,
-    cmp::min,
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    cmp::{max, min},
     collections::BTreeMap,
+    env,
     ffi::CString,
     fmt, io,
     mem::{replace, size_of},
     net::SocketAddr,
+    ops::{Deref, DerefMut},
     os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
     ptr::{self, NonNull},
     slice::{from_raw_parts, from_raw_parts_mut},
     sync::{
         atomic::{AtomicI32, Ordering},
         Arc, Mutex, RwLock,
     },
+    task::{Context, Poll},
     time::{Duration, Instant},
 };
 
 use bytes::Bytes;
-use futures::{channel::mpsc, sink::SinkExt, StreamExt};
+use futures::{
+    channel::mpsc,
+    future::FutureExt,
+    sink::SinkExt,
+    stream::{Peekable, Stream, StreamExt},
+};
 use lazy_static::lazy_static;
-use log::{error, warn};
+use log::{error, warn, LevelFilter};
 use srt_tokio::{
     options::{ListenerOptions, Passphrase, SocketOptions, StreamId, Validation},
     SrtListener, SrtSocket,
@@ -107,11 +124,13 @@ pub enum SRT_SOCKOPT {
     // #endif
     SRTO_PACKETFILTER = 60,   // Add and configure a packet filter
     SRTO_RETRANSMITALGO = 61, // An option to select packet retransmission algorithm
+    SRTO_CRYPTOMODE = 62,     // Encryption cipher mode (AES-CTR, AES-GCM, ...)
 
     SRTO_E_SIZE, // Always last element, not a valid option.
 }
 
 #[repr(C)]
+#[derive(Default)]
 pub struct SRT_TRACEBSTATS {
     // global measurements
     msTimeStamp: i64,        // time since the UDT entity is started, in milliseconds
@@ -232,6 +251,40 @@ pub struct SRT_MSGCTRL {
     grpdata_size: usize,
 }
 
+impl Default for SRT_MSGCTRL {
+    /// The same as `srt_msgctrl_default` of the reference implementation
+    fn default() -> Self {
+        Self {
+            flags: 0,
+            msgttl: -1,
+            inorder: 0,
+            boundary: 0,
+            srctime: 0,
+            pktseq: -1,
+            msgno: -1,
+            grpdata: ptr::null_mut(),
+            grpdata_size: 0,
+        }
+    }
+}
+
+impl SRT_MSGCTRL {
+    /// The options for sending a message, and its source time
+    fn message_control(&self) -> (MessageControl, Instant) {
+        let control = MessageControl {
+            // a negative ttl means the message never expires
+            ttl: u64::try_from(self.msgttl).ok().map(Duration::from_millis),
+            in_order: self.inorder != 0,
+            ..MessageControl::default()
+        };
+        let srctime = match u64::try_from(self.srctime) {
+            Ok(srctime) if srctime > 0 => *SRT_CLOCK_EPOCH + Duration::from_micros(srctime),
+            _ => Instant::now(),
+        };
+        (control, srctime)
+    }
+}
+
 #[repr(C)]
 pub enum SRT_TRANSTYPE {
     SRTT_LIVE,
@@ -265,6 +318,8 @@ pub const SRT_INVALID_SOCK: SRTSOCKET = -1;
 
 lazy_static! {
     static ref TOKIO_RUNTIME: Runtime = Runtime::new().unwrap();
+    // the origin of the times in microseconds of srt_time_now and SRT_MSGCTRL
+    static ref SRT_CLOCK_EPOCH: Instant = Instant::now();
     static ref SOCKETS: RwLock<BTreeMap<SRTSOCKET, Arc<Mutex<SocketData>>>> =
         RwLock::new(BTreeMap::new());
 }
@@ -294,20 +349,52 @@ impl Default for ApiOptions {
     }
 }
 
+/// The messages received by a socket, along with where they were in the stream
+#[derive(Debug)]
+struct MessageStream(SrtSocket);
+
+impl Stream for MessageStream {
+    type Item = Result<SequencedMessage, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_message(cx)
+    }
+}
+
+impl Deref for MessageStream {
+    type Target = SrtSocket;
+
+    fn deref(&self) -> &SrtSocket {
+        &self.0
+    }
+}
+
+impl DerefMut for MessageStream {
+    fn deref_mut(&mut self) -> &mut SrtSocket {
+        &mut self.0
+    }
+}
+
 #[derive(Debug)]
 enum SocketData {
     Initialized(SocketOptions, Option<StreamId>, ApiOptions),
     ConnectingNonBlocking(JoinHandle<()>, ApiOptions),
-    Established(SrtSocket, ApiOptions),
+    // peekable for epoll to find out whether data can be received, the statistics are those at
+    // the start of the interval reported by srt_bstats
+    Established(
+        Box<Peekable<MessageStream>>,
+        ApiOptions,
+        Box<SocketStatistics>,
+    ),
     Listening(
         SrtListener,
-        Option<mpsc::Receiver<(SRTSOCKET, SocketAddr)>>,
+        Option<Peekable<mpsc::Receiver<(SRTSOCKET, SocketAddr)>>>,
         JoinHandle<()>,
         ApiOptions,
     ),
     ConnectFailed(io::Error),
 
-    Accepting(Option<KeySettings>),
+    Accepting(Option<KeySettings>, Option<Duration>),
 
     InvalidIntermediateState,
     Closed,
@@ -319,7 +406,7 @@ impl SocketData {
         match self {
             Initialized(_, _, opts)
             | ConnectingNonBlocking(_, opts)
-            | Established(_, opts)
+            | Established(_, opts, _)
             | Listening(_, _, _, opts) => Some(opts),
             _ => None,
         }
@@ -334,8 +421,8 @@ impl SocketData {
     }
 
     fn conn_settings(&self) -> Option<&ConnectionSettings> {
-        if let SocketData::Established(sock, _) = self {
-            Some(sock.settings())
+        if let SocketData::Established(sock, _, _) = self {
+            Some(sock.get_ref().settings())
         } else {
             None
         }
@@ -345,7 +432,7 @@ impl SocketData {
         use SocketData::*;
         match self {
             Initialized(so, _, ai) => (Some(ai), Some(so)),
-            ConnectingNonBlocking(_, ai) | Established(_, ai) | Listening(_, _, _, ai) => {
+            ConnectingNonBlocking(_, ai) | Established(_, ai, _) | Listening(_, _, _, ai) => {
                 (Some(ai), None)
             }
             _ => (None, None),
@@ -359,17 +446,30 @@ fn get_sock(sock: SRTSOCKET) -> Option<Arc<Mutex<SocketData>>> {
 
 #[no_mangle]
 pub extern "C" fn srt_startup() -> c_int {
-    lazy_static::initialize(&TOKIO_RUNTIME);
-    lazy_static::initialize(&SOCKETS);
-
-    let _ = pretty_env_logger::try_init();
-    SRT_SUCCESS
+    catch_panic(SRT_ERROR, || {
+        lazy_static::initialize(&TOKIO_RUNTIME);
+        lazy_static::initialize(&SOCKETS);
+        lazy_static::initialize(&SRT_CLOCK_EPOCH);
+
+        // without RUST_LOG, srt_setloglevel decides what is logged, starting with errors only
+        let filters = env::var("RUST_LOG");
+        let logger = pretty_env_logger::formatted_builder()
+            .parse_filters(filters.as_deref().unwrap_or("trace"))
+            .try_init();
+        if logger.is_ok() && filters.is_err() {
+            log::set_max_level(LevelFilter::Error);
+        }
+        SRT_SUCCESS
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn srt_cleanup() -> c_int {
-    SOCKETS.write().unwrap().clear();
-    SRT_SUCCESS
+    catch_panic(SRT_ERROR, || {
+        epoll::release_all();
+        SOCKETS.write().unwrap().clear();
+        SRT_SUCCESS
+    })
 }
 
 #[no_mangle]
@@ -378,139 +478,156 @@ pub extern "C" fn srt_bind(
     name: Option<&libc::sockaddr>,
     namelen: c_int,
 ) -> c_int {
-    let name = match name {
-        Some(name) => name,
-        None => return set_error_fmt(SRT_EINVPARAM, "Invalid socket address"),
-    };
-    let name = unsafe {
-        OsSocketAddr::from_raw_parts(name as *const libc::sockaddr as *const u8, namelen as usize)
-    };
-    let name = match name.into_addr() {
-        Some(name) => name,
-        None => return set_error(SRT_EINVPARAM),
-    };
+    catch_panic(SRT_ERROR, || {
+        let name = match name {
+            Some(name) => name,
+            None => return set_error_fmt(SRT_EINVPARAM, "Invalid socket address"),
+        };
+        let name = unsafe {
+            OsSocketAddr::from_raw_parts(
+                name as *const libc::sockaddr as *const u8,
+                namelen as usize,
+            )
+        };
+        let name = match name.into_addr() {
+            Some(name) => name,
+            None => return set_error(SRT_EINVPARAM),
+        };
 
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-    let mut l = sock.lock().unwrap();
-    if let SocketData::Initialized(ref mut b, _, _) = *l {
-        b.connect.local = name;
-        SRT_SUCCESS
-    } else {
-        set_error(SRT_ECONNSOCK)
-    }
+        let mut l = sock.lock().unwrap();
+        if let SocketData::Initialized(ref mut b, _, _) = *l {
+            b.connect.local = name;
+            SRT_SUCCESS
+        } else {
+            set_error(SRT_ECONNSOCK)
+        }
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn srt_listen(sock: SRTSOCKET, _backlog: c_int) -> c_int {
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
-
-    let mut l = sock.lock().unwrap();
-    let sd = replace(&mut *l, SocketData::InvalidIntermediateState);
-    if let SocketData::Initialized(so, _, initial_opts) = sd {
-        let options = match (ListenerOptions { socket: so }.try_validate()) {
-            Ok(options) => options,
-            Err(e) => return set_error_fmt(SRT_EINVOP, format_args!("Invalid options: {}", e)),
-        };
-        let ret = TOKIO_RUNTIME.block_on(SrtListener::bind(options));
-        let (listener, mut incoming) = match ret {
-            Ok(l) => l,
-            Err(e) => {
-                return set_error_fmt(
-                    SRT_EINVOP,
-                    format_args!("Failed to listen on socket: {}", e),
-                )
-            }
+    catch_panic(SRT_ERROR, || {
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
         };
 
-        let (mut s, r) = mpsc::channel(1024);
-        let sock = sock.clone();
-        let task = TOKIO_RUNTIME.spawn(async move {
-            let incoming_stream = incoming.incoming();
-            while let Some(req) = incoming_stream.next().await {
-                let new_sock = insert_socket(SocketData::Accepting(None));
-
-                // get latest opts--callback may be changed at any point
-                let opts = {
-                    let l = sock.lock().unwrap();
-                    *l.api_opts().unwrap()
-                };
+        let mut l = sock.lock().unwrap();
+        let sd = replace(&mut *l, SocketData::InvalidIntermediateState);
+        if let SocketData::Initialized(so, _, initial_opts) = sd {
+            let options = match (ListenerOptions { socket: so }.try_validate()) {
+                Ok(options) => options,
+                Err(e) => return set_error_fmt(SRT_EINVOP, format_args!("Invalid options: {}", e)),
+            };
+            let ret = TOKIO_RUNTIME.block_on(SrtListener::bind(options));
+            let (listener, mut incoming) = match ret {
+                Ok(l) => l,
+                Err(e) => return set_io_error(SRT_EINVOP, "Failed to listen on socket", &e),
+            };
 
-                let req = req;
-                let accept = if let Some(cb) = opts.listen_cb {
-                    let streamid_cstr = req
-                        .stream_id()
-                        .map(|id| CString::new(id.to_string()).unwrap());
-                    let streamid_ptr = match &streamid_cstr {
-                        Some(cstr) => cstr.as_ptr(),
-                        None => ptr::null(),
+            let (mut s, r) = mpsc::channel(1024);
+            let sock = sock.clone();
+            let task = TOKIO_RUNTIME.spawn(async move {
+                let incoming_stream = incoming.incoming();
+                while let Some(req) = incoming_stream.next().await {
+                    let new_sock = insert_socket(SocketData::Accepting(None, None));
+
+                    // get latest opts--callback may be changed at any point
+                    let opts = match sock.lock().unwrap().api_opts() {
+                        Some(opts) => *opts,
+                        None => break,
                     };
 
-                    let mut ret: c_int = 0;
-
-                    let exception_thrown = unsafe {
-                        call_callback_wrap_exception(
-                            cb,
-                            opts.listen_cb_opaque,
-                            new_sock,
-                            5,
-                            OsSocketAddr::from(req.remote()).as_ptr() as *const libc::sockaddr,
-                            streamid_ptr,
-                            &mut ret,
-                        )
+                    let req = req;
+                    let accept = if let Some(cb) = opts.listen_cb {
+                        let streamid_cstr = req
+                            .stream_id()
+                            .and_then(|id| CString::new(id.to_string()).ok());
+                        let streamid_ptr = match &streamid_cstr {
+                            Some(cstr) => cstr.as_ptr(),
+                            None => ptr::null(),
+                        };
+
+                        let mut ret: c_int = 0;
+
+                        let exception_thrown = unsafe {
+                            call_callback_wrap_exception(
+                                cb,
+                                opts.listen_cb_opaque,
+                                new_sock,
+                                5,
+                                OsSocketAddr::from(req.remote()).as_ptr() as *const libc::sockaddr,
+                                streamid_ptr,
+                                &mut ret,
+                            )
+                        };
+
+                        exception_thrown == 0 && ret == 0
+                    } else {
+                        true
                     };
 
-                    exception_thrown == 0 && ret == 0
-                } else {
-                    true
-                };
-
-                if !accept {
-                    // connection rejected! try again
-                    srt_close(new_sock);
-                    continue;
-                }
-
-                let new_sock_entry = get_sock(new_sock).expect("socket closed in a weird way?");
-                let key_settings = {
-                    let mut l = new_sock_entry.lock().unwrap();
-                    if let SocketData::Accepting(ref mut key_settings) = *l {
-                        key_settings.take()
-                    } else {
-                        // uhh definitely strange
+                    if !accept {
+                        // connection rejected! try again
+                        srt_close(new_sock);
                         continue;
                     }
-                };
 
-                let remote = req.remote();
-                let srt_socket = match req.accept(key_settings).await {
-                    Ok(sock) => sock,
-                    Err(_e) => continue, // TODO: remove from sockets
-                };
+                    let new_sock_entry = match get_sock(new_sock) {
+                        Some(entry) => entry,
+                        // closed by the listen callback
+                        None => continue,
+                    };
+                    let parameters = {
+                        let mut l = new_sock_entry.lock().unwrap();
+                        if let SocketData::Accepting(ref mut key_settings, recv_latency) = *l {
+                            let mut parameters = key_settings
+                                .take()
+                                .map(AcceptParameters::from)
+                                .unwrap_or_default();
+                            if let Some(recv_latency) = recv_latency {
+                                parameters.set_recv_latency(recv_latency);
+                            }
+                            parameters
+                        } else {
+                            // uhh definitely strange
+                            continue;
+                        }
+                    };
 
-                {
-                    let mut l = new_sock_entry.lock().unwrap();
-                    *l = SocketData::Established(srt_socket, opts);
-                }
+                    let remote = req.remote();
+                    let srt_socket = match req.accept_with(parameters).await {
+                        Ok(sock) => sock,
+                        Err(_e) => continue, // TODO: remove from sockets
+                    };
+
+                    {
+                        let mut l = new_sock_entry.lock().unwrap();
+                        *l = SocketData::Established(
+                            Box::new(MessageStream(srt_socket).peekable()),
+                            opts,
+                            Box::default(),
+                        );
+                    }
 
-                if s.send((new_sock, remote)).await.is_err() {
-                    break;
+                    if s.send((new_sock, remote)).await.is_err() {
+                        break;
+                    }
                 }
-            }
-        });
-        *l = SocketData::Listening(listener, Some(r), task, initial_opts)
-    } else {
-        *l = sd;
-        return set_error(SRT_ECONNSOCK);
-    }
+            });
+            *l = SocketData::Listening(listener, Some(r.peekable()), task, initial_opts)
+        } else {
+            *l = sd;
+            return set_error(SRT_ECONNSOCK);
+        }
 
-    SRT_SUCCESS
+        SRT_SUCCESS
+    })
 }
 
 #[repr(C)]
@@ -579,154 +696,127 @@ pub enum SRT_EPOLL_OPT {
 pub const SRT_EPOLL_CONNECT: SRT_EPOLL_OPT = SRT_EPOLL_OPT::SRT_EPOLL_OUT;
 pub const SRT_EPOLL_ACCEPT: SRT_EPOLL_OPT = SRT_EPOLL_OPT::SRT_EPOLL_IN;
 
-#[no_mangle]
-pub extern "C" fn srt_epoll_create() -> c_int {
-    todo!()
-}
-
-#[no_mangle]
-pub extern "C" fn srt_epoll_add_usock(
-    _eid: c_int,
-    _sock: SRTSOCKET,
-    _events: *const c_int,
-) -> c_int {
-    todo!()
-}
-
-#[no_mangle]
-pub extern "C" fn srt_epoll_remove_usock(_eid: c_int, _sock: SRTSOCKET) -> c_int {
-    todo!()
-}
-
-#[no_mangle]
-pub extern "C" fn srt_epoll_release(_eid: c_int) -> c_int {
-    todo!()
-}
-
-#[no_mangle]
-pub extern "C" fn srt_epoll_wait(
-    _eid: c_int,
-    _readfds: *const SRTSOCKET,
-    _rnum: *const c_int,
-    _writefds: *const SRTSOCKET,
-    _wnum: *const c_int,
-    _msTimeOut: i64,
-    _lrfds: *const SRTSOCKET,
-    _lrnum: *const c_int,
-    _lwfds: *const SRTSOCKET,
-    _lwnum: *const c_int,
-) -> c_int {
-    todo!()
-}
-
 #[no_mangle]
 pub extern "C" fn srt_connect(
     sock: SRTSOCKET,
     name: Option<&libc::sockaddr>,
     namelen: c_int,
 ) -> c_int {
-    let name = match name {
-        Some(name) => name,
-        None => return set_error_fmt(SRT_EINVPARAM, "Invalid socket address"),
-    };
-    let name = unsafe {
-        OsSocketAddr::from_raw_parts(name as *const libc::sockaddr as *const u8, namelen as usize)
-    };
-    let name = match name.into_addr() {
-        Some(name) => name,
-        None => return set_error(SRT_EINVPARAM),
-    };
+    catch_panic(SRT_ERROR, || {
+        let name = match name {
+            Some(name) => name,
+            None => return set_error_fmt(SRT_EINVPARAM, "Invalid socket address"),
+        };
+        let name = unsafe {
+            OsSocketAddr::from_raw_parts(
+                name as *const libc::sockaddr as *const u8,
+                namelen as usize,
+            )
+        };
+        let name = match name.into_addr() {
+            Some(name) => name,
+            None => return set_error(SRT_EINVPARAM),
+        };
 
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-    let mut l = sock.lock().unwrap();
-    let sd = replace(&mut *l, SocketData::InvalidIntermediateState);
-    if let SocketData::Initialized(so, streamid, options) = sd {
-        let sb = SrtSocket::builder().with(so.clone());
-        if options.rcv_syn {
-            // blocking mode, wait on oneshot
-            let res = TOKIO_RUNTIME
-                .block_on(async { sb.call(name, streamid.as_ref().map(|s| s.as_str())).await });
-            match res {
-                Ok(sock) => *l = SocketData::Established(sock, options),
-                Err(e) => {
-                    *l = SocketData::Initialized(so, streamid, options);
-                    return set_error_fmt(SRT_ENOSERVER, format_args!("Failed to connect: {}", e));
+        let mut l = sock.lock().unwrap();
+        let sd = replace(&mut *l, SocketData::InvalidIntermediateState);
+        if let SocketData::Initialized(so, streamid, options) = sd {
+            let sb = SrtSocket::builder().with(so.clone());
+            if options.rcv_syn {
+                // blocking mode, wait on oneshot
+                let res = TOKIO_RUNTIME
+                    .block_on(async { sb.call(name, streamid.as_ref().map(|s| s.as_str())).await });
+                match res {
+                    Ok(sock) => {
+                        *l = SocketData::Established(
+                            Box::new(MessageStream(sock).peekable()),
+                            options,
+                            Box::default(),
+                        )
+                    }
+                    Err(e) => {
+                        *l = SocketData::Initialized(so, streamid, options);
+                        return set_io_error(SRT_ENOSERVER, "Failed to connect", &e.into());
+                    }
                 }
+            } else {
+                // nonblocking mode
+                let sock_clone = sock.clone();
+                let task = TOKIO_RUNTIME.spawn(async move {
+                    let res = sb.call(name, None).await;
+                    let mut l = sock_clone.lock().unwrap();
+                    match res {
+                        Ok(s) => {
+                            *l = SocketData::Established(
+                                Box::new(MessageStream(s).peekable()),
+                                options,
+                                Box::default(),
+                            )
+                        }
+                        Err(e) => *l = SocketData::ConnectFailed(e.into()),
+                    }
+                });
+                *l = SocketData::ConnectingNonBlocking(task, options);
             }
         } else {
-            // nonblocking mode
-            let sock_clone = sock.clone();
-            let task = TOKIO_RUNTIME.spawn(async move {
-                let res = sb.call(name, None).await;
-                let mut l = sock_clone.lock().unwrap();
-                match res {
-                    Ok(s) => *l = SocketData::Established(s, options),
-                    Err(e) => *l = SocketData::ConnectFailed(e),
-                }
-            });
-            *l = SocketData::ConnectingNonBlocking(task, options);
+            *l = sd; // restore state
+            return set_error(SRT_ECONNSOCK);
         }
-    } else {
-        *l = sd; // restore state
-        return set_error(SRT_ECONNSOCK);
-    }
 
-    SRT_SUCCESS
+        SRT_SUCCESS
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn srt_accept(
-    sock: SRTSOCKET,
+    socknum: SRTSOCKET,
     addr: Option<&mut libc::sockaddr>,
     addrlen: Option<&mut c_int>,
 ) -> SRTSOCKET {
-    let addr = match (addr, addrlen) {
-        (None, None) | (None, Some(_)) => None,
-        (Some(addr), Some(addrlen)) => Some((addr, addrlen)),
-        (Some(_), None) => return set_error(SRT_EINVPARAM),
-    };
-
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
-
-    let mut l = sock.lock().unwrap();
-    if let SocketData::Listening(ref _listener, ref mut incoming, ref _jh, opts) = *l {
-        let mut incoming = match incoming.take() {
-            Some(l) => l,
-            None => {
-                return set_error_fmt(
-                    SRT_EINVOP,
-                    "accept can only be called from one thread at a time",
-                )
-            }
+    catch_panic(SRT_INVALID_SOCK, || {
+        let addr = match (addr, addrlen) {
+            (None, None) | (None, Some(_)) => None,
+            (Some(addr), Some(addrlen)) => Some((addr, addrlen)),
+            (Some(_), None) => return set_error(SRT_EINVPARAM),
         };
 
-        drop(l); // release mutex so other calls don't block
+        let sock = match get_sock(socknum) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-        TOKIO_RUNTIME.block_on(async {
-            let req = if opts.rcv_syn {
-                // blocking
-                incoming.next().await
-            } else {
-                // nonblocking--10ms for now but could be shorter potentially
-                match timeout(Duration::from_millis(10), incoming.next()).await {
-                    Err(_) => return set_error(SRT_EASYNCRCV),
-                    Ok(req) => req,
+        let mut l = sock.lock().unwrap();
+        if let SocketData::Listening(ref _listener, ref mut incoming, ref _jh, opts) = *l {
+            let mut incoming = match incoming.take() {
+                Some(l) => l,
+                None => {
+                    return set_error_fmt(
+                        SRT_EINVOP,
+                        "accept can only be called from one thread at a time",
+                    )
                 }
             };
 
-            let (new_sock, remote) = match req {
-                Some(req) => req,
-                None => return set_error(SRT_ESCLOSED),
-            };
+            drop(l); // release mutex so other calls don't block
+
+            let req = TOKIO_RUNTIME.block_on(async {
+                if opts.rcv_syn {
+                    // blocking
+                    Some(incoming.next().await)
+                } else {
+                    // nonblocking--10ms for now but could be shorter potentially
+                    timeout(Duration::from_millis(10), incoming.next())
+                        .await
+                        .ok()
+                }
+            });
 
-            // put listener back
+            // put listener back, also when nothing was accepted, so it can accept again
             {
                 let mut l = sock.lock().unwrap();
                 if let SocketData::Listening(_listener, in_state, _jh, _opts) = &mut *l {
@@ -734,6 +824,16 @@ pub extern "C" fn srt_accept(
                 }
             }
 
+            let req = match req {
+                Some(req) => req,
+                None => return set_error(SRT_EASYNCRCV),
+            };
+            epoll::rearm(socknum, SRT_EPOLL_OPT::SRT_EPOLL_IN);
+            let (new_sock, remote) = match req {
+                Some(req) => req,
+                None => return set_error(SRT_ESCLOSED),
+            };
+
             if let Some((addr, len)) = addr {
                 let osa = OsSocketAddr::from(remote);
                 *addr = unsafe { *(osa.as_ptr() as *const libc::sockaddr) };
@@ -741,32 +841,73 @@ pub extern "C" fn srt_accept(
             }
 
             new_sock
-        })
-    } else {
-        set_error(SRT_ENOLISTEN)
-    }
+        } else {
+            set_error(SRT_ENOLISTEN)
+        }
+    })
 }
 
 fn set_error_fmt(err: SRT_ERRNO, args: impl fmt::Display) -> c_int {
+    set_error_errno(err, 0, args)
+}
+
+fn set_error(err: SRT_ERRNO) -> c_int {
+    set_error_fmt(err, err)
+}
+
+/// Sets the error along with the system error that caused it, if any
+fn set_io_error(err: SRT_ERRNO, context: impl fmt::Display, error: &io::Error) -> c_int {
+    let errno = error.raw_os_error().unwrap_or(0);
+    set_error_errno(err, errno, format_args!("{}: {}", context, error))
+}
+
+fn set_error_errno(err: SRT_ERRNO, errno: c_int, args: impl fmt::Display) -> c_int {
     LAST_ERROR_STR.with(|l| {
         // TODO: it would be great if this could reuse the same buffer
-        *l.borrow_mut() = CString::new(format!("{}", args)).unwrap();
+        *l.borrow_mut() = CString::new(format!("{}", args)).unwrap_or_default();
     });
     LAST_ERROR.with(|l| *l.borrow_mut() = err);
+    LAST_ERRNO.with(|l| l.set(errno));
     SRT_ERROR
 }
 
-fn set_error(err: SRT_ERRNO) -> c_int {
-    set_error_fmt(err, err)
+/// Runs the body of an API function, turning a panic into an error, as unwinding into the caller
+/// would abort the process
+fn catch_panic<T>(error: T, f: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(ret) => ret,
+        Err(payload) => {
+            let message = match (
+                payload.downcast_ref::<&str>(),
+                payload.downcast_ref::<String>(),
+            ) {
+                (Some(message), _) => message,
+                (_, Some(message)) => message.as_str(),
+                (None, None) => "unknown panic",
+            };
+            set_error_fmt(SRT_EUNKNOWN, format_args!("Internal error: {}", message));
+            error
+        }
+    }
 }
 
 thread_local! {
     pub static LAST_ERROR_STR: RefCell<CString> = RefCell::new(CString::new("(no error set on this thread)").unwrap());
     pub static LAST_ERROR: RefCell<SRT_ERRNO> = RefCell::new(SRT_ERRNO::SRT_SUCCESS);
+    // the system error behind LAST_ERROR, or 0
+    pub static LAST_ERRNO: Cell<c_int> = const { Cell::new(0) };
 }
 
+/// Returns the last error on the calling thread, and stores the system error behind it, or 0, in
+/// `errno_loc` if it is not null
+///
+/// # Safety
+/// `errno_loc` must be null or valid to write
 #[no_mangle]
-pub extern "C" fn srt_getlasterror(_errno_loc: *mut c_int) -> c_int {
+pub unsafe extern "C" fn srt_getlasterror(errno_loc: *mut c_int) -> c_int {
+    if let Some(errno_loc) = errno_loc.as_mut() {
+        *errno_loc = LAST_ERRNO.with(|l| l.get());
+    }
     LAST_ERROR.with(|l| *l.borrow()) as c_int
 }
 
@@ -775,20 +916,32 @@ pub extern "C" fn srt_getlasterror_str() -> *const c_char {
     LAST_ERROR_STR.with(|f| f.borrow().as_c_str().as_ptr())
 }
 
+#[no_mangle]
+pub extern "C" fn srt_clearlasterror() {
+    set_error_errno(SRT_ERRNO::SRT_SUCCESS, 0, SRT_ERRNO::SRT_SUCCESS);
+}
+
 #[no_mangle]
 pub extern "C" fn srt_send(sock: SRTSOCKET, buf: *const c_char, len: c_int) -> c_int {
     srt_sendmsg2(sock, buf, len, None)
 }
 
+/// Sends a message that is dropped if it could not be delivered within `ttl` milliseconds, or
+/// never if negative
 #[no_mangle]
 pub extern "C" fn srt_sendmsg(
-    _sock: SRTSOCKET,
-    _buf: *const c_char,
-    _len: c_int,
-    _ttl: c_int,
-    _inorder: c_int,
+    sock: SRTSOCKET,
+    buf: *const c_char,
+    len: c_int,
+    ttl: c_int,
+    inorder: c_int,
 ) -> c_int {
-    todo!()
+    let mctrl = SRT_MSGCTRL {
+        msgttl: ttl,
+        inorder,
+        ..SRT_MSGCTRL::default()
+    };
+    srt_sendmsg2(sock, buf, len, Some(&mctrl))
 }
 
 /// Returns number of bytes written
@@ -797,91 +950,284 @@ pub extern "C" fn srt_sendmsg2(
     sock: SRTSOCKET,
     buf: *const c_char,
     len: c_int,
-    _mctrl: Option<&SRT_MSGCTRL>,
+    mctrl: Option<&SRT_MSGCTRL>,
 ) -> c_int {
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+    catch_panic(SRT_ERROR, || {
+        let (control, srctime) = match mctrl {
+            Some(mctrl) => mctrl.message_control(),
+            None => (MessageControl::default(), Instant::now()),
+        };
 
-    let mut l = sock.lock().unwrap();
-    match *l {
-        SocketData::Established(ref mut sock, _opts) => {
-            // TODO: implement blocking mode
-            // TODO: use _mctrl
-            if sock
-                .try_send(
-                    Instant::now(),
-                    Bytes::copy_from_slice(unsafe {
-                        from_raw_parts(buf as *const u8, len as usize)
-                    }),
-                )
-                .is_err()
-            {
-                return set_error(SRT_ELARGEMSG);
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
+
+        let mut l = sock.lock().unwrap();
+        match *l {
+            SocketData::Established(ref mut sock, _opts, _) => {
+                // TODO: implement blocking mode
+                if sock
+                    .get_mut()
+                    .try_send_message(
+                        control,
+                        srctime,
+                        Bytes::copy_from_slice(unsafe {
+                            from_raw_parts(buf as *const u8, len as usize)
+                        }),
+                    )
+                    .is_err()
+                {
+                    return set_error(SRT_ELARGEMSG);
+                }
             }
+            _ => return set_error(SRT_ENOCONN),
         }
-        _ => return set_error(SRT_ENOCONN),
-    }
 
-    len
+        len
+    })
 }
 
 /// Returns the number of bytes read
 #[no_mangle]
-pub extern "C" fn srt_recv(sock: SRTSOCKET, buf: *mut c_char, len: c_int) -> c_int {
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+pub extern "C" fn srt_recv(socknum: SRTSOCKET, buf: *mut c_char, len: c_int) -> c_int {
+    srt_recvmsg2(socknum, buf, len, None)
+}
 
-    let bytes = unsafe { from_raw_parts_mut(buf as *mut u8, len as usize) };
+#[no_mangle]
+pub extern "C" fn srt_recvmsg(sock: SRTSOCKET, buf: *mut c_char, len: c_int) -> c_int {
+    srt_recv(sock, buf, len)
+}
 
-    let mut l = sock.lock().unwrap();
-    if let SocketData::Established(ref mut sock, opts) = *l {
-        TOKIO_RUNTIME.block_on(async {
-            let d = if opts.rcv_syn {
-                // block
-                sock.next().await
-            } else {
-                // nonblock
-                match timeout(Duration::from_millis(10), sock.next()).await {
-                    Err(_) => return set_error(SRT_EASYNCRCV),
-                    Ok(d) => d,
+/// Returns the number of bytes read, and stores the source time and the sequence and message
+/// number of the message in `mctrl`
+#[no_mangle]
+pub extern "C" fn srt_recvmsg2(
+    socknum: SRTSOCKET,
+    buf: *mut c_char,
+    len: c_int,
+    mctrl: Option<&mut SRT_MSGCTRL>,
+) -> c_int {
+    catch_panic(SRT_ERROR, || {
+        let sock = match get_sock(socknum) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
+
+        let bytes = unsafe { from_raw_parts_mut(buf as *mut u8, len as usize) };
+
+        let mut l = sock.lock().unwrap();
+        if let SocketData::Established(ref mut sock, opts, _) = *l {
+            TOKIO_RUNTIME.block_on(async {
+                let d = if opts.rcv_syn {
+                    // block
+                    sock.next().await
+                } else {
+                    // nonblock
+                    match timeout(Duration::from_millis(10), sock.next()).await {
+                        Err(_) => return set_error(SRT_EASYNCRCV),
+                        Ok(d) => d,
+                    }
+                };
+                epoll::rearm(socknum, SRT_EPOLL_OPT::SRT_EPOLL_IN);
+
+                let (info, (srctime, recvd)) = match d {
+                    Some(Ok(d)) => d,
+                    Some(Err(e)) => return set_error_fmt(SRT_ECONNLOST, e), // TODO: not sure which error exactly here
+                    None => return set_error(SRT_ECONNLOST),
+                };
+
+                if let Some(mctrl) = mctrl {
+                    mctrl.srctime = srt_time(srctime);
+                    mctrl.pktseq = info.seq_number.as_raw() as i32;
+                    mctrl.msgno = info.message_number.as_raw() as i32;
                 }
-            };
 
-            let (_, recvd) = match d {
-                Some(Ok(d)) => d,
-                Some(Err(e)) => return set_error_fmt(SRT_ECONNLOST, e), // TODO: not sure which error exactly here
-                None => return set_error(SRT_ECONNLOST),
-            };
+                if bytes.len() < recvd.len() {
+                    error!("Receive buffer was not large enough, truncating...");
+                }
 
-            if bytes.len() < recvd.len() {
-                error!("Receive buffer was not large enough, truncating...");
-            }
+                let bytes_to_write = min(bytes.len(), recvd.len());
+                bytes[..bytes_to_write].copy_from_slice(&recvd[..bytes_to_write]);
+                bytes_to_write as c_int
+            })
+        } else {
+            set_error(SRT_ENOCONN)
+        }
+    })
+}
 
-            let bytes_to_write = min(bytes.len(), recvd.len());
-            bytes[..bytes_to_write].copy_from_slice(&recvd[..bytes_to_write]);
-            bytes_to_write as c_int
-        })
-    } else {
-        set_error(SRT_ENOCONN)
+/// The current time in microseconds, on the clock of the source time of messages
+#[no_mangle]
+pub extern "C" fn srt_time_now() -> i64 {
+    srt_time(Instant::now())
+}
+
+fn srt_time(instant: Instant) -> i64 {
+    instant
+        .saturating_duration_since(*SRT_CLOCK_EPOCH)
+        .as_micros() as i64
+}
+
+impl SRT_TRACEBSTATS {
+    /// The local measurements cover the interval since `start`, the totals at its beginning
+    fn new(
+        total: &SocketStatistics,
+        start: &SocketStatistics,
+        settings: &ConnectionSettings,
+    ) -> Self {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1_000.;
+        let mbps = |bytes: u64, duration: Duration| match duration.as_secs_f64() {
+            secs if secs > 0. => bytes as f64 * 8. / secs / 1_000_000.,
+            _ => 0.,
+        };
+        let interval = total.elapsed_time.saturating_sub(start.elapsed_time);
+        let belated_data = total.rx_belated_data - start.rx_belated_data;
+        let belated_time = total.rx_belated_time.saturating_sub(start.rx_belated_time);
+
+        SRT_TRACEBSTATS {
+            msTimeStamp: total.elapsed_time.as_millis() as i64,
+            pktSentTotal: total.tx_data as i64,
+            pktRecvTotal: total.rx_data as i64,
+            pktSndLossTotal: total.tx_loss_data as c_int,
+            pktRcvLossTotal: total.rx_loss_data as c_int,
+            pktRetransTotal: total.tx_retransmit_data as c_int,
+            pktSentACKTotal: total.tx_ack as c_int,
+            pktRecvACKTotal: total.rx_ack as c_int,
+            pktSentNAKTotal: total.tx_nak as c_int,
+            pktRecvNAKTotal: total.rx_nak as c_int,
+            usSndDurationTotal: total.tx_buffer_time.as_micros() as i64,
+            pktSndDropTotal: total.tx_dropped_data as c_int,
+            pktRcvDropTotal: total.rx_dropped_data as c_int,
+            pktRcvUndecryptTotal: total.rx_decrypt_errors as c_int,
+            byteSentTotal: total.tx_bytes,
+            byteRecvTotal: total.rx_bytes,
+            byteRcvLossTotal: total.rx_loss_bytes,
+            byteRetransTotal: total.tx_retransmit_bytes,
+            byteSndDropTotal: total.tx_dropped_bytes,
+            byteRcvDropTotal: total.rx_dropped_bytes,
+            byteRcvUndecryptTotal: total.rx_decrypt_error_bytes,
+
+            pktSent: (total.tx_data - start.tx_data) as i64,
+            pktRecv: (total.rx_data - start.rx_data) as i64,
+            pktSndLoss: (total.tx_loss_data - start.tx_loss_data) as c_int,
+            pktRcvLoss: (total.rx_loss_data - start.rx_loss_data) as c_int,
+            pktRetrans: (total.tx_retransmit_data - start.tx_retransmit_data) as c_int,
+            pktRcvRetrans: (total.rx_retransmit_data - start.rx_retransmit_data) as c_int,
+            pktSentACK: (total.tx_ack - start.tx_ack) as c_int,
+            pktRecvACK: (total.rx_ack - start.rx_ack) as c_int,
+            pktSentNAK: (total.tx_nak - start.tx_nak) as c_int,
+            pktRecvNAK: (total.rx_nak - start.rx_nak) as c_int,
+            mbpsSendRate: mbps(total.tx_bytes - start.tx_bytes, interval),
+            mbpsRecvRate: mbps(total.rx_bytes - start.rx_bytes, interval),
+            usSndDuration: total
+                .tx_buffer_time
+                .saturating_sub(start.tx_buffer_time)
+                .as_micros() as i64,
+            pktRcvAvgBelatedTime: match belated_data {
+                0 => 0.,
+                count => millis(belated_time) / count as f64,
+            },
+            pktRcvBelated: belated_data as i64,
+            pktSndDrop: (total.tx_dropped_data - start.tx_dropped_data) as c_int,
+            pktRcvDrop: (total.rx_dropped_data - start.rx_dropped_data) as c_int,
+            pktRcvUndecrypt: (total.rx_decrypt_errors - start.rx_decrypt_errors) as c_int,
+            byteSent: total.tx_bytes - start.tx_bytes,
+            byteRecv: total.rx_bytes - start.rx_bytes,
+            byteRcvLoss: total.rx_loss_bytes - start.rx_loss_bytes,
+            byteRetrans: total.tx_retransmit_bytes - start.tx_retransmit_bytes,
+            byteSndDrop: total.tx_dropped_bytes - start.tx_dropped_bytes,
+            byteRcvDrop: total.rx_dropped_bytes - start.rx_dropped_bytes,
+            byteRcvUndecrypt: total.rx_decrypt_error_bytes - start.rx_decrypt_error_bytes,
+
+            usPktSndPeriod: total.tx_snd_period.as_secs_f64() * 1_000_000.,
+            pktFlowWindow: total.tx_flow_window as c_int,
+            pktFlightSize: total.tx_unacknowledged_data as c_int,
+            msRTT: millis(max(total.tx_average_rtt, total.rx_average_rtt)),
+            byteAvailSndBuf: total.tx_buffer_available_bytes as c_int,
+            byteAvailRcvBuf: total.rx_buffer_available_bytes as c_int,
+            mbpsMaxBW: match settings.bandwidth {
+                LiveBandwidthMode::Max(rate) => mbps(rate.0, Duration::from_secs(1)),
+                _ => 0.,
+            },
+            pktSndBuf: total.tx_buffered_data as c_int,
+            byteSndBuf: total.tx_buffered_bytes as c_int,
+            msSndBuf: total.tx_buffered_time.as_millis() as c_int,
+            msSndTsbPdDelay: settings.send_tsbpd_latency.as_millis() as c_int,
+            pktRcvBuf: total.rx_acknowledged_data as c_int,
+            byteRcvBuf: total.rx_acknowledged_bytes as c_int,
+            msRcvBuf: total.rx_acknowledged_time.as_millis() as c_int,
+            msRcvTsbPdDelay: settings.recv_tsbpd_latency.as_millis() as c_int,
+
+            pktSndFilterExtraTotal: total.tx_filter_extra as c_int,
+            pktRcvFilterExtraTotal: total.rx_filter_extra as c_int,
+            pktRcvFilterSupplyTotal: total.rx_filter_supply as c_int,
+            pktRcvFilterLossTotal: total.rx_filter_loss as c_int,
+            pktSndFilterExtra: (total.tx_filter_extra - start.tx_filter_extra) as c_int,
+            pktRcvFilterExtra: (total.rx_filter_extra - start.rx_filter_extra) as c_int,
+            pktRcvFilterSupply: (total.rx_filter_supply - start.rx_filter_supply) as c_int,
+            pktRcvFilterLoss: (total.rx_filter_loss - start.rx_filter_loss) as c_int,
+
+            pktSentUniqueTotal: total.tx_unique_data as i64,
+            pktRecvUniqueTotal: total.rx_unique_data as i64,
+            byteSentUniqueTotal: total.tx_unique_bytes,
+            byteRecvUniqueTotal: total.rx_unique_bytes,
+            pktSentUnique: (total.tx_unique_data - start.tx_unique_data) as i64,
+            pktRecvUnique: (total.rx_unique_data - start.rx_unique_data) as i64,
+            byteSentUnique: total.tx_unique_bytes - start.tx_unique_bytes,
+            byteRecvUnique: total.rx_unique_bytes - start.rx_unique_bytes,
+
+            ..Default::default()
+        }
     }
 }
 
 #[no_mangle]
-pub extern "C" fn srt_recvmsg(sock: SRTSOCKET, buf: *mut c_char, len: c_int) -> c_int {
-    srt_recv(sock, buf, len)
+pub extern "C" fn srt_bstats(
+    sock: SRTSOCKET,
+    perf: Option<&mut SRT_TRACEBSTATS>,
+    clear: c_int,
+) -> c_int {
+    srt_bistats(sock, perf, clear, 0)
 }
 
+/// The statistics are those of the last update, which happens every statistics interval. The
+/// local measurements cover the time since the connection was established, or since the last call
+/// that set `clear`.
+///
+/// The buffer measurements are always instantaneous, so `instantaneous` has no effect.
 #[no_mangle]
-pub extern "C" fn srt_bstats(
-    _sock: SRTSOCKET,
-    _perf: &mut SRT_TRACEBSTATS,
-    _clear: c_int,
+pub extern "C" fn srt_bistats(
+    sock: SRTSOCKET,
+    perf: Option<&mut SRT_TRACEBSTATS>,
+    clear: c_int,
+    _instantaneous: c_int,
 ) -> c_int {
-    todo!()
+    catch_panic(SRT_ERROR, || {
+        let perf = match perf {
+            Some(perf) => perf,
+            None => return set_error(SRT_EINVPARAM),
+        };
+
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
+
+        let mut l = sock.lock().unwrap();
+        match *l {
+            SocketData::Established(ref sock, _, ref mut start) => {
+                let total = sock.get_ref().last_statistics();
+                *perf = SRT_TRACEBSTATS::new(&total, start, sock.get_ref().settings());
+                if clear != 0 {
+                    **start = total;
+                }
+            }
+            _ => return set_error(SRT_ENOCONN),
+        }
+
+        SRT_SUCCESS
+    })
 }
 
 fn insert_socket(data: SocketData) -> SRTSOCKET {
@@ -893,16 +1239,25 @@ fn insert_socket(data: SocketData) -> SRTSOCKET {
 
 #[no_mangle]
 pub extern "C" fn srt_create_socket() -> SRTSOCKET {
-    insert_socket(SocketData::Initialized(
-        Default::default(),
-        None,
-        Default::default(),
-    ))
+    catch_panic(SRT_INVALID_SOCK, || {
+        insert_socket(SocketData::Initialized(
+            Default::default(),
+            None,
+            Default::default(),
+        ))
+    })
 }
 
 #[no_mangle]
-pub extern "C" fn srt_setloglevel(_ll: c_int) {
-    todo!()
+pub extern "C" fn srt_setloglevel(ll: c_int) {
+    // the levels are those of syslog, LOG_ERR (3) to LOG_DEBUG (7)
+    log::set_max_level(match ll {
+        i32::MIN..=3 => LevelFilter::Error,
+        4 => LevelFilter::Warn,
+        5 | 6 => LevelFilter::Info,
+        7 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    });
 }
 
 /// # Safety
@@ -956,9 +1311,47 @@ pub enum SRT_KM_STATE {
     SRT_KM_S_BADSECRET = 4, //Stream encrypted and wrong secret, cannot decrypt Keying Material
 }
 
+impl From<KeyMaterialState> for SRT_KM_STATE {
+    fn from(state: KeyMaterialState) -> Self {
+        use SRT_KM_STATE::*;
+        match state {
+            KeyMaterialState::Unsecured => SRT_KM_S_UNSECURED,
+            KeyMaterialState::Securing => SRT_KM_S_SECURING,
+            KeyMaterialState::Secured => SRT_KM_S_SECURED,
+            KeyMaterialState::NoSecret => SRT_KM_S_NOSECRET,
+            KeyMaterialState::BadSecret => SRT_KM_S_BADSECRET,
+        }
+    }
+}
+
 #[no_mangle]
-pub extern "C" fn srt_getsockstate(_sock: SRTSOCKET) -> SRT_SOCKSTATUS {
-    todo!()
+pub extern "C" fn srt_getsockstate(sock: SRTSOCKET) -> SRT_SOCKSTATUS {
+    catch_panic(SRT_SOCKSTATUS::SRTS_NONEXIST, || {
+        use SocketData::*;
+        use SRT_SOCKSTATUS::*;
+
+        let sock = match get_sock(sock) {
+            None => return SRTS_NONEXIST,
+            Some(sock) => sock,
+        };
+
+        let mut l = sock.lock().unwrap();
+        match &mut *l {
+            Initialized(options, _, _) if options.connect.local == Connect::default().local => {
+                SRTS_INIT
+            }
+            Initialized(..) => SRTS_OPENED,
+            ConnectingNonBlocking(..) | Accepting(..) => SRTS_CONNECTING,
+            // the received data ends once the connection is broken
+            Established(sock, _, _) => match Pin::new(&mut **sock).peek().now_or_never() {
+                Some(None | Some(Err(_))) => SRTS_BROKEN,
+                Some(Some(Ok(_))) | None => SRTS_CONNECTED,
+            },
+            Listening(..) => SRTS_LISTENING,
+            ConnectFailed(_) | InvalidIntermediateState => SRTS_BROKEN,
+            Closed => SRTS_CLOSED,
+        }
+    })
 }
 
 #[repr(C)]
@@ -967,16 +1360,6 @@ pub struct SRT_EPOLL_EVENT {
     events: c_int,
 }
 
-#[no_mangle]
-pub extern "C" fn srt_epoll_uwait(
-    _eid: c_int,
-    _fdsSet: *mut SRT_EPOLL_EVENT,
-    _fdsSize: c_int,
-    _msTimeOut: i64,
-) -> c_int {
-    todo!()
-}
-
 unsafe fn extract_int(val: Option<NonNull<()>>, len: c_int) -> Option<c_int> {
     if let (Some(ptr), 4) = (val, len) {
         return Some(*ptr.cast::<c_int>().as_ref());
@@ -1011,6 +1394,238 @@ unsafe fn extract_str(val: Option<NonNull<()>>, len: c_int) -> Option<String> {
     }
 }
 
+unsafe fn extract_linger(val: Option<NonNull<()>>, len: c_int) -> Option<libc::linger> {
+    match val {
+        Some(ptr) if len as usize == size_of::<libc::linger>() => {
+            Some(*ptr.cast::<libc::linger>().as_ref())
+        }
+        _ => None,
+    }
+}
+
+// the default of SRTO_OHEADBW, used when switching to a bandwidth mode relative to the input rate
+const DEFAULT_OVERHEAD: Percent = Percent(25);
+
+// the minimum SRTO_MSS, which fits the UDP/IP headers and the handshake
+const MIN_MSS: u64 = 76;
+
+// the size of the UDP/IP and SRT headers, which SRTO_PAYLOADSIZE must leave room for in SRTO_MSS
+const DATA_HEADERS_SIZE: u64 = 44;
+
+/// Applies an option to the options of a socket that is not connected yet. Only the ranges of
+/// individual values are checked here, the options are validated by the caller.
+unsafe fn set_socket_option(
+    o: &mut SocketOptions,
+    opt: SRT_SOCKOPT,
+    optval: Option<NonNull<()>>,
+    optlen: c_int,
+) -> Result<(), SRT_ERRNO> {
+    use LiveBandwidthMode::*;
+    use SRT_SOCKOPT::*;
+
+    let int = || extract_int(optval, optlen).ok_or(SRT_EINVPARAM);
+    let int64 = || extract_i64(optval, optlen).ok_or(SRT_EINVPARAM);
+    let boolean = || extract_bool(optval, optlen).ok_or(SRT_EINVPARAM);
+    let string = || extract_str(optval, optlen).ok_or(SRT_EINVPARAM);
+    let unsigned = || int().and_then(|i| u64::try_from(i).map_err(|_| SRT_EINVPARAM));
+    let unsigned64 = || int64().and_then(|i| u64::try_from(i).map_err(|_| SRT_EINVPARAM));
+    let millis = || unsigned().map(Duration::from_millis);
+    let overhead = |bandwidth: &LiveBandwidthMode| match bandwidth {
+        Input { overhead, .. } | Estimated { overhead, .. } => *overhead,
+        Max(_) | Unlimited => DEFAULT_OVERHEAD,
+    };
+
+    match opt {
+        SRTO_MSS => match unsigned()? {
+            mss if mss >= MIN_MSS => o.session.max_segment_size = PacketSize(mss),
+            _ => return Err(SRT_EINVPARAM),
+        },
+        SRTO_FC => o.sender.flow_control_window_size = PacketCount(unsigned()?),
+        SRTO_SNDBUF => match unsigned()? {
+            0 => return Err(SRT_EINVPARAM),
+            size => o.sender.buffer_size = ByteCount(size),
+        },
+        SRTO_RCVBUF => o.receiver.buffer_size = ByteCount(unsigned()?),
+        SRTO_LINGER => {
+            let linger = extract_linger(optval, optlen).ok_or(SRT_EINVPARAM)?;
+            o.connect.linger = match (linger.l_onoff, u64::try_from(linger.l_linger)) {
+                (0, _) => None,
+                (_, Ok(secs)) => Some(Duration::from_secs(secs)),
+                (_, Err(_)) => return Err(SRT_EINVPARAM),
+            };
+        }
+        SRTO_UDP_SNDBUF => o.connect.udp_send_buffer_size = ByteCount(unsigned()?),
+        SRTO_UDP_RCVBUF => o.connect.udp_recv_buffer_size = ByteCount(unsigned()?),
+        SRTO_MAXBW => {
+            o.sender.bandwidth = match int64()? {
+                -1 => Unlimited,
+                // relative to the input rate, set with SRTO_INPUTBW or estimated
+                0 => match o.sender.bandwidth {
+                    Max(_) | Unlimited => Estimated {
+                        expected: DataRate(0),
+                        overhead: DEFAULT_OVERHEAD,
+                    },
+                    ref relative => relative.clone(),
+                },
+                rate if rate > 0 => Max(DataRate(rate as u64)),
+                _ => return Err(SRT_EINVPARAM),
+            }
+        }
+        SRTO_INPUTBW => {
+            let overhead = overhead(&o.sender.bandwidth);
+            o.sender.bandwidth = match (unsigned64()?, &o.sender.bandwidth) {
+                (0, Estimated { expected, .. }) => Estimated {
+                    expected: *expected,
+                    overhead,
+                },
+                (0, _) => Estimated {
+                    expected: DataRate(0),
+                    overhead,
+                },
+                (rate, _) => Input {
+                    rate: DataRate(rate),
+                    overhead,
+                },
+            }
+        }
+        SRTO_OHEADBW => {
+            let percent = match unsigned()? {
+                percent @ 5..=100 => Percent(percent),
+                _ => return Err(SRT_EINVPARAM),
+            };
+            o.sender.bandwidth = match o.sender.bandwidth {
+                Input { rate, .. } => Input {
+                    rate,
+                    overhead: percent,
+                },
+                Estimated { expected, .. } => Estimated {
+                    expected,
+                    overhead: percent,
+                },
+                Max(_) | Unlimited => Estimated {
+                    expected: DataRate(0),
+                    overhead: percent,
+                },
+            }
+        }
+        SRTO_MININPUTBW => {
+            o.sender.bandwidth = Estimated {
+                expected: DataRate(unsigned64()?),
+                overhead: overhead(&o.sender.bandwidth),
+            }
+        }
+        SRTO_CONNTIMEO => o.connect.timeout = millis()?,
+        SRTO_LATENCY => {
+            let latency = millis()?;
+            o.receiver.latency = latency;
+            o.sender.peer_latency = latency;
+        }
+        SRTO_RCVLATENCY => o.receiver.latency = millis()?,
+        SRTO_PEERLATENCY => o.sender.peer_latency = millis()?,
+        SRTO_PASSPHRASE => {
+            // an empty passphrase turns encryption off
+            o.encryption.passphrase = match string()? {
+                passphrase if passphrase.is_empty() => None,
+                passphrase => Some(Passphrase::try_from(passphrase).map_err(|_| SRT_EINVPARAM)?),
+            }
+        }
+        SRTO_PBKEYLEN => {
+            o.encryption.key_size = u8::try_from(int()?)
+                .ok()
+                .and_then(|size| KeySize::try_from(size).ok())
+                .ok_or(SRT_EINVPARAM)?
+        }
+        SRTO_KMREFRESHRATE => {
+            let period = unsigned()?;
+            // like libsrt, shorten the pre-announcement period to fit the new refresh period
+            let pre_announcement_period = &mut o.encryption.km_refresh.pre_announcement_period;
+            if pre_announcement_period.0 > period.saturating_sub(1) / 2 {
+                *pre_announcement_period = PacketCount(period.saturating_sub(1) / 2);
+            }
+            o.encryption.km_refresh.period = PacketCount(period);
+        }
+        SRTO_KMPREANNOUNCE => {
+            o.encryption.km_refresh.pre_announcement_period = PacketCount(unsigned()?)
+        }
+        SRTO_ENFORCEDENCRYPTION => o.encryption.enforced_encryption = boolean()?,
+        SRTO_CRYPTOMODE => {
+            o.encryption.crypto_mode = u8::try_from(int()?)
+                .ok()
+                .and_then(|mode| CryptoMode::try_from(mode).ok())
+                .ok_or(SRT_EINVPARAM)?
+        }
+        SRTO_IPTTL => o.connect.ip_ttl = u8::try_from(int()?).map_err(|_| SRT_EINVPARAM)?,
+        SRTO_IPV6ONLY => {
+            o.connect.ipv6_only = match int()? {
+                0 => false,
+                1 => true,
+                _ => return Err(SRT_EINVPARAM),
+            }
+        }
+        SRTO_TLPKTDROP => o.receiver.too_late_packet_drop = boolean()?,
+        // turning the sender drop off with -1 is not supported
+        SRTO_SNDDROPDELAY => o.sender.drop_delay = millis()?,
+        SRTO_NAKREPORT => o.receiver.nak_report = boolean()?,
+        SRTO_DRIFTTRACER => o.receiver.drift_tracer = boolean()?,
+        SRTO_LOSSMAXTTL => o.receiver.reorder_tolerance_max = PacketCount(unsigned()?),
+        SRTO_MINVERSION => {
+            o.connect.min_version =
+                SrtVersion::parse(u32::try_from(int()?).map_err(|_| SRT_EINVPARAM)?)
+        }
+        SRTO_CONGESTION => {
+            o.session.transmission_type = match string()?.as_str() {
+                "live" => TransmissionType::Live,
+                "file" => TransmissionType::File,
+                _ => return Err(SRT_EINVPARAM),
+            }
+        }
+        SRTO_MESSAGEAPI => o.session.message_api = boolean()?,
+        SRTO_TSBPDMODE => o.session.tsbpd_mode = boolean()?,
+        SRTO_PAYLOADSIZE => match unsigned()? {
+            size if size > 0 && size + DATA_HEADERS_SIZE <= o.session.max_segment_size.0 => {
+                o.sender.max_payload_size = PacketSize(size)
+            }
+            _ => return Err(SRT_EINVPARAM),
+        },
+        SRTO_TRANSTYPE => {
+            // like libsrt, this sets the defaults of the other options for the transmission type
+            let live = match int()? {
+                t if t == SRT_TRANSTYPE::SRTT_LIVE as c_int => true,
+                t if t == SRT_TRANSTYPE::SRTT_FILE as c_int => false,
+                _ => return Err(SRT_EINVPARAM),
+            };
+            o.session.transmission_type = if live {
+                TransmissionType::Live
+            } else {
+                TransmissionType::File
+            };
+            o.session.message_api = live;
+            o.session.tsbpd_mode = live;
+            o.receiver.too_late_packet_drop = live;
+            o.receiver.nak_report = live;
+            if live {
+                o.sender.max_payload_size = Sender::default().max_payload_size;
+            }
+        }
+        SRTO_PEERIDLETIMEO => o.session.peer_idle_timeout = millis()?,
+        SRTO_PACKETFILTER => {
+            o.session.packet_filter = match string()? {
+                filter if filter.is_empty() => None,
+                filter => Some(filter.parse().map_err(|_| SRT_EINVPARAM)?),
+            }
+        }
+        SRTO_RETRANSMITALGO => {
+            o.sender.intensive_retransmission = match int()? {
+                0 => true,
+                1 => false,
+                _ => return Err(SRT_EINVPARAM),
+            }
+        }
+        _ => return Err(SRT_EINVOP),
+    }
+    Ok(())
+}
+
 /// # Safety
 /// `optval` must point to a structure of the right type depending on `optname`, according to
 /// [the option documentation](https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md)
@@ -1021,109 +1636,235 @@ pub unsafe extern "C" fn srt_setsockflag(
     optval: *const (),
     optlen: c_int,
 ) -> c_int {
-    let optval = NonNull::new(optval as *mut ());
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
-
-    let mut sock = sock.lock().unwrap();
-    use SRT_SOCKOPT::*;
+    catch_panic(SRT_ERROR, || {
+        let optval = NonNull::new(optval as *mut ());
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-    if let SocketData::Accepting(ref mut params) = *sock {
-        match opt {
-            SRTO_PASSPHRASE => {
-                *params = Some(KeySettings {
-                    passphrase: match extract_str(optval, optlen).map(Passphrase::try_from) {
-                        Some(Ok(p)) => p,
+        let mut sock = sock.lock().unwrap();
+        use SRT_SOCKOPT::*;
+
+        if let SocketData::Accepting(ref mut params, ref mut recv_latency) = *sock {
+            match opt {
+                SRTO_PASSPHRASE => {
+                    *params = Some(KeySettings {
+                        passphrase: match extract_str(optval, optlen).map(Passphrase::try_from) {
+                            Some(Ok(p)) => p,
+                            Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
+                        },
+                        key_size: params
+                            .as_ref()
+                            .map(|p| p.key_size)
+                            .unwrap_or(KeySize::Unspecified),
+                    })
+                }
+                SRTO_RCVLATENCY => {
+                    *recv_latency = match extract_int(optval, optlen).map(u64::try_from) {
+                        Some(Ok(ms)) => Some(Duration::from_millis(ms)),
                         Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-                    },
-                    key_size: params
-                        .as_ref()
-                        .map(|p| p.key_size)
-                        .unwrap_or(KeySize::Unspecified),
-                })
-            }
-            SRTO_RCVLATENCY => {
-                warn!("Unimplemented! This would require a hook where there currently is none!")
+                    }
+                }
+                _ => {
+                    return set_error_fmt(
+                        SRT_EINVOP,
+                        format_args!("{:?} can not be set from the listen callback", opt),
+                    )
+                }
             }
-            _ => unimplemented!("{:?}", opt),
-        }
-        return SRT_SUCCESS;
-    }
-
-    if opt == SRTO_STREAMID {
-        if let SocketData::Initialized(_, ref mut init, _) = *sock {
-            *init = Some(match extract_str(optval, optlen).map(StreamId::try_from) {
-                Some(Ok(sid)) => sid,
-                Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-            });
             return SRT_SUCCESS;
         }
-        return set_error(SRT_ECONNSOCK);
-    }
 
-    match (opt, sock.opts_mut()) {
-        (SRTO_SENDER, (_, _)) => {}
-        (SRTO_RCVSYN, (Some(o), _)) => {
-            o.rcv_syn = match extract_bool(optval, optlen) {
-                Some(e) => e,
-                None => return set_error(SRT_EINVPARAM),
+        if opt == SRTO_STREAMID {
+            if let SocketData::Initialized(_, ref mut init, _) = *sock {
+                *init = Some(match extract_str(optval, optlen).map(StreamId::try_from) {
+                    Some(Ok(sid)) => sid,
+                    Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
+                });
+                return SRT_SUCCESS;
             }
+            return set_error(SRT_ECONNSOCK);
         }
-        (SRTO_SNDSYN, (Some(o), _)) => {
-            o.snd_syn = match extract_bool(optval, optlen) {
-                Some(e) => e,
-                None => return set_error(SRT_EINVPARAM),
+
+        match (opt, sock.opts_mut()) {
+            (SRTO_SENDER, (_, _)) => {}
+            (SRTO_RCVSYN, (Some(o), _)) => {
+                o.rcv_syn = match extract_bool(optval, optlen) {
+                    Some(e) => e,
+                    None => return set_error(SRT_EINVPARAM),
+                }
+            }
+            (SRTO_SNDSYN, (Some(o), _)) => {
+                o.snd_syn = match extract_bool(optval, optlen) {
+                    Some(e) => e,
+                    None => return set_error(SRT_EINVPARAM),
+                }
+            }
+            (opt, (_, Some(o))) => {
+                // work on a copy, so an invalid value leaves the options untouched
+                let mut options = o.clone();
+                if let Err(e) = set_socket_option(&mut options, opt, optval, optlen) {
+                    return set_error_fmt(e, format_args!("Failed to set {:?}", opt));
+                }
+                // the limits between options (like SRTO_RCVBUF and SRTO_FC) depend on the order they
+                // are set in, so those are only checked by srt_connect and srt_listen
+                let valid = options
+                    .connect
+                    .is_valid()
+                    .and_then(|_| options.session.is_valid())
+                    .and_then(|_| options.encryption.is_valid())
+                    .and_then(|_| options.sender.is_valid())
+                    .and_then(|_| options.receiver.is_valid());
+                if let Err(e) = valid {
+                    return set_error_fmt(SRT_EINVPARAM, format_args!("Invalid {:?}: {}", opt, e));
+                }
+                *o = options;
+            }
+            (opt, _) => {
+                return set_error_fmt(
+                    SRT_ECONNSOCK,
+                    format_args!("{:?} can only be set before connecting", opt),
+                )
             }
         }
-        (SRTO_CONNTIMEO, (_, Some(o))) => {
-            o.connect.timeout =
-                Duration::from_millis(match extract_int(optval, optlen).map(u64::try_from) {
-                    Some(Ok(to)) => to,
-                    Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-                });
+        SRT_SUCCESS
+    })
+}
+
+enum Val<'a> {
+    Bool(bool),
+    Int(c_int),
+    Int64(i64),
+    Linger(libc::linger),
+    Str(Cow<'a, str>),
+}
+
+fn millis(duration: Duration) -> Val<'static> {
+    Val::Int(duration.as_millis() as c_int)
+}
+
+fn linger(linger: Option<Duration>) -> Val<'static> {
+    Val::Linger(libc::linger {
+        l_onoff: linger.is_some() as c_int,
+        l_linger: linger.map_or(0, |linger| linger.as_secs() as c_int),
+    })
+}
+
+fn bandwidth_option(bandwidth: &LiveBandwidthMode, opt: SRT_SOCKOPT) -> Option<Val<'static>> {
+    use LiveBandwidthMode::*;
+    use Val::*;
+    use SRT_SOCKOPT::*;
+
+    Some(match (opt, bandwidth) {
+        (SRTO_MAXBW, Max(rate)) => Int64(rate.0 as i64),
+        (SRTO_MAXBW, Unlimited) => Int64(-1),
+        (SRTO_MAXBW, Input { .. } | Estimated { .. }) => Int64(0),
+        (SRTO_INPUTBW, Input { rate, .. }) => Int64(rate.0 as i64),
+        (SRTO_INPUTBW, _) => Int64(0),
+        (SRTO_OHEADBW, Input { overhead, .. } | Estimated { overhead, .. }) => {
+            Int(overhead.0 as c_int)
         }
-        (SRTO_TSBPDMODE, _) => match extract_bool(optval, optlen) {
-            Some(true) => {}
-            Some(false) => return set_error(SRT_EINVPARAM), // tsbpd=false is not implemented
-            None => return set_error(SRT_EINVPARAM),
-        },
-        (SRTO_PASSPHRASE, (_, Some(o))) => {
-            o.encryption.passphrase = match extract_str(optval, optlen).map(Passphrase::try_from) {
-                Some(Ok(p)) => Some(p),
-                Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-            };
+        (SRTO_OHEADBW, Max(_) | Unlimited) => Int(DEFAULT_OVERHEAD.0 as c_int),
+        (SRTO_MININPUTBW, Max(rate) | Input { rate, .. } | Estimated { expected: rate, .. }) => {
+            Int64(rate.0 as i64)
         }
-        (SRTO_RCVLATENCY, (_, Some(o))) => {
-            o.receiver.latency =
-                Duration::from_millis(match extract_int(optval, optlen).map(u64::try_from) {
-                    Some(Ok(o)) => o,
-                    Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-                });
+        (SRTO_MININPUTBW, Unlimited) => Int64(0),
+        _ => return None,
+    })
+}
+
+fn transmission_type_option(
+    transmission_type: TransmissionType,
+    opt: SRT_SOCKOPT,
+) -> Option<Val<'static>> {
+    use SRT_SOCKOPT::*;
+
+    Some(match (opt, transmission_type) {
+        (SRTO_TRANSTYPE, TransmissionType::Live) => Val::Int(SRT_TRANSTYPE::SRTT_LIVE as c_int),
+        (SRTO_TRANSTYPE, TransmissionType::File) => Val::Int(SRT_TRANSTYPE::SRTT_FILE as c_int),
+        (SRTO_CONGESTION, _) => Val::Str(transmission_type.congestion_control_type().into()),
+        _ => return None,
+    })
+}
+
+/// The value of an option of a socket that is not connected yet
+fn socket_option(o: &SocketOptions, opt: SRT_SOCKOPT) -> Option<Val<'static>> {
+    use Val::*;
+    use SRT_SOCKOPT::*;
+
+    Some(match opt {
+        SRTO_MSS => Int(o.session.max_segment_size.0 as c_int),
+        SRTO_FC => Int(o.sender.flow_control_window_size.0 as c_int),
+        SRTO_SNDBUF => Int(o.sender.buffer_size.0 as c_int),
+        SRTO_RCVBUF => Int(o.receiver.buffer_size.0 as c_int),
+        SRTO_LINGER => linger(o.connect.linger),
+        SRTO_UDP_SNDBUF => Int(o.connect.udp_send_buffer_size.0 as c_int),
+        SRTO_UDP_RCVBUF => Int(o.connect.udp_recv_buffer_size.0 as c_int),
+        SRTO_MAXBW | SRTO_INPUTBW | SRTO_OHEADBW | SRTO_MININPUTBW => {
+            return bandwidth_option(&o.sender.bandwidth, opt)
         }
-        (SRTO_PEERLATENCY, (_, Some(o))) => {
-            o.sender.peer_latency =
-                Duration::from_millis(match extract_int(optval, optlen).map(u64::try_from) {
-                    Some(Ok(o)) => o,
-                    Some(Err(_)) | None => return set_error(SRT_EINVPARAM),
-                });
+        SRTO_CONNTIMEO => millis(o.connect.timeout),
+        SRTO_LATENCY | SRTO_RCVLATENCY => millis(o.receiver.latency),
+        SRTO_PEERLATENCY => millis(o.sender.peer_latency),
+        SRTO_PBKEYLEN => Int(o.encryption.key_size.as_raw() as c_int),
+        SRTO_KMREFRESHRATE => Int(o.encryption.km_refresh.period.0 as c_int),
+        SRTO_KMPREANNOUNCE => Int(o.encryption.km_refresh.pre_announcement_period.0 as c_int),
+        SRTO_ENFORCEDENCRYPTION => Bool(o.encryption.enforced_encryption),
+        SRTO_CRYPTOMODE => Int(o.encryption.crypto_mode as c_int),
+        SRTO_IPTTL => Int(o.connect.ip_ttl as c_int),
+        SRTO_IPV6ONLY => Int(o.connect.ipv6_only as c_int),
+        SRTO_TLPKTDROP => Bool(o.receiver.too_late_packet_drop),
+        SRTO_SNDDROPDELAY => millis(o.sender.drop_delay),
+        SRTO_NAKREPORT => Bool(o.receiver.nak_report),
+        SRTO_DRIFTTRACER => Bool(o.receiver.drift_tracer),
+        SRTO_LOSSMAXTTL => Int(o.receiver.reorder_tolerance_max.0 as c_int),
+        SRTO_MINVERSION => Int(o.connect.min_version.to_u32() as c_int),
+        SRTO_TRANSTYPE | SRTO_CONGESTION => {
+            return transmission_type_option(o.session.transmission_type, opt)
         }
-        (SRTO_MININPUTBW, (_, Some(o))) => {
-            o.sender.bandwidth = LiveBandwidthMode::Estimated {
-                expected: match extract_i64(optval, optlen) {
-                    Some(i) => DataRate(i as u64),
-                    None => return set_error(SRT_EINVPARAM),
-                },
-                overhead: match o.sender.bandwidth {
-                    LiveBandwidthMode::Estimated { overhead, .. } => overhead,
-                    _ => Percent(25), // TODO: what should this be
-                },
-            }
+        SRTO_MESSAGEAPI => Bool(o.session.message_api),
+        SRTO_TSBPDMODE => Bool(o.session.tsbpd_mode),
+        SRTO_PAYLOADSIZE => Int(o.sender.max_payload_size.0 as c_int),
+        SRTO_PEERIDLETIMEO => millis(o.session.peer_idle_timeout),
+        SRTO_PACKETFILTER => Str(match &o.session.packet_filter {
+            Some(filter) => filter.to_string().into(),
+            None => "".into(),
+        }),
+        SRTO_RETRANSMITALGO => Int(if o.sender.intensive_retransmission {
+            0
+        } else {
+            1
+        }),
+        _ => return None,
+    })
+}
+
+/// The value of an option of a connected socket, as negotiated during the handshake
+fn connection_option(settings: &ConnectionSettings, opt: SRT_SOCKOPT) -> Option<Val<'static>> {
+    use Val::*;
+    use SRT_SOCKOPT::*;
+
+    Some(match opt {
+        SRTO_FC => Int(settings.max_flow_size.0 as c_int),
+        SRTO_LINGER => linger(settings.linger),
+        SRTO_MAXBW | SRTO_INPUTBW | SRTO_OHEADBW | SRTO_MININPUTBW => {
+            return bandwidth_option(&settings.bandwidth, opt)
         }
-        (o, _) => unimplemented!("{:?}", o),
-    }
-    SRT_SUCCESS
+        SRTO_LATENCY | SRTO_RCVLATENCY => millis(settings.recv_tsbpd_latency),
+        SRTO_PEERLATENCY => millis(settings.send_tsbpd_latency),
+        SRTO_TRANSTYPE | SRTO_CONGESTION => {
+            return transmission_type_option(settings.transmission_type, opt)
+        }
+        SRTO_MESSAGEAPI => Bool(settings.message_api),
+        SRTO_TSBPDMODE => Bool(settings.recv_tsbpd_mode),
+        SRTO_PAYLOADSIZE => Int(settings.max_packet_size.0 as c_int),
+        SRTO_PACKETFILTER => Str(match &settings.packet_filter {
+            Some(filter) => filter.to_string().into(),
+            None => "".into(),
+        }),
+        _ => return None,
+    })
 }
 
 /// # Safety
@@ -1137,103 +1878,107 @@ pub unsafe extern "C" fn srt_getsockflag(
     optval: *mut (),
     optlen: Option<&mut c_int>,
 ) -> c_int {
-    let optval = NonNull::new(optval);
-    let (optval, optlen) = match (optval, optlen) {
-        (Some(optval), Some(optlen)) => (optval, optlen),
-        _ => return set_error(SRT_EINVPARAM),
-    };
+    catch_panic(SRT_ERROR, || {
+        let optval = NonNull::new(optval);
+        let (optval, optlen) = match (optval, optlen) {
+            (Some(optval), Some(optlen)) => (optval, optlen),
+            _ => return set_error(SRT_EINVPARAM),
+        };
 
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-    let l = sock.lock().unwrap();
+        let l = sock.lock().unwrap();
 
-    enum Val<'a> {
-        Bool(bool),
-        Int(c_int),
-        Int64(i64),
-        Str(&'a str),
-    }
-    use LiveBandwidthMode::*;
-    use Val::*;
-    use SRT_SOCKOPT::*;
+        use Val::*;
+        use SRT_SOCKOPT::*;
 
-    let val = if opt == SRTO_STREAMID {
-        match &*l {
-            SocketData::Initialized(_, sid, _) => {
-                Str(sid.as_ref().map(|s| s.as_str()).unwrap_or(""))
-            }
-            SocketData::Established(sock, _) => {
-                Str(sock.settings().stream_id.as_deref().unwrap_or(""))
-            }
-            _ => return set_error(SRT_EBOUNDSOCK),
-        }
-    } else {
-        match (opt, l.api_opts(), l.sock_opts(), l.conn_settings()) {
-            (SRTO_RCVSYN, Some(opts), _, _) => Bool(opts.rcv_syn),
-            (SRTO_SNDSYN, Some(opts), _, _) => Bool(opts.snd_syn),
-            (SRTO_CONNTIMEO, _, Some(opts), _) => Int(opts.connect.timeout.as_millis() as c_int),
-            (SRTO_RCVLATENCY, _, Some(opts), _) => Int(opts.receiver.latency.as_millis() as c_int),
-            (SRTO_RCVLATENCY, _, _, Some(cs)) => Int(cs.recv_tsbpd_latency.as_millis() as c_int),
-            (SRTO_PEERLATENCY, _, Some(opts), _) => {
-                Int(opts.sender.peer_latency.as_millis() as c_int)
+        let val = if opt == SRTO_STREAMID {
+            match &*l {
+                SocketData::Initialized(_, sid, _) => {
+                    Str(sid.as_ref().map(|s| s.as_str()).unwrap_or("").into())
+                }
+                SocketData::Established(sock, _, _) => Str(sock
+                    .get_ref()
+                    .settings()
+                    .stream_id
+                    .as_deref()
+                    .unwrap_or("")
+                    .into()),
+                _ => return set_error(SRT_EBOUNDSOCK),
             }
-            (SRTO_PEERLATENCY, _, _, Some(cs)) => Int(cs.send_tsbpd_latency.as_millis() as c_int),
-            (
-                SRTO_MININPUTBW,
-                _,
-                Some(SocketOptions {
-                    sender: Sender { bandwidth, .. },
-                    ..
-                }),
-                _,
-            )
-            | (SRTO_MININPUTBW, _, _, Some(ConnectionSettings { bandwidth, .. })) => {
-                Int64(match bandwidth {
-                    Max(rate) | Input { rate, .. } | Estimated { expected: rate, .. } => {
-                        rate.0 as i64
-                    }
-                    Unlimited => 0,
-                })
+        } else if matches!(opt, SRTO_KMSTATE | SRTO_SNDKMSTATE | SRTO_RCVKMSTATE) {
+            let state = match &*l {
+                // a duplex connection reports the receiving side for SRTO_KMSTATE
+                SocketData::Established(sock, _, _) if opt == SRTO_SNDKMSTATE => {
+                    sock.get_ref().send_key_material_state()
+                }
+                SocketData::Established(sock, _, _) => sock.get_ref().recv_key_material_state(),
+                _ => KeyMaterialState::Unsecured,
+            };
+            Int(SRT_KM_STATE::from(state) as c_int)
+        } else {
+            let val = match (opt, l.api_opts(), l.sock_opts(), l.conn_settings()) {
+                (SRTO_RCVSYN, Some(opts), _, _) => Some(Bool(opts.rcv_syn)),
+                (SRTO_SNDSYN, Some(opts), _, _) => Some(Bool(opts.snd_syn)),
+                (SRTO_VERSION, _, _, _) => Some(Int(SrtVersion::CURRENT.to_u32() as c_int)),
+                (opt, _, Some(opts), _) => socket_option(opts, opt),
+                (opt, _, _, Some(settings)) => connection_option(settings, opt),
+                _ => None,
+            };
+            match val {
+                Some(val) => val,
+                None => {
+                    return set_error_fmt(
+                        SRT_EINVOP,
+                        format_args!("{:?} is not available in this socket state", opt),
+                    )
+                }
             }
-            _ => unimplemented!("{:?} {:?}", opt, *l),
-        }
-    };
+        };
 
-    match val {
-        Bool(b) => {
-            if *optlen != size_of::<c_int>() as c_int {
-                return set_error(SRT_EINVPARAM);
+        match val {
+            Bool(b) => {
+                if *optlen != size_of::<c_int>() as c_int {
+                    return set_error(SRT_EINVPARAM);
+                }
+                *optval.cast::<c_int>().as_mut() = if b { 1 } else { 0 };
             }
-            *optval.cast::<c_int>().as_mut() = if b { 1 } else { 0 };
-        }
-        Int(i) => {
-            if *optlen < size_of::<c_int>() as c_int {
-                return set_error(SRT_EINVPARAM);
+            Int(i) => {
+                if *optlen < size_of::<c_int>() as c_int {
+                    return set_error(SRT_EINVPARAM);
+                }
+                *optlen = size_of::<c_int>() as c_int;
+                *optval.cast::<c_int>().as_mut() = i;
             }
-            *optlen = size_of::<c_int>() as c_int;
-            *optval.cast::<c_int>().as_mut() = i;
-        }
-        Int64(i) => {
-            if *optlen < size_of::<i64>() as c_int {
-                return set_error(SRT_EINVPARAM);
+            Int64(i) => {
+                if *optlen < size_of::<i64>() as c_int {
+                    return set_error(SRT_EINVPARAM);
+                }
+                *optlen = size_of::<i64>() as c_int;
+                *optval.cast::<i64>().as_mut() = i;
             }
-            *optlen = size_of::<i64>() as c_int;
-            *optval.cast::<i64>().as_mut() = i;
-        }
-        Str(str) => {
-            if *optlen < (str.as_bytes().len() + 1) as c_int {
-                return set_error(SRT_EINVPARAM);
+            Linger(linger) => {
+                if *optlen < size_of::<libc::linger>() as c_int {
+                    return set_error(SRT_EINVPARAM);
+                }
+                *optlen = size_of::<libc::linger>() as c_int;
+                *optval.cast::<libc::linger>().as_mut() = linger;
+            }
+            Str(str) => {
+                if *optlen < (str.as_bytes().len() + 1) as c_int {
+                    return set_error(SRT_EINVPARAM);
+                }
+                let optval = from_raw_parts_mut(optval.cast::<u8>().as_mut(), *optlen as usize);
+                optval[..str.as_bytes().len()].copy_from_slice(str.as_bytes());
+                optval[str.as_bytes().len()] = 0; // null terminator
+                *optlen = str.as_bytes().len() as c_int;
             }
-            let optval = from_raw_parts_mut(optval.cast::<u8>().as_mut(), *optlen as usize);
-            optval[..str.as_bytes().len()].copy_from_slice(str.as_bytes());
-            optval[str.as_bytes().len()] = 0; // null terminator
-            *optlen = str.as_bytes().len() as c_int;
         }
-    }
-    SRT_SUCCESS
+        SRT_SUCCESS
+    })
 }
 
 #[no_mangle]
@@ -1242,7 +1987,7 @@ pub extern "C" fn srt_getsockname(
     _name: *mut libc::sockaddr,
     _namelen: *mut c_int,
 ) -> c_int {
-    todo!()
+    set_error_fmt(SRT_EINVOP, "srt_getsockname is not implemented")
 }
 
 #[no_mangle]
@@ -1251,7 +1996,7 @@ pub extern "C" fn srt_getpeername(
     _name: *mut libc::sockaddr,
     _namelen: *mut c_int,
 ) -> c_int {
-    todo!()
+    set_error_fmt(SRT_EINVOP, "srt_getpeername is not implemented")
 }
 
 type srt_listen_callback_fn = extern "C" fn(
@@ -1272,19 +2017,21 @@ pub unsafe extern "C" fn srt_listen_callback(
     hook_fn: srt_listen_callback_fn,
     hook_opaque: *mut (),
 ) -> c_int {
-    let sock = match get_sock(sock) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
-    let mut l = sock.lock().unwrap();
+    catch_panic(SRT_ERROR, || {
+        let sock = match get_sock(sock) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
+        let mut l = sock.lock().unwrap();
 
-    if let (Some(o), _) = l.opts_mut() {
-        o.listen_cb = Some(hook_fn);
-        o.listen_cb_opaque = hook_opaque;
-        SRT_SUCCESS
-    } else {
-        set_error(SRT_ENOCONN) // TODO: which error here?
-    }
+        if let (Some(o), _) = l.opts_mut() {
+            o.listen_cb = Some(hook_fn);
+            o.listen_cb_opaque = hook_opaque;
+            SRT_SUCCESS
+        } else {
+            set_error(SRT_ENOCONN) // TODO: which error here?
+        }
+    })
 }
 
 extern "C" {
@@ -1301,34 +2048,40 @@ extern "C" {
 
 #[no_mangle]
 pub extern "C" fn srt_close(socknum: SRTSOCKET) -> c_int {
-    let sock = match get_sock(socknum) {
-        None => return set_error(SRT_EINVSOCK),
-        Some(sock) => sock,
-    };
+    catch_panic(SRT_ERROR, || {
+        let sock = match get_sock(socknum) {
+            None => return set_error(SRT_EINVSOCK),
+            Some(sock) => sock,
+        };
 
-    let mut retcode = SRT_SUCCESS;
+        let mut retcode = SRT_SUCCESS;
 
-    let mut l = sock.lock().unwrap();
-    match &mut *l {
-        SocketData::Established(ref mut s, _) => {
-            let res = TOKIO_RUNTIME.block_on(async move { s.close_and_finish().await });
-            if let Err(e) = res {
-                retcode = set_error_fmt(SRT_EINVOP, format_args!("Failed to close socket: {}", e));
+        let mut l = sock.lock().unwrap();
+        match &mut *l {
+            SocketData::Established(ref mut s, _, _) => {
+                let res =
+                    TOKIO_RUNTIME.block_on(async move { s.get_mut().close_and_finish().await });
+                if let Err(e) = res {
+                    retcode = set_io_error(SRT_EINVOP, "Failed to close socket", &e);
+                }
+                *l = SocketData::Closed
             }
-            *l = SocketData::Closed
-        }
-        SocketData::Listening(ref mut listener, _, jh, _) => {
-            TOKIO_RUNTIME.block_on(async {
-                listener.close().await;
-                jh.await.unwrap();
-            });
-            *l = SocketData::Closed;
+            SocketData::Listening(ref mut listener, _, jh, _) => {
+                TOKIO_RUNTIME.block_on(async {
+                    listener.close().await;
+                    if let Err(e) = jh.await {
+                        error!("Listener task failed: {}", e);
+                    }
+                });
+                *l = SocketData::Closed;
+            }
+            _ => (),
         }
-        _ => (),
-    }
 
-    let mut sockets = SOCKETS.write().unwrap();
-    sockets.remove(&socknum);
+        let mut sockets = SOCKETS.write().unwrap();
+        sockets.remove(&socknum);
+        epoll::remove_socket(socknum);
 
-    retcode
+        retcode
+    })
 }
@@ -58,6 +58,22 @@
 
 #define SRT_LIVE_DEF_PLSIZE 1316
 
+typedef enum SRT_EPOLL_FLAGS {
+  /**
+   * This allows the EID container to be empty when calling the waiting
+   * function with infinite time. This means an infinite hangup, although
+   * a socket can be added to this EID from a separate thread.
+   */
+  SRT_EPOLL_ENABLE_EMPTY = 1,
+  /**
+   * This makes the waiting function check if there is output container
+   * passed to it, and report an error if it isn't. By default it is allowed
+   * that the output container is 0 size or NULL and therefore the readiness
+   * state is reported only as a number of ready sockets from return value.
+   */
+  SRT_EPOLL_ENABLE_OUTPUTCHECK = 2,
+} SRT_EPOLL_FLAGS;
+
 typedef enum SRT_EPOLL_OPT {
   SRT_EPOLL_OPT_NONE = 0,
   /**
@@ -224,6 +240,7 @@ typedef enum SRT_SOCKOPT {
   SRTO_BINDTODEVICE,
   SRTO_PACKETFILTER = 60,
   SRTO_RETRANSMITALGO = 61,
+  SRTO_CRYPTOMODE = 62,
   SRTO_E_SIZE,
 } SRT_SOCKOPT;
 
@@ -247,6 +264,8 @@ typedef enum SRT_TRANSTYPE {
 
 typedef int32_t SRTSOCKET;
 
+typedef int SYSSOCKET;
+
 typedef struct SRT_MSGCTRL {
   int flags;
   int msgttl;
@@ -367,52 +386,132 @@ int srt_listen(SRTSOCKET sock, int _backlog);
 
 int srt_epoll_create(void);
 
-int srt_epoll_add_usock(int _eid, SRTSOCKET _sock, const int *_events);
+/**
+ * # Safety
+ * `events` must be null, which subscribes to all events, or point to the events to subscribe to
+ */
+int srt_epoll_add_usock(int eid, SRTSOCKET sock, const int *events);
+
+/**
+ * # Safety
+ * `events` must be null, which subscribes to all events, or point to the events to subscribe to
+ */
+int srt_epoll_update_usock(int eid, SRTSOCKET sock, const int *events);
+
+int srt_epoll_remove_usock(int eid, SRTSOCKET sock);
+
+/**
+ * # Safety
+ * `events` must be null, which subscribes to all events, or point to the events to subscribe to
+ */
+int srt_epoll_add_ssock(int eid, SYSSOCKET sock, const int *events);
+
+/**
+ * # Safety
+ * `events` must be null, which subscribes to all events, or point to the events to subscribe to
+ */
+int srt_epoll_update_ssock(int eid, SYSSOCKET sock, const int *events);
+
+int srt_epoll_remove_ssock(int eid, SYSSOCKET sock);
+
+/**
+ * Sets the flags of the container, or only returns them when `flags` is -1. Returns the flags
+ * from before the call.
+ */
+int srt_epoll_set(int eid, int flags);
 
-int srt_epoll_remove_usock(int _eid, SRTSOCKET _sock);
+int srt_epoll_release(int eid);
 
-int srt_epoll_release(int _eid);
+/**
+ * Returns the number of ready sockets, the sockets with errors are reported both as ready for
+ * reading and for writing
+ *
+ * # Safety
+ * Each array must be null or have room for as many sockets as its length, the lengths must be
+ * null or valid
+ */
+int srt_epoll_wait(int eid,
+                   SRTSOCKET *readfds,
+                   int *rnum,
+                   SRTSOCKET *writefds,
+                   int *wnum,
+                   int64_t msTimeOut,
+                   SYSSOCKET *lrfds,
+                   int *lrnum,
+                   SYSSOCKET *lwfds,
+                   int *lwnum);
 
-int srt_epoll_wait(int _eid,
-                   const SRTSOCKET *_readfds,
-                   const int *_rnum,
-                   const SRTSOCKET *_writefds,
-                   const int *_wnum,
-                   int64_t _msTimeOut,
-                   const SRTSOCKET *_lrfds,
-                   const int *_lrnum,
-                   const SRTSOCKET *_lwfds,
-                   const int *_lwnum);
+/**
+ * Returns the number of ready sockets, which may be more than fit into `fdsSet`, or 0 if the
+ * timeout expired
+ *
+ * # Safety
+ * `fdsSet` must be null or have room for `fdsSize` events
+ */
+int srt_epoll_uwait(int eid, struct SRT_EPOLL_EVENT *fdsSet, int fdsSize, int64_t msTimeOut);
 
 int srt_connect(SRTSOCKET sock, const sockaddr *name, int namelen);
 
-SRTSOCKET srt_accept(SRTSOCKET sock, sockaddr *addr, int *addrlen);
+SRTSOCKET srt_accept(SRTSOCKET socknum, sockaddr *addr, int *addrlen);
 
-int srt_getlasterror(int *_errno_loc);
+/**
+ * Returns the last error on the calling thread, and stores the system error behind it, or 0, in
+ * `errno_loc` if it is not null
+ *
+ * # Safety
+ * `errno_loc` must be null or valid to write
+ */
+int srt_getlasterror(int *errno_loc);
 
 const char *srt_getlasterror_str(void);
 
+void srt_clearlasterror(void);
+
 int srt_send(SRTSOCKET sock, const char *buf, int len);
 
-int srt_sendmsg(SRTSOCKET _sock, const char *_buf, int _len, int _ttl, int _inorder);
+/**
+ * Sends a message that is dropped if it could not be delivered within `ttl` milliseconds, or
+ * never if negative
+ */
+int srt_sendmsg(SRTSOCKET sock, const char *buf, int len, int ttl, int inorder);
 
 /**
  * Returns number of bytes written
  */
-int srt_sendmsg2(SRTSOCKET sock, const char *buf, int len, const struct SRT_MSGCTRL *_mctrl);
+int srt_sendmsg2(SRTSOCKET sock, const char *buf, int len, const struct SRT_MSGCTRL *mctrl);
 
 /**
  * Returns the number of bytes read
  */
-int srt_recv(SRTSOCKET sock, char *buf, int len);
+int srt_recv(SRTSOCKET socknum, char *buf, int len);
 
 int srt_recvmsg(SRTSOCKET sock, char *buf, int len);
 
-int srt_bstats(SRTSOCKET _sock, struct SRT_TRACEBSTATS *_perf, int _clear);
+/**
+ * Returns the number of bytes read, and stores the source time and the sequence and message
+ * number of the message in `mctrl`
+ */
+int srt_recvmsg2(SRTSOCKET socknum, char *buf, int len, struct SRT_MSGCTRL *mctrl);
+
+/**
+ * The current time in microseconds, on the clock of the source time of messages
+ */
+int64_t srt_time_now(void);
+
+int srt_bstats(SRTSOCKET sock, struct SRT_TRACEBSTATS *perf, int clear);
+
+/**
+ * The statistics are those of the last update, which happens every statistics interval. The
+ * local measurements cover the time since the connection was established, or since the last call
+ * that set `clear`.
+ *
+ * The buffer measurements are always instantaneous, so `instantaneous` has no effect.
+ */
+int srt_bistats(SRTSOCKET sock, struct SRT_TRACEBSTATS *perf, int clear, int _instantaneous);
 
 SRTSOCKET srt_create_socket(void);
 
-void srt_setloglevel(int _ll);
+void srt_setloglevel(int ll);
 
 /**
  * # Safety
@@ -437,9 +536,7 @@ int srt_getsockopt(SRTSOCKET sock,
                    void *optval,
                    int *optlen);
 
-enum SRT_SOCKSTATUS srt_getsockstate(SRTSOCKET _sock);
-
-int srt_epoll_uwait(int _eid, struct SRT_EPOLL_EVENT *_fdsSet, int _fdsSize, int64_t _msTimeOut);
+enum SRT_SOCKSTATUS srt_getsockstate(SRTSOCKET sock);
 
 /**
  * # Safety
@@ -6,7 +6,8 @@ use std::{
 };
 
 pub use crate::packet::{RejectReason, ServerRejectReason};
-pub use crate::settings::{AcceptParameters, StreamAcceptor};
+pub use crate::protocol::pending_connection::AccessControlRequest;
+pub use crate::settings::{AcceptParameters, Rejection, StreamAcceptor};
 
 // See https://datatracker.ietf.org/doc/html/draft-sharabayko-srt-00#appendix-B
 #[derive(Debug, PartialEq, Eq)]
@@ -0,0 +1,69 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use crate::packet::{MsgNumber, SeqNumber};
+
+/// How a single message is sent
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct MessageControl {
+    /// Drop the message, and ask the receiver to do the same, if it could not be sent (or
+    /// retransmitted) within this time of being queued. None keeps it until it is acknowledged,
+    /// or too late for the latency.
+    pub ttl: Option<Duration>,
+
+    /// Whether the message has to be delivered in order, which is always the case with TSBPD
+    pub in_order: bool,
+
+    /// Messages that wait for the packets already in the send buffer to go out are sent highest
+    /// priority first, and in the order they were queued among the same priority
+    pub priority: u8,
+}
+
+impl MessageControl {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            ttl: Some(ttl),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_priority(priority: u8) -> Self {
+        Self {
+            priority,
+            ..Self::default()
+        }
+    }
+}
+
+/// Where a received message was in the stream of the peer
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MessageInfo {
+    pub message_number: MsgNumber,
+    /// The sequence number of the first packet of the message
+    pub seq_number: SeqNumber,
+    /// The sequence number of the last packet of the message
+    pub last_seq_number: SeqNumber,
+    /// Whether any packet of the message had to be retransmitted
+    pub retransmitted: bool,
+    /// The packets dropped between the previous message and this one, if any
+    pub gap: Option<Discontinuity>,
+}
+
+/// Packets that were dropped instead of being delivered, because they were too late or the
+/// sender asked to drop them, so the stream is discontinuous
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Discontinuity {
+    pub seq_numbers: Range<SeqNumber>,
+    /// The number of messages dropped, according to the message numbers
+    pub messages: u32,
+    /// The payload size of the dropped packets that were received, the size of the packets that
+    /// never arrived is unknown
+    pub bytes: u64,
+}
+
+/// A released message, along with where it was in the stream
+pub type SequencedMessage = (MessageInfo, (Instant, Bytes));
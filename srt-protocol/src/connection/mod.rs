@@ -1,4 +1,7 @@
+pub mod message;
 pub mod status;
+
+pub use message::*;
 pub use status::*;
 
 use std::{
@@ -19,9 +22,9 @@ use crate::{
         output::Output,
         receiver::{Receiver, ReceiverContext},
         sender::{Sender, SenderContext},
-        time::Timers,
+        time::{Rtt, Timers},
     },
-    settings::CipherSettings,
+    settings::{CipherSettings, GroupSettings, KeyMaterialState},
     statistics::SocketStatistics,
 };
 
@@ -67,9 +70,37 @@ pub struct ConnectionSettings {
     /// Size of the send buffer, in packets
     pub send_buffer_size: PacketCount,
     pub cipher: Option<CipherSettings>,
+    /// Whether the peer can decrypt the sent data, Unsecured when it is sent unencrypted
+    pub send_key_material_state: KeyMaterialState,
+    /// Whether the received data can be decrypted, Unsecured when it is received unencrypted
+    pub recv_key_material_state: KeyMaterialState,
     pub stream_id: Option<String>,
     pub bandwidth: LiveBandwidthMode,
     pub statistics_interval: Duration,
+
+    /// The transmission type negotiated during the handshake, live or file
+    pub transmission_type: TransmissionType,
+
+    /// False when the stream API is used, and message boundaries are not preserved
+    pub message_api: bool,
+
+    /// Whether received messages are delivered at their TSBPD time, rather than as soon as they
+    /// are complete, only for live transmission
+    pub recv_tsbpd_mode: bool,
+
+    /// Whether received packets that are too late to play are dropped (TLPKTDROP), and whether
+    /// the peer does so, in which case sent packets that are too late are dropped as well
+    pub recv_too_late_packet_drop: bool,
+    pub send_too_late_packet_drop: bool,
+
+    /// How long to keep sending unacknowledged data after the socket is closed, for file transmission
+    pub linger: Option<Duration>,
+
+    /// The group membership announced by the peer, when this connection is a socket group member
+    pub peer_group: Option<GroupSettings>,
+
+    /// The packet filter configuration agreed on by both parties
+    pub packet_filter: Option<FilterSpec>,
 }
 
 #[derive(Debug)]
@@ -87,7 +118,7 @@ pub struct DuplexConnection {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Action<'a> {
-    ReleaseData((Instant, Bytes)),
+    ReleaseData(SequencedMessage),
     SendPacket((Packet, SocketAddr)),
     UpdateStatistics(&'a SocketStatistics),
     WaitForData(Duration),
@@ -108,13 +139,29 @@ pub enum Input {
 impl DuplexConnection {
     pub fn new(connection: Connection) -> DuplexConnection {
         let settings = connection.settings;
+        // file transmission keeps retransmitting after close, until all data is acknowledged, as
+        // does live transmission when the peer waits for packets however late
+        let sender_timeout = match settings.linger {
+            Some(linger)
+                if settings.transmission_type == TransmissionType::File
+                    || !settings.send_too_late_packet_drop =>
+            {
+                linger
+            }
+            _ => settings.send_tsbpd_latency,
+        };
         DuplexConnection {
             settings: settings.clone(),
             handshake: connection.handshake,
             output: Output::new(&settings),
-            status: ConnectionStatus::new(settings.send_tsbpd_latency),
+            status: ConnectionStatus::new(settings.send_tsbpd_latency)
+                .with_sender_timeout(sender_timeout),
             timers: Timers::new(settings.socket_start_time, settings.statistics_interval),
-            stats: SocketStatistics::new(),
+            stats: SocketStatistics {
+                tx_key_material_state: settings.send_key_material_state,
+                rx_key_material_state: settings.recv_key_material_state,
+                ..SocketStatistics::new()
+            },
             receiver: Receiver::new(settings.clone()),
             sender: Sender::new(settings),
         }
@@ -136,7 +183,7 @@ impl DuplexConnection {
             Action::UpdateStatistics(&self.stats)
         } else if let Some(packet) = self.next_packet(now) {
             Action::SendPacket(packet)
-        } else if let Some(data) = self.next_data(now) {
+        } else if let Some(data) = self.next_sequenced_data(now) {
             Action::ReleaseData(data)
         } else {
             Action::WaitForData(self.next_timer(now) - now)
@@ -150,10 +197,24 @@ impl DuplexConnection {
         self.status.is_open()
     }
 
+    /// Whether the connection was lost, because the peer stopped responding or the socket
+    /// failed, rather than shut down by either side
+    pub fn is_broken(&self) -> bool {
+        self.status.is_broken()
+    }
+
     pub fn settings(&self) -> &ConnectionSettings {
         &self.settings
     }
 
+    /// Whether new data can be accepted for sending. This is always the case for live
+    /// transmission, where the oldest data is dropped when the send buffer is full. For file
+    /// transmission, callers should wait for acknowledgements to free up the send buffer.
+    pub fn can_accept_data(&self) -> bool {
+        self.settings.transmission_type == TransmissionType::Live
+            || !self.sender.is_send_buffer_full()
+    }
+
     pub fn update_statistics(&mut self, now: Instant) {
         self.stats.elapsed_time = now - self.settings.socket_start_time;
         self.stats.tx_buffered_time = self.sender.tx_buffered_time();
@@ -161,6 +222,9 @@ impl DuplexConnection {
         self.stats.tx_buffered_bytes = self.sender.tx_buffered_bytes();
 
         self.stats.rx_acknowledged_time = self.receiver.rx_acknowledged_time();
+
+        self.stats.tx_key_material_state = self.sender.key_material_state();
+        self.stats.rx_key_material_state = self.receiver.key_material_state();
     }
 
     pub fn next_packet(&mut self, now: Instant) -> Option<(Packet, SocketAddr)> {
@@ -196,7 +260,12 @@ impl DuplexConnection {
     }
 
     pub fn next_data(&mut self, now: Instant) -> Option<(Instant, Bytes)> {
-        match self.receiver.arq.pop_next_message(now) {
+        self.next_sequenced_data(now).map(|(_, data)| data)
+    }
+
+    /// The next message, along with where it was in the stream of the peer
+    pub fn next_sequenced_data(&mut self, now: Instant) -> Option<SequencedMessage> {
+        match self.receiver.arq.pop_next_sequenced_message(now) {
             Ok(Some(data)) => {
                 self.debug(now, "output", &data);
                 Some(data)
@@ -274,7 +343,8 @@ impl DuplexConnection {
         self.debug(now, "input", &data);
         match data {
             Some(item) => {
-                self.sender().handle_data(now, item);
+                self.sender()
+                    .handle_data(now, MessageControl::default(), item);
             }
             None => {
                 self.handle_data_stream_close(now);
@@ -282,6 +352,39 @@ impl DuplexConnection {
         }
     }
 
+    /// Queue a message for sending, with options that override the defaults of the connection
+    pub fn handle_message_input(
+        &mut self,
+        now: Instant,
+        control: MessageControl,
+        item: (Instant, Bytes),
+    ) {
+        self.debug(now, "input", &(&control, &item));
+        self.sender().queue_message(now, control, item);
+    }
+
+    /// The sequence and message number the next message will be sent with
+    pub fn next_message_position(&self) -> (SeqNumber, MsgNumber) {
+        self.sender.next_message_position()
+    }
+
+    /// The oldest sent sequence number that has not been acknowledged by the peer yet
+    pub fn first_unacknowledged(&self) -> SeqNumber {
+        self.sender.first_unacknowledged()
+    }
+
+    /// The round trip time, as last reported by the peer
+    pub fn rtt(&self) -> Rtt {
+        self.sender.rtt()
+    }
+
+    /// Discard unacknowledged data and continue sending from the given position, used to keep
+    /// the sequence numbers of socket group members aligned
+    pub fn skip_to(&mut self, now: Instant, seq_number: SeqNumber, message_number: MsgNumber) {
+        self.debug(now, "skip", &(seq_number, message_number));
+        self.sender().skip_to(now, seq_number, message_number);
+    }
+
     pub fn handle_packet_input(&mut self, now: Instant, packet: ReceivePacketResult) {
         self.debug(now, "packet", &packet);
         use ReceivePacketError::*;
@@ -358,6 +461,11 @@ impl DuplexConnection {
     }
 
     fn handle_handshake_packet(&mut self, now: Instant, handshake: HandshakeControlInfo) {
+        // the initiator can still reject the handshake response, e.g. a passphrase mismatch
+        if let ShakeType::Rejection(_) = handshake.shake_type {
+            self.status.handle_shutdown_packet(now);
+            return;
+        }
         if let Some(control) = self.handshake.handle_handshake(handshake) {
             self.output.send_control(now, control);
         }
@@ -366,7 +474,12 @@ impl DuplexConnection {
     fn handle_srt_control_packet(&mut self, now: Instant, pack: SrtControlPacket) {
         use self::SrtControlPacket::*;
         match pack {
-            HandshakeRequest(_) | HandshakeResponse(_) => self.warn(now, "handshake", &pack),
+            HandshakeRequest(_) | HandshakeResponse(_) => {
+                match self.handshake.handle_srt_handshake(&pack) {
+                    Some(control) => self.output.send_control(now, control),
+                    None => self.warn(now, "handshake", &pack),
+                }
+            }
             KeyRefreshRequest(keying_material) => self
                 .receiver()
                 .handle_key_refresh_request(now, keying_material),
@@ -471,9 +584,19 @@ mod duplex_connection {
                 recv_buffer_size: PacketCount(1024),
                 send_buffer_size: PacketCount(1024),
                 cipher: None,
+                send_key_material_state: KeyMaterialState::Unsecured,
+                recv_key_material_state: KeyMaterialState::Unsecured,
                 stream_id: None,
                 bandwidth: LiveBandwidthMode::Unlimited,
                 statistics_interval: Duration::from_secs(10),
+                transmission_type: TransmissionType::Live,
+                message_api: true,
+                recv_tsbpd_mode: true,
+                recv_too_late_packet_drop: true,
+                send_too_late_packet_drop: true,
+                linger: None,
+                peer_group: None,
+                packet_filter: None,
             },
             handshake: crate::protocol::handshake::Handshake::Connector,
         }
@@ -644,4 +767,71 @@ mod duplex_connection {
             ))
         );
     }
+
+    #[test]
+    fn packet_filter_payload_size() {
+        let start = Instant::now();
+        let mut connection = new_connection(start);
+        connection.settings.packet_filter = Some("fec,cols:2,rows:1".parse().unwrap());
+        let mut connection = DuplexConnection::new(connection);
+
+        let mut now = start;
+        for _ in 0..2 {
+            let data = Bytes::from(vec![0u8; 1316]);
+            connection.handle_input(now, Input::Data(Some((start, data))));
+        }
+
+        let mut data_packets = 0;
+        let mut control_packets = 0;
+        while control_packets < 2 {
+            match connection.handle_input(now, Input::Timer) {
+                SendPacket((Data(packet), _)) if !packet.retransmitted => {
+                    // FEC packets put their header in front of the combined payloads, which
+                    // still have to fit in a packet
+                    assert!(packet.payload.len() <= 1316, "{:?}", packet.payload.len());
+                    if packet.message_number == MsgNumber(0) {
+                        control_packets += 1;
+                    } else {
+                        data_packets += 1;
+                    }
+                }
+                WaitForData(wait) => now += wait,
+                _ => {}
+            }
+        }
+        assert_eq!(data_packets, 4);
+        assert_eq!(control_packets, 2);
+    }
+
+    #[test]
+    fn message_priority() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+
+        let mut now = start;
+        // the first message goes in the send buffer right away, the others wait for it to be sent
+        for (priority, data) in [(0, "first"), (0, "low"), (1, "high")] {
+            let control = MessageControl::with_priority(priority);
+            connection.handle_message_input(now, control, (start, Bytes::from(data)));
+        }
+
+        let mut sent = Vec::new();
+        while sent.len() < 3 {
+            match connection.handle_input(now, Input::Timer) {
+                SendPacket((Data(packet), _)) if !packet.retransmitted => {
+                    sent.push((packet.message_number, packet.payload))
+                }
+                WaitForData(wait) => now += wait,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            sent,
+            [
+                (MsgNumber(1), Bytes::from("first")),
+                (MsgNumber(2), Bytes::from("high")),
+                (MsgNumber(3), Bytes::from("low")),
+            ]
+        );
+    }
 }
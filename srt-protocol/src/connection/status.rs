@@ -13,6 +13,8 @@ pub struct ConnectionStatus {
     connection: Status,
     sender: Status,
     receiver: Status,
+    // the peer stopped responding or the socket failed, rather than either side closing
+    broken: bool,
 }
 
 impl ConnectionStatus {
@@ -21,6 +23,14 @@ impl ConnectionStatus {
             connection: Status::Open(timeout),
             receiver: Status::Open(timeout),
             sender: Status::Open(timeout),
+            broken: false,
+        }
+    }
+
+    pub fn with_sender_timeout(self, timeout: Duration) -> Self {
+        Self {
+            sender: Status::Open(timeout),
+            ..self
         }
     }
 
@@ -32,6 +42,12 @@ impl ConnectionStatus {
         matches!(self.connection, Status::Closed)
     }
 
+    /// Whether the connection was lost, because the peer stopped responding or the socket
+    /// failed, instead of being shut down by either side
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
     pub fn should_drain_send_buffer(&self) -> bool {
         use Status::*;
         matches!(self.sender, Shutdown(_) | Drain(_))
@@ -48,6 +64,7 @@ impl ConnectionStatus {
         use Status::*;
         if let Open(timeout) = self.receiver {
             self.receiver = Drain(now + timeout);
+            self.broken = true;
         }
     }
 
@@ -55,6 +72,7 @@ impl ConnectionStatus {
         use Status::*;
         if let Open(timeout) = self.receiver {
             self.receiver = Drain(now + timeout);
+            self.broken = true;
         }
     }
 
@@ -132,4 +150,22 @@ mod tests {
         assert!(!status.is_closed());
         assert!(!status.should_drain_send_buffer());
     }
+
+    #[test]
+    fn broken() {
+        let timeout = Duration::from_secs(10);
+        let now = Instant::now();
+
+        let mut status = ConnectionStatus::new(timeout);
+        status.handle_shutdown_packet(now);
+        assert!(status.check_receive_close_timeout(now + timeout * 2, true));
+        assert!(status.is_closed());
+        assert!(!status.is_broken(), "the peer shut the connection down");
+
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_peer_idle_timeout(now);
+        assert!(status.check_receive_close_timeout(now + timeout * 2, true));
+        assert!(status.is_closed());
+        assert!(status.is_broken());
+    }
 }
@@ -0,0 +1,57 @@
+use std::{cmp::Reverse, time::Instant};
+
+use super::{GroupMember, SocketGroup};
+
+// Main/backup member selection, similar to the reference implementation's SRT_GTYPE_BACKUP
+//
+// Data is sent over the highest priority (weight, then longest active) stable member. When no
+// active member is stable, the highest weight idle member is activated and catches up by
+// resending the unacknowledged messages. Unstable members keep sending until they either
+// recover or break, and once a stable member with a higher priority exists, the other stable
+// members are silenced again.
+impl SocketGroup {
+    pub(super) fn update_backup_members(&mut self, now: Instant) {
+        let min_stability_timeout = self.min_stability_timeout;
+        let has_stable_active = self
+            .members
+            .iter()
+            .any(|m| m.is_active() && m.is_stable(now, min_stability_timeout));
+
+        if !has_stable_active {
+            let backup = self
+                .members
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| !m.is_active())
+                .max_by_key(|(i, m)| (m.weight(), Reverse(*i)))
+                .map(|(i, _)| i);
+            if let Some(index) = backup {
+                self.activate_member(now, index);
+            }
+            return;
+        }
+
+        let main = self
+            .members
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.is_active() && m.is_stable(now, min_stability_timeout))
+            .max_by_key(|(i, m)| priority(*i, m))
+            .map(|(i, _)| i);
+
+        for (i, member) in self.members.iter_mut().enumerate() {
+            if Some(i) != main && member.is_active() && member.is_stable(now, min_stability_timeout)
+            {
+                member.silence();
+            }
+        }
+    }
+}
+
+fn priority(index: usize, member: &GroupMember) -> (u16, Reverse<Option<Instant>>, Reverse<usize>) {
+    (
+        member.weight(),
+        Reverse(member.activation_time()),
+        Reverse(index),
+    )
+}
@@ -0,0 +1,18 @@
+use std::time::Instant;
+
+use super::SocketGroup;
+
+// Broadcast member selection, similar to the reference implementation's SRT_GTYPE_BROADCAST
+//
+// Every member is active and sends all data, with the same sequence numbers, so the receiving
+// group can merge the members into one stream, dropping the duplicates. A member joining later
+// catches up by resending the messages that are still buffered.
+impl SocketGroup {
+    pub(super) fn update_broadcast_members(&mut self, now: Instant) {
+        for index in 0..self.members.len() {
+            if !self.members[index].is_active() {
+                self.activate_member(now, index);
+            }
+        }
+    }
+}
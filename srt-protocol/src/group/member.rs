@@ -0,0 +1,119 @@
+use std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+use crate::{connection::DuplexConnection, packet::*};
+
+use super::GroupMessage;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemberState {
+    /// Connected, but not used for sending
+    Idle,
+    /// Used for sending
+    Active,
+}
+
+#[derive(Debug)]
+pub struct GroupMember {
+    connection: DuplexConnection,
+    weight: u16,
+    state: MemberState,
+    activation_time: Option<Instant>,
+    // time of the first data sent after the last packet received from the peer
+    unresponded_since: Option<Instant>,
+}
+
+impl GroupMember {
+    pub fn new(connection: DuplexConnection) -> Self {
+        let weight = connection
+            .settings()
+            .peer_group
+            .map(|group| group.weight)
+            .unwrap_or_default();
+        Self {
+            connection,
+            weight,
+            state: MemberState::Idle,
+            activation_time: None,
+            unresponded_since: None,
+        }
+    }
+
+    pub fn id(&self) -> SocketId {
+        self.connection.settings().local_sockid
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn state(&self) -> MemberState {
+        self.state
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == MemberState::Active
+    }
+
+    pub fn activation_time(&self) -> Option<Instant> {
+        self.activation_time
+    }
+
+    pub fn connection(&self) -> &DuplexConnection {
+        &self.connection
+    }
+
+    pub fn connection_mut(&mut self) -> &mut DuplexConnection {
+        &mut self.connection
+    }
+
+    /// A member is unstable when the peer has not responded to sent data within the stability
+    /// timeout, which is at least `min_stability_timeout`, and grows with the round trip time
+    pub fn is_stable(&self, now: Instant, min_stability_timeout: Duration) -> bool {
+        self.unstable_time(min_stability_timeout)
+            .map_or(true, |unstable| now < unstable)
+    }
+
+    pub fn unstable_time(&self, min_stability_timeout: Duration) -> Option<Instant> {
+        let rtt = self.connection.rtt();
+        let timeout = max(
+            min_stability_timeout,
+            2 * rtt.mean_as_duration() + 4 * rtt.variance_as_duration(),
+        );
+        self.unresponded_since.map(|since| since + timeout)
+    }
+
+    pub fn activate(&mut self, now: Instant) {
+        self.state = MemberState::Active;
+        self.activation_time = Some(now);
+        self.unresponded_since = None;
+    }
+
+    pub fn silence(&mut self) {
+        self.state = MemberState::Idle;
+        self.activation_time = None;
+        self.unresponded_since = None;
+    }
+
+    pub fn on_packet(&mut self) {
+        self.unresponded_since = None;
+    }
+
+    /// Sends the message, unless this member already did. When the member is behind the message,
+    /// it skips ahead, so the message has the same sequence number on every member.
+    pub(super) fn send(&mut self, now: Instant, message: &GroupMessage) {
+        let (seq_number, _) = self.connection.next_message_position();
+        if seq_number > message.seq_number {
+            return;
+        }
+        if seq_number < message.seq_number {
+            self.connection
+                .skip_to(now, message.seq_number, message.message_number);
+        }
+        self.connection
+            .handle_data_input(now, Some(message.data.clone()));
+        self.unresponded_since.get_or_insert(now);
+    }
+}
@@ -0,0 +1,487 @@
+//! Socket groups bond multiple member connections to the same peer, for redundancy
+mod backup;
+mod broadcast;
+mod member;
+
+pub use member::*;
+
+use std::{
+    cmp::min,
+    collections::{BTreeMap, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use crate::{
+    connection::{DuplexConnection, MessageInfo},
+    packet::*,
+    protocol::sender::{latency_window, Encapsulation},
+    settings::{GroupSettings, GroupType},
+    statistics::{GroupStatistics, MemberStatistics, SocketStatistics},
+};
+
+// A message sent over the group, kept until a member acknowledges it, so it can be resent over
+// a backup member with the same sequence numbers.
+#[derive(Debug)]
+pub(crate) struct GroupMessage {
+    seq_number: SeqNumber,
+    message_number: MsgNumber,
+    // the sequence number following the last packet of the message
+    end: SeqNumber,
+    data: (Instant, Bytes),
+}
+
+/// Only main/backup and broadcast groups are supported
+#[derive(Error, Copy, Clone, Debug, Eq, PartialEq)]
+#[error("{0:?} groups are not supported")]
+pub struct UnsupportedGroupType(pub GroupType);
+
+// how the members are used for sending, one of the supported group types
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum GroupMode {
+    MainBackup,
+    Broadcast,
+}
+
+#[derive(Debug)]
+pub struct SocketGroup {
+    settings: GroupSettings,
+    mode: GroupMode,
+    min_stability_timeout: Duration,
+    members: Vec<GroupMember>,
+    // the position of the next message shared by all members, and how messages are split into
+    // packets, taken from the first member
+    encapsulation: Option<Encapsulation>,
+    send_buffer: VecDeque<GroupMessage>,
+    max_send_buffer_size: usize,
+    latency_window: Duration,
+    // messages released by members with their packet counts, ordered by sequence number to drop
+    // duplicates
+    released: BTreeMap<SeqNumber, (u64, (Instant, Bytes))>,
+    last_released: Option<SeqNumber>,
+    data_closed: bool,
+    // the group's own unique data counters
+    statistics: SocketStatistics,
+}
+
+impl SocketGroup {
+    /// The reference implementation's default for SRTO_GROUPMINSTABLETIMEO
+    pub const DEFAULT_MIN_STABILITY_TIMEOUT: Duration = Duration::from_millis(60);
+
+    pub fn new(
+        settings: GroupSettings,
+        min_stability_timeout: Duration,
+    ) -> Result<Self, UnsupportedGroupType> {
+        let mode = match settings.group_type {
+            GroupType::MainBackup => GroupMode::MainBackup,
+            GroupType::Broadcast => GroupMode::Broadcast,
+            group_type => return Err(UnsupportedGroupType(group_type)),
+        };
+        Ok(Self {
+            settings,
+            mode,
+            min_stability_timeout,
+            members: Vec::new(),
+            encapsulation: None,
+            send_buffer: VecDeque::new(),
+            max_send_buffer_size: 0,
+            latency_window: Duration::from_secs(1),
+            released: BTreeMap::new(),
+            last_released: None,
+            data_closed: false,
+            statistics: SocketStatistics::default(),
+        })
+    }
+
+    pub fn settings(&self) -> &GroupSettings {
+        &self.settings
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &GroupMember> {
+        self.members.iter()
+    }
+
+    pub fn contains_member(&self, id: SocketId) -> bool {
+        self.members.iter().any(|m| m.id() == id)
+    }
+
+    pub fn add_member(&mut self, now: Instant, mut connection: DuplexConnection) {
+        if self.encapsulation.is_none() {
+            let settings = connection.settings();
+            let mut encapsulation = Encapsulation::new(settings);
+            let (seq_number, message_number) = connection.next_message_position();
+            encapsulation.skip_to(seq_number, message_number);
+            self.encapsulation = Some(encapsulation);
+            self.max_send_buffer_size = settings.send_buffer_size.0 as usize;
+            self.latency_window = latency_window(settings);
+        }
+        if self.data_closed {
+            connection.handle_data_input(now, None);
+        }
+        self.members.push(GroupMember::new(connection));
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    /// Whether new data can be accepted for sending, see [`DuplexConnection::can_accept_data`]
+    pub fn can_accept_data(&self) -> bool {
+        self.members
+            .iter()
+            .filter(|m| m.is_active())
+            .all(|m| m.connection().can_accept_data())
+    }
+
+    pub fn handle_data_input(&mut self, now: Instant, data: Option<(Instant, Bytes)>) {
+        match data {
+            Some(data) => self.send_message(now, data),
+            None => {
+                self.data_closed = true;
+                for member in &mut self.members {
+                    member.connection_mut().handle_data_input(now, None);
+                }
+            }
+        }
+    }
+
+    pub fn handle_packet_input(
+        &mut self,
+        now: Instant,
+        member: SocketId,
+        packet: ReceivePacketResult,
+    ) {
+        if let Some(member) = self.members.iter_mut().find(|m| m.id() == member) {
+            if packet.is_ok() {
+                member.on_packet();
+            }
+            member.connection_mut().handle_packet_input(now, packet);
+        }
+    }
+
+    /// The next packet to send, along with the id of the member sending it
+    pub fn next_packet(&mut self, now: Instant) -> Option<(SocketId, (Packet, SocketAddr))> {
+        self.members.iter_mut().find_map(|member| {
+            let id = member.id();
+            member
+                .connection_mut()
+                .next_packet(now)
+                .map(|packet| (id, packet))
+        })
+    }
+
+    /// The next message received over any member, in sequence number order, without duplicates
+    pub fn next_data(&mut self, now: Instant) -> Option<(Instant, Bytes)> {
+        for member in &mut self.members {
+            while let Some((info, data)) = member.connection_mut().next_sequenced_data(now) {
+                let MessageInfo {
+                    seq_number,
+                    last_seq_number,
+                    ..
+                } = info;
+                if self.last_released.map_or(true, |last| seq_number > last) {
+                    let packets = u64::from(last_seq_number - seq_number) + 1;
+                    self.released.entry(seq_number).or_insert((packets, data));
+                }
+            }
+        }
+
+        let seq_number = *self.released.keys().next()?;
+        self.last_released = Some(seq_number);
+        let (packets, data) = self.released.remove(&seq_number)?;
+
+        self.statistics.rx_unique_data += packets;
+        self.statistics.rx_unique_bytes += Self::wire_size(&data.1, packets);
+        Some(data)
+    }
+
+    pub fn should_update_statistics(&mut self, now: Instant) -> bool {
+        // check every member, so they all restart their statistics timer
+        let mut update = false;
+        for member in &mut self.members {
+            update |= member.connection_mut().should_update_statistics(now);
+        }
+        update
+    }
+
+    pub fn statistics(&self) -> GroupStatistics {
+        let members = self
+            .members
+            .iter()
+            .map(|member| MemberStatistics {
+                id: member.id(),
+                weight: member.weight(),
+                state: member.state(),
+                statistics: member.connection().statistics().clone(),
+            })
+            .collect();
+        GroupStatistics::new(&self.statistics, members)
+    }
+
+    pub fn check_timers(&mut self, now: Instant) -> Instant {
+        self.members.retain(|m| m.connection().is_open());
+
+        self.update_members(now);
+        self.prune_send_buffer(now);
+
+        let min_stability_timeout = self.min_stability_timeout;
+        let mut next_timer = now + Duration::from_millis(100);
+        for member in &mut self.members {
+            next_timer = min(next_timer, member.connection_mut().check_timers(now));
+            if let Some(unstable) = member
+                .unstable_time(min_stability_timeout)
+                .filter(|unstable| *unstable > now)
+            {
+                next_timer = min(next_timer, unstable);
+            }
+        }
+        next_timer
+    }
+
+    fn update_members(&mut self, now: Instant) {
+        match self.mode {
+            GroupMode::MainBackup => self.update_backup_members(now),
+            GroupMode::Broadcast => self.update_broadcast_members(now),
+        }
+    }
+
+    fn send_message(&mut self, now: Instant, data: (Instant, Bytes)) {
+        self.update_members(now);
+
+        let encapsulation = match &mut self.encapsulation {
+            Some(encapsulation) => encapsulation,
+            None => return,
+        };
+        let seq_number = encapsulation.next_sequence_number();
+        let message_number = encapsulation.next_message_number();
+        let packets = encapsulation.packet_count(data.1.len()) as u64;
+        let end = seq_number + packets as u32;
+        encapsulation.skip_to(end, message_number + 1);
+        self.statistics.tx_unique_data += packets;
+        self.statistics.tx_unique_bytes += Self::wire_size(&data.1, packets);
+
+        let message = GroupMessage {
+            seq_number,
+            message_number,
+            end,
+            data,
+        };
+        for member in self.members.iter_mut().filter(|m| m.is_active()) {
+            member.send(now, &message);
+        }
+
+        self.send_buffer.push_back(message);
+    }
+
+    // the payload length plus the headers of every packet, as counted for the member statistics
+    fn wire_size(data: &Bytes, packets: u64) -> u64 {
+        data.len() as u64 + DataPacket::HEADER_SIZE as u64 * packets
+    }
+
+    fn activate_member(&mut self, now: Instant, index: usize) {
+        let member = &mut self.members[index];
+        member.activate(now);
+
+        // catch up from the oldest message no active member has acknowledged yet
+        for message in &self.send_buffer {
+            member.send(now, message);
+        }
+        if let Some(encapsulation) = &self.encapsulation {
+            let seq_number = encapsulation.next_sequence_number();
+            let message_number = encapsulation.next_message_number();
+            if member.connection().next_message_position().0 < seq_number {
+                member
+                    .connection_mut()
+                    .skip_to(now, seq_number, message_number);
+            }
+        }
+    }
+
+    fn prune_send_buffer(&mut self, now: Instant) {
+        let acknowledged = self
+            .members
+            .iter()
+            .filter(|m| m.is_active())
+            .map(|m| m.connection().first_unacknowledged())
+            .max();
+
+        while let Some(message) = self.send_buffer.front() {
+            let acknowledged = acknowledged.map_or(false, |ack| message.end <= ack);
+            let too_late = now > message.data.0 + self.latency_window;
+            let overflow = self.send_buffer.len() > self.max_send_buffer_size;
+            if !(acknowledged || too_late || overflow) {
+                break;
+            }
+            self.send_buffer.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        connection::{Connection, ConnectionSettings},
+        options::*,
+        protocol::handshake::Handshake,
+        settings::KeyMaterialState,
+    };
+
+    use super::*;
+
+    const MILLIS: Duration = Duration::from_millis(1);
+
+    fn remote_addr() -> SocketAddr {
+        ([127, 0, 0, 1], 2223).into()
+    }
+
+    fn connection(start: Instant, local: u32, remote: u32) -> DuplexConnection {
+        DuplexConnection::new(Connection {
+            settings: ConnectionSettings {
+                remote: remote_addr(),
+                remote_sockid: SocketId(remote),
+                local_sockid: SocketId(local),
+                socket_start_time: start,
+                rtt: Duration::default(),
+                init_seq_num: SeqNumber::new_truncate(0),
+                max_packet_size: PacketSize(1316),
+                max_flow_size: PacketCount(8192),
+                send_tsbpd_latency: Duration::from_millis(120),
+                recv_tsbpd_latency: Duration::from_millis(120),
+                recv_buffer_size: PacketCount(8192),
+                send_buffer_size: PacketCount(8192),
+                cipher: None,
+                send_key_material_state: KeyMaterialState::Unsecured,
+                recv_key_material_state: KeyMaterialState::Unsecured,
+                stream_id: None,
+                bandwidth: LiveBandwidthMode::Unlimited,
+                statistics_interval: Duration::from_secs(10),
+                transmission_type: TransmissionType::Live,
+                message_api: true,
+                recv_tsbpd_mode: true,
+                recv_too_late_packet_drop: true,
+                send_too_late_packet_drop: true,
+                linger: None,
+                peer_group: None,
+                packet_filter: None,
+            },
+            handshake: Handshake::Connector,
+        })
+    }
+
+    // a sending and a receiving group, connected by two member links: 1 to 11 and 2 to 12
+    fn connected_groups(group_type: GroupType, start: Instant) -> (SocketGroup, SocketGroup) {
+        let new = || {
+            SocketGroup::new(
+                GroupSettings::new(group_type, 0),
+                SocketGroup::DEFAULT_MIN_STABILITY_TIMEOUT,
+            )
+            .unwrap()
+        };
+        let (mut sender, mut receiver) = (new(), new());
+        for link in 1..=2 {
+            sender.add_member(start, connection(start, link, 10 + link));
+            receiver.add_member(start, connection(start, 10 + link, link));
+        }
+        (sender, receiver)
+    }
+
+    // sends a message every 10ms, exchanging the packets of the groups over the links that are
+    // up, and returns the messages the receiving group released
+    fn transmit(
+        sender: &mut SocketGroup,
+        receiver: &mut SocketGroup,
+        start: Instant,
+        messages: u32,
+        link_up: impl Fn(Instant, SocketId) -> bool,
+    ) -> Vec<Bytes> {
+        let mut received = Vec::new();
+        let mut sent = 0;
+        let mut now = start;
+        while now < start + Duration::from_secs(5) {
+            if sent < messages && now >= start + sent * 10 * MILLIS {
+                let data = Bytes::from(format!("message {}", sent));
+                sender.handle_data_input(now, Some((now, data)));
+                sent += 1;
+            }
+
+            sender.check_timers(now);
+            receiver.check_timers(now);
+            while let Some((member, (packet, _))) = sender.next_packet(now) {
+                if link_up(now, member) {
+                    let to = packet.dest_sockid();
+                    receiver.handle_packet_input(now, to, Ok((packet, remote_addr())));
+                }
+            }
+            while let Some((_, (packet, _))) = receiver.next_packet(now) {
+                let to = packet.dest_sockid();
+                if link_up(now, to) {
+                    sender.handle_packet_input(now, to, Ok((packet, remote_addr())));
+                }
+            }
+            while let Some((_, data)) = receiver.next_data(now) {
+                received.push(data);
+            }
+            now += MILLIS;
+        }
+        received
+    }
+
+    fn expected_messages(messages: u32) -> Vec<Bytes> {
+        (0..messages)
+            .map(|i| Bytes::from(format!("message {}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn broadcast_drops_duplicates() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = connected_groups(GroupType::Broadcast, start);
+
+        let received = transmit(&mut sender, &mut receiver, start, 50, |_, _| true);
+
+        assert_eq!(received, expected_messages(50));
+        assert!(sender.members().all(|m| m.is_active()));
+        let statistics = receiver.statistics();
+        assert_eq!(statistics.aggregate.rx_unique_data, 50);
+        for member in &statistics.members {
+            assert_eq!(member.statistics.rx_data, 50, "{:?}", member.id);
+        }
+    }
+
+    #[test]
+    fn backup_member_takes_over() {
+        let start = Instant::now();
+        let (mut sender, mut receiver) = connected_groups(GroupType::MainBackup, start);
+        let failure = start + Duration::from_millis(300);
+
+        let received = transmit(&mut sender, &mut receiver, start, 100, |now, member| {
+            now < failure || !matches!(member, SocketId(1) | SocketId(11))
+        });
+
+        assert_eq!(received, expected_messages(100));
+        let backup = sender.members().find(|m| m.id() == SocketId(2)).unwrap();
+        assert!(backup.is_active());
+        assert!(backup.activation_time().unwrap() > failure);
+        assert_eq!(receiver.statistics().aggregate.rx_unique_data, 100);
+    }
+
+    #[test]
+    fn unsupported_group_type() {
+        let new = |group_type| {
+            SocketGroup::new(
+                GroupSettings::new(group_type, 0),
+                SocketGroup::DEFAULT_MIN_STABILITY_TIMEOUT,
+            )
+        };
+
+        assert!(new(GroupType::MainBackup).is_ok());
+        assert!(new(GroupType::Broadcast).is_ok());
+        assert_eq!(
+            new(GroupType::Balancing).unwrap_err(),
+            UnsupportedGroupType(GroupType::Balancing)
+        );
+    }
+}
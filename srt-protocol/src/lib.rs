@@ -1,5 +1,6 @@
 pub mod access;
 pub mod connection;
+pub mod group;
 pub mod listener;
 pub mod options;
 pub mod packet;
@@ -21,6 +21,22 @@ impl TryFrom<&str> for SocketAddress {
     type Error = SocketAddressParseError;
 
     fn try_from(address: &str) -> Result<Self, Self::Error> {
+        // IPv6 addresses are enclosed in brackets to set them apart from the port, e.g. "[::]:3000"
+        if let Some(bracketed) = address.strip_prefix('[') {
+            let invalid = || SocketAddressParseError::Invalid(address.to_string());
+            let (host, port) = bracketed.split_once(']').ok_or_else(invalid)?;
+            let host = Ipv6Addr::from_str(host)
+                .map_err(|_| SocketAddressParseError::InvalidHost(host.to_string()))?;
+            let port = match port {
+                "" => 0,
+                port => u16::from_str(port.strip_prefix(':').ok_or_else(invalid)?)?,
+            };
+            return Ok(Self {
+                host: SocketHost::Ipv6(host),
+                port,
+            });
+        }
+
         let mut split = address.split(':');
         match (split.next(), split.next(), split.next()) {
             (Some(""), Some(port), None) => Ok(Self {
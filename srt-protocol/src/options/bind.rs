@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BindOptions {
     Listen(Valid<ListenerOptions>),
     Call(Valid<CallerOptions>),
@@ -5,7 +5,7 @@ use std::{
 
 use super::*;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CallerOptions {
     pub remote: SocketAddress,
     pub stream_id: Option<StreamId>,
@@ -5,7 +5,7 @@ use std::{
 
 use super::*;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Connect {
     pub local: SocketAddr,
 
@@ -16,10 +16,29 @@ pub struct Connect {
     /// Default is 3 seconds.
     pub timeout: Duration,
 
+    /// How often a caller repeats its handshake requests until the listener responds, see
+    /// [`RetryPolicy`]. The connect timeout applies regardless.
+    pub retry: RetryPolicy,
+
+    /// How often a rendezvous peer repeats its handshake until the other peer responds, which
+    /// also keeps the mapping in its NAT alive. Applies to the rendezvous connection mode only.
+    ///
+    /// Default is 100ms
+    pub waving_interval: Duration,
+
+    /// Whether a caller connects again when its connection is lost, e.g. because the listener
+    /// stopped responding, but not when the listener shut it down. The attempts are spaced and
+    /// limited by the retry policy, and stop when the socket is closed or the listener rejects
+    /// it. Applies to the caller connection mode only.
+    ///
+    /// Default is false
+    pub autoreconnect: bool,
+
     /// SRTO_MINVERSION
     /// The minimum SRT version that is required from the peer. A connection to a peer that does not
     /// satisfy the minimum version requirement will be rejected. See SRTO_VERSION for the version
-    /// format.
+    /// format. Peers before SRT v1.3.0 connect with the HSv4 handshake, which is only accepted
+    /// while the minimum version allows it.
     ///
     /// The default value is 0x010000 (SRT v1.0.0).
     pub min_version: SrtVersion,
@@ -47,6 +66,15 @@ pub struct Connect {
     /// Sender: user configurable, default: 64
     pub ip_ttl: u8,
 
+    /// SRTO_IPV6ONLY
+    ///
+    /// Whether a socket bound to an IPv6 address communicates over IPv6 only (see IPV6_V6ONLY).
+    /// Otherwise a socket bound to `[::]` is dual-stack, and also connects with IPv4 peers. Applies
+    /// to IPv6 local addresses only.
+    ///
+    /// Default is false
+    pub ipv6_only: bool,
+
     /// Linger time on close (see [SO_LINGER](http://man7.org/linux/man-pages/man7/socket.7.html)).
     /// Set to None to disable linger
     ///
@@ -54,16 +82,111 @@ pub struct Connect {
     pub linger: Option<Duration>,
 }
 
+/// The schedule of the handshake requests sent while waiting for the peer to respond, each
+/// interval is the previous one times the multiplier, up to the maximum interval
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The interval between the first request and its first repetition
+    ///
+    /// Default is 100ms
+    pub initial_interval: Duration,
+
+    /// The longest interval between repetitions
+    ///
+    /// Default is 1s
+    pub max_interval: Duration,
+
+    /// The factor each interval is longer than the previous one by, at least 1.0. A multiplier
+    /// of 1.0 repeats the request at the initial interval.
+    ///
+    /// Default is 1.0
+    pub multiplier: f64,
+
+    /// The largest fraction, between 0.0 and 1.0, each interval is randomly lengthened or
+    /// shortened by, to spread out the requests of callers that started at the same time
+    ///
+    /// Default is 0.0
+    pub jitter: f64,
+
+    /// The most requests sent in each stage of the handshake, after which the connection fails
+    /// as timed out. None repeats the requests until the connect timeout.
+    ///
+    /// Default is None
+    pub max_attempts: Option<u32>,
+}
+
+impl RetryPolicy {
+    /// The interval following the given attempt, counting from 1, without jitter
+    pub fn interval(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let interval = self.initial_interval.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::from_secs_f64(interval.min(self.max_interval.as_secs_f64()))
+    }
+
+    /// The interval following the given attempt, with the jitter scaled by a sample from -1.0
+    /// to 1.0
+    pub fn jittered_interval(&self, attempt: u32, sample: f64) -> Duration {
+        let interval = self.interval(attempt).as_secs_f64();
+        let jitter = interval * self.jitter * sample.clamp(-1.0, 1.0);
+        Duration::from_secs_f64((interval + jitter).max(0.0))
+    }
+
+    /// Whether the requests are exhausted after the given attempt, counting from 1
+    pub fn is_exhausted(&self, attempt: u32) -> bool {
+        matches!(self.max_attempts, Some(max_attempts) if attempt >= max_attempts)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(1),
+            multiplier: 1.0,
+            jitter: 0.0,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Validation for RetryPolicy {
+    type Error = OptionsError;
+
+    fn is_valid(&self) -> Result<(), Self::Error> {
+        if self.initial_interval.is_zero() || self.initial_interval > self.max_interval {
+            return Err(OptionsError::InvalidRetryInterval(
+                self.initial_interval,
+                self.max_interval,
+            ));
+        }
+        if !self.multiplier.is_finite() || self.multiplier < 1.0 {
+            return Err(OptionsError::InvalidRetryMultiplier);
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(OptionsError::InvalidRetryJitter);
+        }
+        if self.max_attempts == Some(0) {
+            return Err(OptionsError::InvalidRetryAttempts);
+        }
+
+        Ok(())
+    }
+}
+
 impl Connect {}
 impl Default for Connect {
     fn default() -> Self {
         Self {
             local: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
             timeout: Duration::from_secs(3),
+            retry: RetryPolicy::default(),
+            waving_interval: Duration::from_millis(100),
+            autoreconnect: false,
             min_version: SrtVersion::new(1, 0, 0),
             udp_recv_buffer_size: ByteCount(65536),
             udp_send_buffer_size: ByteCount(65536),
             ip_ttl: 64,
+            ipv6_only: false,
             linger: Some(Duration::from_secs(180)),
         }
     }
@@ -76,6 +199,10 @@ impl Validation for Connect {
         if self.ip_ttl == 0 {
             return Err(OptionsError::InvalidIpTtl);
         }
+        if self.waving_interval.is_zero() {
+            return Err(OptionsError::InvalidWavingInterval);
+        }
+        self.retry.is_valid()?;
 
         Ok(())
     }
@@ -96,4 +223,85 @@ mod test {
             Err(OptionsError::InvalidIpTtl)
         );
     }
+
+    #[test]
+    fn waving_interval_validate() {
+        assert_eq!(
+            Connect {
+                waving_interval: Duration::ZERO,
+                ..Default::default()
+            }
+            .is_valid(),
+            Err(OptionsError::InvalidWavingInterval)
+        );
+    }
+
+    #[test]
+    fn retry_validate() {
+        let retry = |retry: RetryPolicy| {
+            Connect {
+                retry,
+                ..Default::default()
+            }
+            .is_valid()
+        };
+
+        assert_eq!(retry(RetryPolicy::default()), Ok(()));
+        assert_eq!(
+            retry(RetryPolicy {
+                initial_interval: Duration::ZERO,
+                ..Default::default()
+            }),
+            Err(OptionsError::InvalidRetryInterval(
+                Duration::ZERO,
+                Duration::from_secs(1)
+            ))
+        );
+        assert_eq!(
+            retry(RetryPolicy {
+                multiplier: 0.5,
+                ..Default::default()
+            }),
+            Err(OptionsError::InvalidRetryMultiplier)
+        );
+        assert_eq!(
+            retry(RetryPolicy {
+                jitter: 1.5,
+                ..Default::default()
+            }),
+            Err(OptionsError::InvalidRetryJitter)
+        );
+        assert_eq!(
+            retry(RetryPolicy {
+                max_attempts: Some(0),
+                ..Default::default()
+            }),
+            Err(OptionsError::InvalidRetryAttempts)
+        );
+    }
+
+    #[test]
+    fn retry_intervals() {
+        let retry = RetryPolicy {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_millis(500),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: Some(4),
+        };
+
+        assert_eq!(retry.interval(1), Duration::from_millis(100));
+        assert_eq!(retry.interval(2), Duration::from_millis(200));
+        assert_eq!(retry.interval(3), Duration::from_millis(400));
+        assert_eq!(retry.interval(4), Duration::from_millis(500));
+        assert_eq!(retry.interval(u32::MAX), Duration::from_millis(500));
+
+        assert_eq!(retry.jittered_interval(2, -1.0), Duration::from_millis(100));
+        assert_eq!(retry.jittered_interval(2, 0.0), Duration::from_millis(200));
+        assert_eq!(retry.jittered_interval(2, 1.0), Duration::from_millis(300));
+
+        assert!(!retry.is_exhausted(3));
+        assert!(retry.is_exhausted(4));
+        assert!(!RetryPolicy::default().is_exhausted(u32::MAX));
+    }
 }
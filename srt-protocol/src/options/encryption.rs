@@ -7,7 +7,7 @@ use super::*;
 
 // https://datatracker.ietf.org/doc/html/draft-sharabayko-srt-00#section-6
 // https://github.com/Haivision/srt/blob/master/docs/features/encryption.md
-#[derive(Clone, Default, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Encryption {
     // TODO: support unspecified key length
     //  also check to ensure we implement key negotiation algorithm correctly
@@ -75,6 +75,43 @@ pub struct Encryption {
     pub passphrase: Option<Passphrase>,
 
     pub km_refresh: KeyMaterialRefresh,
+
+    /// SRTO_CRYPTOMODE
+    /// The cipher used to encrypt the payload of the data packets.
+    ///
+    /// AES-CTR only encrypts the payload. AES-GCM also authenticates the payload and the header of
+    /// each data packet, so that packets tampered with on the way are detected and dropped by the
+    /// receiver, at the cost of a 16 byte authentication tag per packet.
+    ///
+    /// The cipher is announced in the Keying Material message of the initiator. A responder set to
+    /// Auto accepts the cipher of the initiator, otherwise both sides must agree or the connection
+    /// is rejected. An initiator set to Auto uses AES-CTR.
+    ///
+    /// AES-GCM can not be combined with a packet filter.
+    pub crypto_mode: CryptoMode,
+
+    /// SRTO_ENFORCEDENCRYPTION
+    /// Reject the connection when only one side has set a passphrase, or the passphrases do not
+    /// match (default true).
+    ///
+    /// When false, the connection is established anyway, but packets that can not be decrypted
+    /// are dropped and counted as decryption errors, so that no garbage is delivered. The outcome
+    /// is reported by the key material state of the connection, NoSecret when only one side has a
+    /// passphrase and BadSecret when they do not match. Either side enforcing the encryption is
+    /// enough to reject the connection.
+    pub enforced_encryption: bool,
+}
+
+impl Default for Encryption {
+    fn default() -> Self {
+        Self {
+            key_size: Default::default(),
+            passphrase: None,
+            km_refresh: Default::default(),
+            crypto_mode: Default::default(),
+            enforced_encryption: true,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -231,6 +268,34 @@ impl KeySize {
     }
 }
 
+// https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#srto_cryptomode
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CryptoMode {
+    Auto,
+    AesCtr,
+    AesGcm,
+}
+
+impl Default for CryptoMode {
+    fn default() -> Self {
+        CryptoMode::Auto
+    }
+}
+
+impl TryFrom<u8> for CryptoMode {
+    type Error = OptionsError;
+
+    fn try_from(value: u8) -> Result<Self, OptionsError> {
+        use CryptoMode::*;
+        match value {
+            0 => Ok(Auto),
+            1 => Ok(AesCtr),
+            2 => Ok(AesGcm),
+            value => Err(OptionsError::InvalidCryptoMode(value)),
+        }
+    }
+}
+
 impl TryFrom<u8> for KeySize {
     type Error = OptionsError;
 
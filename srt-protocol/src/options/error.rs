@@ -13,6 +13,8 @@ pub enum OptionsError {
     PassphraseLength(usize),
     #[error("Invalid encryption key size: {0}. Valid sizes are 16, 24, or 32 bytes.")]
     InvalidKeySize(u8),
+    #[error("Invalid crypto mode: {0}. Valid modes are 0 (auto), 1 (AES-CTR), or 2 (AES-GCM).")]
+    InvalidCryptoMode(u8),
 
     #[error("MMS out of range: {0}. The maximum size of a UDP packet is 1500 bytes.")]
     MaxSegmentSizeOutOfRange(PacketSize),
@@ -62,8 +64,35 @@ pub enum OptionsError {
     #[error("IP TTL is invalid, must be > 0")]
     InvalidIpTtl,
 
+    #[error("Connect retry interval is invalid: {0:?}. It must be non-zero and at most the maximum interval {1:?}.")]
+    InvalidRetryInterval(Duration, Duration),
+
+    #[error("Connect retry multiplier is invalid, it must be a finite number of at least 1.0.")]
+    InvalidRetryMultiplier,
+
+    #[error("Connect retry jitter is invalid, it must be between 0.0 and 1.0.")]
+    InvalidRetryJitter,
+
+    #[error("Connect retry attempts are invalid, the maximum must be non-zero.")]
+    InvalidRetryAttempts,
+
+    #[error("Rendezvous waving interval is invalid, it must be non-zero.")]
+    InvalidWavingInterval,
+
     #[error("Statistics interval is out of range: {0:?}. The minimum interval is 200ms.")]
     StatisticsIntervalOutOfRange(Duration),
+
+    #[error("The stream API (message_api = false) is only supported by file transmission.")]
+    StreamApiRequiresFileTransmission,
+
+    #[error("Invalid packet filter: {0}")]
+    InvalidPacketFilter(PacketFilterError),
+
+    #[error("Packet filters are only supported by live transmission.")]
+    PacketFilterRequiresLiveTransmission,
+
+    #[error("AES-GCM encryption can not be combined with a packet filter.")]
+    PacketFilterWithAesGcm,
 }
 
 impl From<OptionsError> for io::Error {
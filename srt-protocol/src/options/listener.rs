@@ -5,7 +5,7 @@ use std::{
 
 use super::*;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ListenerOptions {
     pub socket: SocketOptions,
 }
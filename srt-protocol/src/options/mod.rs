@@ -6,6 +6,7 @@ mod connect;
 mod encryption;
 mod error;
 mod listener;
+mod packet_filter;
 mod receiver;
 mod rendezvous;
 mod sender;
@@ -25,6 +26,7 @@ pub use connect::*;
 pub use encryption::*;
 pub use error::*;
 pub use listener::*;
+pub use packet_filter::*;
 pub use receiver::*;
 pub use rendezvous::*;
 pub use sender::*;
@@ -37,8 +39,6 @@ pub use validation::*;
 
 // see https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md
 
-// SRTO_PACKETFILTER - FEC not supported... yet
-
 // TODO: look over these options, they could be useful for statistics
 //
 // SRTO_EVENT - events? not a configuration option
@@ -57,7 +57,6 @@ pub use validation::*;
 // SRTO_IPTTL - socket specific
 // SRTO_IPV6ONLY - socket specific
 // SRTO_LINGER - socket specific
-// SRTO_TRANSTYPE - socket specific
 // SRTO_BINDTODEVICE - socket only
 // SRTO_REUSEADDR - socket specific
 // SRTO_STATE - socket specific
@@ -71,5 +70,4 @@ pub use validation::*;
 // SRTO_UDP_RCVBUF - not really relevant for tokio, is it?
 // SRTO_SENDER - always duplex
 // SRTO_MESSAGEAPI - only "live" is supported
-// SRTO_CONGESTION - only "live" is supported
-// SRTO_ENFORCEDENCRYPTION - awkward/arcane - is this actually useful?
+// SRTO_CONGESTION - selected by SRTO_TRANSTYPE
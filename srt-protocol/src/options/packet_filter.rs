@@ -0,0 +1,253 @@
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+pub use crate::packet::FilterSpec;
+
+// SRTO_PACKETFILTER
+/// The configuration of the built-in "fec" packet filter, a row/column XOR forward error
+/// correction, e.g. "fec,cols:10,rows:5,layout:staircase,arq:onreq".
+///
+/// The sender groups consecutive data packets into rows of `columns` packets, and into columns of
+/// `rows` packets, and sends an extra packet with the XOR of each complete group. The receiver can
+/// rebuild a single lost packet of a group from the other packets of the group and its FEC packet.
+///
+/// See https://github.com/Haivision/srt/blob/master/docs/features/packet-filtering-and-fec.md
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FecConfig {
+    /// cols: the number of packets in a row group, 1 to 255, required. FEC packets identify
+    /// their column with a single byte.
+    pub columns: u8,
+
+    /// rows: the number of packets in a column group, 1 (the default) disables column groups
+    pub rows: u16,
+
+    /// false when rows is given as a negative number, disabling row groups
+    pub row_groups: bool,
+
+    /// layout: the arrangement of the column groups, defaults to even
+    pub layout: FecLayout,
+
+    /// arq: the retransmission mode alongside the filter, defaults to onreq
+    pub arq: ArqLevel,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FecLayout {
+    /// Column groups start at the same packet, the FEC packets of all columns are sent together
+    Even,
+    /// Each column group starts one packet later than the previous one, spreading the FEC packets
+    Staircase,
+}
+
+impl Default for FecLayout {
+    fn default() -> Self {
+        FecLayout::Even
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ArqLevel {
+    /// Losses are reported as soon as they are detected, as without a packet filter
+    Always,
+    /// Losses are only reported once the filter failed to recover them
+    OnRequest,
+    /// Losses are never reported, lost packets are only recovered by the filter
+    Never,
+}
+
+impl Default for ArqLevel {
+    fn default() -> Self {
+        ArqLevel::OnRequest
+    }
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum PacketFilterError {
+    #[error("Unsupported packet filter type: {0}")]
+    UnsupportedType(String),
+    #[error("Unsupported packet filter parameter: {0}")]
+    UnsupportedParameter(String),
+    #[error("Invalid value for packet filter parameter {0}: {1}")]
+    InvalidValue(String, String),
+    #[error("Missing packet filter parameter: {0}")]
+    MissingParameter(String),
+    #[error("Conflicting values for packet filter parameter {key}: {local} and {peer}")]
+    Conflict {
+        key: String,
+        local: String,
+        peer: String,
+    },
+}
+
+impl FecConfig {
+    pub const FILTER_TYPE: &'static str = "fec";
+
+    /// Checks a local packet filter configuration, which may leave parameters to the peer
+    pub fn validate(spec: &FilterSpec) -> Result<(), PacketFilterError> {
+        FecParameters::parse(spec).map(|_| ())
+    }
+
+    /// Merges the local and peer configurations, each side may set parameters the other side left
+    /// out, but the parameters set by both sides must agree
+    pub fn negotiate(
+        local: &FilterSpec,
+        peer: &FilterSpec,
+    ) -> Result<FilterSpec, PacketFilterError> {
+        if local.name != peer.name {
+            return Err(PacketFilterError::UnsupportedType(peer.name.clone()));
+        }
+        let mut parameters = local.parameters.clone();
+        for (key, value) in &peer.parameters {
+            match parameters.get(key) {
+                Some(local) if local != value => {
+                    return Err(PacketFilterError::Conflict {
+                        key: key.clone(),
+                        local: local.clone(),
+                        peer: value.clone(),
+                    })
+                }
+                _ => {
+                    parameters.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        let spec = FilterSpec {
+            name: local.name.clone(),
+            parameters,
+        };
+        FecConfig::try_from(&spec)?;
+        Ok(spec)
+    }
+
+    pub fn has_column_groups(&self) -> bool {
+        self.rows > 1
+    }
+}
+
+impl TryFrom<&FilterSpec> for FecConfig {
+    type Error = PacketFilterError;
+
+    fn try_from(spec: &FilterSpec) -> Result<Self, Self::Error> {
+        let parameters = FecParameters::parse(spec)?;
+        let rows = parameters.rows.unwrap_or(1);
+        Ok(FecConfig {
+            columns: parameters
+                .columns
+                .ok_or_else(|| PacketFilterError::MissingParameter("cols".into()))?,
+            rows: rows.unsigned_abs(),
+            row_groups: rows > 0,
+            layout: parameters.layout.unwrap_or_default(),
+            arq: parameters.arq.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct FecParameters {
+    columns: Option<u8>,
+    rows: Option<i16>,
+    layout: Option<FecLayout>,
+    arq: Option<ArqLevel>,
+}
+
+impl FecParameters {
+    fn parse(spec: &FilterSpec) -> Result<Self, PacketFilterError> {
+        use PacketFilterError::*;
+        if spec.name != FecConfig::FILTER_TYPE {
+            return Err(UnsupportedType(spec.name.clone()));
+        }
+
+        let invalid = |key: &String, value: &String| InvalidValue(key.clone(), value.clone());
+        let mut parameters = FecParameters::default();
+        for (key, value) in &spec.parameters {
+            match key.as_str() {
+                "cols" => {
+                    let columns = value.parse().map_err(|_| invalid(key, value))?;
+                    if columns == 0 {
+                        return Err(invalid(key, value));
+                    }
+                    parameters.columns = Some(columns);
+                }
+                "rows" => {
+                    // a single packet column group would only duplicate the packets
+                    let rows: i16 = value.parse().map_err(|_| invalid(key, value))?;
+                    if rows == 0 || rows == -1 {
+                        return Err(invalid(key, value));
+                    }
+                    parameters.rows = Some(rows);
+                }
+                "layout" => {
+                    parameters.layout = Some(match value.as_str() {
+                        "even" => FecLayout::Even,
+                        "staircase" => FecLayout::Staircase,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                "arq" => {
+                    parameters.arq = Some(match value.as_str() {
+                        "always" => ArqLevel::Always,
+                        "onreq" => ArqLevel::OnRequest,
+                        "never" => ArqLevel::Never,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                _ => return Err(UnsupportedParameter(key.clone())),
+            }
+        }
+        Ok(parameters)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spec(s: &str) -> FilterSpec {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let config =
+            FecConfig::try_from(&spec("fec,cols:10,rows:5,layout:staircase,arq:never")).unwrap();
+        assert_eq!(
+            config,
+            FecConfig {
+                columns: 10,
+                rows: 5,
+                row_groups: true,
+                layout: FecLayout::Staircase,
+                arq: ArqLevel::Never,
+            }
+        );
+
+        let config = FecConfig::try_from(&spec("fec,cols:4,rows:-3")).unwrap();
+        assert_eq!((config.rows, config.row_groups), (3, false));
+        assert_eq!(config.arq, ArqLevel::OnRequest);
+
+        assert_eq!(
+            FecConfig::try_from(&spec("fec,rows:3")),
+            Err(PacketFilterError::MissingParameter("cols".into()))
+        );
+        assert!(FecConfig::validate(&spec("fec,rows:3")).is_ok());
+        assert!(FecConfig::validate(&spec("fec,cols:0")).is_err());
+        assert!(FecConfig::validate(&spec("fec,cols:255")).is_ok());
+        assert!(FecConfig::validate(&spec("fec,cols:256")).is_err());
+        assert!(FecConfig::validate(&spec("fec,cols:3,size:4")).is_err());
+        assert!(FecConfig::validate(&spec("rot13,cols:3")).is_err());
+    }
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(
+            FecConfig::negotiate(&spec("fec,cols:10"), &spec("fec,rows:5,arq:never")),
+            Ok(spec("fec,cols:10,rows:5,arq:never"))
+        );
+        assert!(matches!(
+            FecConfig::negotiate(&spec("fec,cols:10"), &spec("fec,cols:5")),
+            Err(PacketFilterError::Conflict { .. })
+        ));
+        assert!(FecConfig::negotiate(&spec("fec,rows:10"), &spec("fec,rows:10")).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use std::convert::TryInto;
 
 use super::*;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct RendezvousOptions {
     pub remote: SocketAddress,
     pub socket: SocketOptions,
@@ -26,6 +26,73 @@ pub struct Session {
     pub max_segment_size: PacketSize,
 
     pub statistics_interval: Duration,
+
+    /// SRTO_TRANSTYPE
+    /// Sets the transmission type for the socket.
+    ///
+    /// Live: TSBPD and too-late packet drop enabled, live congestion control (LiveCC)
+    /// File: TSBPD and too-late packet drop disabled, file congestion control (FileCC), latency
+    /// settings are ignored and every packet is retransmitted until it is acknowledged
+    ///
+    /// Both parties must use the same transmission type, otherwise the connection is rejected.
+    pub transmission_type: TransmissionType,
+
+    /// SRTO_TSBPDMODE
+    /// Timestamp based packet delivery. When true, received messages are delivered at the time
+    /// they were sent plus the latency. When false, they are delivered as soon as they are complete
+    /// and in order, lost packets are still retransmitted. The peer is told to do the same with
+    /// the messages sent to it.
+    ///
+    /// Only live transmission uses timestamp based packet delivery.
+    ///
+    /// The default value is true
+    pub tsbpd_mode: bool,
+
+    /// SRTO_MESSAGEAPI
+    /// When true, each sent buffer is delivered to the receiver as a whole message, exactly as it
+    /// was sent. When false (stream API), the receiver reads contiguous bytes regardless of the
+    /// boundaries of the sent buffers.
+    ///
+    /// The stream API is only available for file transmission, and both parties must use the same
+    /// setting, otherwise the connection is rejected.
+    ///
+    /// The default value is true
+    pub message_api: bool,
+
+    /// SRTO_PACKETFILTER
+    /// The packet filter configuration, e.g. "fec,cols:10,rows:5", see [`FecConfig`] for the
+    /// built-in "fec" filter. The parties may each set some of the parameters, and the parameters
+    /// set by both parties must agree, otherwise the connection is rejected. A party without a
+    /// packet filter adopts the configuration of its peer.
+    ///
+    /// Packet filters are only available for live transmission.
+    ///
+    /// The default value is None
+    pub packet_filter: Option<FilterSpec>,
+}
+
+/// https://github.com/Haivision/srt/blob/master/docs/features/live-streaming.md
+/// https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#SRTO_TRANSTYPE
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TransmissionType {
+    Live,
+    File,
+}
+
+impl Default for TransmissionType {
+    fn default() -> Self {
+        TransmissionType::Live
+    }
+}
+
+impl TransmissionType {
+    /// The congestion control type name, as exchanged in the SRT handshake congestion extension
+    pub fn congestion_control_type(&self) -> &'static str {
+        match self {
+            TransmissionType::Live => "live",
+            TransmissionType::File => "file",
+        }
+    }
 }
 
 impl Default for Session {
@@ -34,6 +101,10 @@ impl Default for Session {
             peer_idle_timeout: Duration::from_secs(5),
             max_segment_size: PacketSize(1500),
             statistics_interval: Duration::from_secs(1),
+            transmission_type: TransmissionType::Live,
+            tsbpd_mode: true,
+            message_api: true,
+            packet_filter: None,
         }
     }
 }
@@ -47,6 +118,14 @@ impl Validation for Session {
             Err(MaxSegmentSizeOutOfRange(self.max_segment_size))
         } else if self.statistics_interval < Duration::from_millis(200) {
             Err(StatisticsIntervalOutOfRange(self.statistics_interval))
+        } else if !self.message_api && self.transmission_type == TransmissionType::Live {
+            Err(StreamApiRequiresFileTransmission)
+        } else if let Some(filter) = &self.packet_filter {
+            if self.transmission_type != TransmissionType::Live {
+                Err(PacketFilterRequiresLiveTransmission)
+            } else {
+                FecConfig::validate(filter).map_err(InvalidPacketFilter)
+            }
         } else {
             Ok(())
         }
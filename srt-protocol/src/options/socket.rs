@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct SocketOptions {
     pub connect: Connect,
     pub session: Session,
@@ -63,6 +63,12 @@ impl CompositeValidation for SocketOptions {
             });
         }
 
+        // the packet filter rebuilds packets without the header fields authenticated by AES-GCM
+        if self.encryption.crypto_mode == CryptoMode::AesGcm && self.session.packet_filter.is_some()
+        {
+            return Err(OptionsError::PacketFilterWithAesGcm);
+        }
+
         Ok(())
     }
 }
@@ -12,7 +12,7 @@ use url::{Host, ParseError, Url};
 use crate::options::*;
 
 /// see https://github.com/Haivision/srt/blob/master/docs/apps/srt-live-transmit.md#medium-srt
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SrtUri(BindOptions);
 
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
@@ -25,8 +25,12 @@ pub enum SrtUriError {
     InvalidAdapter(#[from] AddrParseError),
     #[error("Invalid mode: {0}")]
     InvalidMode(String),
+    #[error("Invalid transmission type: {0}")]
+    InvalidTransmissionType(String),
     #[error("Invalid parameter: {0}={1}, expected positive integer")]
     InvalidIntParameter(&'static str, String),
+    #[error("Invalid parameter: {0}={1}, expected boolean")]
+    InvalidBoolParameter(&'static str, String),
     #[error("Unimplemented parameter: {0}")]
     UnimplementedParameter(&'static str),
 }
@@ -151,7 +155,10 @@ impl SrtUri {
                     socket.connect.timeout = Duration::from_millis(value);
                 }
                 "drifttracer" => unimplemented!(),
-                "enforcedencryption" => unimplemented!(),
+                "enforcedencryption" => {
+                    socket.encryption.enforced_encryption =
+                        Self::parse_bool_param("enforcedencryption", value)?;
+                }
                 "fc" => {
                     let value = Self::parse_int_param("fc", value)?;
                     socket.sender.flow_control_window_size = PacketCount(value);
@@ -172,7 +179,9 @@ impl SrtUri {
                     }
                     socket.connect.ip_ttl = value as u8;
                 }
-                "ipv6only" => unimplemented!(),
+                "ipv6only" => {
+                    socket.connect.ipv6_only = Self::parse_bool_param("ipv6only", value)?;
+                }
                 "kmpreannounce" => {
                     let value = Self::parse_int_param("kmpreannounce", value)?;
                     socket.encryption.km_refresh.period = PacketCount(value);
@@ -207,7 +216,9 @@ impl SrtUri {
                         mininputbw = Some(DataRate(value));
                     }
                 }
-                "messageapi" => return Err(UnimplementedParameter("messageapi")),
+                "messageapi" => {
+                    socket.session.message_api = Self::parse_bool_param("messageapi", value)?;
+                }
                 "minversion" => {
                     let digits: Result<Vec<_>, _> =
                         value.as_ref().split('.').map(u8::from_str).collect();
@@ -282,9 +293,20 @@ impl SrtUri {
                 "streamid" => {
                     stream_id = Some(value.to_owned());
                 }
-                "tlpktdrop" => unimplemented!(),
-                "transtype" => return Err(UnimplementedParameter("transtype")),
-                "tsbpdmode" => return Err(UnimplementedParameter("tsbpdmode")),
+                "tlpktdrop" => {
+                    socket.receiver.too_late_packet_drop =
+                        Self::parse_bool_param("tlpktdrop", value)?;
+                }
+                "transtype" => {
+                    socket.session.transmission_type = match value.as_ref() {
+                        "live" => TransmissionType::Live,
+                        "file" => TransmissionType::File,
+                        value => return Err(InvalidTransmissionType(value.to_string())),
+                    };
+                }
+                "tsbpdmode" => {
+                    socket.session.tsbpd_mode = Self::parse_bool_param("tsbpdmode", value)?;
+                }
                 _ => {}
             }
         }
@@ -330,6 +352,14 @@ impl SrtUri {
             Ok(n) => Ok(n),
         }
     }
+
+    fn parse_bool_param(key: &'static str, value: Cow<str>) -> Result<bool, SrtUriError> {
+        match value.as_ref() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(SrtUriError::InvalidBoolParameter(key, value.to_string())),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -443,6 +473,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_transmission_type() {
+        let mut socket = SocketOptions::default();
+        socket.session.transmission_type = TransmissionType::File;
+
+        assert_eq!(
+            "srt://10.1.0.1:1234?transtype=file".parse(),
+            Ok(SrtUri(
+                CallerOptions::with("10.1.0.1:1234", None, socket)
+                    .unwrap()
+                    .into()
+            ))
+        );
+        assert_eq!(
+            "srt://10.1.0.1:1234?transtype=live".parse(),
+            Ok(SrtUri(
+                CallerOptions::new("10.1.0.1:1234", None).unwrap().into()
+            ))
+        );
+        assert_eq!(
+            "srt://10.1.0.1:1234?transtype=bulk".parse::<SrtUri>(),
+            Err(SrtUriError::InvalidTransmissionType("bulk".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_message_api() {
+        let mut socket = SocketOptions::default();
+        socket.session.transmission_type = TransmissionType::File;
+        socket.session.message_api = false;
+
+        assert_eq!(
+            "srt://10.1.0.1:1234?transtype=file&messageapi=0".parse(),
+            Ok(SrtUri(
+                CallerOptions::with("10.1.0.1:1234", None, socket)
+                    .unwrap()
+                    .into()
+            ))
+        );
+        assert_eq!(
+            "srt://10.1.0.1:1234?messageapi=0".parse::<SrtUri>(),
+            Err(SrtUriError::InvalidOptions(
+                OptionsError::StreamApiRequiresFileTransmission
+            ))
+        );
+        assert_eq!(
+            "srt://10.1.0.1:1234?messageapi=maybe".parse::<SrtUri>(),
+            Err(SrtUriError::InvalidBoolParameter(
+                "messageapi",
+                "maybe".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_too_late_packet_drop() {
+        let mut socket = SocketOptions::default();
+        socket.receiver.too_late_packet_drop = false;
+
+        assert_eq!(
+            "srt://10.1.0.1:1234?tlpktdrop=0".parse(),
+            Ok(SrtUri(
+                CallerOptions::with("10.1.0.1:1234", None, socket)
+                    .unwrap()
+                    .into()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_tsbpd_mode() {
+        let mut socket = SocketOptions::default();
+        socket.session.tsbpd_mode = false;
+
+        assert_eq!(
+            "srt://10.1.0.1:1234?tsbpdmode=0".parse(),
+            Ok(SrtUri(
+                CallerOptions::with("10.1.0.1:1234", None, socket)
+                    .unwrap()
+                    .into()
+            ))
+        );
+    }
+
     #[test]
     fn parse_bandiwdth() {
         let mut socket = SocketOptions::default();
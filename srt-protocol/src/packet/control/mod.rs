@@ -138,6 +138,12 @@ pub struct HsV5Info {
 
     /// The SID
     pub sid: Option<String>,
+
+    /// The congestion control type, "file" or "live" (assumed when absent)
+    pub congestion: Option<String>,
+
+    /// The packet filter configuration
+    pub filter: Option<FilterSpec>,
 }
 
 /// HS-version dependenent data
@@ -289,6 +295,7 @@ pub enum CoreRejectReason {
     Filter = 1014,
     Group = 1015,
     Timeout = 1016,
+    Crypto = 1017,
 }
 
 #[non_exhaustive]
@@ -341,7 +348,12 @@ impl HandshakeVsInfo {
             HandshakeVsInfo::V4(ty) => *ty as u32,
             HandshakeVsInfo::V5(hs) => {
                 if shake_type == ShakeType::Induction
-                    && (hs.ext_hs.is_some() || hs.ext_km.is_some() || hs.sid.is_some())
+                    && (hs.ext_hs.is_some()
+                        || hs.ext_km.is_some()
+                        || hs.ext_group.is_some()
+                        || hs.sid.is_some()
+                        || hs.congestion.is_some()
+                        || hs.filter.is_some())
                 {
                     // induction does not include any extensions, and instead has the
                     // magic code. this is an incompatialbe place to be.
@@ -356,7 +368,11 @@ impl HandshakeVsInfo {
                 if hs.ext_km.is_some() {
                     flags |= ExtFlags::KM;
                 }
-                if hs.sid.is_some() {
+                if hs.sid.is_some()
+                    || hs.congestion.is_some()
+                    || hs.filter.is_some()
+                    || hs.ext_group.is_some()
+                {
                     flags |= ExtFlags::CONFIG;
                 }
                 // take the crypto size, get rid of the frist three (guaranteed zero) bits, then shift it into the
@@ -589,8 +605,11 @@ impl ControlTypes {
                             // parse out extensions
 
                             let mut sid = None;
+                            let mut congestion = None;
                             let mut ext_hs = None;
                             let mut ext_km = None;
+                            let mut ext_group = None;
+                            let mut filter = None;
 
                             while buf.remaining() > 4 {
                                 let pack_type = buf.get_u16();
@@ -633,6 +652,16 @@ impl ControlTypes {
                                             SrtControlPacket::StreamId(stream_id) => {
                                                 sid = Some(stream_id)
                                             }
+                                            //6 = congestion:
+                                            SrtControlPacket::Congestion(ctype) => {
+                                                congestion = Some(ctype)
+                                            }
+                                            //7 = filter:
+                                            SrtControlPacket::Filter(spec) => filter = Some(spec),
+                                            //8 = group:
+                                            group @ SrtControlPacket::Group { .. } => {
+                                                ext_group = Some(group)
+                                            }
                                             _ => unimplemented!("Implement other kinds"),
                                         }
                                     }
@@ -654,8 +683,10 @@ impl ControlTypes {
                                 crypto_size,
                                 ext_hs,
                                 ext_km,
-                                ext_group: None,
+                                ext_group,
                                 sid,
+                                congestion,
+                                filter,
                             })
                         }
                     }
@@ -1073,6 +1104,15 @@ impl Debug for HandshakeVsInfo {
                 if let Some(sid) = &hs.sid {
                     write!(f, " sid={:?}", sid)?;
                 }
+                if let Some(congestion) = &hs.congestion {
+                    write!(f, " congestion={:?}", congestion)?;
+                }
+                if let Some(filter) = &hs.filter {
+                    write!(f, " filter={:?}", filter.to_string())?;
+                }
+                if let Some(group) = &hs.ext_group {
+                    write!(f, " {:?}", group)?;
+                }
                 Ok(())
             }
         }
@@ -1176,6 +1216,7 @@ impl TryFrom<i32> for CoreRejectReason {
             1014 => Filter,
             1015 => Group,
             1016 => Timeout,
+            1017 => Crypto,
             other => return Err(other),
         })
     }
@@ -1208,6 +1249,7 @@ impl Display for CoreRejectReason {
             CoreRejectReason::Filter => write!(f, "incompatible packet filter"),
             CoreRejectReason::Group => write!(f, "incompatible group"),
             CoreRejectReason::Timeout => write!(f, "connection timeout"),
+            CoreRejectReason::Crypto => write!(f, "conflicting cryptographic configurations"),
         }
     }
 }
@@ -1295,7 +1337,13 @@ impl HandshakeControlInfo {
                 +
                 info.ext_km.as_ref().map(|hs| 2 * size_of::<u16>() + usize::from(hs.size_words()) * size_of::<u32>()).unwrap_or(0)
                 +
+                info.ext_group.as_ref().map(|g| 2 * size_of::<u16>() + usize::from(g.size_words()) * size_of::<u32>()).unwrap_or(0)
+                +
                 info.sid.as_ref().map(|sid| 2 * size_of::<u16>() + ((sid.len() + 3) / 4 * 4)).unwrap_or(0)
+                +
+                info.congestion.as_ref().map(|c| 2 * size_of::<u16>() + ((c.len() + 3) / 4 * 4)).unwrap_or(0)
+                +
+                info.filter.as_ref().map(|f| 2 * size_of::<u16>() + ((f.to_string().len() + 3) / 4 * 4)).unwrap_or(0)
             }
         }
     }
@@ -1331,6 +1379,9 @@ impl HandshakeControlInfo {
                 &hs.ext_hs,
                 &hs.ext_km,
                 &hs.sid.clone().map(SrtControlPacket::StreamId),
+                &hs.congestion.clone().map(SrtControlPacket::Congestion),
+                &hs.filter.clone().map(SrtControlPacket::Filter),
+                &hs.ext_group,
             ])
             .filter_map(|s| s.as_ref())
             {
@@ -1424,6 +1475,44 @@ mod test {
                     ext_km: None,
                     ext_group: None,
                     sid: None,
+                    congestion: None,
+                    filter: None,
+                }),
+            }),
+        });
+    }
+
+    #[test]
+    fn handshake_group_ser_des_test() {
+        ser_des_test(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketId(0),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber::new_truncate(1_827_131),
+                max_packet_size: PacketSize(1500),
+                max_flow_size: PacketCount(25600),
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketId(1231),
+                syn_cookie: 0,
+                peer_addr: "127.0.0.1".parse().unwrap(),
+                info: HandshakeVsInfo::V5(HsV5Info {
+                    crypto_size: 0,
+                    ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
+                        version: SrtVersion::CURRENT,
+                        flags: SrtShakeFlags::SUPPORTED,
+                        send_latency: Duration::from_millis(120),
+                        recv_latency: Duration::from_millis(120),
+                    })),
+                    ext_km: None,
+                    ext_group: Some(SrtControlPacket::Group {
+                        group_id: SocketId(0x4000_0001),
+                        ty: GroupType::MainBackup,
+                        flags: GroupFlags::empty(),
+                        weight: 10,
+                    }),
+                    sid: Some("group".into()),
+                    congestion: None,
+                    filter: None,
                 }),
             }),
         });
@@ -1479,6 +1568,8 @@ mod test {
                     ext_hs: None,
                     ext_group: None,
                     sid: None,
+                    congestion: None,
+                    filter: None,
                 }),
             }),
         });
@@ -1503,6 +1594,34 @@ mod test {
                     ext_hs: None,
                     ext_group: None,
                     sid: Some("Hello hello".into()),
+                    congestion: None,
+                    filter: None,
+                }),
+            }),
+        });
+    }
+
+    #[test]
+    fn congestion_hs_ser_des_test() {
+        ser_des_test(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketId(0),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber(0),
+                max_packet_size: PacketSize(1816),
+                max_flow_size: PacketCount(0),
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketId(0),
+                syn_cookie: 0,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVsInfo::V5(HsV5Info {
+                    crypto_size: 0,
+                    ext_km: None,
+                    ext_hs: None,
+                    ext_group: None,
+                    sid: Some("Hello hello".into()),
+                    congestion: Some("file".into()),
+                    filter: None,
                 }),
             }),
         });
@@ -1550,6 +1669,7 @@ mod test {
             timestamp: TimeStamp::from_micros(100),
             dest_sockid: rand::random(),
             control_type: ControlTypes::Srt(SrtControlPacket::Group {
+                group_id: SocketId(0x4000_1234),
                 ty: GroupType::MainBackup,
                 flags: GroupFlags::MSG_SYNC,
                 weight: 123,
@@ -1574,9 +1694,10 @@ mod test {
         ser_des_test(ControlPacket {
             timestamp: TimeStamp::from_micros(100),
             dest_sockid: rand::random(),
-            control_type: ControlTypes::Srt(SrtControlPacket::Filter(FilterSpec(
-                IntoIter::new([("hi".to_string(), "bye".to_string())]).collect(),
-            ))),
+            control_type: ControlTypes::Srt(SrtControlPacket::Filter(FilterSpec {
+                name: "fec".to_string(),
+                parameters: IntoIter::new([("hi".to_string(), "bye".to_string())]).collect(),
+            })),
         });
     }
 
@@ -1661,6 +1782,8 @@ mod test {
                         ext_km: None,
                         ext_group: None,
                         sid: None,
+                        congestion: None,
+                        filter: None,
                     })
                 })
             }
@@ -1709,6 +1832,8 @@ mod test {
                         ext_km: None,
                         ext_group: None,
                         sid: Some(String::from("abcdefghij")),
+                        congestion: None,
+                        filter: None,
                     })
                 })
             }
@@ -1766,6 +1891,8 @@ mod test {
                         })),
                         ext_group: None,
                         sid: None,
+                        congestion: None,
+                        filter: None,
                     })
                 })
             }
@@ -1862,6 +1989,8 @@ mod test {
                     })),
                     ext_group: None,
                     sid: Some("#!::u=hex".into()),
+                    congestion: None,
+                    filter: None,
                 }),
             }),
         };
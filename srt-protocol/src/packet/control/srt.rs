@@ -1,13 +1,16 @@
 use std::{
     fmt::{self, Display, Formatter},
-    {collections::BTreeMap, convert::TryFrom, time::Duration},
+    {collections::BTreeMap, convert::TryFrom, str::FromStr, time::Duration},
 };
 
 use bitflags::bitflags;
 use bytes::{Buf, BufMut};
 use log::warn;
 
-use crate::{options::SrtVersion, packet::PacketParseError};
+use crate::{
+    options::SrtVersion,
+    packet::{PacketParseError, SocketId},
+};
 
 /// The SRT-specific control packets
 /// These are `Packet::Custom` types
@@ -41,22 +44,27 @@ pub enum SrtControlPacket {
     /// ID = 6
     Congestion(String),
 
+    /// Packet filter configuration (SRTO_PACKETFILTER)
     /// ID = 7
-    /// Filter seems to be a string of
-    /// comma-separted key-value pairs like:
-    /// a:b,c:d
     Filter(FilterSpec),
 
-    // ID = 8
+    /// Group membership, sent by group members during the handshake
+    /// ID = 8
     Group {
+        group_id: SocketId,
         ty: GroupType,
         flags: GroupFlags,
         weight: u16,
     },
 }
 
+/// A packet filter configuration, a string of the filter type followed by comma-separated
+/// key-value pairs like: fec,cols:10,rows:5
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FilterSpec(pub BTreeMap<String, String>);
+pub struct FilterSpec {
+    pub name: String,
+    pub parameters: BTreeMap<String, String>,
+}
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum GroupType {
@@ -151,6 +159,7 @@ impl fmt::Debug for KeyingMaterialMessage {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Auth {
     None = 0,
+    AesGcm = 1,
 }
 
 impl TryFrom<u8> for Auth {
@@ -158,6 +167,7 @@ impl TryFrom<u8> for Auth {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(Auth::None),
+            1 => Ok(Auth::AesGcm),
             e => Err(PacketParseError::BadAuth(e)),
         }
     }
@@ -213,6 +223,7 @@ pub enum CipherType {
     Ecb = 1,
     Ctr = 2,
     Cbc = 3,
+    Gcm = 4,
 }
 
 /// The SRT handshake object
@@ -257,14 +268,14 @@ bitflags! {
         /// One bit in payload packet msgno is "retransmitted" flag
         const REXMITFLG = 0x20;
 
-        /// This entity supports stream ID packets
+        /// Stream API (as opposed to message API) for file transmission
         const STREAM = 0x40;
 
         /// Again not sure... TODO:
         const PACKET_FILTER = 0x80;
 
         // currently implemented flags
-        const SUPPORTED = Self::TSBPDSND.bits | Self::TSBPDRCV.bits | Self::HAICRYPT.bits | Self::REXMITFLG.bits;
+        const SUPPORTED = Self::TSBPDSND.bits | Self::TSBPDRCV.bits | Self::HAICRYPT.bits | Self::TLPKTDROP.bits | Self::REXMITFLG.bits;
     }
 }
 
@@ -309,16 +320,43 @@ fn string_to_le_bytes(str: &str, into: &mut impl BufMut) {
 
 impl Display for FilterSpec {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        for (i, (k, v)) in self.0.iter().enumerate() {
-            write!(f, "{}:{}", k, v)?;
-            if i != self.0.len() - 1 {
-                write!(f, ",")?;
-            }
+        write!(f, "{}", self.name)?;
+        for (k, v) in self.parameters.iter() {
+            write!(f, ",{}:{}", k, v)?;
         }
         Ok(())
     }
 }
 
+impl FromStr for FilterSpec {
+    type Err = PacketParseError;
+
+    fn from_str(filter_str: &str) -> Result<Self, Self::Err> {
+        let bad_filter = || PacketParseError::BadFilter(filter_str.to_string());
+        let mut split = filter_str.split(',');
+        let name = split
+            .next()
+            .filter(|name| !name.is_empty() && !name.contains(':'))
+            .ok_or_else(bad_filter)?;
+        let parameters = split
+            .map(|kv| {
+                let mut colon_split_iter = kv.split(':');
+                let k = colon_split_iter.next().ok_or_else(bad_filter)?;
+                let v = colon_split_iter.next().ok_or_else(bad_filter)?;
+                // only one colon
+                if colon_split_iter.next().is_some() {
+                    return Err(bad_filter());
+                }
+                Ok((k.to_string(), v.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(FilterSpec {
+            name: name.to_string(),
+            parameters,
+        })
+    }
+}
+
 impl SrtControlPacket {
     pub fn parse<T: Buf>(
         packet_type: u16,
@@ -339,33 +377,23 @@ impl SrtControlPacket {
             }
             6 => le_bytes_to_string(buf).map(Congestion),
             // Filter
-            7 => {
-                let filter_str = le_bytes_to_string(buf)?;
-                Ok(Filter(FilterSpec(
-                    filter_str
-                        .split(',')
-                        .map(|kv| {
-                            let mut colon_split_iter = kv.split(':');
-                            let k = colon_split_iter
-                                .next()
-                                .ok_or_else(|| PacketParseError::BadFilter(filter_str.clone()))?;
-                            let v = colon_split_iter
-                                .next()
-                                .ok_or_else(|| PacketParseError::BadFilter(filter_str.clone()))?;
-                            // only one colon
-                            if colon_split_iter.next().is_some() {
-                                return Err(PacketParseError::BadFilter(filter_str.clone()));
-                            }
-                            Ok((k.to_string(), v.to_string()))
-                        })
-                        .collect::<Result<_, _>>()?,
-                )))
-            }
+            7 => le_bytes_to_string(buf)?.parse().map(Filter),
             8 => {
+                if buf.remaining() < 8 {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let group_id = SocketId(buf.get_u32());
+                // type, flags and weight are packed into a single big endian word,
+                // the same as the reference implementation
                 let ty = buf.get_u8().into();
                 let flags = GroupFlags::from_bits_truncate(buf.get_u8());
-                let weight = buf.get_u16_le();
-                Ok(Group { ty, flags, weight })
+                let weight = buf.get_u16();
+                Ok(Group {
+                    group_id,
+                    ty,
+                    flags,
+                    weight,
+                })
             }
             _ => Err(PacketParseError::UnsupportedSrtExtensionType(packet_type)),
         }
@@ -400,10 +428,16 @@ impl SrtControlPacket {
             Filter(filter) => {
                 string_to_le_bytes(&format!("{}", filter), into);
             }
-            Group { ty, flags, weight } => {
+            Group {
+                group_id,
+                ty,
+                flags,
+                weight,
+            } => {
+                into.put_u32(group_id.0);
                 into.put_u8((*ty).into());
                 into.put_u8(flags.bits());
-                into.put_u16_le(*weight);
+                into.put_u16(*weight);
             }
             Reject => {}
             StreamId(str) | Congestion(str) => {
@@ -425,8 +459,8 @@ impl SrtControlPacket {
                 4 + k.salt.len() as u16 / 4 + k.wrapped_keys.len() as u16 / 4
             }
             Congestion(str) | StreamId(str) => ((str.len() + 3) / 4) as u16, // round up to nearest multiple of 4
-            // 1 32-bit word packed with type, flags, and weight
-            Group { .. } => 1,
+            // group id, and 1 32-bit word packed with type, flags, and weight
+            Group { .. } => 2,
             Filter(filter) => ((format!("{}", filter).len() + 3) / 4) as u16, // TODO: not optimial performace, but probably okay
             _ => unimplemented!("{:?}", self),
         }
@@ -652,8 +686,17 @@ impl fmt::Debug for SrtControlPacket {
             SrtControlPacket::StreamId(sid) => write!(f, "streamid={}", sid),
             SrtControlPacket::Congestion(ctype) => write!(f, "congestion={}", ctype),
             SrtControlPacket::Filter(filter) => write!(f, "filter={:?}", filter),
-            SrtControlPacket::Group { ty, flags, weight } => {
-                write!(f, "group=({:?}, {:?}, {:?})", ty, flags, weight)
+            SrtControlPacket::Group {
+                group_id,
+                ty,
+                flags,
+                weight,
+            } => {
+                write!(
+                    f,
+                    "group=({:?}, {:?}, {:?}, {:?})",
+                    group_id, ty, flags, weight
+                )
             }
         }
     }
@@ -667,6 +710,7 @@ impl TryFrom<u8> for CipherType {
             1 => Ok(CipherType::Ecb),
             2 => Ok(CipherType::Ctr),
             3 => Ok(CipherType::Cbc),
+            4 => Ok(CipherType::Gcm),
             e => Err(PacketParseError::BadCipherKind(e)),
         }
     }
@@ -61,6 +61,30 @@ impl Salt {
         StreamInitializationVector(out)
     }
 
+    pub fn generate_gcm_iv_for(&self, seq_number: SeqNumber) -> [u8; 12] {
+        /* HaiCrypt-TP GCM mode IV (96-bit): (all these are in bytes)
+         *    0   1   2   3   4   5   6   7   8   9   10  11
+         * +---+---+---+---+---+---+---+---+---+---+---+---+
+         * |                   0s          |      pki      |
+         * +---+---+---+---+---+---+---+---+---+---+---+---+
+         *                            XOR
+         * +---+---+---+---+---+---+---+---+---+---+---+---+
+         * |                         nonce                 |
+         * +---+---+---+---+---+---+---+---+---+---+---+---+
+         *
+         * pki    (32-bit): packet index (sequence number)
+         * nonce  (96-bit): number used once (first 12 bytes of salt)
+         */
+        let mut out = [0; 12];
+        out.copy_from_slice(&self.0[..12]);
+
+        for (i, b) in seq_number.0.to_be_bytes().iter().enumerate() {
+            out[i + 8] ^= *b;
+        }
+
+        out
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         &self.0
     }
@@ -346,4 +370,18 @@ mod test {
 
         assert_ne!(Salt::new_random(), Salt::new_random());
     }
+
+    #[test]
+    fn generate_gcm_iv() {
+        let salt =
+            Salt::try_from(&hex::decode(b"87647f8a2361fb1a9e692de576985949").unwrap()[..]).unwrap();
+
+        let iv = salt.generate_gcm_iv_for(SeqNumber(709520665));
+
+        assert_eq!(
+            iv[..],
+            hex::decode(b"87647f8a2361fb1ab42340fc").unwrap()[..]
+        );
+        assert_ne!(iv, salt.generate_gcm_iv_for(SeqNumber(709520666)));
+    }
 }
@@ -8,7 +8,7 @@ use bytes::BytesMut;
 
 use crate::{packet::*, settings::*};
 
-use stream::KeyMaterialError;
+use stream::{KeyMaterialError, GCM_TAG_SIZE};
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum DecryptionError {
@@ -20,28 +20,38 @@ pub enum DecryptionError {
 }
 
 #[derive(Debug)]
-pub struct Decryption(Option<(StreamEncryptionKeys, KeySettings)>);
+pub struct Decryption {
+    keys: Option<(StreamEncryptionKeys, KeySettings)>,
+    key_material_state: KeyMaterialState,
+}
 
 impl Decryption {
-    pub fn new(settings: Option<CipherSettings>) -> Self {
-        Self(settings.map(|settings| (settings.stream_keys, settings.key_settings)))
+    pub fn new(settings: Option<CipherSettings>, key_material_state: KeyMaterialState) -> Self {
+        Self {
+            keys: settings.map(|settings| (settings.stream_keys, settings.key_settings)),
+            key_material_state,
+        }
+    }
+
+    pub fn key_material_state(&self) -> KeyMaterialState {
+        self.key_material_state
     }
 
     pub fn decrypt(&self, packet: DataPacket) -> Result<(usize, DataPacket), DecryptionError> {
         use DecryptionError::*;
         let mut packet = packet;
-        match (packet.encryption, &self.0) {
+        match (packet.encryption, &self.keys) {
             (DataEncryption::None, None) => Ok((0, packet)),
             (DataEncryption::None, Some(_)) => Err(UnexpectedUnencryptedPacket(packet)),
             (DataEncryption::Even | DataEncryption::Odd, None) => {
                 Err(UnexpectedEncryptedPacket(packet))
             }
-            (selected_sek, Some((stream_keys, _))) => {
+            (_, Some((stream_keys, _))) => {
                 // this requires an extra copy here...maybe DataPacket should have a BytesMut in it instead...
                 let mut data = BytesMut::with_capacity(packet.payload.len());
                 data.extend_from_slice(&packet.payload[..]);
                 let bytes = stream_keys
-                    .decrypt(selected_sek, packet.seq_number, &mut data)
+                    .decrypt(&packet, &mut data)
                     .ok_or(DecryptionFailure)?;
                 packet.encryption = DataEncryption::None;
                 packet.payload = data.freeze();
@@ -54,9 +64,32 @@ impl Decryption {
         &mut self,
         keying_material: KeyingMaterialMessage,
     ) -> Result<Option<KeyingMaterialMessage>, KeyMaterialError> {
-        let (stream_keys, key_settings) = self.0.as_mut().ok_or(KeyMaterialError::NoKeys)?;
-        *stream_keys = StreamEncryptionKeys::unwrap_from(key_settings, &keying_material)?;
-        Ok(Some(keying_material))
+        let (stream_keys, key_settings) = self.keys.as_mut().ok_or(KeyMaterialError::NoKeys)?;
+        let new_keys = StreamEncryptionKeys::unwrap_from(key_settings, &keying_material).and_then(
+            |new_keys| {
+                // the cipher is agreed during the handshake, a refresh must not downgrade it
+                if new_keys.cipher() == stream_keys.cipher() {
+                    Ok(new_keys)
+                } else {
+                    Err(KeyMaterialError::UnsupportedCipher(
+                        keying_material.cipher,
+                        keying_material.auth,
+                    ))
+                }
+            },
+        );
+        match new_keys {
+            Ok(new_keys) => {
+                *stream_keys = new_keys;
+                self.key_material_state = KeyMaterialState::Secured;
+                Ok(Some(keying_material))
+            }
+            Err(error) => {
+                // the packets encrypted with the new keys will not be decryptable
+                self.key_material_state = KeyMaterialState::BadSecret;
+                Err(error)
+            }
+        }
     }
 }
 
@@ -65,6 +98,7 @@ pub struct Encryption(Option<EncryptionState>);
 
 #[derive(Debug)]
 struct EncryptionState {
+    key_material_state: KeyMaterialState,
     key_settings: KeySettings,
     key_refresh: KeyMaterialRefreshSettings,
     stream_keys: StreamEncryptionKeys,
@@ -78,12 +112,10 @@ struct EncryptionState {
 impl EncryptionState {
     fn try_encrypt_packet(&mut self, mut packet: DataPacket) -> Option<(usize, DataPacket)> {
         // this requires an extra copy here...maybe DataPacket should have a BytesMut in it instead...
-        let mut data = BytesMut::with_capacity(packet.payload.len());
+        let mut data = BytesMut::with_capacity(packet.payload.len() + GCM_TAG_SIZE);
         data.extend_from_slice(&packet.payload[..]);
-        let bytes = self
-            .stream_keys
-            .encrypt(self.active_sek, packet.seq_number, &mut data)?;
         packet.encryption = self.active_sek;
+        let bytes = self.stream_keys.encrypt(&packet, &mut data)?;
         packet.payload = data.freeze();
         Some((bytes, packet))
     }
@@ -123,14 +155,18 @@ impl EncryptionState {
                     Odd => Even,
                     None => None,
                 };
+            } else if self.key_material_state == KeyMaterialState::Secured {
+                // the peer has not acknowledged the new keys in time, keep using the old ones
+                self.key_material_state = KeyMaterialState::Securing;
             }
         }
     }
 }
 
 impl Encryption {
-    pub fn new(settings: Option<CipherSettings>) -> Self {
+    pub fn new(settings: Option<CipherSettings>, key_material_state: KeyMaterialState) -> Self {
         Self(settings.map(|settings| EncryptionState {
+            key_material_state,
             key_settings: settings.key_settings,
             key_refresh: settings.key_refresh.clone(),
             stream_keys: settings.stream_keys,
@@ -144,6 +180,13 @@ impl Encryption {
         }))
     }
 
+    /// Unsecured when the data is sent unencrypted
+    pub fn key_material_state(&self) -> KeyMaterialState {
+        self.0
+            .as_ref()
+            .map_or(KeyMaterialState::Unsecured, |this| this.key_material_state)
+    }
+
     pub fn encrypt(
         &mut self,
         packet: DataPacket,
@@ -175,6 +218,7 @@ impl Encryption {
             if keying_material == *expected_key_material {
                 settings.packets_until_transmit = 0;
                 settings.last_key_material = None;
+                settings.key_material_state = KeyMaterialState::Secured;
             } else {
                 return Err(InvalidRefreshResponse(keying_material));
             }
@@ -195,7 +239,7 @@ mod tests {
     }
 
     fn new_settings() -> CipherSettings {
-        CipherSettings::new_random(&key_settings(), &Default::default())
+        CipherSettings::new_random(&key_settings(), &Default::default(), CipherType::Ctr)
     }
 
     fn data_packet(encryption: DataEncryption, payload: &str) -> DataPacket {
@@ -217,27 +261,103 @@ mod tests {
         let settings = new_settings();
         let original_packet = data_packet(DataEncryption::None, "test round_trip");
 
-        let mut encryption = Encryption::new(Some(settings.clone()));
+        let mut encryption = Encryption::new(Some(settings.clone()), KeyMaterialState::Secured);
         let (bytes, encrypted_packet, key_material) =
             encryption.encrypt(original_packet.clone()).unwrap();
         assert_eq!(bytes, original_packet.payload.len());
         assert_ne!(encrypted_packet, original_packet);
         assert_eq!(key_material, None);
 
-        let decryption = Decryption::new(Some(settings));
+        let decryption = Decryption::new(Some(settings), KeyMaterialState::Secured);
         let (bytes, decrypted_packet) = decryption.decrypt(encrypted_packet).unwrap();
         assert_eq!(bytes, original_packet.payload.len());
         assert_eq!(decrypted_packet, original_packet);
     }
 
+    #[test]
+    fn gcm_round_trip() {
+        let settings =
+            CipherSettings::new_random(&key_settings(), &Default::default(), CipherType::Gcm);
+        let original_packet = data_packet(DataEncryption::None, "test gcm_round_trip");
+
+        let mut encryption = Encryption::new(Some(settings.clone()), KeyMaterialState::Secured);
+        let (bytes, encrypted_packet, _) = encryption.encrypt(original_packet.clone()).unwrap();
+        assert_eq!(bytes, original_packet.payload.len());
+        assert_eq!(
+            encrypted_packet.payload.len(),
+            original_packet.payload.len() + GCM_TAG_SIZE
+        );
+
+        // the retransmitted flag is not authenticated
+        let decryption = Decryption::new(Some(settings), KeyMaterialState::Secured);
+        let retransmitted_packet = DataPacket {
+            retransmitted: true,
+            ..encrypted_packet.clone()
+        };
+        let (bytes, decrypted_packet) = decryption.decrypt(retransmitted_packet).unwrap();
+        assert_eq!(bytes, original_packet.payload.len());
+        assert_eq!(
+            decrypted_packet,
+            DataPacket {
+                retransmitted: true,
+                ..original_packet
+            }
+        );
+
+        let mut payload = encrypted_packet.payload.to_vec();
+        payload[0] ^= 1;
+        let tampered_payload = DataPacket {
+            payload: payload.into(),
+            ..encrypted_packet.clone()
+        };
+        assert_eq!(
+            decryption.decrypt(tampered_payload),
+            Err(DecryptionError::DecryptionFailure)
+        );
+
+        let tampered_header = DataPacket {
+            timestamp: TimeStamp::MIN + TimeSpan::from_micros(1),
+            ..encrypted_packet.clone()
+        };
+        assert_eq!(
+            decryption.decrypt(tampered_header),
+            Err(DecryptionError::DecryptionFailure)
+        );
+
+        let truncated = DataPacket {
+            payload: encrypted_packet.payload.slice(..GCM_TAG_SIZE - 1),
+            ..encrypted_packet
+        };
+        assert_eq!(
+            decryption.decrypt(truncated),
+            Err(DecryptionError::DecryptionFailure)
+        );
+    }
+
+    #[test]
+    fn refresh_key_material_keeps_cipher() {
+        let gcm_settings =
+            CipherSettings::new_random(&key_settings(), &Default::default(), CipherType::Gcm);
+        let mut decryption = Decryption::new(Some(gcm_settings), KeyMaterialState::Secured);
+
+        let ctr_key_material = new_settings().wrap_keying_material().unwrap();
+        assert_eq!(
+            decryption.refresh_key_material(ctr_key_material),
+            Err(KeyMaterialError::UnsupportedCipher(
+                CipherType::Ctr,
+                Auth::None
+            ))
+        );
+    }
+
     #[test]
     fn decryption_falure() {
         use DecryptionError::*;
         let with_keys = |with_keys| {
             if with_keys {
-                Decryption::new(Some(new_settings()))
+                Decryption::new(Some(new_settings()), KeyMaterialState::Secured)
             } else {
-                Decryption::new(None)
+                Decryption::new(None, KeyMaterialState::Unsecured)
             }
         };
 
@@ -271,8 +391,8 @@ mod tests {
             key_refresh: KeyMaterialRefreshSettings::new(3_000, 1_000).unwrap(),
             ..new_settings()
         };
-        let mut encryption = Encryption::new(Some(settings.clone()));
-        let mut decryption = Decryption::new(Some(settings.clone()));
+        let mut encryption = Encryption::new(Some(settings.clone()), KeyMaterialState::Secured);
+        let mut decryption = Decryption::new(Some(settings.clone()), KeyMaterialState::Secured);
         let original_packet = data_packet(DataEncryption::None, "test refresh_key_material");
 
         let count = settings.key_refresh.period() - settings.key_refresh.pre_announcement_period();
@@ -336,7 +456,7 @@ mod tests {
             key_refresh: KeyMaterialRefreshSettings::new(44_000, 20_000).unwrap(),
             ..new_settings()
         };
-        let mut encryption = Encryption::new(Some(settings.clone()));
+        let mut encryption = Encryption::new(Some(settings.clone()), KeyMaterialState::Secured);
         let original_packet = data_packet(DataEncryption::None, "test refresh_key_material");
 
         let mut km_resp = None;
@@ -370,4 +490,48 @@ mod tests {
         // none received after the response
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn key_material_state() {
+        use KeyMaterialState::*;
+        let settings = CipherSettings {
+            key_refresh: KeyMaterialRefreshSettings::new(3_000, 1_000).unwrap(),
+            ..new_settings()
+        };
+        let mut encryption = Encryption::new(Some(settings.clone()), Secured);
+        let mut decryption = Decryption::new(Some(settings.clone()), Secured);
+        let original_packet = data_packet(DataEncryption::None, "test key_material_state");
+
+        // the keys are due to be switched, but the new ones have not been acknowledged yet
+        let mut key_material = None;
+        for _ in 0..=settings.key_refresh.period() {
+            let (_, _, km) = encryption.encrypt(original_packet.clone()).unwrap();
+            key_material = km.or(key_material);
+        }
+        assert_eq!(encryption.key_material_state(), Securing);
+
+        let key_material = key_material.unwrap();
+        assert!(decryption
+            .refresh_key_material(key_material.clone())
+            .is_ok());
+        assert_eq!(decryption.key_material_state(), Secured);
+        assert_eq!(encryption.handle_key_refresh_response(key_material), Ok(()));
+        assert_eq!(encryption.key_material_state(), Secured);
+
+        // key material wrapped with another passphrase
+        let other_key_settings = KeySettings {
+            passphrase: "0987654321".into(),
+            ..key_settings()
+        };
+        let other_settings =
+            CipherSettings::new_random(&other_key_settings, &Default::default(), CipherType::Ctr);
+        let key_material = other_settings.wrap_keying_material().unwrap();
+        assert!(decryption.refresh_key_material(key_material).is_err());
+        assert_eq!(decryption.key_material_state(), BadSecret);
+
+        assert_eq!(
+            Encryption::new(None, Unsecured).key_material_state(),
+            Unsecured
+        );
+    }
 }
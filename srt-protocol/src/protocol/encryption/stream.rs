@@ -1,12 +1,24 @@
 use std::fmt::Debug;
 
 use aes::cipher::StreamCipher;
+use aes_gcm::{
+    aead::{consts::U12, AeadInPlace},
+    AesGcm, KeyInit, Tag,
+};
+use bytes::BytesMut;
 use cipher::KeyIvInit;
 
 type Aes128Ctr = ctr::Ctr64BE<aes::Aes128>;
 type Aes192Ctr = ctr::Ctr64BE<aes::Aes192>;
 type Aes256Ctr = ctr::Ctr64BE<aes::Aes256>;
 
+type Aes128Gcm = AesGcm<aes::Aes128, U12>;
+type Aes192Gcm = AesGcm<aes::Aes192, U12>;
+type Aes256Gcm = AesGcm<aes::Aes256, U12>;
+
+/// The size of the authentication tag appended to the payload by AES-GCM
+pub const GCM_TAG_SIZE: usize = 16;
+
 use crate::{
     packet::*,
     settings::{KeySettings, KeySize},
@@ -21,10 +33,12 @@ pub enum KeyMaterialError {
     InvalidKeyFlags(KeyFlags, KeySize, usize),
     InvalidInitializationVector(WrapInitializationVector),
     InvalidRefreshResponse(KeyingMaterialMessage),
+    UnsupportedCipher(CipherType, Auth),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StreamEncryptionKeys {
+    cipher: CipherType,
     salt: Salt,
     even_key: Option<EncryptionKey>,
     odd_key: Option<EncryptionKey>,
@@ -33,14 +47,16 @@ pub struct StreamEncryptionKeys {
 impl StreamEncryptionKeys {
     pub fn new(salt: Salt) -> Self {
         Self {
+            cipher: CipherType::Ctr,
             salt,
             even_key: None,
             odd_key: None,
         }
     }
 
-    pub fn new_random(key_size: KeySize) -> Self {
+    pub fn new_random(key_size: KeySize, cipher: CipherType) -> Self {
         Self {
+            cipher,
             salt: Salt::new_random(),
             even_key: Some(EncryptionKey::new_random(key_size)),
             odd_key: Some(EncryptionKey::new_random(key_size)),
@@ -78,6 +94,10 @@ impl StreamEncryptionKeys {
     ) -> Result<Self, KeyMaterialError> {
         use KeyMaterialError::*;
         // TODO: revisit errors, KeyingMaterialMessage has a lot of fields that ought be validated
+        let cipher = match (key_material.cipher, key_material.auth) {
+            (CipherType::Ctr, Auth::None) | (CipherType::Gcm, Auth::AesGcm) => key_material.cipher,
+            (cipher, auth) => return Err(UnsupportedCipher(cipher, auth)),
+        };
         let salt = Salt::try_from(key_material.salt.as_slice()).map_err(|_| InvalidSaltLength)?;
         let kek = KeyEncryptionKey::new(key_settings, &salt);
 
@@ -111,6 +131,7 @@ impl StreamEncryptionKeys {
         };
 
         Ok(StreamEncryptionKeys {
+            cipher,
             salt,
             even_key,
             odd_key,
@@ -139,51 +160,92 @@ impl StreamEncryptionKeys {
                 (None, None) => return None,
             },
             keki: 0, // xxx
-            cipher: CipherType::Ctr,
-            auth: Auth::None,
+            cipher: self.cipher,
+            auth: match self.cipher {
+                CipherType::Gcm => Auth::AesGcm,
+                _ => Auth::None,
+            },
             salt: self.salt.as_slice().to_vec(),
             wrapped_keys,
         })
     }
 
-    pub fn decrypt(
-        &self,
-        sek_selection: DataEncryption,
-        seq_number: SeqNumber,
-        data: &mut [u8],
-    ) -> Option<usize> {
-        let sek = self.get_key(sek_selection)?;
-        let iv = self.salt.generate_strean_iv_for(seq_number);
+    pub fn cipher(&self) -> CipherType {
+        self.cipher
+    }
+
+    /// Decrypts the payload of the packet in place, an AES-GCM payload is also checked against the
+    /// authentication tag at its end, which is then removed
+    pub fn decrypt(&self, packet: &DataPacket, data: &mut BytesMut) -> Option<usize> {
+        let sek = self.get_key(packet.encryption)?;
 
-        let nonce = iv.as_bytes();
         use EncryptionKey::*;
-        match sek {
-            Bytes16(key) => Aes128Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-            Bytes24(key) => Aes192Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-            Bytes32(key) => Aes256Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-        };
+        if self.cipher == CipherType::Gcm {
+            let nonce = self.salt.generate_gcm_iv_for(packet.seq_number);
+            let aad = authenticated_header(packet);
+            let tag = data.split_off(data.len().checked_sub(GCM_TAG_SIZE)?);
+            let tag = Tag::from_slice(&tag[..]);
+            let nonce = (&nonce).into();
+            match sek {
+                Bytes16(key) => {
+                    Aes128Gcm::new(key.into()).decrypt_in_place_detached(nonce, &aad, data, tag)
+                }
+                Bytes24(key) => {
+                    Aes192Gcm::new(key.into()).decrypt_in_place_detached(nonce, &aad, data, tag)
+                }
+                Bytes32(key) => {
+                    Aes256Gcm::new(key.into()).decrypt_in_place_detached(nonce, &aad, data, tag)
+                }
+            }
+            .ok()?;
+        } else {
+            let iv = self.salt.generate_strean_iv_for(packet.seq_number);
+            let nonce = iv.as_bytes();
+            match sek {
+                Bytes16(key) => Aes128Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+                Bytes24(key) => Aes192Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+                Bytes32(key) => Aes256Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+            };
+        }
 
         Some(data.len())
     }
 
-    pub fn encrypt(
-        &self,
-        sek_selection: DataEncryption,
-        seq_number: SeqNumber,
-        data: &mut [u8],
-    ) -> Option<usize> {
-        let sek = self.get_key(sek_selection)?;
-        let iv = self.salt.generate_strean_iv_for(seq_number);
+    /// Encrypts the payload of the packet in place, with the key selected by the packet, AES-GCM
+    /// appends the authentication tag to the payload
+    pub fn encrypt(&self, packet: &DataPacket, data: &mut BytesMut) -> Option<usize> {
+        let sek = self.get_key(packet.encryption)?;
+        let bytes = data.len();
 
-        let nonce = iv.as_bytes();
         use EncryptionKey::*;
-        match sek {
-            Bytes16(key) => Aes128Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-            Bytes24(key) => Aes192Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-            Bytes32(key) => Aes256Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
-        };
+        if self.cipher == CipherType::Gcm {
+            let nonce = self.salt.generate_gcm_iv_for(packet.seq_number);
+            let aad = authenticated_header(packet);
+            let nonce = (&nonce).into();
+            let tag = match sek {
+                Bytes16(key) => {
+                    Aes128Gcm::new(key.into()).encrypt_in_place_detached(nonce, &aad, data)
+                }
+                Bytes24(key) => {
+                    Aes192Gcm::new(key.into()).encrypt_in_place_detached(nonce, &aad, data)
+                }
+                Bytes32(key) => {
+                    Aes256Gcm::new(key.into()).encrypt_in_place_detached(nonce, &aad, data)
+                }
+            }
+            .ok()?;
+            data.extend_from_slice(&tag[..]);
+        } else {
+            let iv = self.salt.generate_strean_iv_for(packet.seq_number);
+            let nonce = iv.as_bytes();
+            match sek {
+                Bytes16(key) => Aes128Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+                Bytes24(key) => Aes192Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+                Bytes32(key) => Aes256Ctr::new(key.into(), nonce[..].into()).apply_keystream(data),
+            };
+        }
 
-        Some(data.len())
+        Some(bytes)
     }
 
     fn get_key(&self, active: DataEncryption) -> Option<&EncryptionKey> {
@@ -196,6 +258,19 @@ impl StreamEncryptionKeys {
     }
 }
 
+// AES-GCM authenticates the header of the data packet, except for the retransmitted flag, which is
+// set on the already encrypted packet when it is sent again
+fn authenticated_header(packet: &DataPacket) -> Vec<u8> {
+    let header = DataPacket {
+        retransmitted: false,
+        payload: Default::default(),
+        ..packet.clone()
+    };
+    let mut aad = Vec::with_capacity(16);
+    header.serialize(&mut aad);
+    aad
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -212,6 +287,7 @@ mod test {
     fn wrap_keys() {
         let salt = b"\x00\x00\x00\x00\x00\x00\x00\x00\x85\x2c\x3c\xcd\x02\x65\x1a\x22";
         let stream_encryption = StreamEncryptionKeys {
+            cipher: CipherType::Ctr,
             salt: Salt::try_from(salt).unwrap(),
             odd_key: None,
             even_key: EncryptionKey::try_from(b"\r\xab\xc8n/2\xb4\xa7\xb9\xbb\xa2\xf31*\xe4\"")
@@ -271,6 +347,7 @@ mod test {
     fn wrap_key2() {
         let salt = b"\x00\x00\x00\x00\x00\x00\x00\x00n\xd5+\x196\nq8";
         let stream_encryption = StreamEncryptionKeys {
+            cipher: CipherType::Ctr,
             salt: Salt::try_from(salt).unwrap(),
             odd_key: EncryptionKey::try_from(b"\r\xab\xc8n/2\xb4\xa7\xb9\xbb\xa2\xf31*\xe4\"").ok(),
             even_key: None,
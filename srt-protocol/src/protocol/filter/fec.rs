@@ -0,0 +1,527 @@
+use std::{
+    cmp::max,
+    collections::{HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{
+    options::{ArqLevel, FecConfig, FecLayout},
+    packet::*,
+};
+
+use super::{ReceiverPacketFilter, SenderPacketFilter};
+
+// the group index of row FEC packets, column FEC packets carry their column instead
+const ROW_INDEX: u8 = 0xFF;
+
+/// The group index, flag clip and length clip in front of the payload clip of FEC packets
+pub const FEC_HEADER_SIZE: usize = 4;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum GroupId {
+    Row(u64),
+    /// The column, and the group within the column
+    Column(u64, u64),
+}
+
+/// The XOR of the header fields and payloads of the data packets in a group
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct Clip {
+    timestamp: u32,
+    flags: u8,
+    length: u16,
+    payload: Vec<u8>,
+}
+
+impl Clip {
+    fn add_packet(&mut self, packet: &DataPacket) {
+        self.add(
+            packet.timestamp.as_micros(),
+            packet.encryption as u8 >> 3,
+            packet.payload.len() as u16,
+            &packet.payload,
+        );
+    }
+
+    fn add_clip(&mut self, other: &Clip) {
+        self.add(other.timestamp, other.flags, other.length, &other.payload);
+    }
+
+    fn add(&mut self, timestamp: u32, flags: u8, length: u16, payload: &[u8]) {
+        self.timestamp ^= timestamp;
+        self.flags ^= flags;
+        self.length ^= length;
+        if self.payload.len() < payload.len() {
+            self.payload.resize(payload.len(), 0);
+        }
+        for (clip, byte) in self.payload.iter_mut().zip(payload) {
+            *clip ^= byte;
+        }
+    }
+
+    fn parse(packet: &DataPacket) -> Option<(u8, Clip)> {
+        let mut payload = packet.payload.clone();
+        if payload.len() < FEC_HEADER_SIZE {
+            return None;
+        }
+        let index = payload.get_u8();
+        let flags = payload.get_u8();
+        let length = payload.get_u16();
+        Some((
+            index,
+            Clip {
+                timestamp: packet.timestamp.as_micros(),
+                flags,
+                length,
+                payload: payload.to_vec(),
+            },
+        ))
+    }
+
+    fn to_payload(&self, index: u8) -> Bytes {
+        let mut payload = BytesMut::with_capacity(FEC_HEADER_SIZE + self.payload.len());
+        payload.put_u8(index);
+        payload.put_u8(self.flags);
+        payload.put_u16(self.length);
+        payload.put_slice(&self.payload);
+        payload.freeze()
+    }
+
+    fn to_data_packet(&self, seq_number: SeqNumber, dest_sockid: SocketId) -> DataPacket {
+        let length = usize::from(self.length).min(self.payload.len());
+        DataPacket {
+            seq_number,
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: match self.flags {
+                1 => DataEncryption::Even,
+                2 => DataEncryption::Odd,
+                _ => DataEncryption::None,
+            },
+            retransmitted: false,
+            // the message number is not protected, recovered packets are always single packet
+            // messages, which live transmission sends anyway
+            message_number: MsgNumber(1),
+            timestamp: TimeStamp::from_micros(self.timestamp),
+            dest_sockid,
+            payload: Bytes::copy_from_slice(&self.payload[..length]),
+        }
+    }
+}
+
+/// Arranges the data packets, numbered from the initial sequence number on, into a matrix with one
+/// row group per row and column groups of `rows` packets in each column
+#[derive(Debug)]
+struct Matrix {
+    config: FecConfig,
+    init_seq_num: SeqNumber,
+    // the highest sequence number seen and its index, to continue numbering across wrapping
+    last: (SeqNumber, u64),
+}
+
+impl Matrix {
+    fn new(config: FecConfig, init_seq_num: SeqNumber) -> Self {
+        Self {
+            config,
+            init_seq_num,
+            last: (init_seq_num, 0),
+        }
+    }
+
+    fn index(&mut self, seq_number: SeqNumber) -> Option<u64> {
+        let (last_seq_number, last_index) = self.last;
+        let index = if seq_number >= last_seq_number {
+            last_index + u64::from(seq_number - last_seq_number)
+        } else {
+            last_index.checked_sub(u64::from(last_seq_number - seq_number))?
+        };
+        if index > last_index {
+            self.last = (seq_number, index);
+        }
+        Some(index)
+    }
+
+    fn seq_number(&self, index: u64) -> SeqNumber {
+        self.init_seq_num + (index % u64::from(SeqNumber::MAX)) as u32
+    }
+
+    fn columns(&self) -> u64 {
+        u64::from(self.config.columns)
+    }
+
+    fn rows(&self) -> u64 {
+        u64::from(self.config.rows)
+    }
+
+    // the staircase layout starts each column group one row later than the previous column
+    fn first_row(&self, column: u64) -> u64 {
+        match self.config.layout {
+            FecLayout::Even => 0,
+            FecLayout::Staircase => column % self.rows(),
+        }
+    }
+
+    fn groups(&self, index: u64) -> impl Iterator<Item = GroupId> {
+        let (row, column) = (index / self.columns(), index % self.columns());
+        let row_group = if self.config.row_groups {
+            Some(GroupId::Row(row))
+        } else {
+            None
+        };
+        // the rows before the first column group in the staircase layout are only in row groups
+        let first_row = self.first_row(column);
+        let column_group = if self.config.has_column_groups() && row >= first_row {
+            Some(GroupId::Column(column, (row - first_row) / self.rows()))
+        } else {
+            None
+        };
+        row_group.into_iter().chain(column_group)
+    }
+
+    fn members(&self, group: GroupId) -> impl Iterator<Item = u64> {
+        let (columns, rows) = (self.columns(), self.rows());
+        let (first, step, count) = match group {
+            GroupId::Row(row) => (row * columns, 1, columns),
+            GroupId::Column(column, group) => (
+                (group * rows + self.first_row(column)) * columns + column,
+                columns,
+                rows,
+            ),
+        };
+        (0..count).map(move |i| first + i * step)
+    }
+
+    fn size(&self, group: GroupId) -> usize {
+        match group {
+            GroupId::Row(_) => self.config.columns.into(),
+            GroupId::Column(_, _) => self.config.rows.into(),
+        }
+    }
+
+    fn last_member(&self, group: GroupId) -> u64 {
+        self.members(group).last().unwrap_or_default()
+    }
+
+    /// How far behind the newest packet a packet can still be recovered, the length of a column
+    /// group, plus a row to allow for reordering
+    fn horizon(&self) -> u64 {
+        self.columns() * (self.rows() + 1)
+    }
+}
+
+/// The sending side of the "fec" filter, sends an FEC packet after each complete group
+#[derive(Debug)]
+pub struct FecSender {
+    matrix: Matrix,
+    groups: HashMap<GroupId, (usize, Clip)>,
+    control_packets: VecDeque<DataPacket>,
+}
+
+impl FecSender {
+    pub fn new(config: FecConfig, init_seq_num: SeqNumber) -> Self {
+        Self {
+            matrix: Matrix::new(config, init_seq_num),
+            groups: HashMap::new(),
+            control_packets: VecDeque::new(),
+        }
+    }
+}
+
+impl SenderPacketFilter for FecSender {
+    fn on_data_sent(&mut self, packet: &DataPacket) {
+        let index = match self.matrix.index(packet.seq_number) {
+            Some(index) => index,
+            None => return,
+        };
+        for group in self.matrix.groups(index) {
+            let (count, clip) = self.groups.entry(group).or_default();
+            *count += 1;
+            clip.add_packet(packet);
+            if *count < self.matrix.size(group) {
+                continue;
+            }
+
+            let group_index = match group {
+                GroupId::Row(_) => ROW_INDEX,
+                // the column is below the column count, which fits in a byte
+                GroupId::Column(column, _) => u8::try_from(column).unwrap(),
+            };
+            if let Some((_, clip)) = self.groups.remove(&group) {
+                self.control_packets.push_back(DataPacket {
+                    seq_number: packet.seq_number,
+                    message_loc: PacketLocation::ONLY,
+                    in_order_delivery: false,
+                    encryption: DataEncryption::None,
+                    retransmitted: false,
+                    message_number: MsgNumber(0),
+                    timestamp: TimeStamp::from_micros(clip.timestamp),
+                    dest_sockid: packet.dest_sockid,
+                    payload: clip.to_payload(group_index),
+                });
+            }
+        }
+
+        // groups missing packets that were never sent, e.g. dropped too late, never complete
+        let horizon = index.saturating_sub(self.matrix.horizon());
+        let matrix = &self.matrix;
+        self.groups
+            .retain(|group, _| matrix.last_member(*group) >= horizon);
+    }
+
+    fn next_control_packet(&mut self) -> Option<DataPacket> {
+        self.control_packets.pop_front()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReceiverGroup {
+    count: usize,
+    clip: Clip,
+    fec: Option<Clip>,
+}
+
+/// The receiving side of the "fec" filter, rebuilds the single missing packet of a group from the
+/// other packets of the group and its FEC packet
+#[derive(Debug)]
+pub struct FecReceiver {
+    matrix: Matrix,
+    dest_sockid: SocketId,
+    // packets received or recovered, from the oldest packet that can still be recovered on
+    received: HashSet<u64>,
+    groups: HashMap<GroupId, ReceiverGroup>,
+    oldest: u64,
+    newest: u64,
+    recovered: VecDeque<DataPacket>,
+    lost: VecDeque<SeqNumber>,
+}
+
+impl FecReceiver {
+    pub fn new(config: FecConfig, init_seq_num: SeqNumber) -> Self {
+        Self {
+            matrix: Matrix::new(config, init_seq_num),
+            dest_sockid: SocketId(0),
+            received: HashSet::new(),
+            groups: HashMap::new(),
+            oldest: 0,
+            newest: 0,
+            recovered: VecDeque::new(),
+            lost: VecDeque::new(),
+        }
+    }
+
+    fn add_member(&mut self, index: u64, clip: &Clip, skip: Option<GroupId>) {
+        let groups: Vec<_> = self
+            .matrix
+            .groups(index)
+            .filter(|g| Some(*g) != skip)
+            .collect();
+        for group in groups {
+            let state = self.groups.entry(group).or_default();
+            state.count += 1;
+            state.clip.add_clip(clip);
+            self.try_recover(group);
+        }
+    }
+
+    fn try_recover(&mut self, group: GroupId) {
+        let size = self.matrix.size(group);
+        let state = match self.groups.get_mut(&group) {
+            Some(state) if state.count + 1 == size && state.fec.is_some() => state,
+            _ => return,
+        };
+        let received = &self.received;
+        let missing = self
+            .matrix
+            .members(group)
+            .find(|index| !received.contains(index));
+        let missing = match missing {
+            Some(missing) if missing >= self.oldest => missing,
+            _ => return,
+        };
+
+        let mut clip = state.clip.clone();
+        if let Some(fec) = &state.fec {
+            clip.add_clip(fec);
+        }
+        state.count += 1;
+        self.received.insert(missing);
+        self.recovered
+            .push_back(clip.to_data_packet(self.matrix.seq_number(missing), self.dest_sockid));
+
+        // the recovered packet may complete its other group, recovering another packet
+        self.add_member(missing, &clip, Some(group));
+    }
+
+    fn advance(&mut self, index: u64) {
+        self.newest = max(self.newest, index);
+        let horizon = self.newest.saturating_sub(self.matrix.horizon());
+        if self.oldest >= horizon {
+            return;
+        }
+        while self.oldest < horizon {
+            if !self.received.remove(&self.oldest) {
+                self.lost.push_back(self.matrix.seq_number(self.oldest));
+            }
+            self.oldest += 1;
+        }
+        let (matrix, oldest) = (&self.matrix, self.oldest);
+        self.groups
+            .retain(|group, _| matrix.last_member(*group) >= oldest);
+    }
+}
+
+impl ReceiverPacketFilter for FecReceiver {
+    fn arq_level(&self) -> ArqLevel {
+        self.matrix.config.arq
+    }
+
+    fn on_data_received(&mut self, packet: &DataPacket) {
+        self.dest_sockid = packet.dest_sockid;
+        let index = match self.matrix.index(packet.seq_number) {
+            Some(index) if index >= self.oldest => index,
+            _ => return,
+        };
+        if !self.received.insert(index) {
+            return;
+        }
+        let mut clip = Clip::default();
+        clip.add_packet(packet);
+        self.add_member(index, &clip, None);
+        self.advance(index);
+    }
+
+    fn on_control_received(&mut self, packet: DataPacket) {
+        // FEC packets carry the sequence number of the last packet of their group
+        let index = match self.matrix.index(packet.seq_number) {
+            Some(index) if index >= self.oldest => index,
+            _ => return,
+        };
+        let (group_index, fec) = match Clip::parse(&packet) {
+            Some(fec) => fec,
+            None => return,
+        };
+        let group = self.matrix.groups(index).find(|group| match group {
+            GroupId::Row(_) => group_index == ROW_INDEX,
+            GroupId::Column(column, _) => {
+                group_index != ROW_INDEX && *column == u64::from(group_index)
+            }
+        });
+        if let Some(group) = group {
+            self.groups.entry(group).or_default().fec = Some(fec);
+            self.try_recover(group);
+        }
+        self.advance(index);
+    }
+
+    fn next_recovered_packet(&mut self) -> Option<DataPacket> {
+        self.recovered.pop_front()
+    }
+
+    fn next_lost_packet(&mut self) -> Option<SeqNumber> {
+        self.lost.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(spec: &str) -> FecConfig {
+        FecConfig::try_from(&spec.parse::<FilterSpec>().unwrap()).unwrap()
+    }
+
+    fn data_packet(seq_number: u32) -> DataPacket {
+        DataPacket {
+            seq_number: SeqNumber(seq_number),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber(1),
+            timestamp: TimeStamp::from_micros(seq_number.wrapping_mul(1000)),
+            dest_sockid: SocketId(7),
+            payload: Bytes::from(vec![seq_number as u8; 10 + seq_number as usize % 5]),
+        }
+    }
+
+    // sends packets 0..count through the sender, dropping the lost packets before the receiver
+    fn transmit(
+        spec: &str,
+        init_seq_num: u32,
+        count: u32,
+        lost: &[u32],
+    ) -> (FecReceiver, Vec<DataPacket>, usize) {
+        let mut sender = FecSender::new(config(spec), SeqNumber(init_seq_num));
+        let mut receiver = FecReceiver::new(config(spec), SeqNumber(init_seq_num));
+        let mut extra = 0;
+        for seq_number in (0..count).map(|i| (init_seq_num + i) % SeqNumber::MAX) {
+            let packet = data_packet(seq_number);
+            sender.on_data_sent(&packet);
+            if !lost.contains(&seq_number) {
+                receiver.on_data_received(&packet);
+            }
+            while let Some(control) = sender.next_control_packet() {
+                assert!(super::super::is_control_packet(&control));
+                extra += 1;
+                receiver.on_control_received(control);
+            }
+        }
+        let recovered = std::iter::from_fn(|| receiver.next_recovered_packet()).collect();
+        (receiver, recovered, extra)
+    }
+
+    #[test]
+    fn row_recovery() {
+        let (_, recovered, extra) = transmit("fec,cols:4", 0, 12, &[1, 7]);
+        assert_eq!(extra, 3);
+        assert_eq!(recovered, vec![data_packet(1), data_packet(7)]);
+    }
+
+    #[test]
+    fn column_recovery() {
+        // two packets lost in the same row are recovered by their columns
+        let (_, recovered, extra) = transmit("fec,cols:4,rows:3", 0, 12, &[5, 6]);
+        assert_eq!(extra, 3 + 4);
+        assert_eq!(recovered.len(), 2);
+        assert!(recovered.contains(&data_packet(5)));
+        assert!(recovered.contains(&data_packet(6)));
+
+        // column only
+        let (_, recovered, extra) = transmit("fec,cols:4,rows:-3", 0, 12, &[5, 6]);
+        assert_eq!(extra, 4);
+        assert_eq!(recovered.len(), 2);
+    }
+
+    #[test]
+    fn cascading_recovery() {
+        // 5 and 6 share a row, 5 and 9 a column; once 6 and 9 are recovered by their other
+        // groups, 5 can be recovered as well
+        let (_, recovered, _) = transmit("fec,cols:4,rows:3", 0, 12, &[5, 6, 9]);
+        assert_eq!(recovered.len(), 3);
+        assert!(recovered.contains(&data_packet(5)));
+    }
+
+    #[test]
+    fn staircase_layout() {
+        let (_, recovered, _) =
+            transmit("fec,cols:4,rows:3,layout:staircase", 0, 40, &[13, 14, 22]);
+        assert_eq!(recovered.len(), 3);
+    }
+
+    #[test]
+    fn sequence_number_wrapping() {
+        let init_seq_num = SeqNumber::MAX - 5;
+        let (_, recovered, _) = transmit("fec,cols:4", init_seq_num, 12, &[1]);
+        assert_eq!(recovered, vec![data_packet(1)]);
+    }
+
+    #[test]
+    fn unrecoverable_loss() {
+        let (mut receiver, recovered, _) = transmit("fec,cols:4", 0, 40, &[1, 2]);
+        assert_eq!(recovered, vec![]);
+        let lost: Vec<_> = std::iter::from_fn(|| receiver.next_lost_packet()).collect();
+        assert_eq!(lost, vec![SeqNumber(1), SeqNumber(2)]);
+    }
+}
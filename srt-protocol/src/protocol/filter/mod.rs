@@ -0,0 +1,69 @@
+//! Packet filters (SRTO_PACKETFILTER) see the data packets between the send/receive buffers and
+//! the wire. They may send their own control packets alongside the data, and supply the receiver
+//! with the data packets they recovered.
+mod fec;
+
+pub use fec::{FecReceiver, FecSender, FEC_HEADER_SIZE};
+
+use std::{convert::TryFrom, fmt::Debug};
+
+use crate::{
+    connection::ConnectionSettings,
+    options::{ArqLevel, FecConfig},
+    packet::*,
+};
+
+/// The sending side of a packet filter
+pub trait SenderPacketFilter: Debug + Send {
+    /// Called with each original data packet, as it is sent
+    fn on_data_sent(&mut self, packet: &DataPacket);
+
+    /// The next filter control packet to send
+    fn next_control_packet(&mut self) -> Option<DataPacket>;
+}
+
+/// The receiving side of a packet filter
+pub trait ReceiverPacketFilter: Debug + Send {
+    /// How lost packets are reported to the sender alongside the filter
+    fn arq_level(&self) -> ArqLevel;
+
+    /// Called with each received data packet, before decryption
+    fn on_data_received(&mut self, packet: &DataPacket);
+
+    /// Called with each received filter control packet
+    fn on_control_received(&mut self, packet: DataPacket);
+
+    /// The next data packet rebuilt by the filter
+    fn next_recovered_packet(&mut self) -> Option<DataPacket>;
+
+    /// The next data packet the filter gave up on recovering
+    fn next_lost_packet(&mut self) -> Option<SeqNumber>;
+}
+
+/// Filter control packets are data packets with the message number 0, which is never used for
+/// data
+pub fn is_control_packet(packet: &DataPacket) -> bool {
+    packet.message_number == MsgNumber(0)
+}
+
+pub fn new_sender_filter(settings: &ConnectionSettings) -> Option<Box<dyn SenderPacketFilter>> {
+    let spec = settings.packet_filter.as_ref()?;
+    match spec.name.as_str() {
+        FecConfig::FILTER_TYPE => Some(Box::new(FecSender::new(
+            FecConfig::try_from(spec).ok()?,
+            settings.init_seq_num,
+        ))),
+        _ => None,
+    }
+}
+
+pub fn new_receiver_filter(settings: &ConnectionSettings) -> Option<Box<dyn ReceiverPacketFilter>> {
+    let spec = settings.packet_filter.as_ref()?;
+    match spec.name.as_str() {
+        FecConfig::FILTER_TYPE => Some(Box::new(FecReceiver::new(
+            FecConfig::try_from(spec).ok()?,
+            settings.init_seq_num,
+        ))),
+        _ => None,
+    }
+}
@@ -1,10 +1,13 @@
-use crate::packet::{ControlTypes, HandshakeControlInfo, ShakeType};
+use crate::packet::{ControlTypes, HandshakeControlInfo, ShakeType, SrtControlPacket};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum Handshake {
     Connector,
     Listener(ControlTypes),
+    /// The conclusion response and HSRSP of a listener with a HSv4 caller, which repeats its
+    /// handshake requests until it gets a response
+    Hsv4Listener(ControlTypes, SrtControlPacket),
     Rendezvous(Option<ControlTypes>),
 }
 
@@ -12,8 +15,19 @@ impl Handshake {
     pub fn handle_handshake(&self, handshake: HandshakeControlInfo) -> Option<ControlTypes> {
         match (self, handshake.shake_type) {
             (Handshake::Rendezvous(control), ShakeType::Conclusion) => control.clone(),
-            (Handshake::Listener(control), _) => Some(control.clone()),
+            (Handshake::Listener(control), _) | (Handshake::Hsv4Listener(control, _), _) => {
+                Some(control.clone())
+            }
             (Handshake::Connector, _) | (Handshake::Rendezvous(_), _) => None,
         }
     }
+
+    pub fn handle_srt_handshake(&self, request: &SrtControlPacket) -> Option<ControlTypes> {
+        match (self, request) {
+            (Handshake::Hsv4Listener(_, response), SrtControlPacket::HandshakeRequest(_)) => {
+                Some(ControlTypes::Srt(response.clone()))
+            }
+            _ => None,
+        }
+    }
 }
@@ -1,8 +1,7 @@
 use std::io::ErrorKind;
-use std::{
-    net::{IpAddr, SocketAddr},
-    time::Instant,
-};
+use std::{net::SocketAddr, time::Instant};
+
+use rand::random;
 
 use ConnectError::*;
 use ConnectState::*;
@@ -13,8 +12,9 @@ use crate::{
 };
 
 use super::{
-    hsv5::{start_hsv5_initiation, StartedInitiator},
-    ConnectError, ConnectionReject, ConnectionResult,
+    hsv4::{hsv4_supported, start_hsv4_initiation, StartedHsv4Initiator},
+    hsv5::{start_hsv5_initiation, FinishHsv5Error, StartedInitiator},
+    peer_ip, ConnectError, ConnectionReject, ConnectionResult, HandshakeStage,
 };
 
 #[allow(clippy::large_enum_variant)]
@@ -25,6 +25,10 @@ enum ConnectState {
     InductionResponseWait(Packet),
     /// keep conclusion packet around for retransmit
     ConclusionResponseWait(Packet, StartedInitiator),
+    /// the same, with a listener that only supports the HSv4 handshake
+    Hsv4ConclusionResponseWait(Packet, StartedHsv4Initiator),
+    /// keep the conclusion response around, to complete it with the HSRSP
+    Hsv4ResponseWait(HandshakeControlInfo, StartedHsv4Initiator),
 }
 
 impl Default for ConnectState {
@@ -41,32 +45,54 @@ impl ConnectState {
 
 pub struct Connect {
     remote: SocketAddr,
-    local_addr: IpAddr,
     init_settings: ConnInitSettings,
     state: ConnectState,
     streamid: Option<String>,
     starting_send_seqnum: SeqNumber,
+    // the requests sent in the current stage of the handshake, and when the next one is due
+    attempts: u32,
+    next_attempt: Option<Instant>,
 }
 
 impl Connect {
     pub fn new(
         remote: SocketAddr,
-        local_addr: IpAddr,
         init_settings: ConnInitSettings,
         streamid: Option<String>,
         starting_send_seqnum: SeqNumber,
     ) -> Self {
         Connect {
             remote,
-            local_addr,
             init_settings,
             state: ConnectState::new(),
             streamid,
             starting_send_seqnum,
+            attempts: 0,
+            next_attempt: None,
         }
     }
 
-    fn on_start(&mut self) -> ConnectionResult {
+    /// When the pending handshake request is due to be repeated by handle_tick
+    pub fn next_attempt(&self, now: Instant) -> Instant {
+        self.next_attempt.unwrap_or(now)
+    }
+
+    // a new stage of the handshake starts over with the initial interval
+    fn schedule_first_attempt(&mut self, now: Instant) {
+        self.attempts = 1;
+        self.schedule_next_attempt(now);
+    }
+
+    fn schedule_next_attempt(&mut self, now: Instant) {
+        let sample = random::<f64>() * 2.0 - 1.0;
+        let interval = self
+            .init_settings
+            .retry
+            .jittered_interval(self.attempts, sample);
+        self.next_attempt = Some(now + interval);
+    }
+
+    fn on_start(&mut self, now: Instant) -> ConnectionResult {
         let packet = Packet::Control(ControlPacket {
             dest_sockid: SocketId(0),
             timestamp: TimeStamp::from_micros(0), // TODO: this is not zero in the reference implementation
@@ -76,12 +102,13 @@ impl Connect {
                 max_flow_size: self.init_settings.max_flow_size,
                 socket_id: self.init_settings.local_sockid,
                 shake_type: ShakeType::Induction,
-                peer_addr: self.local_addr,
+                peer_addr: peer_ip(self.remote),
                 syn_cookie: 0,
                 info: HandshakeVsInfo::V4(SocketType::Datagram),
             }),
         });
         self.state = InductionResponseWait(packet.clone());
+        self.schedule_first_attempt(now);
         SendPacket((packet, self.remote))
     }
 
@@ -106,13 +133,42 @@ impl Connect {
                         socket_id: self.init_settings.local_sockid,
                         info: hsv5,
                         init_seq_num: self.starting_send_seqnum,
+                        peer_addr: peer_ip(from),
                         ..info
                     }),
                 });
                 self.state = ConclusionResponseWait(packet.clone(), cm);
+                self.schedule_first_attempt(now);
                 SendPacket((packet, from))
             }
-            (ShakeType::Induction, HandshakeVsInfo::V5 { .. }, from) => {
+            // legacy listeners respond with the HSv4 handshake, the SRT handshake follows later
+            (ShakeType::Induction, HandshakeVsInfo::V4(_), from)
+                if from == self.remote
+                    && hsv4_supported(&self.init_settings, self.streamid.as_ref()) =>
+            {
+                let initiator = start_hsv4_initiation(self.init_settings.clone(), now);
+
+                let packet = Packet::Control(ControlPacket {
+                    timestamp,
+                    dest_sockid: SocketId(0),
+                    control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                        shake_type: ShakeType::Conclusion,
+                        socket_id: self.init_settings.local_sockid,
+                        info: HandshakeVsInfo::V4(SocketType::Datagram),
+                        init_seq_num: self.starting_send_seqnum,
+                        peer_addr: peer_ip(from),
+                        ..info
+                    }),
+                });
+                self.state = Hsv4ConclusionResponseWait(packet.clone(), initiator);
+                self.schedule_first_attempt(now);
+                SendPacket((packet, from))
+            }
+            (ShakeType::Induction, HandshakeVsInfo::V4(_), from) if from == self.remote => {
+                let r = ConnectionReject::Rejecting(CoreRejectReason::Version.into());
+                self.make_rejection(&info, from, r)
+            }
+            (ShakeType::Induction, _, from) if from != self.remote => {
                 NotHandled(UnexpectedHost(self.remote, from))
             }
             (ShakeType::Induction, version, _) => {
@@ -133,7 +189,8 @@ impl Connect {
             (ShakeType::Conclusion, 5, from) if from == self.remote => {
                 let settings = match initiator.finish_hsv5_initiation(&info, from, now) {
                     Ok(s) => s,
-                    Err(rr) => return NotHandled(rr),
+                    Err(FinishHsv5Error::NotHandled(e)) => return NotHandled(e),
+                    Err(FinishHsv5Error::Reject(r)) => return self.make_rejection(&info, from, r),
                 };
 
                 // TODO: no handshake retransmit packet needed? is this right? Needs testing.
@@ -156,6 +213,108 @@ impl Connect {
         }
     }
 
+    fn wait_for_hsv4_conclusion(
+        &mut self,
+        from: SocketAddr,
+        now: Instant,
+        info: HandshakeControlInfo,
+        initiator: StartedHsv4Initiator,
+    ) -> ConnectionResult {
+        match (info.shake_type, info.info.version(), from) {
+            (ShakeType::Conclusion, 4, from) if from == self.remote => {
+                let packet = initiator.first_request(info.socket_id, now);
+                self.state = Hsv4ResponseWait(info, initiator);
+                self.schedule_first_attempt(now);
+                SendPacket((packet.into(), from))
+            }
+            (ShakeType::Conclusion, 4, from) => NotHandled(UnexpectedHost(self.remote, from)),
+            (ShakeType::Conclusion, version, _) => NotHandled(UnsupportedProtocolVersion(version)),
+            (ShakeType::Rejection(rej), _, from) if from == self.remote => {
+                Reject(None, ConnectionReject::Rejected(rej))
+            }
+            (ShakeType::Rejection(_), _, from) => NotHandled(UnexpectedHost(self.remote, from)),
+            (ShakeType::Induction, _, _) => NoAction,
+            (_, _, _) => NotHandled(ConclusionExpected(info)),
+        }
+    }
+
+    fn wait_for_hsv4_response(
+        &mut self,
+        from: SocketAddr,
+        now: Instant,
+        response: SrtControlPacket,
+        conclusion: HandshakeControlInfo,
+        mut initiator: StartedHsv4Initiator,
+    ) -> ConnectionResult {
+        use SrtControlPacket::*;
+        if from != self.remote {
+            return NotHandled(UnexpectedHost(self.remote, from));
+        }
+        match response {
+            KeyRefreshResponse(_) => {
+                initiator.handle_km_response(response);
+                let packet = initiator.hs_request(conclusion.socket_id, now);
+                self.state = Hsv4ResponseWait(conclusion, initiator);
+                self.schedule_first_attempt(now);
+                SendPacket((packet.into(), from))
+            }
+            HandshakeResponse(_) => {
+                let settings =
+                    match initiator.finish_hsv4_initiation(&conclusion, response, from, now) {
+                        Ok(s) => s,
+                        Err(FinishHsv5Error::NotHandled(e)) => return NotHandled(e),
+                        Err(FinishHsv5Error::Reject(r)) => {
+                            return self.make_rejection(&conclusion, from, r)
+                        }
+                    };
+                Connected(
+                    None,
+                    Connection {
+                        settings,
+                        handshake: Handshake::Connector,
+                    },
+                )
+            }
+            _ => NotHandled(ExpectedHsResp),
+        }
+    }
+
+    fn make_rejection(
+        &self,
+        response_to: &HandshakeControlInfo,
+        from: SocketAddr,
+        r: ConnectionReject,
+    ) -> ConnectionResult {
+        Reject(
+            Some((
+                ControlPacket {
+                    timestamp: TimeStamp::from_micros(0),
+                    dest_sockid: response_to.socket_id,
+                    control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                        shake_type: ShakeType::Rejection(r.reason()),
+                        socket_id: self.init_settings.local_sockid,
+                        peer_addr: peer_ip(from),
+                        ..response_to.clone()
+                    }),
+                }
+                .into(),
+                from,
+            )),
+            r,
+        )
+    }
+
+    /// How far the handshake got, the stage of a rejection or timeout
+    pub fn stage(&self) -> HandshakeStage {
+        match self.state {
+            Configured | InductionResponseWait(_) => HandshakeStage::Induction,
+            ConclusionResponseWait(_, _) | Hsv4ConclusionResponseWait(_, _) => {
+                HandshakeStage::Conclusion
+            }
+            Hsv4ResponseWait(_, _) => HandshakeStage::SrtHandshake,
+        }
+    }
+
     pub fn handle_packet(&mut self, packet: ReceivePacketResult, now: Instant) -> ConnectionResult {
         use ReceivePacketError::*;
         match packet {
@@ -176,6 +335,28 @@ impl Connect {
                         control_type => NotHandled(HandshakeExpected(control_type)),
                     }
                 }
+                (Hsv4ConclusionResponseWait(_, initiator), Packet::Control(control)) => {
+                    match control.control_type {
+                        ControlTypes::Handshake(shake) => {
+                            self.wait_for_hsv4_conclusion(from, now, shake, initiator)
+                        }
+                        control_type => NotHandled(HandshakeExpected(control_type)),
+                    }
+                }
+                (Hsv4ResponseWait(conclusion, initiator), Packet::Control(control)) => {
+                    match control.control_type {
+                        ControlTypes::Srt(srt) => {
+                            self.wait_for_hsv4_response(from, now, srt, conclusion, initiator)
+                        }
+                        // the listener can still reject the connection, e.g. for a bad passphrase
+                        ControlTypes::Handshake(HandshakeControlInfo {
+                            shake_type: ShakeType::Rejection(rej),
+                            ..
+                        }) if from == self.remote => Reject(None, ConnectionReject::Rejected(rej)),
+                        ControlTypes::Handshake(_) => NoAction,
+                        control_type => NotHandled(HandshakeExpected(control_type)),
+                    }
+                }
                 (_, Packet::Data(data)) => NotHandled(ControlExpected(data)),
                 (_, _) => NoAction,
             },
@@ -188,15 +369,34 @@ impl Connect {
         }
     }
 
-    pub fn handle_tick(&mut self, _now: Instant) -> ConnectionResult {
+    /// Sends the first handshake request, and repeats the pending one when it is due, until the
+    /// retry policy runs out of attempts and the connection fails as timed out
+    pub fn handle_tick(&mut self, now: Instant) -> ConnectionResult {
+        if matches!(self.next_attempt, Some(next_attempt) if now < next_attempt) {
+            return NoAction;
+        }
+        if !matches!(self.state, Configured) {
+            if self.init_settings.retry.is_exhausted(self.attempts) {
+                let r = ConnectionReject::Rejecting(CoreRejectReason::Timeout.into());
+                return Reject(None, r);
+            }
+            self.attempts += 1;
+            self.schedule_next_attempt(now);
+        }
+
         match &self.state {
-            Configured => self.on_start(),
+            Configured => self.on_start(now),
             InductionResponseWait(request_packet) => {
                 SendPacket((request_packet.clone(), self.remote))
             }
-            ConclusionResponseWait(request_packet, _) => {
+            ConclusionResponseWait(request_packet, _)
+            | Hsv4ConclusionResponseWait(request_packet, _) => {
                 SendPacket((request_packet.clone(), self.remote))
             }
+            Hsv4ResponseWait(conclusion, initiator) => SendPacket((
+                initiator.hs_request(conclusion.socket_id, now).into(),
+                self.remote,
+            )),
         }
     }
 }
@@ -221,6 +421,7 @@ mod test {
     fn reject() {
         let mut c = test_connect(Some("#!::u=test".into()));
         c.handle_tick(Instant::now());
+        assert_eq!(c.stage(), HandshakeStage::Induction);
 
         let first = Packet::Control(ControlPacket {
             timestamp: TimeStamp::from_micros(0),
@@ -249,6 +450,7 @@ mod test {
                 }), ..
             }), _)) if socket_id == TEST_SOCKID
         );
+        assert_eq!(c.stage(), HandshakeStage::Conclusion);
 
         // send rejection
         let rejection = Packet::Control(ControlPacket {
@@ -274,6 +476,41 @@ mod test {
                 ConnectionReject::Rejected(RejectReason::Server(ServerRejectReason::BadMode)),
             )
         );
+        // the rejection is reported in the stage it happened in
+        assert_eq!(c.stage(), HandshakeStage::Conclusion);
+    }
+
+    #[test]
+    fn retry() {
+        let mut c = test_connect(None);
+        c.init_settings.retry = options::RetryPolicy {
+            initial_interval: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_attempts: Some(3),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        assert_matches!(c.handle_tick(start), ConnectionResult::SendPacket(_));
+        assert_eq!(c.next_attempt(start), ms(100));
+
+        // the requests are repeated at growing intervals
+        assert_matches!(c.handle_tick(ms(99)), ConnectionResult::NoAction);
+        assert_matches!(c.handle_tick(ms(100)), ConnectionResult::SendPacket(_));
+        assert_eq!(c.next_attempt(ms(100)), ms(300));
+        assert_matches!(c.handle_tick(ms(299)), ConnectionResult::NoAction);
+        assert_matches!(c.handle_tick(ms(300)), ConnectionResult::SendPacket(_));
+
+        // until the attempts run out
+        assert_matches!(
+            c.handle_tick(ms(700)),
+            ConnectionResult::Reject(
+                None,
+                ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Timeout))
+            )
+        );
+        assert_eq!(c.stage(), HandshakeStage::Induction);
     }
 
     fn test_remote() -> SocketAddr {
@@ -283,11 +520,12 @@ mod test {
     fn test_connect(sid: Option<String>) -> Connect {
         Connect::new(
             test_remote(),
-            [127, 0, 0, 1].into(),
             ConnInitSettings {
                 local_sockid: TEST_SOCKID,
                 key_settings: None,
                 key_refresh: Default::default(),
+                crypto_mode: Default::default(),
+                enforced_encryption: true,
                 send_latency: Duration::from_millis(20),
                 recv_latency: Duration::from_millis(20),
                 bandwidth: Default::default(),
@@ -296,6 +534,15 @@ mod test {
                 send_buffer_size: options::PacketCount(8192),
                 max_packet_size: options::PacketSize(1500),
                 max_flow_size: options::PacketCount(8192),
+                transmission_type: Default::default(),
+                tsbpd_mode: true,
+                message_api: true,
+                too_late_packet_drop: true,
+                linger: None,
+                min_version: options::SrtVersion::new(1, 0, 0),
+                retry: Default::default(),
+                group: None,
+                packet_filter: None,
             },
             sid,
             random(),
@@ -0,0 +1,125 @@
+//! Defines the HSv4 handshake, used by SRT versions before 1.3.0
+//!
+//! The HSv4 handshake only establishes the UDT connection. The SRT settings follow in control
+//! packets: the caller sends a HSREQ, and a KMREQ when it encrypts, which the listener answers
+//! with a HSRSP and KMRSP. These carry the same information as the HSv5 handshake extensions, so
+//! they are negotiated the same way.
+
+use std::{net::SocketAddr, time::Instant};
+
+use crate::{
+    connection::ConnectionSettings, options::*, packet::*, protocol::time::TimeBase, settings::*,
+};
+
+use super::hsv5::{start_hsv5_initiation, FinishHsv5Error, StartedInitiator};
+
+/// The first SRT version with the HSv5 handshake
+pub const HSV5_MIN_VERSION: SrtVersion = SrtVersion {
+    major: 1,
+    minor: 3,
+    patch: 0,
+};
+
+/// Whether a peer using the HSv4 handshake is acceptable, which requires a minimum version before
+/// 1.3.0, and a connection without any of the features such peers lack: file transmission, stream
+/// ids, groups and packet filters
+pub fn hsv4_supported(settings: &ConnInitSettings, stream_id: Option<&String>) -> bool {
+    settings.min_version < HSV5_MIN_VERSION
+        && settings.transmission_type == TransmissionType::Live
+        && settings.group.is_none()
+        && settings.packet_filter.is_none()
+        && stream_id.is_none()
+}
+
+/// The SRT control packets exchanged after a HSv4 handshake, as HSv5 handshake extensions
+pub fn hsv4_extensions(ext_hs: SrtControlPacket, ext_km: Option<SrtControlPacket>) -> HsV5Info {
+    // the HSv4 handshake has no key size field, it follows from the size of the wrapped keys
+    let crypto_size = match &ext_km {
+        Some(SrtControlPacket::KeyRefreshRequest(km))
+        | Some(SrtControlPacket::KeyRefreshResponse(km)) => {
+            let keys = km.key_flags.bits().count_ones() as usize;
+            (km.wrapped_keys.len().saturating_sub(8) / keys.max(1)) as u8
+        }
+        _ => 0,
+    };
+    HsV5Info {
+        crypto_size,
+        ext_hs: Some(ext_hs),
+        ext_km,
+        ..HsV5Info::default()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StartedHsv4Initiator {
+    hs_request: SrtControlPacket,
+    km_request: Option<SrtControlPacket>,
+    km_response: Option<SrtControlPacket>,
+    initiator: StartedInitiator,
+    // the connection is timed from the start of the initiation, as it will be once connected
+    time_base: TimeBase,
+}
+
+pub fn start_hsv4_initiation(settings: ConnInitSettings, now: Instant) -> StartedHsv4Initiator {
+    let (info, initiator) = start_hsv5_initiation(settings, None, now);
+    match info {
+        HandshakeVsInfo::V5(HsV5Info {
+            ext_hs: Some(hs_request),
+            ext_km: km_request,
+            ..
+        }) => StartedHsv4Initiator {
+            hs_request,
+            km_request,
+            km_response: None,
+            initiator,
+            time_base: TimeBase::new(now),
+        },
+        _ => unreachable!("the HSv5 initiation always includes a HSREQ"),
+    }
+}
+
+impl StartedHsv4Initiator {
+    /// The KMREQ goes ahead of the HSREQ, so that the listener has the keying material at hand
+    /// when it completes the connection
+    pub fn first_request(&self, remote_sockid: SocketId, now: Instant) -> ControlPacket {
+        let request = self.km_request.as_ref().unwrap_or(&self.hs_request);
+        self.control_packet(remote_sockid, request.clone(), now)
+    }
+
+    /// The HSREQ, which is repeated until the HSRSP arrives
+    pub fn hs_request(&self, remote_sockid: SocketId, now: Instant) -> ControlPacket {
+        self.control_packet(remote_sockid, self.hs_request.clone(), now)
+    }
+
+    pub fn handle_km_response(&mut self, km_response: SrtControlPacket) {
+        self.km_response = Some(km_response);
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn finish_hsv4_initiation(
+        self,
+        conclusion: &HandshakeControlInfo,
+        hs_response: SrtControlPacket,
+        from: SocketAddr,
+        now: Instant,
+    ) -> Result<ConnectionSettings, FinishHsv5Error> {
+        let response = HandshakeControlInfo {
+            info: HandshakeVsInfo::V5(hsv4_extensions(hs_response, self.km_response)),
+            ..conclusion.clone()
+        };
+        self.initiator.finish_hsv5_initiation(&response, from, now)
+    }
+
+    fn control_packet(
+        &self,
+        remote_sockid: SocketId,
+        srt: SrtControlPacket,
+        now: Instant,
+    ) -> ControlPacket {
+        ControlPacket {
+            timestamp: self.time_base.timestamp_from(now),
+            dest_sockid: remote_sockid,
+            control_type: ControlTypes::Srt(srt),
+        }
+    }
+}
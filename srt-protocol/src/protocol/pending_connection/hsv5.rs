@@ -2,6 +2,7 @@
 
 use std::{
     cmp::{max, min},
+    convert::TryFrom,
     net::SocketAddr,
     time::Instant,
 };
@@ -17,6 +18,19 @@ pub enum GenHsv5Result {
     Reject(ConnectionReject),
 }
 
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum FinishHsv5Error {
+    NotHandled(ConnectError),
+    Reject(ConnectionReject),
+}
+
+impl From<ConnectError> for FinishHsv5Error {
+    fn from(error: ConnectError) -> Self {
+        FinishHsv5Error::NotHandled(error)
+    }
+}
+
 pub fn gen_hsv5_response(
     settings: &mut ConnInitSettings,
     with_hsv5: &HandshakeControlInfo,
@@ -40,7 +54,7 @@ pub fn gen_hsv5_response(
         induction_time,
         with_hsv5.clone(),
         incoming.clone(),
-        None,
+        AcceptParameters::default(),
     )
 }
 
@@ -51,12 +65,10 @@ pub fn gen_access_control_response(
     induction_time: Instant,
     with_hsv5: HandshakeControlInfo,
     incoming: HsV5Info,
-    key_settings: Option<KeySettings>,
+    parameters: AcceptParameters,
 ) -> GenHsv5Result {
     // apply parameters generated by acceptor
-    if let Some(ks) = key_settings {
-        settings.key_settings = Some(ks);
-    }
+    parameters.apply_to(settings);
 
     let hs = match incoming.ext_hs {
         Some(SrtControlPacket::HandshakeRequest(hs)) => hs,
@@ -64,34 +76,114 @@ pub fn gen_access_control_response(
         None => return GenHsv5Result::NotHandled(ConnectError::ExpectedExtFlags),
     };
 
+    if hs.version < settings.min_version {
+        return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+            CoreRejectReason::Version.into(),
+        ));
+    }
+
+    // both parties must agree on the congestion control type, "live" is implied when absent
+    let congestion = incoming.congestion.as_deref().unwrap_or("live");
+    if congestion != settings.transmission_type.congestion_control_type() {
+        return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+            CoreRejectReason::Congestion.into(),
+        ));
+    }
+
+    // the stream API must be used by either both or neither parties
+    if hs.flags.contains(SrtShakeFlags::STREAM) == settings.message_api {
+        return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+            CoreRejectReason::MessageApi.into(),
+        ));
+    }
+
+    // group members may only connect to peers accepting members of the same group type
+    let peer_group = incoming
+        .ext_group
+        .as_ref()
+        .and_then(GroupSettings::from_extension);
+    let outgoing_ext_group = match (&settings.group, &peer_group) {
+        (None, None) => None,
+        (Some(local), Some(peer)) if local.group_type == peer.group_type => {
+            Some(local.with_weight(peer.weight).to_extension())
+        }
+        _ => {
+            return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+                CoreRejectReason::Group.into(),
+            ))
+        }
+    };
+
+    // the packet filter configurations are merged, the agreed configuration is sent back
+    let packet_filter =
+        match negotiate_packet_filter(settings.packet_filter.as_ref(), incoming.filter.as_ref()) {
+            Ok(packet_filter) => packet_filter,
+            Err(_) => {
+                return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+                    CoreRejectReason::Filter.into(),
+                ))
+            }
+        };
+
     // crypto
-    let cipher = match (&settings.key_settings, &incoming.ext_km) {
+    let reject = |reason: CoreRejectReason| {
+        GenHsv5Result::Reject(ConnectionReject::Rejecting(reason.into()))
+    };
+    let (cipher, key_material_state) = match (&settings.key_settings, &incoming.ext_km) {
         // ok, both sizes have crypto
         (Some(key_settings), Some(SrtControlPacket::KeyRefreshRequest(km))) => {
-            if key_settings.key_size.as_usize() != incoming.crypto_size as usize {
-                unimplemented!("Key size mismatch");
+            // the initiator picks the cipher, unless this side requires a specific one
+            let compatible = match (settings.crypto_mode, km.cipher) {
+                (CryptoMode::Auto, cipher) => matches!(cipher, CipherType::Ctr | CipherType::Gcm),
+                (CryptoMode::AesCtr, cipher) => cipher == CipherType::Ctr,
+                (CryptoMode::AesGcm, cipher) => cipher == CipherType::Gcm,
+            };
+            // the packet filter rebuilds packets without the header fields authenticated by GCM
+            if !compatible || (km.cipher == CipherType::Gcm && packet_filter.is_some()) {
+                return reject(CoreRejectReason::Crypto);
             }
 
-            let cipher = match CipherSettings::new(key_settings, &settings.key_refresh, km) {
-                Ok(cm) => cm,
-                Err(_) => {
-                    return GenHsv5Result::Reject(ConnectionReject::Rejecting(
-                        CoreRejectReason::BadSecret.into(),
-                    ))
-                }
+            // a side that left the key size unspecified adopts the key size of the peer
+            let key_settings = match KeySize::try_from(incoming.crypto_size) {
+                Ok(key_size) if key_settings.key_size == KeySize::Unspecified => KeySettings {
+                    key_size,
+                    ..key_settings.clone()
+                },
+                _ => key_settings.clone(),
             };
-            Some(cipher)
+
+            match CipherSettings::new(&key_settings, &settings.key_refresh, km) {
+                Ok(cipher) => (Some(cipher), KeyMaterialState::Secured),
+                Err(_) if settings.enforced_encryption => {
+                    return reject(CoreRejectReason::BadSecret)
+                }
+                // the connection goes ahead, but the packets of the peer can not be decrypted
+                Err(_) => (None, KeyMaterialState::BadSecret),
+            }
         }
         // ok, neither have crypto
-        (None, None) => None,
+        (None, None) => (None, KeyMaterialState::Unsecured),
         // bad cases
-        (Some(_), Some(_)) => unimplemented!("Expected kmreq"),
-        (Some(_), None) => {
-            return GenHsv5Result::Reject(ConnectionReject::Rejecting(
-                CoreRejectReason::Unsecure.into(),
-            ))
+        (_, Some(SrtControlPacket::KeyRefreshRequest(_))) | (Some(_), None)
+            if settings.enforced_encryption =>
+        {
+            return reject(CoreRejectReason::Unsecure)
         }
-        (None, Some(_)) => unimplemented!("expected no secrets"),
+        (_, Some(SrtControlPacket::KeyRefreshRequest(_))) | (Some(_), None) => {
+            (None, KeyMaterialState::NoSecret)
+        }
+        (_, Some(_)) => return reject(CoreRejectReason::Rogue),
+    };
+
+    // the responder only encrypts when the keys were exchanged, the initiator whenever it has a
+    // passphrase
+    let send_key_material_state = match cipher {
+        Some(_) => KeyMaterialState::Secured,
+        None => KeyMaterialState::Unsecured,
+    };
+    let recv_key_material_state = match incoming.ext_km {
+        Some(_) => key_material_state,
+        None => KeyMaterialState::Unsecured,
     };
 
     let outgoing_ext_km = cipher
@@ -108,19 +200,25 @@ pub fn gen_access_control_response(
 
     GenHsv5Result::Accept(
         HandshakeVsInfo::V5(HsV5Info {
+            // advertised even when the keys could not be exchanged, so that the initiator can
+            // tell a passphrase mismatch from a missing passphrase
             crypto_size: cipher
                 .as_ref()
-                .map(|c| c.key_settings.key_size.as_usize())
+                .map(|c| &c.key_settings)
+                .or(settings.key_settings.as_ref())
+                .map(|ks| ks.key_size.as_usize())
                 .unwrap_or(0) as u8,
             ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: shake_flags(settings),
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km: outgoing_ext_km.map(SrtControlPacket::KeyRefreshResponse),
-            ext_group: None,
+            ext_group: outgoing_ext_group,
             sid,
+            congestion: congestion_ext(settings.transmission_type),
+            filter: packet_filter.clone(),
         }),
         ConnectionSettings {
             remote: from,
@@ -129,6 +227,8 @@ pub fn gen_access_control_response(
             remote_sockid: with_hsv5.socket_id,
             init_seq_num: with_hsv5.init_seq_num,
             cipher,
+            send_key_material_state,
+            recv_key_material_state,
             stream_id: incoming.sid,
             max_flow_size: max(settings.max_flow_size, with_hsv5.max_flow_size),
             max_packet_size: min(settings.max_packet_size, with_hsv5.max_packet_size),
@@ -139,10 +239,57 @@ pub fn gen_access_control_response(
             recv_buffer_size: settings.recv_buffer_size,
             send_buffer_size: settings.send_buffer_size,
             statistics_interval: settings.statistics_interval,
+            transmission_type: settings.transmission_type,
+            message_api: settings.message_api,
+            recv_tsbpd_mode: settings.tsbpd_mode && hs.flags.contains(SrtShakeFlags::TSBPDSND),
+            recv_too_late_packet_drop: settings.too_late_packet_drop,
+            send_too_late_packet_drop: settings.too_late_packet_drop
+                && hs.flags.contains(SrtShakeFlags::TLPKTDROP),
+            linger: settings.linger,
+            peer_group,
+            packet_filter,
         },
     )
 }
 
+// without timestamp based packet delivery, as always for file transmission, nothing is ever too
+// late
+fn shake_flags(settings: &ConnInitSettings) -> SrtShakeFlags {
+    let mut flags = SrtShakeFlags::SUPPORTED;
+    if settings.transmission_type == TransmissionType::File || !settings.tsbpd_mode {
+        flags.remove(SrtShakeFlags::TSBPDSND | SrtShakeFlags::TSBPDRCV | SrtShakeFlags::TLPKTDROP);
+    }
+    if !settings.too_late_packet_drop {
+        flags.remove(SrtShakeFlags::TLPKTDROP);
+    }
+    if !settings.message_api {
+        flags.insert(SrtShakeFlags::STREAM);
+    }
+    flags
+}
+
+// a party without a packet filter adopts the configuration of its peer
+fn negotiate_packet_filter(
+    local: Option<&FilterSpec>,
+    peer: Option<&FilterSpec>,
+) -> Result<Option<FilterSpec>, PacketFilterError> {
+    let spec = match (local, peer) {
+        (None, None) => return Ok(None),
+        (Some(local), Some(peer)) => FecConfig::negotiate(local, peer)?,
+        (Some(spec), None) | (None, Some(spec)) => spec.clone(),
+    };
+    FecConfig::try_from(&spec)?;
+    Ok(Some(spec))
+}
+
+// the congestion extension is only sent for non-default congestion control types
+fn congestion_ext(transmission_type: TransmissionType) -> Option<String> {
+    match transmission_type {
+        TransmissionType::Live => None,
+        TransmissionType::File => Some(transmission_type.congestion_control_type().into()),
+    }
+}
+
 #[derive(Debug, Clone)] // TOOD: make not clone
 pub struct StartedInitiator {
     cipher: Option<CipherSettings>,
@@ -168,7 +315,11 @@ pub fn start_hsv5_initiation(
     // }
 
     let (cipher, ext_km) = if let Some(ks) = &settings.key_settings {
-        let cipher = CipherSettings::new_random(ks, &settings.key_refresh);
+        let cipher = match settings.crypto_mode {
+            CryptoMode::Auto | CryptoMode::AesCtr => CipherType::Ctr,
+            CryptoMode::AesGcm => CipherType::Gcm,
+        };
+        let cipher = CipherSettings::new_random(ks, &settings.key_refresh, cipher);
         let keying_material = cipher
             .wrap_keying_material()
             .map(SrtControlPacket::KeyRefreshRequest);
@@ -182,13 +333,15 @@ pub fn start_hsv5_initiation(
             crypto_size: self_crypto_size,
             ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: shake_flags(&settings),
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km,
-            ext_group: None,
+            ext_group: settings.group.map(GroupSettings::to_extension),
             sid: streamid.clone(),
+            congestion: congestion_ext(settings.transmission_type),
+            filter: settings.packet_filter.clone(),
         }),
         StartedInitiator {
             cipher,
@@ -205,20 +358,74 @@ impl StartedInitiator {
         response: &HandshakeControlInfo,
         from: SocketAddr,
         now: Instant,
-    ) -> Result<ConnectionSettings, ConnectError> {
+    ) -> Result<ConnectionSettings, FinishHsv5Error> {
         // TODO: factor this out with above...
         let incoming = match &response.info {
             HandshakeVsInfo::V5(hs) => hs,
-            i => return Err(ConnectError::UnsupportedProtocolVersion(i.version())),
+            i => return Err(ConnectError::UnsupportedProtocolVersion(i.version()).into()),
         };
 
         let hs = match incoming.ext_hs {
             Some(SrtControlPacket::HandshakeResponse(hs)) => hs,
-            Some(_) => return Err(ConnectError::ExpectedHsResp),
-            None => return Err(ConnectError::ExpectedExtFlags),
+            Some(_) => return Err(ConnectError::ExpectedHsResp.into()),
+            None => return Err(ConnectError::ExpectedExtFlags.into()),
+        };
+
+        let reject = |reason: CoreRejectReason| {
+            FinishHsv5Error::Reject(ConnectionReject::Rejecting(reason.into()))
+        };
+        if hs.version < self.settings.min_version {
+            return Err(reject(CoreRejectReason::Version));
+        }
+        // the responder only returns keying material when it could unwrap ours, and advertises its
+        // key size whenever it has a passphrase
+        let key_material_state = match (&self.cipher, &incoming.ext_km, incoming.crypto_size) {
+            (Some(cipher), Some(SrtControlPacket::KeyRefreshResponse(km)), _) => {
+                if km.cipher != cipher.cipher() {
+                    return Err(reject(CoreRejectReason::Crypto));
+                }
+                KeyMaterialState::Secured
+            }
+            (None, None, 0) => KeyMaterialState::Unsecured,
+            (Some(_), _, crypto_size) if crypto_size != 0 => KeyMaterialState::BadSecret,
+            _ => KeyMaterialState::NoSecret,
         };
+        match key_material_state {
+            KeyMaterialState::BadSecret if self.settings.enforced_encryption => {
+                return Err(reject(CoreRejectReason::BadSecret))
+            }
+            KeyMaterialState::NoSecret if self.settings.enforced_encryption => {
+                return Err(reject(CoreRejectReason::Unsecure))
+            }
+            _ => {}
+        }
+        // the initiator encrypts whenever it has a passphrase, the responder only when it could
+        // unwrap the keys
+        let send_key_material_state = match self.cipher {
+            Some(_) => key_material_state,
+            None => KeyMaterialState::Unsecured,
+        };
+        let recv_key_material_state = match key_material_state {
+            KeyMaterialState::Secured => KeyMaterialState::Secured,
+            _ => KeyMaterialState::Unsecured,
+        };
+
+        let peer_group = incoming
+            .ext_group
+            .as_ref()
+            .and_then(GroupSettings::from_extension);
+        match (&self.settings.group, &peer_group) {
+            (None, None) => {}
+            (Some(local), Some(peer)) if local.group_type == peer.group_type => {}
+            _ => return Err(ConnectError::ExpectedGroup.into()),
+        }
 
-        // todo: validate km!
+        // the responder sends back the agreed configuration, which must include our own
+        let packet_filter = match (&self.settings.packet_filter, &incoming.filter) {
+            (Some(_), None) => return Err(ConnectError::ExpectedFilter.into()),
+            (local, peer) => negotiate_packet_filter(local.as_ref(), peer.as_ref())
+                .map_err(|_| ConnectError::ExpectedFilter)?,
+        };
 
         // validate response
         Ok(ConnectionSettings {
@@ -228,6 +435,8 @@ impl StartedInitiator {
             init_seq_num: response.init_seq_num,
             remote_sockid: response.socket_id,
             cipher: self.cipher,
+            send_key_material_state,
+            recv_key_material_state,
             stream_id: self.streamid,
             max_flow_size: max(self.settings.max_flow_size, response.max_flow_size),
             max_packet_size: min(self.settings.max_packet_size, response.max_packet_size),
@@ -238,6 +447,15 @@ impl StartedInitiator {
             recv_buffer_size: self.settings.recv_buffer_size,
             send_buffer_size: self.settings.send_buffer_size,
             statistics_interval: self.settings.statistics_interval,
+            transmission_type: self.settings.transmission_type,
+            message_api: self.settings.message_api,
+            recv_tsbpd_mode: self.settings.tsbpd_mode && hs.flags.contains(SrtShakeFlags::TSBPDSND),
+            recv_too_late_packet_drop: self.settings.too_late_packet_drop,
+            send_too_late_packet_drop: self.settings.too_late_packet_drop
+                && hs.flags.contains(SrtShakeFlags::TLPKTDROP),
+            linger: self.settings.linger,
+            peer_group,
+            packet_filter,
         })
     }
 }
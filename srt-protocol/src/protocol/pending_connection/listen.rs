@@ -1,11 +1,16 @@
-use std::{convert::TryInto, net::SocketAddr, time::Instant};
+use std::{cmp::min, convert::TryInto, net::SocketAddr, time::Instant};
 
-use crate::{packet::*, protocol::handshake::Handshake, settings::*};
+use crate::{
+    connection::ConnectionSettings, packet::*, protocol::handshake::Handshake, settings::*,
+};
 
 use super::{
-    cookie::gen_cookie, hsv5::gen_access_control_response, hsv5::GenHsv5Result,
-    AccessControlRequest, AccessControlResponse, ConnectError, Connection, ConnectionReject,
-    ConnectionResult,
+    cookie::gen_cookie,
+    hsv4::{hsv4_extensions, hsv4_supported},
+    hsv5::gen_access_control_response,
+    hsv5::GenHsv5Result,
+    peer_ip, AccessControlRequest, AccessControlResponse, ConnectError, Connection,
+    ConnectionReject, ConnectionResult,
 };
 
 use ConnectionResult::*;
@@ -24,6 +29,17 @@ pub struct ConclusionWaitState {
     cookie: i32,
     induction_response: Packet,
     induction_time: Instant,
+    // HSv4 callers complete the SRT handshake after the conclusion, which the connection is timed
+    // from nonetheless
+    conclusion_time: Option<Instant>,
+}
+
+/// The conclusion of a HSv4 caller, and the SRT control packets it sent since
+#[derive(Clone, Debug)]
+struct Hsv4RequestWaitState {
+    shake: HandshakeControlInfo,
+    hs_request: Option<SrtControlPacket>,
+    km_request: Option<SrtControlPacket>,
 }
 
 #[derive(Clone, Debug)]
@@ -31,6 +47,7 @@ pub struct ConclusionWaitState {
 enum ListenState {
     InductionWait,
     ConclusionWait(ConclusionWaitState),
+    Hsv4RequestWait(ConclusionWaitState, Hsv4RequestWaitState),
     AccessControlRequested(
         ConclusionWaitState,
         TimeStamp,
@@ -71,12 +88,14 @@ impl Listen {
     ) -> ConnectionResult {
         match self.state.clone() {
             // TODO: something other than ExpectedHsReq
-            InductionWait | ConclusionWait(_) => NotHandled(ConnectError::ExpectedHsReq),
+            InductionWait | ConclusionWait(_) | Hsv4RequestWait(_, _) => {
+                NotHandled(ConnectError::ExpectedHsReq)
+            }
             AccessControlRequested(state, timestamp, shake, info) => {
                 use AccessControlResponse::*;
                 match response {
-                    Accepted(key_settings) => {
-                        self.accept_connection(now, &state, timestamp, shake, info, key_settings)
+                    Accepted(parameters) => {
+                        self.accept_connection(now, &state, timestamp, shake, info, parameters)
                     }
                     Rejected(rr) => self.make_rejection(
                         &shake,
@@ -109,6 +128,12 @@ impl Listen {
             (InductionWait, ControlTypes::Handshake(shake)) => {
                 self.wait_for_induction(from, control.timestamp, shake, now)
             }
+            // a different caller starts over, e.g. after the previous one was rejected
+            (ConclusionWait(state), ControlTypes::Handshake(shake))
+                if shake.shake_type == ShakeType::Induction && from != state.from =>
+            {
+                self.wait_for_induction(from, control.timestamp, shake, now)
+            }
             (ConclusionWait(state), ControlTypes::Handshake(shake)) => self.wait_for_conclusion(
                 now,
                 from,
@@ -117,10 +142,32 @@ impl Listen {
                 state,
                 shake,
             ),
+            (Hsv4RequestWait(state, _), ControlTypes::Handshake(shake))
+                if shake.shake_type == ShakeType::Induction && from != state.from =>
+            {
+                self.wait_for_induction(from, control.timestamp, shake, now)
+            }
+            // the caller repeats its conclusion until it gets a response
+            (Hsv4RequestWait(state, request), ControlTypes::Handshake(_)) if from == state.from => {
+                let response =
+                    self.hsv4_conclusion_response(&state, control.timestamp, &request.shake);
+                SendPacket((response.into(), from))
+            }
+            (Hsv4RequestWait(state, request), ControlTypes::Srt(srt)) if from == state.from => self
+                .wait_for_hsv4_request(
+                    now,
+                    control.dest_sockid,
+                    control.timestamp,
+                    state,
+                    request,
+                    srt,
+                ),
             (AccessControlRequested(_, _, _, _), _) => {
                 NotHandled(ConnectError::ExpectedAccessControlResponse)
             }
-            (InductionWait, control_type) | (ConclusionWait(_), control_type) => {
+            (InductionWait, control_type)
+            | (ConclusionWait(_), control_type)
+            | (Hsv4RequestWait(_, _), control_type) => {
                 NotHandled(ConnectError::HandshakeExpected(control_type))
             }
         }
@@ -153,6 +200,7 @@ impl Listen {
                         syn_cookie: cookie,
                         socket_id: self.init_settings.local_sockid,
                         info: HandshakeVsInfo::V5(HsV5Info::default()),
+                        peer_addr: peer_ip(from),
                         ..shake
                     }),
                 });
@@ -164,6 +212,7 @@ impl Listen {
                     cookie,
                     induction_response: save_induction_response,
                     induction_time: now,
+                    conclusion_time: None,
                 });
                 SendPacket((induction_response, from))
             }
@@ -190,6 +239,7 @@ impl Listen {
         // However, it must send back response packet as long as it receives any
         // further handshakes from the same client.
 
+        const VERSION_4: u32 = 4;
         const VERSION_5: u32 = 5;
 
         match (shake.shake_type, shake.info.version(), shake.syn_cookie) {
@@ -211,11 +261,32 @@ impl Listen {
                 if self.enable_access_control {
                     self.request_access(from, local_socket_id, timestamp, state, shake, incoming)
                 } else {
-                    let key_settings = self.settings().key_settings.clone();
-                    self.accept_connection(now, &state, timestamp, shake, incoming, key_settings)
+                    let parameters = AcceptParameters::default();
+                    self.accept_connection(now, &state, timestamp, shake, incoming, parameters)
                 }
             }
-            (ShakeType::Conclusion, VERSION_5, syn_cookie) => NotHandled(
+            (ShakeType::Conclusion, VERSION_4, syn_cookie) if syn_cookie == state.cookie => {
+                if !hsv4_supported(&self.init_settings, None) {
+                    let r = ConnectionReject::Rejecting(CoreRejectReason::Version.into());
+                    return self.make_rejection(&shake, from, timestamp, r);
+                }
+
+                // the SRT handshake follows in control packets, once the caller is connected
+                let response = self.hsv4_conclusion_response(&state, timestamp, &shake);
+                self.state = Hsv4RequestWait(
+                    ConclusionWaitState {
+                        conclusion_time: Some(now),
+                        ..state
+                    },
+                    Hsv4RequestWaitState {
+                        shake,
+                        hs_request: None,
+                        km_request: None,
+                    },
+                );
+                SendPacket((response.into(), from))
+            }
+            (ShakeType::Conclusion, VERSION_4 | VERSION_5, syn_cookie) => NotHandled(
                 ConnectError::InvalidHandshakeCookie(state.cookie, syn_cookie),
             ),
             (ShakeType::Conclusion, version, _) => {
@@ -225,6 +296,59 @@ impl Listen {
         }
     }
 
+    fn wait_for_hsv4_request(
+        &mut self,
+        now: Instant,
+        local_socket_id: SocketId,
+        timestamp: TimeStamp,
+        state: ConclusionWaitState,
+        mut request: Hsv4RequestWaitState,
+        srt: SrtControlPacket,
+    ) -> ConnectionResult {
+        use SrtControlPacket::*;
+        let hs_request = match srt {
+            KeyRefreshRequest(_) => {
+                request.km_request = Some(srt);
+                request.hs_request.take()
+            }
+            // older callers send the KMREQ right after the HSREQ, which they repeat until they get
+            // a response, so the first HSREQ waits for the keying material of an encrypted caller
+            HandshakeRequest(_)
+                if self.init_settings.key_settings.is_some()
+                    && request.km_request.is_none()
+                    && request.hs_request.is_none() =>
+            {
+                request.hs_request = Some(srt);
+                None
+            }
+            HandshakeRequest(_) => Some(srt),
+            _ => return NotHandled(ConnectError::ExpectedHsReq),
+        };
+
+        let hs_request = match hs_request {
+            Some(hs_request) => hs_request,
+            None => {
+                self.state = Hsv4RequestWait(state, request);
+                return NoAction;
+            }
+        };
+
+        let incoming = hsv4_extensions(hs_request, request.km_request);
+        if self.enable_access_control {
+            self.request_access(
+                state.from,
+                local_socket_id,
+                timestamp,
+                state,
+                request.shake,
+                incoming,
+            )
+        } else {
+            let parameters = AcceptParameters::default();
+            self.accept_connection(now, &state, timestamp, request.shake, incoming, parameters)
+        }
+    }
+
     fn request_access(
         &mut self,
         remote: SocketAddr,
@@ -234,6 +358,12 @@ impl Listen {
         shake: HandshakeControlInfo,
         incoming: HsV5Info,
     ) -> ConnectionResult {
+        // the acceptor can take the handshake of the caller into account
+        let hs = match incoming.ext_hs {
+            Some(SrtControlPacket::HandshakeRequest(hs)) => hs,
+            Some(_) => return NotHandled(ConnectError::ExpectedHsReq),
+            None => return NotHandled(ConnectError::ExpectedExtFlags),
+        };
         // TODO: handle StreamId parsing error
         let stream_id = incoming.sid.clone().and_then(|s| s.try_into().ok());
         let remote_socket_id = shake.socket_id;
@@ -245,6 +375,9 @@ impl Listen {
             remote,
             remote_socket_id,
             stream_id,
+            peer_version: hs.version,
+            peer_send_latency: hs.send_latency,
+            peer_recv_latency: hs.recv_latency,
         })
     }
 
@@ -255,16 +388,16 @@ impl Listen {
         timestamp: TimeStamp,
         shake: HandshakeControlInfo,
         info: HsV5Info,
-        key_settings: Option<KeySettings>,
+        parameters: AcceptParameters,
     ) -> ConnectionResult {
         let response = gen_access_control_response(
-            now,
+            state.conclusion_time.unwrap_or(now),
             &mut self.init_settings,
             state.from,
             state.induction_time,
             shake.clone(),
             info,
-            key_settings,
+            parameters,
         );
         let (hsv5, settings) = match response {
             GenHsv5Result::Accept(h, c) => (h, c),
@@ -274,6 +407,10 @@ impl Listen {
             }
         };
 
+        if let HandshakeVsInfo::V4(_) = shake.info {
+            return self.accept_hsv4_connection(state, timestamp, &shake, hsv5, settings);
+        }
+
         let resp_handshake = ControlPacket {
             timestamp,
             dest_sockid: shake.socket_id,
@@ -282,7 +419,10 @@ impl Listen {
                 socket_id: self.init_settings.local_sockid,
                 info: hsv5,
                 shake_type: ShakeType::Conclusion,
-                ..shake // TODO: this will pass peer wrong
+                // the caller adopts the packet size when it is smaller than its own
+                max_packet_size: settings.max_packet_size,
+                peer_addr: peer_ip(state.from),
+                ..shake
             }),
         };
 
@@ -296,6 +436,62 @@ impl Listen {
         )
     }
 
+    fn accept_hsv4_connection(
+        &self,
+        state: &ConclusionWaitState,
+        timestamp: TimeStamp,
+        shake: &HandshakeControlInfo,
+        hsv5: HandshakeVsInfo,
+        settings: ConnectionSettings,
+    ) -> ConnectionResult {
+        let (hs_response, km_response) = match hsv5 {
+            HandshakeVsInfo::V5(HsV5Info {
+                ext_hs: Some(hs_response),
+                ext_km,
+                ..
+            }) => (hs_response, ext_km),
+            _ => return NotHandled(ConnectError::ExpectedHsResp),
+        };
+
+        // the caller completes the connection with the HSRSP, so the KMRSP has to go first, the
+        // HSRSP is sent in response to the HSREQ the caller repeats
+        let response = ControlPacket {
+            timestamp,
+            dest_sockid: shake.socket_id,
+            control_type: ControlTypes::Srt(km_response.unwrap_or_else(|| hs_response.clone())),
+        };
+        let conclusion = self.hsv4_conclusion_response(state, timestamp, shake);
+
+        Connected(
+            Some((response.into(), state.from)),
+            Connection {
+                settings,
+                handshake: Handshake::Hsv4Listener(conclusion.control_type, hs_response),
+            },
+        )
+    }
+
+    fn hsv4_conclusion_response(
+        &self,
+        state: &ConclusionWaitState,
+        timestamp: TimeStamp,
+        shake: &HandshakeControlInfo,
+    ) -> ControlPacket {
+        ControlPacket {
+            timestamp,
+            dest_sockid: shake.socket_id,
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                syn_cookie: state.cookie,
+                socket_id: self.init_settings.local_sockid,
+                info: HandshakeVsInfo::V4(SocketType::Datagram),
+                shake_type: ShakeType::Conclusion,
+                max_packet_size: min(self.init_settings.max_packet_size, shake.max_packet_size),
+                peer_addr: peer_ip(state.from),
+                ..shake.clone()
+            }),
+        }
+    }
+
     fn make_rejection(
         &self,
         response_to: &HandshakeControlInfo,
@@ -311,6 +507,7 @@ impl Listen {
                     control_type: ControlTypes::Handshake(HandshakeControlInfo {
                         shake_type: ShakeType::Rejection(r.reason()),
                         socket_id: self.init_settings.local_sockid,
+                        peer_addr: peer_ip(from),
                         ..response_to.clone()
                     }),
                 }
@@ -378,6 +575,8 @@ mod test {
                 ext_km: None,
                 ext_group: None,
                 sid: None,
+                congestion: None,
+                filter: None,
             }),
         }
     }
@@ -423,6 +622,37 @@ mod test {
         );
     }
 
+    #[test]
+    fn responses_carry_caller_address() {
+        let mut l = test_listen();
+        let caller = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)), 8765);
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), caller)),
+        );
+        assert_matches!(
+            resp,
+            SendPacket((Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(HandshakeControlInfo { peer_addr, .. }),
+                ..
+            }), _)) if peer_addr == caller.ip()
+        );
+
+        let conclusion = HandshakeControlInfo {
+            syn_cookie: gen_cookie(&caller),
+            ..test_conclusion()
+        };
+        let resp = l.handle_packet(Instant::now(), Ok((build_hs_pack(conclusion), caller)));
+        assert_matches!(
+            resp,
+            Connected(Some((Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(HandshakeControlInfo { peer_addr, .. }),
+                ..
+            }), _)), _) if peer_addr == caller.ip()
+        );
+    }
+
     #[test]
     fn send_data_packet() {
         let mut l = test_listen();
@@ -502,6 +732,31 @@ mod test {
         )
     }
 
+    #[test]
+    fn induction_from_another_caller() {
+        let mut l = test_listen();
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(resp, SendPacket(_));
+
+        // e.g. the first caller was rejected, the next caller gets its own cookie
+        let other_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8766);
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), other_addr)),
+        );
+        assert_matches!(
+            resp,
+            SendPacket((Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(HandshakeControlInfo { syn_cookie, .. }),
+                ..
+            }), addr)) if syn_cookie == gen_cookie(&other_addr) && addr == other_addr
+        );
+    }
+
     #[test]
     fn send_v4_conclusion() {
         let mut l = test_listen();
@@ -517,9 +772,20 @@ mod test {
 
         let resp = l.handle_packet(Instant::now(), Ok((build_hs_pack(c), conn_addr())));
 
+        // the SRT handshake follows in control packets
         assert_matches!(
             resp,
-            NotHandled(ConnectError::UnsupportedProtocolVersion(4))
+            SendPacket((
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                        shake_type: ShakeType::Conclusion,
+                        info: HandshakeVsInfo::V4(SocketType::Datagram),
+                        ..
+                    }),
+                    ..
+                }),
+                _
+            ))
         );
     }
 
@@ -569,4 +835,79 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn accept_with_parameters() {
+        let mut l = Listen::new(ConnInitSettings::default(), true);
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(resp, SendPacket(_));
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_conclusion()), conn_addr())),
+        );
+        let request = assert_matches!(resp, RequestAccess(request) => request);
+        assert_eq!(request.peer_version, SrtVersion::CURRENT);
+        assert_eq!(request.peer_send_latency, Duration::from_secs(1));
+        assert_eq!(request.peer_recv_latency, Duration::from_secs(2));
+
+        let mut parameters = AcceptParameters::new();
+        parameters
+            .set_latency(Duration::from_millis(1500))
+            .set_max_payload_size(PacketSize(1000))
+            .set_bandwidth(LiveBandwidthMode::Max(DataRate(1_000_000)));
+        let resp = l.handle_access_control_response(
+            Instant::now(),
+            AccessControlResponse::Accepted(parameters),
+        );
+        let (packet, settings) = assert_matches!(
+            resp,
+            Connected(Some((Packet::Control(packet), _)), Connection { settings, .. }) => (packet, settings)
+        );
+        assert_eq!(settings.send_tsbpd_latency, Duration::from_secs(2));
+        assert_eq!(settings.recv_tsbpd_latency, Duration::from_millis(1500));
+        assert_eq!(settings.max_packet_size, PacketSize(1000));
+        assert_eq!(
+            settings.bandwidth,
+            LiveBandwidthMode::Max(DataRate(1_000_000))
+        );
+        assert_matches!(
+            packet.control_type,
+            ControlTypes::Handshake(HandshakeControlInfo {
+                max_packet_size: PacketSize(1000),
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn accept_with_recv_latency() {
+        let mut l = Listen::new(ConnInitSettings::default(), true);
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(resp, SendPacket(_));
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_conclusion()), conn_addr())),
+        );
+        assert_matches!(resp, RequestAccess(_));
+
+        let mut parameters = AcceptParameters::new();
+        parameters.set_recv_latency(Duration::from_secs(3));
+        let resp = l.handle_access_control_response(
+            Instant::now(),
+            AccessControlResponse::Accepted(parameters),
+        );
+        let settings = assert_matches!(resp, Connected(_, Connection { settings, .. }) => settings);
+        assert_eq!(settings.recv_tsbpd_latency, Duration::from_secs(3));
+        assert_eq!(settings.send_tsbpd_latency, Duration::from_secs(2));
+    }
 }
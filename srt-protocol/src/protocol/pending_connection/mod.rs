@@ -1,13 +1,24 @@
 pub mod connect;
+mod hsv4;
 mod hsv5;
 pub mod listen;
 pub mod rendezvous;
 
 pub(crate) mod cookie;
 
-use std::{error::Error, fmt, io, net::SocketAddr};
+use std::{
+    error::Error,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
 
-use crate::{connection::Connection, options::StreamId, packet::*, settings::KeySettings};
+use crate::{
+    connection::Connection,
+    options::{SrtVersion, StreamId},
+    packet::*,
+    settings::AcceptParameters,
+};
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -29,24 +40,46 @@ pub enum ConnectError {
     ExpectedExtFlags,
     ExpectedNoExtFlags,
     ExpectedAccessControlResponse,
+    ExpectedGroup,
+    ExpectedFilter,
     ParseFailed(PacketParseError),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AccessControlRequest {
     pub local_socket_id: SocketId,
     pub remote: SocketAddr,
     pub remote_socket_id: SocketId,
     pub stream_id: Option<StreamId>,
+    /// The SRT version of the caller
+    pub peer_version: SrtVersion,
+    /// The latency the caller requested for the data it sends, its SRTO_PEERLATENCY
+    pub peer_send_latency: Duration,
+    /// The latency the caller requested for the data it receives, its SRTO_RCVLATENCY
+    pub peer_recv_latency: Duration,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum AccessControlResponse {
-    Accepted(Option<KeySettings>),
+    Accepted(AcceptParameters),
     Rejected(RejectReason),
     Dropped,
 }
 
+/// How far the handshake of a caller or rendezvous got, e.g. when it was rejected or timed out
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HandshakeStage {
+    /// Waiting for the induction response, the peer has not responded yet
+    Induction,
+    /// Waiting for the conclusion response, the peer responded to the induction
+    Conclusion,
+    /// Waiting for the HSRSP of a HSv4 listener, the peer accepted the UDT connection
+    SrtHandshake,
+    /// Exchanging handshakes with a rendezvous peer
+    Rendezvous,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum ConnectionReject {
     /// local rejected remote
@@ -68,6 +101,20 @@ pub enum ConnectionResult {
     Failure(io::Error),
 }
 
+// the peer address sent in handshakes, an IPv4 peer reached over a dual stack socket is sent
+// as the IPv4 address, like the reference implementation does
+fn peer_ip(remote: SocketAddr) -> IpAddr {
+    match remote.ip() {
+        IpAddr::V6(ip) => match ip.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                Ipv4Addr::new(a, b, c, d).into()
+            }
+            _ => IpAddr::V6(ip),
+        },
+        ip => ip,
+    }
+}
+
 impl fmt::Display for ConnectError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use ConnectError::*;
@@ -118,6 +165,14 @@ impl fmt::Display for ConnectError {
                 f,
                 "Expected an access control response but instead received a packet from the peer"
             ),
+            ExpectedGroup => write!(
+                f,
+                "Initiator got a handshake response without a matching group extension"
+            ),
+            ExpectedFilter => write!(
+                f,
+                "Initiator got a handshake response without a matching packet filter extension"
+            ),
         }
     }
 }
@@ -134,8 +189,20 @@ impl fmt::Display for ConnectionReject {
     }
 }
 
+impl fmt::Display for HandshakeStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use HandshakeStage::*;
+        match self {
+            Induction => write!(f, "induction"),
+            Conclusion => write!(f, "conclusion"),
+            SrtHandshake => write!(f, "SRT handshake"),
+            Rendezvous => write!(f, "rendezvous"),
+        }
+    }
+}
+
 impl ConnectionReject {
-    fn reason(&self) -> RejectReason {
+    pub fn reason(&self) -> RejectReason {
         match self {
             ConnectionReject::Rejecting(r) | ConnectionReject::Rejected(r) => *r,
         }
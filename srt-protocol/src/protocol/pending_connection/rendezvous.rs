@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, net::SocketAddr, time::Instant};
+use std::{cmp::Ordering, fmt, net::SocketAddr, time::Instant};
 
 use log::debug;
 
@@ -16,19 +16,56 @@ use crate::{
 
 use super::{
     cookie::gen_cookie,
-    hsv5::{gen_hsv5_response, start_hsv5_initiation, GenHsv5Result, StartedInitiator},
-    ConnectError, ConnectionReject, ConnectionResult,
+    hsv5::{
+        gen_hsv5_response, start_hsv5_initiation, FinishHsv5Error, GenHsv5Result, StartedInitiator,
+    },
+    peer_ip, ConnectError, ConnectionReject, ConnectionResult,
 };
 
 pub struct Rendezvous {
     init_settings: ConnInitSettings,
-    local_addr: SocketAddr,
     remote_public: SocketAddr,
     state: RendezvousState,
     cookie: i32,
     last_packet: (Packet, SocketAddr),
     last_send: Option<Instant>,
     starting_seqnum: SeqNumber,
+    peer_seen: bool,
+    cookie_contest: Option<CookieContest>,
+}
+
+/// The phases of the rendezvous handshake, see haivision/srt/docs/handshake.md
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RendezvousPhase {
+    /// Sending wave-a-hand handshakes, no handshake arrived from the peer yet
+    Waving,
+    /// The peer's wave-a-hand arrived, exchanging conclusions
+    Attention,
+    /// The peer's conclusion arrived while waving, exchanging conclusions
+    Fine,
+    /// The conclusions were exchanged, waiting for the agreement
+    Initiated,
+}
+
+/// The result of comparing the cookies of both peers, which decides their roles
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CookieContest {
+    Initiator,
+    Responder,
+    /// Both cookies are equal, so neither can take a role, e.g. when both peers use the same
+    /// local address
+    Draw,
+}
+
+/// How far a rendezvous handshake got, to tell why it did not complete
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RendezvousDiagnostics {
+    pub phase: RendezvousPhase,
+    /// Whether any packet arrived from the peer's address. If not, the peer's NAT or firewall
+    /// drops the handshakes, or the peer is not running.
+    pub peer_seen: bool,
+    /// The result of the cookie contest, once a handshake arrived from the peer
+    pub cookie_contest: Option<CookieContest>,
 }
 
 // see haivision/srt/docs/handshake.md for documentation
@@ -63,7 +100,7 @@ impl Rendezvous {
                     max_flow_size: init_settings.max_flow_size,
                     socket_id: init_settings.local_sockid,
                     shake_type: ShakeType::Waveahand,
-                    peer_addr: local_addr.ip(),
+                    peer_addr: peer_ip(remote_public),
                     syn_cookie: cookie, // TODO: !!
                     info: Rendezvous::empty_flags(),
                 }),
@@ -77,10 +114,25 @@ impl Rendezvous {
             cookie,
             last_packet,
             init_settings,
-            local_addr,
             remote_public,
             last_send: None,
             starting_seqnum,
+            peer_seen: false,
+            cookie_contest: None,
+        }
+    }
+
+    pub fn diagnostics(&self) -> RendezvousDiagnostics {
+        let phase = match self.state {
+            Waving => RendezvousPhase::Waving,
+            AttentionInitiator(..) | AttentionResponder(_) => RendezvousPhase::Attention,
+            FineInitiator(..) | FineResponder(_) => RendezvousPhase::Fine,
+            InitiatedInitiator(_) | InitiatedResponder(_) => RendezvousPhase::Initiated,
+        };
+        RendezvousDiagnostics {
+            phase,
+            peer_seen: self.peer_seen,
+            cookie_contest: self.cookie_contest,
         }
     }
 }
@@ -133,7 +185,7 @@ impl Rendezvous {
             max_flow_size: self.init_settings.max_flow_size,
             socket_id: self.init_settings.local_sockid,
             shake_type,
-            peer_addr: self.local_addr.ip(),
+            peer_addr: peer_ip(self.remote_public),
             syn_cookie: self.cookie, // TODO: !!
             info,
         }
@@ -179,6 +231,7 @@ impl Rendezvous {
                     control_type: ControlTypes::Handshake(HandshakeControlInfo {
                         shake_type: ShakeType::Rejection(r.reason()),
                         socket_id: self.init_settings.local_sockid,
+                        peer_addr: peer_ip(self.remote_public),
                         ..response_to.clone()
                     }),
                 }
@@ -223,10 +276,16 @@ impl Rendezvous {
         assert!(matches!(self.state, Waving));
 
         // NOTE: the cookie comparison behavior is not correctly documented. See haivision/srt#1267
-        let role = match self.cookie.wrapping_sub(info.syn_cookie).cmp(&0) {
-            Ordering::Greater => Initiator,
-            Ordering::Less => Responder,
-            Ordering::Equal => return NotHandled(CookiesMatched(self.cookie)),
+        let contest = match self.cookie.wrapping_sub(info.syn_cookie).cmp(&0) {
+            Ordering::Greater => CookieContest::Initiator,
+            Ordering::Less => CookieContest::Responder,
+            Ordering::Equal => CookieContest::Draw,
+        };
+        self.cookie_contest = Some(contest);
+        let role = match contest {
+            CookieContest::Initiator => Initiator,
+            CookieContest::Responder => Responder,
+            CookieContest::Draw => return NotHandled(CookiesMatched(self.cookie)),
         };
 
         debug!(
@@ -317,7 +376,10 @@ impl Rendezvous {
                     let settings =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
                             Ok(s) => s,
-                            Err(r) => return NotHandled(r),
+                            Err(FinishHsv5Error::NotHandled(e)) => return NotHandled(e),
+                            Err(FinishHsv5Error::Reject(r)) => {
+                                return self.make_rejection(info, TimeStamp::from_micros(0), r)
+                            }
                         };
 
                     self.set_connected(settings, Some(agreement.clone()), Some(agreement))
@@ -383,7 +445,10 @@ impl Rendezvous {
                     let settings =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
                             Ok(s) => s,
-                            Err(r) => return NotHandled(r),
+                            Err(FinishHsv5Error::NotHandled(e)) => return NotHandled(e),
+                            Err(FinishHsv5Error::Reject(r)) => {
+                                return self.make_rejection(info, TimeStamp::from_micros(0), r)
+                            }
                         };
 
                     self.set_connected(settings, Some(agreement.clone()), Some(agreement))
@@ -432,7 +497,10 @@ impl Rendezvous {
                     let connection =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
                             Ok(c) => c,
-                            Err(e) => return NotHandled(e),
+                            Err(FinishHsv5Error::NotHandled(e)) => return NotHandled(e),
+                            Err(FinishHsv5Error::Reject(r)) => {
+                                return self.make_rejection(info, TimeStamp::from_micros(0), r)
+                            }
                         };
 
                     let agreement =
@@ -481,6 +549,7 @@ impl Rendezvous {
                 if from != self.remote_public {
                     return NotHandled(UnexpectedHost(self.remote_public, from));
                 }
+                self.peer_seen = true;
 
                 let hs = get_handshake(&packet);
                 match (self.state.clone(), hs) {
@@ -514,3 +583,30 @@ impl Rendezvous {
         SendPacket(self.last_packet.clone())
     }
 }
+
+impl fmt::Display for RendezvousPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use RendezvousPhase::*;
+        match self {
+            Waving => write!(f, "waving"),
+            Attention => write!(f, "attention"),
+            Fine => write!(f, "fine"),
+            Initiated => write!(f, "initiated"),
+        }
+    }
+}
+
+impl fmt::Display for RendezvousDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reached the {} phase", self.phase)?;
+        if !self.peer_seen {
+            return write!(f, ", no packet arrived from the peer");
+        }
+        match self.cookie_contest {
+            Some(CookieContest::Initiator) => write!(f, ", as initiator"),
+            Some(CookieContest::Responder) => write!(f, ", as responder"),
+            Some(CookieContest::Draw) => write!(f, ", the cookie contest was a draw"),
+            None => write!(f, ", no handshake arrived from the peer"),
+        }
+    }
+}
@@ -8,6 +8,7 @@ use arraydeque::{behavior::Wrapping, ArrayDeque};
 use bytes::Bytes;
 
 use crate::{
+    connection::SequencedMessage,
     options::PacketCount,
     packet::*,
     protocol::{
@@ -173,9 +174,11 @@ pub struct AutomaticRepeatRequestAlgorithm {
 impl AutomaticRepeatRequestAlgorithm {
     pub fn new(
         socket_start_time: Instant,
-        tsbpd_latency: Duration,
+        tsbpd_latency: Option<Duration>,
         init_seq_num: SeqNumber,
         buffer_size_packets: PacketCount,
+        message_api: bool,
+        too_late_packet_drop: bool,
     ) -> Self {
         Self {
             link_capacity_estimate: LinkCapacityEstimate::new(),
@@ -185,8 +188,13 @@ impl AutomaticRepeatRequestAlgorithm {
                 tsbpd_latency,
                 init_seq_num,
                 buffer_size_packets,
+            )
+            .with_message_api(message_api)
+            .with_too_late_packet_drop(too_late_packet_drop),
+            ack_history_window: AckHistoryWindow::new(
+                tsbpd_latency.unwrap_or_default(),
+                init_seq_num,
             ),
-            ack_history_window: AckHistoryWindow::new(tsbpd_latency, init_seq_num),
             rtt: Rtt::default(),
         }
     }
@@ -239,8 +247,8 @@ impl AutomaticRepeatRequestAlgorithm {
             rtt: self.rtt,
             buffer_available: self.receive_buffer.buffer_available() as u32,
             packet_receive_rate: arrival_speed.map(|(packets, _)| packets),
-            estimated_link_capacity: arrival_speed.map(|(_, bytes)| bytes),
-            data_receive_rate: self.link_capacity_estimate.calculate(),
+            estimated_link_capacity: self.link_capacity_estimate.calculate(),
+            data_receive_rate: arrival_speed.map(|(_, bytes)| bytes),
         };
 
         Some(Acknowledgement::Full(dsn, statistics, fasn))
@@ -318,6 +326,13 @@ impl AutomaticRepeatRequestAlgorithm {
         self.receive_buffer.pop_next_message(now)
     }
 
+    pub fn pop_next_sequenced_message(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<SequencedMessage>, MessageError> {
+        self.receive_buffer.pop_next_sequenced_message(now)
+    }
+
     pub fn rx_acknowledged_time(&self) -> Duration {
         self.receive_buffer.rx_acknowledged_time()
     }
@@ -352,9 +367,11 @@ mod automatic_repeat_request_algorithm {
         let init_seq_num = SeqNumber(5);
         let mut arq = AutomaticRepeatRequestAlgorithm::new(
             start,
-            Duration::from_secs(2),
+            Some(Duration::from_secs(2)),
             init_seq_num,
             PacketCount(8192),
+            true,
+            true,
         );
 
         assert_eq!(arq.on_full_ack_event(start), None);
@@ -409,9 +426,11 @@ mod automatic_repeat_request_algorithm {
         let init_seq_num = SeqNumber(1);
         let mut arq = AutomaticRepeatRequestAlgorithm::new(
             start,
-            Duration::from_secs(2),
+            Some(Duration::from_secs(2)),
             init_seq_num,
             PacketCount(8192),
+            true,
+            true,
         );
 
         assert_eq!(
@@ -471,15 +490,54 @@ mod automatic_repeat_request_algorithm {
         assert!(!arq.is_flushed());
     }
 
+    #[test]
+    fn full_ack_statistics() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(0);
+        let mut arq = AutomaticRepeatRequestAlgorithm::new(
+            start,
+            Some(Duration::from_secs(2)),
+            init_seq_num,
+            PacketCount(8192),
+            true,
+            true,
+        );
+
+        // 1,000 byte packets every millisecond, enough for 16 packet pairs
+        let mut now = start;
+        for n in 0..(16 * 16 + 2) {
+            now += Duration::from_millis(1);
+            let _ = arq.handle_data_packet(
+                now,
+                DataPacket {
+                    seq_number: init_seq_num + n,
+                    payload: Bytes::from(vec![0; 1_000]),
+                    ..basic_pack()
+                },
+            );
+        }
+
+        // the link capacity is in packets per second, the receive rate in bytes per second
+        let statistics = assert_matches!(
+            arq.on_full_ack_event(now),
+            Some(Acknowledgement::Full(_, statistics, _)) => statistics
+        );
+        assert_eq!(statistics.packet_receive_rate, Some(1_000));
+        assert_eq!(statistics.estimated_link_capacity, Some(1_000));
+        assert_eq!(statistics.data_receive_rate, Some(1_000_000));
+    }
+
     #[test]
     fn ack2_packet() {
         let start = Instant::now();
         let init_seq_num = SeqNumber(1);
         let mut arq = AutomaticRepeatRequestAlgorithm::new(
             start,
-            Duration::from_secs(2),
+            Some(Duration::from_secs(2)),
             init_seq_num,
             PacketCount(8192),
+            true,
+            true,
         );
 
         let _ = arq.handle_data_packet(
@@ -519,9 +577,11 @@ mod automatic_repeat_request_algorithm {
         let init_seq_num = SeqNumber(1);
         let mut arq = AutomaticRepeatRequestAlgorithm::new(
             start,
-            Duration::from_secs(1),
+            Some(Duration::from_secs(1)),
             init_seq_num,
             PacketCount(8192),
+            true,
+            true,
         );
 
         let _ = arq.handle_data_packet(
@@ -570,9 +630,11 @@ mod automatic_repeat_request_algorithm {
         let init_seq_num = SeqNumber(5);
         let mut arq = AutomaticRepeatRequestAlgorithm::new(
             start,
-            tsbpd_latency,
+            Some(tsbpd_latency),
             init_seq_num,
             PacketCount(8192),
+            true,
+            true,
         );
 
         let now = start;
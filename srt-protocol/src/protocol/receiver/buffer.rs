@@ -9,7 +9,11 @@ use std::{
 use bytes::{Bytes, BytesMut};
 use take_until::TakeUntilExt;
 
-use crate::{options::PacketCount, packet::*};
+use crate::{
+    connection::{Discontinuity, MessageInfo, SequencedMessage},
+    options::PacketCount,
+    packet::*,
+};
 
 use super::{
     time::{ClockAdjustment, SynchronizedRemoteClock},
@@ -172,7 +176,17 @@ pub struct MessageError {
 
 #[derive(Debug)]
 pub struct ReceiveBuffer {
-    tsbpd_latency: Duration,
+    // None when timestamp based packet delivery is disabled (i.e. file transmission),
+    // messages are then released as soon as they are complete
+    tsbpd_latency: Option<Duration>,
+
+    // false for the stream API, where message boundaries are ignored and all contiguous
+    // received packets are released together
+    message_api: bool,
+
+    // false to wait for missing packets to be retransmitted, however late, instead of dropping
+    // them when the following message is due
+    too_late_packet_drop: bool,
 
     // Sequence number that all packets up to have been received + 1
     lrsn: SeqNumber,
@@ -180,6 +194,14 @@ pub struct ReceiveBuffer {
     // first sequence number in the list
     seqno0: SeqNumber,
 
+    // sequence number following the last released message, anything skipped in between was
+    // dropped
+    next_release_dsn: SeqNumber,
+
+    // message number expected after the last released message, and the payload dropped since
+    next_release_message: MsgNumber,
+    dropped_bytes: u64,
+
     remote_clock: SynchronizedRemoteClock,
     buffer: VecDeque<BufferPacket>,
     max_buffer_size: PacketCount,
@@ -188,20 +210,39 @@ pub struct ReceiveBuffer {
 impl ReceiveBuffer {
     pub fn new(
         socket_start_time: Instant,
-        tsbpd_latency: Duration,
+        tsbpd_latency: Option<Duration>,
         init_seq_num: SeqNumber,
         max_buffer_size: PacketCount,
     ) -> Self {
         Self {
             tsbpd_latency,
+            message_api: true,
+            too_late_packet_drop: true,
             lrsn: init_seq_num,
             seqno0: init_seq_num,
+            next_release_dsn: init_seq_num,
+            next_release_message: MsgNumber(1),
+            dropped_bytes: 0,
             remote_clock: SynchronizedRemoteClock::new(socket_start_time),
             buffer: VecDeque::with_capacity(max_buffer_size.into()),
             max_buffer_size,
         }
     }
 
+    pub fn with_message_api(self, message_api: bool) -> Self {
+        Self {
+            message_api,
+            ..self
+        }
+    }
+
+    pub fn with_too_late_packet_drop(self, too_late_packet_drop: bool) -> Self {
+        Self {
+            too_late_packet_drop,
+            ..self
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
@@ -264,6 +305,21 @@ impl ReceiveBuffer {
         &mut self,
         now: Instant,
     ) -> Result<Option<(Instant, Bytes)>, MessageError> {
+        Ok(self
+            .pop_next_sequenced_message(now)?
+            .map(|(_, message)| message))
+    }
+
+    /// Like pop_next_message, but also returns where the message was in the stream, whether it
+    /// had to be retransmitted and whether packets were dropped before it
+    pub fn pop_next_sequenced_message(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<SequencedMessage>, MessageError> {
+        if !self.too_late_packet_drop {
+            self.skip_dropped_packets();
+        }
+
         let timestamp = match self.front_ts() {
             Some(timestamp) => timestamp,
             None => {
@@ -274,9 +330,11 @@ impl ReceiveBuffer {
             }
         };
 
-        let sent_time = self.remote_clock.instant_from(timestamp);
-        if now < sent_time + self.tsbpd_latency {
-            return Ok(None);
+        if let Some(tsbpd_latency) = self.tsbpd_latency {
+            let sent_time = self.remote_clock.instant_from(timestamp);
+            if now < sent_time + tsbpd_latency {
+                return Ok(None);
+            }
         }
 
         let packet_count = match self.next_message_packet_count() {
@@ -289,7 +347,35 @@ impl ReceiveBuffer {
             }
         };
 
-        self.seqno0 += u32::try_from(packet_count).unwrap();
+        // the front is a data packet, it has a timestamp
+        let message_number = match self.buffer.front().and_then(BufferPacket::data_packet) {
+            Some(packet) => packet.message_number,
+            None => return Ok(None),
+        };
+        let last_seq_number = self.seqno0 + u32::try_from(packet_count - 1).unwrap();
+        let info = MessageInfo {
+            message_number,
+            seq_number: self.seqno0,
+            last_seq_number,
+            retransmitted: self
+                .buffer
+                .range(0..packet_count)
+                .filter_map(BufferPacket::data_packet)
+                .any(|p| p.retransmitted),
+            gap: (self.seqno0 != self.next_release_dsn).then(|| Discontinuity {
+                seq_numbers: self.next_release_dsn..self.seqno0,
+                messages: message_count(self.next_release_message, message_number),
+                bytes: self.dropped_bytes,
+            }),
+        };
+        self.seqno0 = last_seq_number + 1;
+        self.next_release_dsn = self.seqno0;
+        // message number 0 is skipped when wrapping around
+        self.next_release_message = match message_number + 1 {
+            MsgNumber(0) => MsgNumber(1),
+            next => next,
+        };
+        self.dropped_bytes = 0;
 
         let release_time = self.remote_clock.monotonic_instant_from(timestamp);
         let message = if packet_count == 1 {
@@ -297,7 +383,7 @@ impl ReceiveBuffer {
         } else {
             self.release_full_message(release_time, packet_count)
         };
-        Ok(message)
+        Ok(message.map(|message| (info, message)))
     }
 
     fn front_ts(&mut self) -> Option<TimeStamp> {
@@ -357,18 +443,45 @@ impl ReceiveBuffer {
         // if start of the range has been dropped already, just drop everything after
         let first_idx = self.clamped_index_for_seqno(range.start);
         let last_idx = self.clamped_index_for_seqno(range.end);
-        self.buffer
+        let dropped = self
+            .buffer
             .range_mut(first_idx..last_idx)
             .filter_map(|p| p.drop_unreceived())
-            .count()
+            .count();
+
+        // the sender skipped packets that were never received, like the reference implementation
+        // continue from the end of the range. this is how socket group members stay aligned.
+        let next = self.next_packet_dsn();
+        if range.end > next {
+            if self.buffer.is_empty() {
+                self.seqno0 = range.end;
+                self.lrsn = range.end;
+            } else {
+                let skipped = min(
+                    usize::try_from(range.end - next).unwrap(),
+                    self.buffer_available(),
+                );
+                let skipped = u32::try_from(skipped).unwrap();
+                for i in 0..skipped {
+                    self.buffer.push_back(BufferPacket::Dropped(next + i));
+                }
+                // nothing was lost before the skipped packets, so they don't hold back ACKs
+                if self.lrsn == next {
+                    self.lrsn = next + skipped;
+                }
+            }
+        }
+
+        dropped
     }
 
     pub fn next_message_release_time(&self) -> Option<Instant> {
+        let tsbpd_latency = self.tsbpd_latency?;
         self.buffer
             .front()
             .filter(|p| p.is_first())?
             .data_packet()
-            .map(|d| self.remote_clock.instant_from(d.timestamp) + self.tsbpd_latency)
+            .map(|d| self.remote_clock.instant_from(d.timestamp) + tsbpd_latency)
     }
 
     fn append_next(&mut self, data: DataPacket) -> Result<DataPacketAction, DataPacketError> {
@@ -456,6 +569,15 @@ impl ReceiveBuffer {
     }
 
     fn next_message_packet_count(&self) -> Option<usize> {
+        if !self.message_api {
+            let count = self
+                .buffer
+                .iter()
+                .take_while(|p| p.data_packet().is_some())
+                .count();
+            return (count > 0).then(|| count);
+        }
+
         let first = self.buffer.front()?.data_packet()?;
         self.buffer
             .iter()
@@ -468,7 +590,8 @@ impl ReceiveBuffer {
     /// Drops the packets that are deemed to be too late
     /// i.e.: there is a packet after it that is ready to be released
     fn drop_too_late_packets(&mut self, now: Instant) -> Option<MessageError> {
-        let latency_window = self.tsbpd_latency + Duration::from_millis(5);
+        let tsbpd_latency = self.tsbpd_latency.filter(|_| self.too_late_packet_drop)?;
+        let latency_window = tsbpd_latency + Duration::from_millis(5);
         // Not only does it have to be non-none, it also has to be a First (don't drop half messages)
         let (index, seq_number, timestamp) = self
             .buffer
@@ -483,18 +606,40 @@ impl ReceiveBuffer {
             })
             .filter(|(_, _, timestamp)| now >= *timestamp + latency_window)?;
 
-        let delay = TimeSpan::from_interval(timestamp + self.tsbpd_latency, now);
-        let drop_count = self.buffer.drain(0..index).count();
+        let delay = TimeSpan::from_interval(timestamp + tsbpd_latency, now);
+        let (drop_count, drop_bytes) =
+            self.buffer
+                .drain(0..index)
+                .fold((0, 0), |(count, bytes): (u32, u64), p| {
+                    let size = p.data_packet().map_or(0, |d| d.payload.len());
+                    (count + 1, bytes + size as u64)
+                });
+        self.dropped_bytes += drop_bytes;
 
         self.seqno0 = seq_number;
         self.recalculate_lrsn(0);
 
         Some(MessageError {
-            too_late_packets: seq_number - drop_count as u32..seq_number,
+            too_late_packets: seq_number - drop_count..seq_number,
             delay,
         })
     }
 
+    /// Without too late packet drop, the packets the sender asked to drop are still skipped, as
+    /// they will never arrive
+    fn skip_dropped_packets(&mut self) {
+        let count = self
+            .buffer
+            .iter()
+            .take_while(|p| matches!(p, BufferPacket::Dropped(_)))
+            .count();
+        if count > 0 {
+            self.buffer.drain(0..count);
+            self.seqno0 += u32::try_from(count).unwrap();
+            self.recalculate_lrsn(0);
+        }
+    }
+
     fn recalculate_lrsn(&mut self, start_idx: usize) {
         self.lrsn = self
             .buffer
@@ -520,10 +665,23 @@ impl ReceiveBuffer {
     }
 }
 
+// the number of messages from `first` up to, but not including, `last`, message number 0 is
+// skipped when wrapping around
+fn message_count(first: MsgNumber, last: MsgNumber) -> u32 {
+    let count = last - first;
+    if last.as_raw() < first.as_raw() {
+        count - 1
+    } else {
+        count
+    }
+}
+
 #[cfg(test)]
 mod receive_buffer {
     use super::*;
 
+    use assert_matches::assert_matches;
+
     use DataPacketAction::*;
     use DataPacketError::*;
 
@@ -547,20 +705,168 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(3);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(buf.next_ack_dsn(), init_seq_num);
         assert_eq!(buf.next_message_release_time(), None);
         assert_eq!(buf.pop_next_message(start), Ok(None));
     }
 
+    #[test]
+    fn no_tsbpd() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, None, init_seq_num, PacketCount(8192));
+
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num,
+                ..basic_pack()
+            },
+        );
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 2,
+                ..basic_pack()
+            },
+        );
+
+        // messages are released as soon as they are complete
+        assert_eq!(buf.next_message_release_time(), None);
+        assert_matches!(buf.pop_next_message(start), Ok(Some(_)));
+
+        // lost packets are never too late, the next message waits for retransmission
+        assert_eq!(
+            buf.pop_next_message(start + Duration::from_secs(10)),
+            Ok(None)
+        );
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 1,
+                ..basic_pack()
+            },
+        );
+        assert_matches!(buf.pop_next_message(start), Ok(Some(_)));
+        assert_matches!(buf.pop_next_message(start), Ok(Some(_)));
+        assert_eq!(buf.pop_next_message(start), Ok(None));
+    }
+
+    #[test]
+    fn stream_api() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, None, init_seq_num, PacketCount(8192))
+            .with_message_api(false);
+
+        // the first packet of an incomplete message
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num,
+                message_loc: PacketLocation::FIRST,
+                payload: Bytes::from_static(b"hello"),
+                ..basic_pack()
+            },
+        );
+        // the rest of the message arrives after a lost packet
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 2,
+                message_loc: PacketLocation::LAST,
+                payload: Bytes::from_static(b"!"),
+                ..basic_pack()
+            },
+        );
+
+        // message boundaries are ignored, bytes are released up to the lost packet
+        assert_eq!(
+            buf.pop_next_message(start).unwrap().map(|(_, b)| b),
+            Some(Bytes::from_static(b"hello"))
+        );
+        assert_eq!(buf.pop_next_message(start), Ok(None));
+
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 1,
+                message_loc: PacketLocation::empty(),
+                payload: Bytes::from_static(b" world"),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(
+            buf.pop_next_message(start).unwrap().map(|(_, b)| b),
+            Some(Bytes::from_static(b" world!"))
+        );
+        assert_eq!(buf.pop_next_message(start), Ok(None));
+    }
+
+    #[test]
+    fn drop_request_skips_ahead() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, None, init_seq_num, PacketCount(8192));
+
+        // the sender skipped packets that never arrived, continue after them
+        assert_eq!(buf.drop_packets(init_seq_num..init_seq_num + 10), 0);
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 10);
+        assert_eq!(
+            buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + 10,
+                    ..basic_pack()
+                },
+            ),
+            Ok(Received {
+                lrsn: init_seq_num + 11,
+                recovered: false
+            })
+        );
+        assert_eq!(
+            buf.pop_next_sequenced_message(start)
+                .unwrap()
+                .map(|(info, _)| info.seq_number),
+            Some(init_seq_num + 10)
+        );
+
+        // with packets still buffered, the skipped packets are dropped
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 11,
+                ..basic_pack()
+            },
+        );
+        let _ = buf.drop_packets(init_seq_num + 12..init_seq_num + 15);
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 15);
+        assert_matches!(
+            buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + 15,
+                    ..basic_pack()
+                },
+            ),
+            Ok(Received { .. })
+        );
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 16);
+    }
+
     #[test]
     fn multi_packet_message_not_ready() {
         let tsbpd = Duration::from_secs(2);
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(
             buf.push_packet(
@@ -587,7 +893,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(
             buf.push_packet(
@@ -636,7 +942,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(
             buf.push_packet(
@@ -681,7 +987,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(
             buf.push_packet(
@@ -727,7 +1033,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
         assert_eq!(
             buf.push_packet(
                 start,
@@ -751,7 +1057,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
         assert_eq!(
             buf.push_packet(
                 start,
@@ -815,7 +1121,7 @@ mod receive_buffer {
         let init_seq_num = SeqNumber(5);
         let mean_rtt = TimeSpan::from_micros(10_000);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         assert_eq!(buf.prepare_loss_list(start, mean_rtt), None);
 
@@ -884,7 +1190,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         let now = start;
         let _ = buf.push_packet(
@@ -916,6 +1222,7 @@ mod receive_buffer {
                 timestamp: TimeStamp::MIN + tsbpd,
                 seq_number: init_seq_num + 5,
                 message_loc: PacketLocation::ONLY,
+                message_number: MsgNumber(3),
                 payload: b"yas"[..].into(),
                 ..basic_pack()
             },
@@ -950,12 +1257,78 @@ mod receive_buffer {
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 6);
 
         assert_eq!(
-            buf.pop_next_message(now),
-            Ok(Some((expected_release_time, b"yas"[..].into())))
+            buf.pop_next_sequenced_message(now),
+            Ok(Some((
+                MessageInfo {
+                    message_number: MsgNumber(3),
+                    seq_number: init_seq_num + 5,
+                    last_seq_number: init_seq_num + 5,
+                    retransmitted: false,
+                    gap: Some(Discontinuity {
+                        seq_numbers: init_seq_num..init_seq_num + 5,
+                        messages: 2,
+                        bytes: 10,
+                    }),
+                },
+                (expected_release_time, b"yas"[..].into())
+            )))
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 6);
     }
 
+    #[test]
+    fn too_late_packet_drop_disabled() {
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192))
+            .with_too_late_packet_drop(false);
+
+        for n in 1..4 {
+            let _ = buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + n,
+                    payload: Bytes::from(vec![n as u8]),
+                    ..basic_pack()
+                },
+            );
+        }
+
+        // the missing packet is waited for, however late
+        let now = start + 10 * tsbpd;
+        assert_eq!(buf.pop_next_message(now), Ok(None));
+        assert_eq!(buf.next_ack_dsn(), init_seq_num);
+
+        let _ = buf.push_packet(
+            now,
+            DataPacket {
+                seq_number: init_seq_num,
+                payload: Bytes::from(vec![0]),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 4);
+        assert_eq!(buf.pop_next_message(now), Ok(Some((start, vec![0].into()))));
+        assert_eq!(buf.pop_next_message(now), Ok(Some((start, vec![1].into()))));
+
+        // unless the sender dropped it
+        let _ = buf.push_packet(
+            now,
+            DataPacket {
+                seq_number: init_seq_num + 5,
+                payload: Bytes::from(vec![5]),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(buf.drop_packets(init_seq_num + 4..init_seq_num + 5), 1);
+        assert_eq!(buf.pop_next_message(now), Ok(Some((start, vec![2].into()))));
+        assert_eq!(buf.pop_next_message(now), Ok(Some((start, vec![3].into()))));
+        assert_eq!(buf.pop_next_message(now), Ok(Some((start, vec![5].into()))));
+        assert_eq!(buf.pop_next_message(now), Ok(None));
+    }
+
     #[test]
     fn drop_message() {
         let tsbpd = Duration::from_secs(2);
@@ -963,7 +1336,7 @@ mod receive_buffer {
         let init_seq_num = SeqNumber(5);
         let mean_rtt = TimeSpan::from_micros(10_000);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         let now = start;
         assert_eq!(
@@ -990,13 +1363,141 @@ mod receive_buffer {
         assert_eq!(buf.prepare_loss_list(now, mean_rtt), None);
     }
 
+    #[test]
+    fn sequenced_message_info() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, None, init_seq_num, PacketCount(8192));
+
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num,
+                message_number: MsgNumber(1),
+                ..basic_pack()
+            },
+        );
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 1,
+                message_loc: PacketLocation::FIRST,
+                message_number: MsgNumber(2),
+                ..basic_pack()
+            },
+        );
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 2,
+                message_loc: PacketLocation::LAST,
+                message_number: MsgNumber(2),
+                retransmitted: true,
+                ..basic_pack()
+            },
+        );
+
+        assert_eq!(
+            buf.pop_next_sequenced_message(start)
+                .unwrap()
+                .map(|(info, _)| info),
+            Some(MessageInfo {
+                message_number: MsgNumber(1),
+                seq_number: SeqNumber(5),
+                last_seq_number: SeqNumber(5),
+                retransmitted: false,
+                gap: None,
+            })
+        );
+        assert_eq!(
+            buf.pop_next_sequenced_message(start)
+                .unwrap()
+                .map(|(info, _)| info),
+            Some(MessageInfo {
+                message_number: MsgNumber(2),
+                seq_number: SeqNumber(6),
+                last_seq_number: SeqNumber(7),
+                retransmitted: true,
+                gap: None,
+            })
+        );
+
+        // the sender dropped the next message, the one after it follows a gap
+        let _ = buf.drop_packets(init_seq_num + 3..init_seq_num + 5);
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 5,
+                message_number: MsgNumber(4),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(
+            buf.pop_next_sequenced_message(start)
+                .unwrap()
+                .map(|(info, _)| info),
+            Some(MessageInfo {
+                message_number: MsgNumber(4),
+                seq_number: SeqNumber(10),
+                last_seq_number: SeqNumber(10),
+                retransmitted: false,
+                gap: Some(Discontinuity {
+                    seq_numbers: SeqNumber(8)..SeqNumber(10),
+                    messages: 1,
+                    bytes: 0,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn sequenced_message_gap_wrapping() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, None, init_seq_num, PacketCount(8192));
+
+        let last_message_number = MsgNumber(MsgNumber::MAX - 1);
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num,
+                message_number: last_message_number - 1,
+                ..basic_pack()
+            },
+        );
+        assert_matches!(buf.pop_next_sequenced_message(start), Ok(Some(_)));
+
+        // the last message number and message number 1 are dropped, 0 is never used
+        let _ = buf.drop_packets(init_seq_num + 1..init_seq_num + 3);
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 3,
+                message_number: MsgNumber(2),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(
+            buf.pop_next_sequenced_message(start)
+                .unwrap()
+                .and_then(|(info, _)| info.gap),
+            Some(Discontinuity {
+                seq_numbers: init_seq_num + 1..init_seq_num + 3,
+                messages: 2,
+                bytes: 0,
+            })
+        );
+    }
+
     #[test]
     fn buffer_sizing() {
         let tsbpd = Duration::from_secs(2);
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(10));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(10));
 
         assert_eq!(buf.buffer_available(), 10);
 
@@ -1107,7 +1608,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(8192));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(8192));
 
         let now = start;
         assert_eq!(
@@ -1146,7 +1647,7 @@ mod receive_buffer {
         let start = Instant::now();
         let init_seq_num = SeqNumber(5);
 
-        let mut buf = ReceiveBuffer::new(start, tsbpd, init_seq_num, PacketCount(10));
+        let mut buf = ReceiveBuffer::new(start, Some(tsbpd), init_seq_num, PacketCount(10));
 
         let add_packet = |i, buf: &mut ReceiveBuffer| {
             buf.push_packet(
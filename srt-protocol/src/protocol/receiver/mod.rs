@@ -12,12 +12,15 @@ use arq::AutomaticRepeatRequestAlgorithm;
 
 use crate::{
     connection::ConnectionSettings,
+    options::{ArqLevel, TransmissionType},
     packet::*,
     protocol::{
         encryption::{Decryption, DecryptionError},
+        filter::{self, ReceiverPacketFilter},
         output::Output,
         time::Timers,
     },
+    settings::KeyMaterialState,
     statistics::SocketStatistics,
 };
 
@@ -63,18 +66,27 @@ pub enum DataPacketAction {
 pub struct Receiver {
     pub arq: AutomaticRepeatRequestAlgorithm,
     pub decryption: Decryption,
+    pub filter: Option<Box<dyn ReceiverPacketFilter>>,
 }
 
 impl Receiver {
     pub fn new(settings: ConnectionSettings) -> Self {
+        let tsbpd_latency = match settings.transmission_type {
+            TransmissionType::Live if settings.recv_tsbpd_mode => Some(settings.recv_tsbpd_latency),
+            _ => None,
+        };
+        let filter = filter::new_receiver_filter(&settings);
         Self {
             arq: AutomaticRepeatRequestAlgorithm::new(
                 settings.socket_start_time,
-                settings.recv_tsbpd_latency,
+                tsbpd_latency,
                 settings.init_seq_num,
                 settings.recv_buffer_size,
+                settings.message_api,
+                settings.recv_too_late_packet_drop,
             ),
-            decryption: Decryption::new(settings.cipher),
+            decryption: Decryption::new(settings.cipher, settings.recv_key_material_state),
+            filter,
         }
     }
 
@@ -85,6 +97,17 @@ impl Receiver {
     pub fn rx_acknowledged_time(&self) -> Duration {
         self.arq.rx_acknowledged_time()
     }
+
+    pub fn key_material_state(&self) -> KeyMaterialState {
+        self.decryption.key_material_state()
+    }
+
+    /// Without a packet filter, losses are always reported
+    fn arq_level(&self) -> ArqLevel {
+        self.filter
+            .as_ref()
+            .map_or(ArqLevel::Always, |filter| filter.arq_level())
+    }
 }
 
 pub struct ReceiverContext<'a> {
@@ -117,12 +140,56 @@ impl<'a> ReceiverContext<'a> {
     }
 
     pub fn handle_data_packet(&mut self, now: Instant, data: DataPacket) {
-        use Acknowledgement::*;
-        use ControlTypes::*;
         let bytes = data.wire_size() as u64;
         self.stats.rx_data += 1;
         self.stats.rx_bytes += bytes;
 
+        match &mut self.receiver.filter {
+            Some(filter) if filter::is_control_packet(&data) => {
+                self.stats.rx_filter_extra += 1;
+                filter.on_control_received(data);
+            }
+            Some(filter) => {
+                filter.on_data_received(&data);
+                self.receive_data_packet(now, data, bytes, false);
+            }
+            None => self.receive_data_packet(now, data, bytes, false),
+        }
+        self.receive_filter_output(now);
+    }
+
+    fn receive_filter_output(&mut self, now: Instant) {
+        let filter = match &mut self.receiver.filter {
+            Some(filter) => filter,
+            None => return,
+        };
+        let recovered: Vec<_> = std::iter::from_fn(|| filter.next_recovered_packet()).collect();
+        let lost: Vec<_> = std::iter::from_fn(|| filter.next_lost_packet()).collect();
+
+        for data in recovered {
+            self.stats.rx_filter_supply += 1;
+            let bytes = data.wire_size() as u64;
+            self.receive_data_packet(now, data, bytes, true);
+        }
+
+        // with arq:onreq, only the losses the filter could not recover are reported
+        self.stats.rx_filter_loss += lost.len() as u64;
+        if self.receiver.arq_level() == ArqLevel::OnRequest {
+            if let Some(loss_list) = CompressedLossList::try_from_iter(lost.into_iter()) {
+                self.output.send_control(now, ControlTypes::Nak(loss_list));
+            }
+        }
+    }
+
+    fn receive_data_packet(
+        &mut self,
+        now: Instant,
+        data: DataPacket,
+        bytes: u64,
+        filter_recovered: bool,
+    ) {
+        use Acknowledgement::*;
+        use ControlTypes::*;
         let data = self
             .receiver
             .decryption
@@ -137,7 +204,7 @@ impl<'a> ReceiverContext<'a> {
 
         match data {
             Ok(action) => {
-                if action.is_recovered() {
+                if action.is_recovered() && !filter_recovered {
                     self.stats.rx_retransmit_data += 1;
                 } else {
                     self.stats.rx_unique_data += 1;
@@ -146,7 +213,9 @@ impl<'a> ReceiverContext<'a> {
 
                 use DataPacketAction::*;
                 match action {
-                    ReceivedWithLoss(loss_list) => {
+                    ReceivedWithLoss(loss_list)
+                        if self.receiver.arq_level() == ArqLevel::Always =>
+                    {
                         self.output.send_control(now, Nak(loss_list));
                     }
                     ReceivedWithLightAck { light_ack, .. } => {
@@ -227,6 +296,9 @@ impl<'a> ReceiverContext<'a> {
     }
 
     pub fn on_nak_event(&mut self, now: Instant) {
+        if self.receiver.arq_level() != ArqLevel::Always {
+            return;
+        }
         if let Some(loss_list) = self.receiver.arq.on_nak_event(now) {
             self.output.send_control(now, ControlTypes::Nak(loss_list));
         }
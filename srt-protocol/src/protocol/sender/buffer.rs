@@ -1,5 +1,5 @@
 use std::{
-    cmp::{max, Reverse},
+    cmp::{max, min, Reverse},
     collections::{BTreeSet, VecDeque},
     convert::TryFrom,
     ops::Range,
@@ -10,15 +10,19 @@ use keyed_priority_queue::KeyedPriorityQueue;
 
 use crate::{
     connection::ConnectionSettings,
-    options::{ByteCount, PacketCount},
+    options::{ByteCount, PacketCount, TransmissionType},
     packet::*,
     protocol::time::{Rtt, Timers},
 };
 
 #[derive(Debug)]
 pub struct SendBuffer {
-    latency_window: Duration,
+    // None when too-late packet drop is disabled (i.e. file transmission)
+    latency_window: Option<Duration>,
     flow_window_size: usize,
+    congestion_window_size: usize,
+    // drop the oldest packets when the buffer is full, instead of relying on backpressure
+    drop_when_full: bool,
     buffer: VecDeque<SendBufferEntry>,
     max_buffer_size: usize,
     buffer_len_bytes: usize, // Invariant: buffer_len_bytes = sum of wire sizes of buffer
@@ -39,13 +43,27 @@ struct SendBufferEntry {
     // this is transmit count, including the one that may be lost
     // ie, the first time a packet is sent, this is one
     transmit_count: i32,
+    // the message is dropped if it is not acknowledged by then
+    expiration: Option<TimeStamp>,
+    // the message expired, the packet is kept until acknowledged but never sent again
+    dropped: bool,
 }
 
 type DroppedPackets = (PacketCount, ByteCount);
 type PushDataResult = Result<(), DroppedPackets>;
 
+/// How long sent data is kept for retransmission before it is dropped as too late to be
+/// delivered: 125% of TSBPD, but at least one second
+pub fn latency_window(settings: &ConnectionSettings) -> Duration {
+    max(
+        settings.send_tsbpd_latency + settings.send_tsbpd_latency / 4,
+        Duration::from_secs(1),
+    )
+}
+
 impl SendBuffer {
     pub fn new(settings: &ConnectionSettings) -> Self {
+        let live = settings.transmission_type == TransmissionType::Live;
         Self {
             buffer: VecDeque::new(),
             buffer_len_bytes: 0,
@@ -53,18 +71,22 @@ impl SendBuffer {
             next_full_ack: FullAckSeqNumber::INITIAL,
             lost_list: BTreeSet::new(),
             flow_window_size: settings.max_flow_size.0 as usize,
+            congestion_window_size: settings.max_flow_size.0 as usize,
+            drop_when_full: live,
             max_buffer_size: settings.send_buffer_size.0 as usize,
-            latency_window: max(
-                settings.send_tsbpd_latency + settings.send_tsbpd_latency / 4, // 125% of TSBPD
-                Duration::from_secs(1),
-            ),
+            latency_window: (live && settings.send_too_late_packet_drop)
+                .then(|| latency_window(settings)),
             rtt: Rtt::default(),
             rto_queue: Default::default(),
         }
     }
 
-    pub fn push_data(&mut self, packet: DataPacket) -> PushDataResult {
-        let result = if self.buffer.len() < self.max_buffer_size {
+    pub fn push_data(
+        &mut self,
+        packet: DataPacket,
+        expiration: Option<TimeStamp>,
+    ) -> PushDataResult {
+        let result = if !self.drop_when_full || !self.is_full() {
             Ok(())
         } else if let Some(entry) = self.buffer.pop_front() {
             Err((PacketCount(1), ByteCount(entry.packet.wire_size() as u64)))
@@ -76,11 +98,48 @@ impl SendBuffer {
         self.buffer.push_back(SendBufferEntry {
             packet,
             transmit_count: 0,
+            expiration,
+            dropped: false,
         });
 
         result
     }
 
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() >= self.max_buffer_size
+    }
+
+    /// Whether the buffer is full once the `queued` messages are put in it
+    pub fn is_full_with(&self, queued: usize) -> bool {
+        self.buffer.len() + queued >= self.max_buffer_size
+    }
+
+    /// Whether a queued message should be put in the buffer now, which is when fewer than
+    /// `packets` packets are left to be sent for the first time, or when the `queued` messages
+    /// would not fit in a buffer that drops its oldest packets to make room
+    pub fn takes_queued_message(&self, queued: usize, packets: usize) -> bool {
+        let unsent = match self.front_packet() {
+            Some(front) => self
+                .buffer
+                .len()
+                .saturating_sub((self.next_send - front) as usize),
+            None => 0,
+        };
+        unsent < packets || (self.drop_when_full && self.is_full_with(queued))
+    }
+
+    pub fn set_congestion_window_size(&mut self, window: PacketCount) {
+        self.congestion_window_size = window.0 as usize;
+    }
+
+    pub fn next_send(&self) -> SeqNumber {
+        self.next_send
+    }
+
+    pub fn rtt(&self) -> Rtt {
+        self.rtt
+    }
+
     pub fn is_flushed(&self) -> bool {
         self.lost_list.is_empty() && self.buffer.is_empty()
     }
@@ -106,6 +165,23 @@ impl SendBuffer {
         self.buffer_len_bytes
     }
 
+    /// Discards all buffered packets and continues sending from `seq_number`, which must follow
+    /// every buffered packet. Returns the range of sequence numbers the receiver should drop.
+    pub fn skip_to(&mut self, seq_number: SeqNumber) -> Option<Range<SeqNumber>> {
+        let first = self.front_packet().unwrap_or(self.next_send);
+        if seq_number <= first {
+            return None;
+        }
+
+        self.buffer.clear();
+        self.buffer_len_bytes = 0;
+        self.lost_list.clear();
+        self.rto_queue.clear();
+        self.next_send = seq_number;
+
+        Some(first..seq_number)
+    }
+
     pub fn update_largest_acked_seq_number(
         &mut self,
         ack_number: SeqNumber,
@@ -239,7 +315,7 @@ impl SendBuffer {
     }
 
     fn drop_too_late_packets(&mut self, ts_now: TimeStamp) -> Option<Range<SeqNumber>> {
-        let latency_window = self.latency_window;
+        let latency_window = self.latency_window?;
         let front = &self
             .buffer
             .front()
@@ -278,6 +354,48 @@ impl SendBuffer {
         Some(drop_range)
     }
 
+    // Drops the message of the next packet to retransmit or send, if it expired. The packets stay
+    // in the buffer until the receiver acknowledges them, after it dropped them too.
+    fn drop_expired_message(&mut self, ts_now: TimeStamp) -> Option<(MsgNumber, Range<SeqNumber>)> {
+        let expired = |seq_number: &SeqNumber| {
+            self.get(*seq_number)
+                .and_then(|entry| entry.expiration)
+                .filter(|expiration| ts_now > *expiration)
+                .is_some()
+        };
+        let seq_number = self
+            .lost_list
+            .iter()
+            .next()
+            .copied()
+            .filter(expired)
+            .or_else(|| self.rto_queue.peek().map(|(seq, _)| *seq).filter(expired))
+            .or_else(|| Some(self.next_send).filter(expired))?;
+
+        let front = self.front_packet()?;
+        let index = (seq_number - front) as usize;
+        let message_number = self.buffer.get(index)?.packet.message_number;
+        let in_message = |entry: &&SendBufferEntry| entry.packet.message_number == message_number;
+        let start = index
+            - self
+                .buffer
+                .range(..index)
+                .rev()
+                .take_while(in_message)
+                .count();
+        let end = index + self.buffer.range(index..).take_while(in_message).count();
+
+        for entry in self.buffer.range_mut(start..end) {
+            entry.dropped = true;
+            let _ = self.rto_queue.remove(&entry.packet.seq_number);
+        }
+
+        let range = front + start as u32..front + end as u32;
+        self.lost_list.retain(|seq| !range.contains(seq));
+        self.next_send = max(self.next_send, range.end);
+        Some((message_number, range))
+    }
+
     fn flush_on_close(&mut self, should_drain: bool) -> Option<DataPacket> {
         if should_drain && self.buffer.len() == 1 {
             // self.next_send = None; TODO: i'm not sure what functionality this was supposed to expose
@@ -294,7 +412,7 @@ impl SendBuffer {
     }
 
     fn flow_window_exceeded(&self) -> bool {
-        self.number_of_unacked_packets() > self.flow_window_size
+        self.number_of_unacked_packets() > min(self.flow_window_size, self.congestion_window_size)
     }
 
     fn number_of_unacked_packets(&self) -> usize {
@@ -327,7 +445,7 @@ impl SendBuffer {
         self.buffer.get((seq - self.front_packet()?) as usize)
     }
 
-    fn front_packet(&self) -> Option<SeqNumber> {
+    pub fn front_packet(&self) -> Option<SeqNumber> {
         self.buffer.front().map(|p| p.packet.seq_number)
     }
 }
@@ -378,6 +496,7 @@ where
             (_, next_send) if next >= next_send => (Ignored, next),
             (Some(front), _) if next < front => (Dropped, next),
             (None, _) => (Dropped, next),
+            (Some(_), _) if self.buffer.get(next).map_or(false, |e| e.dropped) => (Dropped, next),
             (Some(_), _) => {
                 self.buffer.lost_list.insert(next);
                 (Added, next)
@@ -423,6 +542,8 @@ pub enum SenderAction {
     // Retransmission from NAK
     RetransmitNak(DataPacket),
     Drop(Range<SeqNumber>),
+    // The message expired before it was acknowledged, the receiver should drop it as well
+    DropMessage(MsgNumber, Range<SeqNumber>),
     WaitForInput,
     // sender flow window exceeded"
     WaitForAck {
@@ -486,6 +607,10 @@ impl<'a> SenderAlgorithmIterator<'a> {
     fn drop(&self, range: Range<SeqNumber>) -> Option<SenderAction> {
         Some(SenderAction::Drop(range))
     }
+
+    fn drop_message(&self, message: MsgNumber, range: Range<SeqNumber>) -> Option<SenderAction> {
+        Some(SenderAction::DropMessage(message, range))
+    }
 }
 
 impl<'a> Iterator for SenderAlgorithmIterator<'a> {
@@ -513,6 +638,11 @@ impl<'a> Iterator for SenderAlgorithmIterator<'a> {
         if let Some(range) = self.buffer.drop_too_late_packets(self.ts_now) {
             self.drop(range)
         }
+        //      The same goes for messages that outlived their own TTL, whether they are in the
+        //      loss list or waiting to be sent for the first time.
+        else if let Some((message, range)) = self.buffer.drop_expired_message(self.ts_now) {
+            self.drop_message(message, range)
+        }
         //   1) If the sender's loss list is not empty, retransmit the first
         //      packet in the list and remove it from the list. Go to 5).
         //
@@ -567,7 +697,10 @@ mod test {
     use assert_matches::assert_matches;
     use bytes::Bytes;
 
-    use crate::options::{PacketCount, PacketSize};
+    use crate::{
+        options::{PacketCount, PacketSize},
+        settings::KeyMaterialState,
+    };
 
     const MILLIS: Duration = Duration::from_millis(1);
     const TSBPD: Duration = Duration::from_secs(2);
@@ -585,11 +718,21 @@ mod test {
             send_tsbpd_latency: TSBPD,
             recv_tsbpd_latency: TSBPD,
             cipher: None,
+            send_key_material_state: KeyMaterialState::Unsecured,
+            recv_key_material_state: KeyMaterialState::Unsecured,
             stream_id: None,
             bandwidth: Default::default(),
             recv_buffer_size: PacketCount(8196),
             send_buffer_size: PacketCount(8196),
             statistics_interval: Duration::from_secs(10),
+            transmission_type: Default::default(),
+            message_api: true,
+            recv_tsbpd_mode: true,
+            recv_too_late_packet_drop: true,
+            send_too_late_packet_drop: true,
+            linger: None,
+            peer_group: None,
+            packet_filter: None,
         }
     }
 
@@ -621,7 +764,7 @@ mod test {
         let start = TimeStamp::MIN;
         let mut buffer = SendBuffer::new(&new_settings());
         for n in 0..=16u32 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         for n in 0..=16 {
@@ -638,6 +781,30 @@ mod test {
         assert!(!buffer.is_flushed());
     }
 
+    #[test]
+    fn skip_to() {
+        use SenderAction::*;
+        let start = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&new_settings());
+        for n in 0..4 {
+            let _ = buffer.push_data(test_data_packet(n, false), None);
+        }
+        let _ = buffer.next_snd_actions(start, 2, false).count();
+
+        // skipping discards unacknowledged and unsent packets
+        assert_eq!(
+            buffer.skip_to(SeqNumber(10)),
+            Some(SeqNumber(0)..SeqNumber(10))
+        );
+        assert!(buffer.is_flushed());
+        assert_eq!(buffer.next_send(), SeqNumber(10));
+        assert_eq!(buffer.skip_to(SeqNumber(10)), None);
+
+        let _ = buffer.push_data(test_data_packet(10, false), None);
+        let actions = buffer.next_snd_actions(start, 1, false).collect::<Vec<_>>();
+        assert_eq!(actions, vec![Send(test_data_packet(10, false))]);
+    }
+
     #[test]
     fn nak_retransmit() {
         use SenderAction::*;
@@ -645,7 +812,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=13 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         let actions = buffer
@@ -686,6 +853,67 @@ mod test {
         assert!(!buffer.has_packets_to_send());
     }
 
+    #[test]
+    fn drop_expired_messages() {
+        use SenderAction::*;
+        let start = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&new_settings());
+
+        // message 1 expires, messages 0 and 2 are kept until they are too late
+        for n in 0..=5 {
+            let expiration = (n / 2 == 1).then(|| start + 10 * MILLIS);
+            let _ = buffer.push_data(test_data_packet(n, false), expiration);
+        }
+        let _ = buffer.next_snd_actions(start, 2, false).count();
+
+        // an expired message is not sent
+        let now = start + 20 * MILLIS;
+        assert_eq!(
+            buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>(),
+            vec![
+                DropMessage(MsgNumber(1), SeqNumber(2)..SeqNumber(4)),
+                send_data_packet(4),
+                send_data_packet(5),
+            ]
+        );
+
+        // the receiver is asked to drop it again when it reports it lost
+        assert_eq!(
+            buffer
+                .add_to_loss_list([SeqNumber(0), SeqNumber(2)].iter().collect())
+                .collect::<Vec<_>>(),
+            vec![
+                (Loss::Added, SeqNumber(0)..SeqNumber(1)),
+                (Loss::Dropped, SeqNumber(2)..SeqNumber(3))
+            ]
+        );
+        assert_eq!(
+            buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>(),
+            vec![nak_retransmit_packet(0), WaitForInput]
+        );
+
+        // nor is it retransmitted
+        for n in 6..=7 {
+            let _ = buffer.push_data(test_data_packet(n, false), Some(now + 10 * MILLIS));
+        }
+        let _ = buffer.next_snd_actions(now, 2, false).count();
+        // dropped packets are removed once the receiver acknowledges them
+        let _ = buffer.update_largest_acked_seq_number(SeqNumber(6), None, None);
+        let _ = buffer
+            .add_to_loss_list([SeqNumber(7)].iter().collect())
+            .count();
+        assert_eq!(
+            buffer
+                .next_snd_actions(now + 20 * MILLIS, 2, false)
+                .collect::<Vec<_>>(),
+            vec![
+                DropMessage(MsgNumber(3), SeqNumber(6)..SeqNumber(8)),
+                WaitForInput
+            ]
+        );
+        assert!(!buffer.has_packets_to_send());
+    }
+
     #[test]
     fn rto_retransmit() {
         use SenderAction::*;
@@ -693,7 +921,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         assert_eq!(buffer.next_snd_actions(start, 3, false).count(), 3);
@@ -723,7 +951,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=5 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         let _ = buffer.next_snd_actions(now, 5, false).count();
@@ -782,7 +1010,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         let _ = buffer.next_snd_actions(now, 3, false).count();
@@ -820,7 +1048,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         let _ = buffer.next_snd_actions(now, 3, false).count();
@@ -847,7 +1075,7 @@ mod test {
         let start = TimeStamp::MIN;
         let mut buffer = SendBuffer::new(&new_settings());
         for n in 0..=4 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
         }
 
         // drop queued packets when they are too late
@@ -903,7 +1131,7 @@ mod test {
         let wire_size = test_data_packet(0, false).wire_size();
 
         for n in 0..10 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), None);
             assert_eq!(buffer.duration(), Duration::from_millis(1) * n);
             assert_eq!(buffer.len(), n as usize + 1);
             assert_eq!(buffer.len_bytes(), wire_size * (n as usize + 1));
@@ -941,7 +1169,7 @@ mod test {
 
         let max_flow_size = new_settings().max_flow_size.0 as u32 + 1;
         for n in 0..max_flow_size {
-            assert_eq!(buffer.push_data(test_data_packet(n, false)), Ok(()));
+            assert_eq!(buffer.push_data(test_data_packet(n, false), None), Ok(()));
         }
 
         // if the buffer is full of unsent packets it
@@ -965,14 +1193,58 @@ mod test {
 
         let send_buffer_size = new_settings().send_buffer_size.0 as u32;
         for n in 0..send_buffer_size {
-            assert_eq!(buffer.push_data(test_data_packet(n, false)), Ok(()));
+            assert_eq!(buffer.push_data(test_data_packet(n, false), None), Ok(()));
         }
 
         let expected_dropped_bytes = test_data_packet(0, false).wire_size() as u64;
         let overflow_packet = test_data_packet(send_buffer_size, false);
         assert_eq!(
-            buffer.push_data(overflow_packet),
+            buffer.push_data(overflow_packet, None),
             Err((PacketCount(1), ByteCount(expected_dropped_bytes)))
         );
     }
+
+    #[test]
+    fn too_late_packet_drop_disabled() {
+        use SenderAction::*;
+        let settings = ConnectionSettings {
+            send_too_late_packet_drop: false,
+            ..new_settings()
+        };
+        let mut buffer = SendBuffer::new(&settings);
+        for n in 0..=1 {
+            let _ = buffer.push_data(test_data_packet(n, false), None);
+        }
+
+        // the peer waits for every packet, however late
+        let late = TimeStamp::MIN + 10 * TSBPD;
+        let actions = buffer.next_snd_actions(late, 5, false).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![send_data_packet(0), send_data_packet(1), WaitForInput]
+        );
+    }
+
+    #[test]
+    fn file_transmission() {
+        use SenderAction::*;
+        let settings = ConnectionSettings {
+            send_buffer_size: PacketCount(4),
+            transmission_type: TransmissionType::File,
+            ..new_settings()
+        };
+        let mut buffer = SendBuffer::new(&settings);
+
+        // no packets are dropped when the buffer is full, the caller applies backpressure
+        for n in 0..5 {
+            assert_eq!(buffer.push_data(test_data_packet(n, false), None), Ok(()));
+        }
+        assert!(buffer.is_full());
+
+        // no packets are too late, no matter how long they have been waiting
+        let late = TimeStamp::MIN + 10 * TSBPD;
+        buffer.set_congestion_window_size(PacketCount(2));
+        let actions = buffer.next_snd_actions(late, 5, false).collect::<Vec<_>>();
+        assert_matches!(actions[..], [Send(_), Send(_), Send(_), WaitForAck { .. }]);
+    }
 }
@@ -1,6 +1,13 @@
+use std::cmp::max;
+
 use bytes::Bytes;
 
-use crate::{connection::ConnectionSettings, options::PacketSize, packet::*};
+use crate::{
+    connection::ConnectionSettings,
+    options::PacketSize,
+    packet::*,
+    protocol::{encryption::stream::GCM_TAG_SIZE, filter::FEC_HEADER_SIZE},
+};
 
 #[derive(Debug)]
 pub struct Encapsulation {
@@ -14,10 +21,44 @@ impl Encapsulation {
     pub fn new(settings: &ConnectionSettings) -> Self {
         Self {
             remote_socket_id: settings.remote_sockid,
-            max_packet_size: settings.max_packet_size,
+            max_packet_size: Self::max_payload_size(settings),
             next_sequence_number: settings.init_seq_num,
-            next_message_number: MsgNumber::new_truncate(0),
+            // message number 0 is reserved for packet filter control packets
+            next_message_number: MsgNumber::new_truncate(1),
+        }
+    }
+
+    fn max_payload_size(settings: &ConnectionSettings) -> PacketSize {
+        let mut size = settings.max_packet_size.0;
+        // leave room for the authentication tag appended by AES-GCM
+        if matches!(&settings.cipher, Some(cipher) if cipher.cipher() == CipherType::Gcm) {
+            size -= GCM_TAG_SIZE as u64;
         }
+        // leave room for the header the packet filter puts in front of the payloads it combines
+        if settings.packet_filter.is_some() {
+            size -= FEC_HEADER_SIZE as u64;
+        }
+        PacketSize(size)
+    }
+
+    pub fn next_sequence_number(&self) -> SeqNumber {
+        self.next_sequence_number
+    }
+
+    pub fn next_message_number(&self) -> MsgNumber {
+        self.next_message_number
+    }
+
+    /// The number of packets a message of the given size is split into
+    pub fn packet_count(&self, len: usize) -> usize {
+        let max_payload = self.max_packet_size.0 as usize;
+        max((len + max_payload - 1) / max_payload, 1)
+    }
+
+    /// Continue numbering from the given position, used to keep socket group members aligned
+    pub fn skip_to(&mut self, seq_number: SeqNumber, message_number: MsgNumber) {
+        self.next_sequence_number = seq_number;
+        self.next_message_number = message_number;
     }
 
     /// In the case of a message longer than the packet size,
@@ -25,11 +66,17 @@ impl Encapsulation {
     pub fn encapsulate(
         &mut self,
         timestamp: TimeStamp,
+        in_order_delivery: bool,
         data: Bytes,
     ) -> impl Iterator<Item = DataPacket> + '_ {
+        let message_number = self.next_message_number.increment();
+        if self.next_message_number == MsgNumber(0) {
+            self.next_message_number = MsgNumber(1);
+        }
         MessageEncapsulationIterator {
             timestamp,
-            message_number: self.next_message_number.increment(),
+            in_order_delivery,
+            message_number,
             remaining: data,
             packet_location: PacketLocation::FIRST,
             remote_socket_id: self.remote_socket_id,
@@ -47,6 +94,7 @@ struct MessageEncapsulationIterator<'a> {
     packet_location: PacketLocation,
     message_number: MsgNumber,
     timestamp: TimeStamp,
+    in_order_delivery: bool,
 }
 
 impl<'a> Iterator for MessageEncapsulationIterator<'a> {
@@ -70,7 +118,7 @@ impl<'a> Iterator for MessageEncapsulationIterator<'a> {
 
         Some(DataPacket {
             dest_sockid: self.remote_socket_id,
-            in_order_delivery: false, // TODO: research this
+            in_order_delivery: self.in_order_delivery,
             encryption: DataEncryption::None,
             retransmitted: false,
             message_number: self.message_number,
@@ -101,7 +149,12 @@ mod encapsulation {
 
         let mut encapsulation = new_encapsulation();
 
-        assert_eq!(encapsulation.encapsulate(TimeStamp::MAX, data).count(), 1);
+        assert_eq!(
+            encapsulation
+                .encapsulate(TimeStamp::MAX, false, data)
+                .count(),
+            1
+        );
     }
 
     #[test]
@@ -110,6 +163,11 @@ mod encapsulation {
 
         let mut encapsulation = new_encapsulation();
 
-        assert_eq!(encapsulation.encapsulate(TimeStamp::MAX, data).count(), 10);
+        assert_eq!(
+            encapsulation
+                .encapsulate(TimeStamp::MAX, false, data)
+                .count(),
+            10
+        );
     }
 }
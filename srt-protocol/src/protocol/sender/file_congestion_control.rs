@@ -0,0 +1,336 @@
+use std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    options::{DataRate, LiveBandwidthMode, PacketCount, PacketSize},
+    packet::SeqNumber,
+    protocol::time::Rtt,
+};
+
+// FileCC, a port of the reference implementation's congestion control for file transmission
+// https://github.com/Haivision/srt/blob/580d8992c20ba4ff48d58b29fddf5fd5e7037f9d/srtcore/congctl.cpp#L281
+//
+// It starts in slow start, growing the congestion window with every acknowledged packet until
+// the flow window size is reached or loss is detected. After that, the sending period is
+// decreased by an AIMD scheme driven by the rate and capacity estimates carried in full ACKs,
+// and increased again when NAKs report loss.
+#[derive(Debug)]
+pub struct FileCongestionControl {
+    max_segment_size: f64,
+    max_congestion_window: f64,
+    max_data_rate: Option<DataRate>,
+
+    slow_start: bool,
+    // in packets
+    congestion_window: f64,
+    // in microseconds
+    snd_period: f64,
+    last_ack: SeqNumber,
+    last_rate_control: Option<Instant>,
+    loss: bool,
+
+    last_dec_seq: SeqNumber,
+    last_dec_period: f64,
+    nak_count: u32,
+    dec_random: u32,
+    avg_nak_num: u32,
+    dec_count: u32,
+}
+
+impl FileCongestionControl {
+    // rate control interval, equal to SYN
+    const RC_INTERVAL: f64 = 10_000.0;
+    const INITIAL_CONGESTION_WINDOW: f64 = 16.0;
+    const DECREASE_FACTOR: f64 = 1.03;
+    const MAX_DECREASES_PER_EPOCH: u32 = 5;
+
+    pub fn new(
+        init_seq_num: SeqNumber,
+        max_flow_size: PacketCount,
+        max_packet_size: PacketSize,
+        bandwidth_mode: LiveBandwidthMode,
+    ) -> Self {
+        let max_data_rate = match bandwidth_mode {
+            LiveBandwidthMode::Max(rate) => Some(rate),
+            _ => None,
+        };
+        Self {
+            max_segment_size: max(max_packet_size.0, 1) as f64,
+            max_congestion_window: max_flow_size.0 as f64,
+            max_data_rate,
+            slow_start: true,
+            congestion_window: Self::INITIAL_CONGESTION_WINDOW,
+            snd_period: 1.0,
+            last_ack: init_seq_num,
+            last_rate_control: None,
+            loss: false,
+            last_dec_seq: init_seq_num - 1,
+            last_dec_period: 1.0,
+            nak_count: 0,
+            dec_random: 1,
+            avg_nak_num: 0,
+            dec_count: 0,
+        }
+    }
+
+    pub fn snd_period(&self) -> Duration {
+        Duration::from_micros(self.snd_period.ceil() as u64)
+    }
+
+    pub fn congestion_window(&self) -> PacketCount {
+        PacketCount(self.congestion_window as u64)
+    }
+
+    /// Full ACK received
+    ///
+    /// * `packet_receive_rate` - the delivery rate reported by the receiver, in packets/sec
+    /// * `estimated_link_capacity` - the link capacity reported by the receiver, in packets/sec
+    pub fn on_ack(
+        &mut self,
+        now: Instant,
+        ack_number: SeqNumber,
+        rtt: Rtt,
+        packet_receive_rate: Option<u32>,
+        estimated_link_capacity: Option<u32>,
+    ) {
+        if let Some(last) = self.last_rate_control {
+            if now < last + Duration::from_micros(Self::RC_INTERVAL as u64) {
+                return;
+            }
+        }
+        self.last_rate_control = Some(now);
+
+        let rtt = rtt.mean_as_duration().as_micros() as f64;
+        let delivery_rate = packet_receive_rate.unwrap_or(0) as f64;
+        if self.slow_start {
+            if ack_number > self.last_ack {
+                self.congestion_window += (ack_number - self.last_ack) as f64;
+                self.last_ack = ack_number;
+            }
+            if self.congestion_window > self.max_congestion_window {
+                self.slow_start = false;
+                self.snd_period = self.post_slow_start_period(rtt, delivery_rate);
+            }
+        } else {
+            self.congestion_window = delivery_rate / 1_000_000.0 * (rtt + Self::RC_INTERVAL)
+                + Self::INITIAL_CONGESTION_WINDOW;
+        }
+
+        if !self.slow_start {
+            if self.loss {
+                self.loss = false;
+            } else {
+                self.increase_rate(estimated_link_capacity.unwrap_or(0) as f64);
+            }
+        }
+
+        self.limit_rate();
+    }
+
+    /// NAK received
+    ///
+    /// * `first_lost` - the first sequence number in the loss report
+    /// * `next_send` - the next sequence number to be sent by the sender
+    pub fn on_nak(&mut self, first_lost: SeqNumber, next_send: SeqNumber, rtt: Rtt) {
+        if self.slow_start {
+            self.slow_start = false;
+            let rtt = rtt.mean_as_duration().as_micros() as f64;
+            self.snd_period = self.post_slow_start_period(rtt, 0.0);
+        }
+
+        self.loss = true;
+
+        if first_lost > self.last_dec_seq {
+            self.last_dec_period = self.snd_period;
+            self.snd_period = (self.snd_period * Self::DECREASE_FACTOR).ceil();
+
+            self.avg_nak_num =
+                (self.avg_nak_num as f64 * 0.97 + self.nak_count as f64 * 0.03).ceil() as u32;
+            self.nak_count = 1;
+            self.dec_count = 1;
+
+            self.last_dec_seq = next_send - 1;
+
+            // the reference implementation seeds the random number with the sequence number too
+            let mut rng = StdRng::seed_from_u64(self.last_dec_seq.as_raw().into());
+            self.dec_random = max(
+                1,
+                (self.avg_nak_num as f64 * rng.gen::<f64>()).ceil() as u32,
+            );
+        } else if self.dec_count < Self::MAX_DECREASES_PER_EPOCH {
+            self.dec_count += 1;
+            self.nak_count += 1;
+            if self.nak_count % self.dec_random == 0 {
+                self.snd_period = (self.snd_period * Self::DECREASE_FACTOR).ceil();
+                self.last_dec_seq = next_send - 1;
+            }
+        }
+
+        self.limit_rate();
+    }
+
+    /// Retransmission timeout expired
+    pub fn on_timeout(&mut self, rtt: Rtt) {
+        if self.slow_start {
+            self.slow_start = false;
+            let rtt = rtt.mean_as_duration().as_micros() as f64;
+            self.snd_period = self.post_slow_start_period(rtt, 0.0);
+        }
+    }
+
+    fn post_slow_start_period(&self, rtt: f64, delivery_rate: f64) -> f64 {
+        if delivery_rate > 0.0 {
+            1_000_000.0 / delivery_rate
+        } else {
+            (rtt + Self::RC_INTERVAL) / self.congestion_window
+        }
+    }
+
+    fn increase_rate(&mut self, bandwidth: f64) {
+        let mss = self.max_segment_size;
+        let loss_bandwidth = 2.0 * (1_000_000.0 / self.last_dec_period);
+        let bandwidth = loss_bandwidth.min(bandwidth);
+
+        let mut available = bandwidth - 1_000_000.0 / self.snd_period;
+        if self.snd_period > self.last_dec_period && bandwidth / 9.0 < available {
+            available = bandwidth / 9.0;
+        }
+
+        let increase = if available <= 0.0 {
+            1.0 / mss
+        } else {
+            // the increase is proportional to the order of magnitude of the spare bandwidth
+            let magnitude = 10f64.powf((available * mss * 8.0).log10().ceil());
+            (magnitude * 0.000_001_5 / mss).max(1.0 / mss)
+        };
+
+        self.snd_period = (self.snd_period * Self::RC_INTERVAL)
+            / (self.snd_period * increase + Self::RC_INTERVAL);
+    }
+
+    fn limit_rate(&mut self) {
+        if let Some(DataRate(rate)) = self.max_data_rate.filter(|r| r.0 > 0) {
+            let min_period = 1_000_000.0 / (rate as f64 / self.max_segment_size);
+            if self.snd_period < min_period {
+                self.snd_period = min_period;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::packet::TimeSpan;
+
+    const MILLIS: Duration = Duration::from_millis(1);
+
+    fn rtt() -> Rtt {
+        Rtt::new(TimeSpan::from_millis(10), TimeSpan::from_millis(1))
+    }
+
+    fn new_control(max_flow_size: u64) -> FileCongestionControl {
+        FileCongestionControl::new(
+            SeqNumber(0),
+            PacketCount(max_flow_size),
+            PacketSize(1316),
+            LiveBandwidthMode::Unlimited,
+        )
+    }
+
+    #[test]
+    fn slow_start() {
+        let start = Instant::now();
+        let mut control = new_control(100);
+        assert_eq!(control.congestion_window(), PacketCount(16));
+        assert_eq!(control.snd_period(), Duration::from_micros(1));
+
+        control.on_ack(start, SeqNumber(10), rtt(), None, None);
+        assert_eq!(control.congestion_window(), PacketCount(26));
+        assert_eq!(control.snd_period(), Duration::from_micros(1));
+
+        // rate control only runs once per interval
+        control.on_ack(start + MILLIS, SeqNumber(20), rtt(), None, None);
+        assert_eq!(control.congestion_window(), PacketCount(26));
+
+        // exceeding the flow window ends slow start, switching to the delivery rate
+        control.on_ack(
+            start + 20 * MILLIS,
+            SeqNumber(100),
+            rtt(),
+            Some(10_000),
+            None,
+        );
+        assert!(!control.slow_start);
+        assert_eq!(control.snd_period(), Duration::from_micros(100));
+    }
+
+    #[test]
+    fn nak_decreases_rate() {
+        let start = Instant::now();
+        let mut control = new_control(100);
+        control.on_ack(start, SeqNumber(10), rtt(), Some(10_000), Some(20_000));
+
+        control.on_nak(SeqNumber(5), SeqNumber(20), rtt());
+        assert!(!control.slow_start);
+        let period = control.snd_period();
+        assert!(period > Duration::from_micros(1));
+
+        // further losses in the same congestion epoch decrease the rate a limited number of times
+        for _ in 1..FileCongestionControl::MAX_DECREASES_PER_EPOCH {
+            control.on_nak(SeqNumber(10), SeqNumber(40), rtt());
+            assert!(control.snd_period() > period);
+        }
+        let period = control.snd_period();
+        control.on_nak(SeqNumber(10), SeqNumber(40), rtt());
+        assert_eq!(control.snd_period(), period);
+
+        // the first ACK after loss does not increase the rate
+        let period = control.snd_period;
+        control.on_ack(
+            start + 20 * MILLIS,
+            SeqNumber(40),
+            rtt(),
+            Some(10_000),
+            Some(20_000),
+        );
+        assert_eq!(control.snd_period, period);
+
+        // subsequent ACKs without loss do
+        control.on_ack(
+            start + 40 * MILLIS,
+            SeqNumber(50),
+            rtt(),
+            Some(10_000),
+            Some(20_000),
+        );
+        assert!(control.snd_period < period);
+    }
+
+    #[test]
+    fn timeout_ends_slow_start() {
+        let mut control = new_control(8192);
+        control.on_timeout(rtt());
+        assert!(!control.slow_start);
+        // (RTT + RC interval) / congestion window
+        assert_eq!(control.snd_period(), Duration::from_micros(1250));
+    }
+
+    #[test]
+    fn max_bandwidth() {
+        let start = Instant::now();
+        let mut control = FileCongestionControl::new(
+            SeqNumber(0),
+            PacketCount(10),
+            PacketSize(1000),
+            LiveBandwidthMode::Max(DataRate(1_000_000)),
+        );
+        control.on_ack(start, SeqNumber(10), rtt(), Some(100_000), Some(100_000));
+        assert_eq!(control.snd_period(), Duration::from_micros(1000));
+    }
+}
@@ -1,8 +1,11 @@
 mod buffer;
 mod congestion_control;
 mod encapsulate;
+mod file_congestion_control;
+mod queue;
 
 use std::{
+    cmp::max,
     convert::TryFrom,
     time::{Duration, Instant},
 };
@@ -10,20 +13,26 @@ use std::{
 use bytes::Bytes;
 
 use crate::{
-    connection::{ConnectionSettings, ConnectionStatus},
+    connection::{ConnectionSettings, ConnectionStatus, MessageControl},
     options::*,
     packet::*,
     protocol::{
         encryption::Encryption,
+        filter::{self, SenderPacketFilter},
         output::Output,
-        time::{TimeBase, Timers},
+        time::{Rtt, TimeBase, Timers},
     },
+    settings::KeyMaterialState,
     statistics::SocketStatistics,
 };
 
 use buffer::{AckAction, Loss, SendBuffer, SenderAction};
 use congestion_control::SenderCongestionControl;
-use encapsulate::Encapsulation;
+use file_congestion_control::FileCongestionControl;
+use queue::{MessageQueue, QueuedMessage};
+
+pub(crate) use buffer::latency_window;
+pub(crate) use encapsulate::Encapsulation;
 
 #[derive(Debug)]
 pub struct Sender {
@@ -31,7 +40,15 @@ pub struct Sender {
     encapsulation: Encapsulation,
     encryption: Encryption,
     send_buffer: SendBuffer,
-    congestion_control: SenderCongestionControl,
+    message_queue: MessageQueue,
+    congestion_control: CongestionControl,
+    filter: Option<Box<dyn SenderPacketFilter>>,
+}
+
+#[derive(Debug)]
+enum CongestionControl {
+    Live(SenderCongestionControl),
+    File(FileCongestionControl),
 }
 
 impl Sender {
@@ -39,18 +56,53 @@ impl Sender {
         Self {
             time_base: TimeBase::new(settings.socket_start_time),
             encapsulation: Encapsulation::new(&settings),
-            encryption: Encryption::new(settings.cipher.clone()),
+            encryption: Encryption::new(settings.cipher.clone(), settings.send_key_material_state),
             send_buffer: SendBuffer::new(&settings),
-            congestion_control: SenderCongestionControl::new(settings.bandwidth.clone()),
+            message_queue: MessageQueue::default(),
+            congestion_control: match settings.transmission_type {
+                TransmissionType::Live => CongestionControl::Live(SenderCongestionControl::new(
+                    settings.bandwidth.clone(),
+                )),
+                TransmissionType::File => CongestionControl::File(FileCongestionControl::new(
+                    settings.init_seq_num,
+                    settings.max_flow_size,
+                    settings.max_packet_size,
+                    settings.bandwidth.clone(),
+                )),
+            },
+            filter: filter::new_sender_filter(&settings),
         }
     }
 
+    pub fn is_send_buffer_full(&self) -> bool {
+        self.send_buffer.is_full_with(self.message_queue.len())
+    }
+
     pub fn is_flushed(&self) -> bool {
-        self.send_buffer.is_flushed()
+        self.message_queue.is_empty() && self.send_buffer.is_flushed()
     }
 
     pub fn has_packets_to_send(&self) -> bool {
-        self.send_buffer.has_packets_to_send()
+        !self.message_queue.is_empty() || self.send_buffer.has_packets_to_send()
+    }
+
+    /// The sequence and message number the next message will be sent with
+    pub fn next_message_position(&self) -> (SeqNumber, MsgNumber) {
+        (
+            self.encapsulation.next_sequence_number(),
+            self.encapsulation.next_message_number(),
+        )
+    }
+
+    pub fn rtt(&self) -> Rtt {
+        self.send_buffer.rtt()
+    }
+
+    /// The oldest sequence number that has not been acknowledged (or dropped) yet
+    pub fn first_unacknowledged(&self) -> SeqNumber {
+        self.send_buffer
+            .front_packet()
+            .unwrap_or_else(|| self.encapsulation.next_sequence_number())
     }
 
     pub fn tx_buffered_time(&self) -> Duration {
@@ -64,6 +116,10 @@ impl Sender {
     pub fn tx_buffered_bytes(&self) -> u64 {
         u64::try_from(self.send_buffer.len_bytes()).unwrap()
     }
+
+    pub fn key_material_state(&self) -> KeyMaterialState {
+        self.encryption.key_material_state()
+    }
 }
 
 pub struct SenderContext<'a> {
@@ -91,19 +147,93 @@ impl<'a> SenderContext<'a> {
         }
     }
 
-    pub fn handle_data(&mut self, now: Instant, item: (Instant, Bytes)) {
+    /// Sends the message right away, after the queued messages, so the position of the message
+    /// is known once this returns
+    pub fn handle_data(&mut self, now: Instant, control: MessageControl, item: (Instant, Bytes)) {
+        let message = self.on_input(now, control, item);
+        self.release_messages(now, usize::MAX);
+        self.send_message(now, message);
+    }
+
+    /// Queues the message, to be sent once the packets already in the send buffer have been
+    /// sent, along with the other queued messages by priority
+    pub fn queue_message(&mut self, now: Instant, control: MessageControl, item: (Instant, Bytes)) {
+        let message = self.on_input(now, control, item);
+        self.sender.message_queue.push(message);
+        self.release_messages(now, 1);
+    }
+
+    fn on_input(
+        &mut self,
+        now: Instant,
+        control: MessageControl,
+        item: (Instant, Bytes),
+    ) -> QueuedMessage {
         let (time, data) = item;
-        let (mut packets, mut bytes) = (0, 0);
-        let ts = self.sender.time_base.timestamp_from(time);
-        for packet in self.sender.encapsulation.encapsulate(ts, data) {
+        let time_base = &self.sender.time_base;
+        let message = QueuedMessage {
+            control,
+            timestamp: time_base.timestamp_from(time),
+            expiration: control.ttl.map(|ttl| time_base.timestamp_from(now) + ttl),
+            data,
+        };
+
+        if let CongestionControl::Live(congestion_control) = &mut self.sender.congestion_control {
+            let packets = self.sender.encapsulation.packet_count(message.data.len());
+            let bytes = message.data.len();
+            let snd_period = congestion_control.on_input(
+                now,
+                PacketCount(packets as u64),
+                ByteCount(bytes as u64),
+            );
+            if let Some(snd_period) = snd_period {
+                self.timers.update_snd_period(snd_period)
+            }
+        }
+
+        message
+    }
+
+    // puts queued messages in the send buffer, until it has the given number of packets to send
+    fn release_messages(&mut self, now: Instant, packets: usize) {
+        let ts_now = self.sender.time_base.timestamp_from(now);
+        while self
+            .sender
+            .send_buffer
+            .takes_queued_message(self.sender.message_queue.len(), packets)
+        {
+            let message = match self.sender.message_queue.pop() {
+                Some(message) => message,
+                None => break,
+            };
+            if matches!(message.expiration, Some(expiration) if ts_now > expiration) {
+                // it was never sent, so the receiver doesn't know about it
+                let packets = self.sender.encapsulation.packet_count(message.data.len());
+                self.stats.tx_dropped_data += packets as u64;
+                self.stats.tx_dropped_bytes += message.data.len() as u64;
+                continue;
+            }
+            self.send_message(now, message);
+        }
+    }
+
+    fn send_message(&mut self, now: Instant, message: QueuedMessage) {
+        let QueuedMessage {
+            control,
+            timestamp,
+            expiration,
+            data,
+        } = message;
+        let encapsulation = &mut self.sender.encapsulation;
+        for packet in encapsulation.encapsulate(timestamp, control.in_order, data) {
             if let Some((bytes_enc, packet, km)) = self.sender.encryption.encrypt(packet) {
-                packets += 1;
-                bytes += packet.payload.len() as u64;
                 if bytes_enc > 0 {
                     self.stats.tx_encrypted_data += 1;
                 }
 
-                if let Err((p_count, b_count)) = self.sender.send_buffer.push_data(packet) {
+                if let Err((p_count, b_count)) =
+                    self.sender.send_buffer.push_data(packet, expiration)
+                {
                     self.stats.tx_dropped_data += p_count.0;
                     self.stats.tx_dropped_bytes += b_count.0;
                 }
@@ -114,14 +244,19 @@ impl<'a> SenderContext<'a> {
                 }
             }
         }
+    }
 
-        let snd_period =
-            self.sender
-                .congestion_control
-                .on_input(now, PacketCount(packets), ByteCount(bytes));
-        if let Some(snd_period) = snd_period {
-            self.timers.update_snd_period(snd_period)
+    /// Continue sending from the given position, asking the receiver to drop everything before it
+    pub fn skip_to(&mut self, now: Instant, seq_number: SeqNumber, message_number: MsgNumber) {
+        if let Some(range) = self.sender.send_buffer.skip_to(seq_number) {
+            self.output.send_control(
+                now,
+                ControlTypes::new_drop_request(MsgNumber::new_truncate(0), range),
+            );
         }
+        self.sender
+            .encapsulation
+            .skip_to(seq_number, message_number);
     }
 
     pub fn handle_ack_packet(&mut self, now: Instant, ack: Acknowledgement) {
@@ -144,6 +279,20 @@ impl<'a> SenderContext<'a> {
                 if let Some(full_ack) = send_ack2 {
                     self.output.send_control(now, ControlTypes::Ack2(full_ack))
                 }
+                if let Acknowledgement::Full(ack_number, statistics, _) = &ack {
+                    if let CongestionControl::File(congestion_control) =
+                        &mut self.sender.congestion_control
+                    {
+                        congestion_control.on_ack(
+                            now,
+                            *ack_number,
+                            statistics.rtt,
+                            statistics.packet_receive_rate,
+                            statistics.estimated_link_capacity,
+                        );
+                        self.update_file_congestion_control();
+                    }
+                }
             }
             Err(_error) => {
                 // self.warn("ack", now, &error);
@@ -155,6 +304,16 @@ impl<'a> SenderContext<'a> {
 
     pub fn handle_nak_packet(&mut self, now: Instant, nak: CompressedLossList) {
         self.stats.rx_nak += 1;
+        if let CongestionControl::File(congestion_control) = &mut self.sender.congestion_control {
+            if let Some(first_lost) = nak.iter_decompressed().next() {
+                congestion_control.on_nak(
+                    first_lost,
+                    self.sender.send_buffer.next_send(),
+                    self.sender.send_buffer.rtt(),
+                );
+                self.update_file_congestion_control();
+            }
+        }
         // 1) Add all sequence numbers carried in the NAK into the sender's loss list.
         for (loss, range) in self.sender.send_buffer.add_to_loss_list(nak) {
             //self.debug("nak", now, &(&loss, &range));
@@ -204,17 +363,29 @@ impl<'a> SenderContext<'a> {
 
     pub fn on_snd_event(&mut self, now: Instant, elapsed_periods: u32) {
         use SenderAction::*;
+        self.release_messages(now, max(elapsed_periods as usize, 1));
         let ts_now = self.sender.time_base.timestamp_from(now);
         let actions = self.sender.send_buffer.next_snd_actions(
             ts_now,
             elapsed_periods,
             self.status.should_drain_send_buffer(),
         );
+        let mut retransmit_timeout = false;
         for action in actions {
             match action {
                 Send(d) => {
                     self.stats.tx_unique_data += 1;
-                    self.output.send_data(now, d);
+                    match &mut self.sender.filter {
+                        Some(filter) => {
+                            filter.on_data_sent(&d);
+                            self.output.send_data(now, d);
+                            while let Some(packet) = filter.next_control_packet() {
+                                self.stats.tx_filter_extra += 1;
+                                self.output.send_data(now, packet);
+                            }
+                        }
+                        None => self.output.send_data(now, d),
+                    }
                 }
                 RetransmitNak(d) => {
                     self.stats.tx_retransmit_data += 1;
@@ -223,8 +394,14 @@ impl<'a> SenderContext<'a> {
                 RetransmitRto(d) => {
                     self.stats.tx_retransmit_data += 1;
                     self.output.send_data(now, d);
+                    retransmit_timeout = true;
                 }
                 Drop(_) => {}
+                DropMessage(message_number, range) => {
+                    self.stats.tx_dropped_data += u64::from(range.end - range.start);
+                    self.output
+                        .send_control(now, ControlTypes::new_drop_request(message_number, range));
+                }
                 WaitForInput => {
                     break;
                 }
@@ -233,5 +410,23 @@ impl<'a> SenderContext<'a> {
                 }
             }
         }
+
+        if retransmit_timeout {
+            if let CongestionControl::File(congestion_control) = &mut self.sender.congestion_control
+            {
+                congestion_control.on_timeout(self.sender.send_buffer.rtt());
+                self.update_file_congestion_control();
+            }
+        }
+    }
+
+    fn update_file_congestion_control(&mut self) {
+        if let CongestionControl::File(congestion_control) = &self.sender.congestion_control {
+            self.timers
+                .update_snd_period(congestion_control.snd_period());
+            self.sender
+                .send_buffer
+                .set_congestion_window_size(congestion_control.congestion_window());
+        }
     }
 }
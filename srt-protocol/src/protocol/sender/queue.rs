@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::{connection::MessageControl, packet::TimeStamp};
+
+#[derive(Debug)]
+pub struct QueuedMessage {
+    pub control: MessageControl,
+    pub timestamp: TimeStamp,
+    // the message is dropped if it is still queued by then
+    pub expiration: Option<TimeStamp>,
+    pub data: Bytes,
+}
+
+/// Messages that are not in the send buffer yet, because the buffer still has packets that were
+/// never sent. They are kept in the order they go out in: highest priority first, and oldest
+/// first among messages of the same priority.
+#[derive(Debug, Default)]
+pub struct MessageQueue {
+    messages: VecDeque<QueuedMessage>,
+}
+
+impl MessageQueue {
+    pub fn push(&mut self, message: QueuedMessage) {
+        let priority = message.control.priority;
+        let index = self
+            .messages
+            .partition_point(|m| m.control.priority >= priority);
+        self.messages.insert(index, message);
+    }
+
+    pub fn pop(&mut self) -> Option<QueuedMessage> {
+        self.messages.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(priority: u8, data: &'static str) -> QueuedMessage {
+        QueuedMessage {
+            control: MessageControl::with_priority(priority),
+            timestamp: TimeStamp::from_micros(0),
+            expiration: None,
+            data: Bytes::from_static(data.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn highest_priority_first_then_oldest() {
+        let mut queue = MessageQueue::default();
+        queue.push(message(0, "a"));
+        queue.push(message(1, "b"));
+        queue.push(message(0, "c"));
+        queue.push(message(2, "d"));
+        queue.push(message(1, "e"));
+
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|m| m.data).collect();
+        assert_eq!(order, ["d", "b", "e", "a", "c"]);
+        assert!(queue.is_empty());
+    }
+}
@@ -1,14 +1,33 @@
-use std::{convert::TryInto, marker::PhantomData, net::SocketAddr};
+use std::{convert::TryInto, error::Error, fmt, marker::PhantomData, time::Duration};
 
-use crate::{packet::RejectReason, settings::KeySettings};
+use crate::{
+    options::{LiveBandwidthMode, PacketSize},
+    packet::{CoreRejectReason, RejectReason, ServerRejectReason},
+    protocol::pending_connection::AccessControlRequest,
+    settings::{ConnInitSettings, KeyMaterialRefreshSettings, KeySettings},
+};
 
+/// The settings chosen for an incoming connection, overriding the settings of the listener
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct AcceptParameters {
     key_settings: Option<KeySettings>,
+    key_refresh: Option<KeyMaterialRefreshSettings>,
+    latency: Option<Duration>,
+    recv_latency: Option<Duration>,
+    bandwidth: Option<LiveBandwidthMode>,
+    max_payload_size: Option<PacketSize>,
 }
 
 impl AcceptParameters {
     pub fn new() -> AcceptParameters {
-        AcceptParameters { key_settings: None }
+        AcceptParameters {
+            key_settings: None,
+            key_refresh: None,
+            latency: None,
+            recv_latency: None,
+            bandwidth: None,
+            max_payload_size: None,
+        }
     }
 
     pub fn set_key_settings(&mut self, passphrase: impl Into<String>, size: u8) -> &mut Self {
@@ -22,6 +41,82 @@ impl AcceptParameters {
     pub fn take_key_settings(&mut self) -> Option<KeySettings> {
         self.key_settings.take()
     }
+
+    /// Set the key material refresh and pre-announcement periods, in packets
+    pub fn set_key_refresh(&mut self, key_refresh: KeyMaterialRefreshSettings) -> &mut Self {
+        self.key_refresh = Some(key_refresh);
+        self
+    }
+
+    /// Set both the send and receive latency, the larger of this and the latency of the caller
+    /// is used
+    pub fn set_latency(&mut self, latency: Duration) -> &mut Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Set only the receive latency, the larger of this and the send latency of the caller is used
+    pub fn set_recv_latency(&mut self, latency: Duration) -> &mut Self {
+        self.recv_latency = Some(latency);
+        self
+    }
+
+    pub fn set_bandwidth(&mut self, bandwidth: LiveBandwidthMode) -> &mut Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Set the maximum payload size, the smaller of this and the size of the caller is used
+    pub fn set_max_payload_size(&mut self, max_payload_size: PacketSize) -> &mut Self {
+        self.max_payload_size = Some(max_payload_size);
+        self
+    }
+
+    pub fn key_settings(&self) -> Option<&KeySettings> {
+        self.key_settings.as_ref()
+    }
+
+    pub fn key_refresh(&self) -> Option<&KeyMaterialRefreshSettings> {
+        self.key_refresh.as_ref()
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    pub fn recv_latency(&self) -> Option<Duration> {
+        self.recv_latency
+    }
+
+    pub fn bandwidth(&self) -> Option<&LiveBandwidthMode> {
+        self.bandwidth.as_ref()
+    }
+
+    pub fn max_payload_size(&self) -> Option<PacketSize> {
+        self.max_payload_size
+    }
+
+    pub(crate) fn apply_to(self, settings: &mut ConnInitSettings) {
+        if let Some(key_settings) = self.key_settings {
+            settings.key_settings = Some(key_settings);
+        }
+        if let Some(key_refresh) = self.key_refresh {
+            settings.key_refresh = key_refresh;
+        }
+        if let Some(latency) = self.latency {
+            settings.send_latency = latency;
+            settings.recv_latency = latency;
+        }
+        if let Some(recv_latency) = self.recv_latency {
+            settings.recv_latency = recv_latency;
+        }
+        if let Some(bandwidth) = self.bandwidth {
+            settings.bandwidth = bandwidth;
+        }
+        if let Some(max_payload_size) = self.max_payload_size {
+            settings.max_packet_size = max_payload_size;
+        }
+    }
 }
 
 impl Default for AcceptParameters {
@@ -30,12 +125,91 @@ impl Default for AcceptParameters {
     }
 }
 
+impl From<KeySettings> for AcceptParameters {
+    fn from(key_settings: KeySettings) -> Self {
+        AcceptParameters {
+            key_settings: Some(key_settings),
+            ..AcceptParameters::new()
+        }
+    }
+}
+
+/// The reason an incoming connection was rejected. Only the reject code reaches the caller, the
+/// message is for the logs of the listener.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Rejection {
+    reason: RejectReason,
+    message: Option<String>,
+}
+
+impl Rejection {
+    /// The first user defined reject code, the codes below are reserved for the core and server
+    pub const USER_CODE_START: i32 = 3000;
+
+    pub fn new(reason: impl Into<RejectReason>) -> Rejection {
+        Rejection {
+            reason: reason.into(),
+            message: None,
+        }
+    }
+
+    /// Reject with a user defined code, which the caller receives as [`RejectReason::User`]
+    ///
+    /// Returns `None` if the code is below [`Rejection::USER_CODE_START`]
+    pub fn user(code: i32, message: impl Into<String>) -> Option<Rejection> {
+        if code < Self::USER_CODE_START {
+            return None;
+        }
+        Some(Rejection::new(RejectReason::User(code)).with_message(message))
+    }
+
+    pub fn with_message(mut self, message: impl Into<String>) -> Rejection {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn reason(&self) -> RejectReason {
+        self.reason
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl From<RejectReason> for Rejection {
+    fn from(reason: RejectReason) -> Self {
+        Rejection::new(reason)
+    }
+}
+
+impl From<CoreRejectReason> for Rejection {
+    fn from(reason: CoreRejectReason) -> Self {
+        Rejection::new(reason)
+    }
+}
+
+impl From<ServerRejectReason> for Rejection {
+    fn from(reason: ServerRejectReason) -> Self {
+        Rejection::new(reason)
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}: {}", self.reason, message),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+impl Error for Rejection {}
+
 pub trait StreamAcceptor {
-    fn accept(
-        &mut self,
-        streamid: Option<&str>,
-        ip: SocketAddr,
-    ) -> Result<AcceptParameters, RejectReason>;
+    /// Decide whether to accept an incoming connection, and with which settings, based on the
+    /// stream id, the address and the handshake of the caller
+    fn accept(&mut self, request: &AccessControlRequest) -> Result<AcceptParameters, Rejection>;
 }
 
 #[derive(Default, Clone, Copy)]
@@ -44,11 +218,7 @@ pub struct AllowAllStreamAcceptor {
 }
 
 impl StreamAcceptor for AllowAllStreamAcceptor {
-    fn accept(
-        &mut self,
-        _streamid: Option<&str>,
-        _ip: SocketAddr,
-    ) -> Result<AcceptParameters, RejectReason> {
+    fn accept(&mut self, _request: &AccessControlRequest) -> Result<AcceptParameters, Rejection> {
         Ok(AcceptParameters::default())
     }
 }
@@ -2,7 +2,10 @@ use std::time::Duration;
 
 use rand::random;
 
-use crate::{options, packet::Packet};
+use crate::{
+    options,
+    packet::{FilterSpec, Packet},
+};
 
 use super::*;
 
@@ -11,6 +14,8 @@ pub struct ConnInitSettings {
     pub local_sockid: SocketId,
     pub key_settings: Option<KeySettings>,
     pub key_refresh: KeyMaterialRefreshSettings,
+    pub crypto_mode: options::CryptoMode,
+    pub enforced_encryption: bool,
     pub send_latency: Duration,
     pub recv_latency: Duration,
     pub bandwidth: options::LiveBandwidthMode,
@@ -22,6 +27,20 @@ pub struct ConnInitSettings {
     pub send_buffer_size: options::PacketCount,
     pub max_packet_size: options::PacketSize,
     pub max_flow_size: options::PacketCount,
+    pub transmission_type: options::TransmissionType,
+    pub tsbpd_mode: bool,
+    pub message_api: bool,
+    /// Whether packets that missed their time to play are dropped, rather than waited for
+    pub too_late_packet_drop: bool,
+    pub linger: Option<Duration>,
+    /// The oldest SRT version accepted from the peer, versions before 1.3.0 use the HSv4 handshake
+    pub min_version: options::SrtVersion,
+    /// How often a caller repeats its handshake requests
+    pub retry: options::RetryPolicy,
+    /// The group this connection is a member of, if any
+    pub group: Option<GroupSettings>,
+    /// The local packet filter configuration, which may leave parameters to the peer
+    pub packet_filter: Option<FilterSpec>,
 }
 
 impl Default for ConnInitSettings {
@@ -56,6 +75,8 @@ impl From<options::SocketOptions> for ConnInitSettings {
                 options.encryption.km_refresh.pre_announcement_period.into(),
             )
             .unwrap(),
+            crypto_mode: options.encryption.crypto_mode,
+            enforced_encryption: options.encryption.enforced_encryption,
             send_latency: options.sender.peer_latency,
             recv_latency: options.receiver.latency,
             bandwidth: options.sender.bandwidth,
@@ -66,6 +87,15 @@ impl From<options::SocketOptions> for ConnInitSettings {
                 / (options.session.max_segment_size - Packet::HEADER_SIZE),
             max_packet_size: options.sender.max_payload_size,
             max_flow_size: options.sender.flow_control_window_size,
+            transmission_type: options.session.transmission_type,
+            tsbpd_mode: options.session.tsbpd_mode,
+            message_api: options.session.message_api,
+            too_late_packet_drop: options.receiver.too_late_packet_drop,
+            linger: options.connect.linger,
+            min_version: options.connect.min_version,
+            retry: options.connect.retry,
+            group: None,
+            packet_filter: options.session.packet_filter,
         }
     }
 }
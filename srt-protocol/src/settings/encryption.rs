@@ -2,7 +2,7 @@ use std::fmt::{self, Debug, Display, Formatter};
 
 pub use crate::{
     options::{KeySize, Passphrase},
-    packet::{DataEncryption, KeyingMaterialMessage},
+    packet::{CipherType, DataEncryption, KeyingMaterialMessage},
     protocol::encryption::{
         key::WrapInitializationVector,
         key::{EncryptionKey, Salt},
@@ -95,6 +95,28 @@ impl KeyMaterialRefreshSettings {
     }
 }
 
+// SRT_KM_STATE
+// https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#srt_km_state
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KeyMaterialState {
+    /// Neither side has set a passphrase, the stream is not encrypted
+    Unsecured,
+    /// The key material exchange is in progress
+    Securing,
+    /// Both sides have set the same passphrase, the stream is encrypted
+    Secured,
+    /// Only one side has set a passphrase, encrypted packets can not be decrypted
+    NoSecret,
+    /// The passphrases do not match, encrypted packets can not be decrypted
+    BadSecret,
+}
+
+impl Default for KeyMaterialState {
+    fn default() -> Self {
+        KeyMaterialState::Unsecured
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CipherSettings {
     pub key_settings: KeySettings,
@@ -103,11 +125,15 @@ pub struct CipherSettings {
 }
 
 impl CipherSettings {
-    pub fn new_random(key_settings: &KeySettings, km_refresh: &KeyMaterialRefreshSettings) -> Self {
+    pub fn new_random(
+        key_settings: &KeySettings,
+        km_refresh: &KeyMaterialRefreshSettings,
+        cipher: CipherType,
+    ) -> Self {
         Self {
             key_settings: key_settings.clone(),
             key_refresh: km_refresh.clone(),
-            stream_keys: StreamEncryptionKeys::new_random(key_settings.key_size),
+            stream_keys: StreamEncryptionKeys::new_random(key_settings.key_size, cipher),
         }
     }
 
@@ -123,6 +149,10 @@ impl CipherSettings {
         })
     }
 
+    pub fn cipher(&self) -> CipherType {
+        self.stream_keys.cipher()
+    }
+
     pub fn wrap_keying_material(&self) -> Option<KeyingMaterialMessage> {
         self.stream_keys.wrap_with(&self.key_settings)
     }
@@ -0,0 +1,62 @@
+use rand::random;
+
+pub use crate::packet::GroupType;
+
+use crate::packet::{GroupFlags, SocketId, SrtControlPacket};
+
+/// Membership of a connection in a socket group, exchanged in the handshake
+///
+/// The peers of every member connection in a group share the same group id.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GroupSettings {
+    pub group_id: SocketId,
+    pub group_type: GroupType,
+    /// The priority of the member link, higher weights are preferred
+    pub weight: u16,
+}
+
+impl GroupSettings {
+    // group ids are distinguished from socket ids by this bit, the same as the reference implementation
+    const GROUP_ID_MASK: u32 = 1 << 30;
+
+    pub fn new(group_type: GroupType, weight: u16) -> Self {
+        Self {
+            group_id: Self::random_group_id(),
+            group_type,
+            weight,
+        }
+    }
+
+    pub fn random_group_id() -> SocketId {
+        SocketId((random::<u32>() & (Self::GROUP_ID_MASK - 1)) | Self::GROUP_ID_MASK)
+    }
+
+    pub fn with_weight(self, weight: u16) -> Self {
+        Self { weight, ..self }
+    }
+
+    pub fn from_extension(extension: &SrtControlPacket) -> Option<Self> {
+        match extension {
+            SrtControlPacket::Group {
+                group_id,
+                ty,
+                weight,
+                ..
+            } => Some(Self {
+                group_id: *group_id,
+                group_type: *ty,
+                weight: *weight,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn to_extension(self) -> SrtControlPacket {
+        SrtControlPacket::Group {
+            group_id: self.group_id,
+            ty: self.group_type,
+            flags: GroupFlags::empty(),
+            weight: self.weight,
+        }
+    }
+}
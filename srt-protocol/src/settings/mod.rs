@@ -1,9 +1,11 @@
 mod accesscontrol;
 mod connection;
 mod encryption;
+mod group;
 
 pub use accesscontrol::*;
 pub use connection::*;
 pub use encryption::*;
+pub use group::*;
 
 pub use crate::packet::SocketId;
@@ -0,0 +1,137 @@
+use std::cmp::max;
+
+use crate::{group::MemberState, packet::SocketId};
+
+use super::SocketStatistics;
+
+/// Statistics of a socket group, for the group as a whole and for each of its members.
+#[derive(Debug, Eq, PartialEq, Default, Clone)]
+#[non_exhaustive]
+pub struct GroupStatistics {
+    /// The aggregate of the member statistics. Packet and byte counters are summed over the
+    /// members, and round trip times are averaged, while the unique data counters
+    /// ([tx_unique_data](SocketStatistics::tx_unique_data),
+    /// [rx_unique_data](SocketStatistics::rx_unique_data) and the matching byte counters) count
+    /// the data sent to, and released by, the group itself, so duplicates are only counted once.
+    pub aggregate: SocketStatistics,
+
+    /// The statistics of each member, in the order the members joined the group.
+    pub members: Vec<MemberStatistics>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[non_exhaustive]
+pub struct MemberStatistics {
+    /// The local socket id of the member connection
+    pub id: SocketId,
+    pub weight: u16,
+    pub state: MemberState,
+    pub statistics: SocketStatistics,
+}
+
+impl GroupStatistics {
+    /// `group` holds the group's own unique data counters
+    pub(crate) fn new(group: &SocketStatistics, members: Vec<MemberStatistics>) -> Self {
+        let mut aggregate = SocketStatistics {
+            tx_unique_data: group.tx_unique_data,
+            rx_unique_data: group.rx_unique_data,
+            tx_unique_bytes: group.tx_unique_bytes,
+            rx_unique_bytes: group.rx_unique_bytes,
+            ..SocketStatistics::default()
+        };
+        for member in &members {
+            aggregate.add_member(&member.statistics);
+        }
+        if !members.is_empty() {
+            let count = members.len() as u32;
+            aggregate.tx_average_rtt /= count;
+            aggregate.rx_average_rtt /= count;
+        }
+        Self { aggregate, members }
+    }
+}
+
+impl SocketStatistics {
+    fn add_member(&mut self, member: &SocketStatistics) {
+        self.elapsed_time = max(self.elapsed_time, member.elapsed_time);
+
+        self.tx_all_packets += member.tx_all_packets;
+        self.rx_all_packets += member.rx_all_packets;
+        self.tx_all_bytes += member.tx_all_bytes;
+        self.rx_all_bytes += member.rx_all_bytes;
+        self.tx_encrypted_data += member.tx_encrypted_data;
+        self.rx_decrypted_data += member.rx_decrypted_data;
+        self.rx_ack2_errors += member.rx_ack2_errors;
+
+        self.tx_data += member.tx_data;
+        self.rx_data += member.rx_data;
+        self.tx_loss_data += member.tx_loss_data;
+        self.rx_loss_data += member.rx_loss_data;
+        self.tx_retransmit_data += member.tx_retransmit_data;
+        self.rx_retransmit_data += member.rx_retransmit_data;
+        self.tx_ack += member.tx_ack;
+        self.rx_ack += member.rx_ack;
+        self.tx_light_ack += member.tx_light_ack;
+        self.rx_light_ack += member.rx_light_ack;
+        self.tx_nak += member.tx_nak;
+        self.rx_nak += member.rx_nak;
+        self.tx_ack2 += member.tx_ack2;
+        self.rx_ack2 += member.rx_ack2;
+        self.tx_dropped_data += member.tx_dropped_data;
+        self.rx_dropped_data += member.rx_dropped_data;
+        self.rx_decrypt_errors += member.rx_decrypt_errors;
+        self.tx_filter_extra += member.tx_filter_extra;
+        self.rx_filter_extra += member.rx_filter_extra;
+        self.rx_filter_supply += member.rx_filter_supply;
+        self.rx_filter_loss += member.rx_filter_loss;
+
+        self.tx_bytes += member.tx_bytes;
+        self.rx_bytes += member.rx_bytes;
+        self.rx_loss_bytes += member.rx_loss_bytes;
+        self.tx_retransmit_bytes += member.tx_retransmit_bytes;
+        self.tx_dropped_bytes += member.tx_dropped_bytes;
+        self.rx_dropped_bytes += member.rx_dropped_bytes;
+        self.rx_decrypt_error_bytes += member.rx_decrypt_error_bytes;
+
+        self.tx_average_rtt += member.tx_average_rtt;
+        self.rx_average_rtt += member.rx_average_rtt;
+        self.tx_bandwidth += member.tx_bandwidth;
+        self.rx_bandwidth += member.rx_bandwidth;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn aggregate() {
+        let member = |id, rx_data, rtt| MemberStatistics {
+            id: SocketId(id),
+            weight: 0,
+            state: MemberState::Active,
+            statistics: SocketStatistics {
+                rx_data,
+                rx_unique_data: rx_data,
+                rx_average_rtt: Duration::from_millis(rtt),
+                ..SocketStatistics::default()
+            },
+        };
+        let group = SocketStatistics {
+            rx_unique_data: 100,
+            ..SocketStatistics::default()
+        };
+
+        let statistics = GroupStatistics::new(&group, vec![member(1, 100, 10), member(2, 90, 30)]);
+
+        assert_eq!(statistics.aggregate.rx_data, 190);
+        assert_eq!(statistics.aggregate.rx_unique_data, 100);
+        assert_eq!(
+            statistics.aggregate.rx_average_rtt,
+            Duration::from_millis(20)
+        );
+        assert_eq!(statistics.members.len(), 2);
+    }
+}
@@ -1,7 +1,12 @@
+mod group;
+
 pub use super::listener::ListenerStatistics;
+pub use group::*;
 
 use std::time::Duration;
 
+use crate::settings::KeyMaterialState;
+
 /// SRT provides a powerful set of statistical data on a socket. This data can be used to keep an eye
 /// on a socket's health and track faulty behavior.
 ///
@@ -22,6 +27,16 @@ pub struct SocketStatistics {
     pub tx_encrypted_data: u64,
     pub rx_decrypted_data: u64,
 
+    /// Whether the peer can decrypt the sent data, which can change with key refreshes.
+    ///
+    /// Unsecured when the data is sent unencrypted.
+    pub tx_key_material_state: KeyMaterialState, // SRTO_SNDKMSTATE
+
+    /// Whether the received data can be decrypted, which can change with key refreshes.
+    ///
+    /// Unsecured when the data is received unencrypted.
+    pub rx_key_material_state: KeyMaterialState, // SRTO_RCVKMSTATE
+
     pub rx_clock_adjustments: u64,
     pub rx_clock_drift_mean: i64,
     pub rx_clock_drift_stddev: i64,
@@ -29,15 +44,15 @@ pub struct SocketStatistics {
     pub rx_ack2_errors: i64,
 
     /// The total number of sent DATA packets, including retransmissions ([tx_retransmit_data](#tx_retransmit_data)).
-    //
-    //  TODO: Should we do this too?
-    //   If the SRTO_PACKETFILTER socket option is enabled (refer to SRT API Socket Options), this statistic counts sent packet filter control packets (pktSndFilterExtraTotal) as well. Introduced in SRT v1.4.0.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is enabled, this statistic counts sent packet
+    /// filter control packets ([tx_filter_extra](#tx_filter_extra)) as well.
     pub tx_data: u64, // pktSentTotal
 
     /// The total number of received DATA packets, including retransmissions ([rx_retransmit_data](#tx_retransmit_data)).
-    //
-    //  TODO: Should we do this too?
-    //   If the `SRTO_PACKETFILTER` socket option is enabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic counts received packet filter control packets ([pktRcvFilterExtraTotal](#pktRcvFilterExtraTotal)) as well. Introduced in SRT v1.4.0.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is enabled, this statistic counts received packet
+    /// filter control packets ([rx_filter_extra](#rx_filter_extra)) as well.
     pub rx_data: u64, // pktRecvTotal
 
     /// The total number of sent *unique* DATA packets.
@@ -49,10 +64,10 @@ pub struct SocketStatistics {
     /// counts every packet sent over the network for the first time, and can be calculated as
     /// follows: `tx_unique_data = tx_data – tx_retransmit_data`. The original DATA packets are sent
     /// only once.
-    //
-    //  TODO: Should we do this?
-    //   or by `pktSentUniqueTotal = pktSentTotal – pktRetransTotal - pktSndFilterExtraTotal` if the  `SRTO_PACKETFILTER` socket option is enabled
-    //   If the `SRTO_PACKETFILTER` socket option is enabled (refer to [SRT API Socket Options](API-socket-options.md)), packet filter control packets ([pktSndFilterExtraTotal](#pktSndFilterExtraTotal)) are also not taken into account.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is enabled, packet filter control packets
+    /// ([tx_filter_extra](#tx_filter_extra)) are also not taken into account, so
+    /// `tx_unique_data = tx_data – tx_retransmit_data - tx_filter_extra`.
     pub tx_unique_data: u64, // pktSentUniqueTotal
 
     /// The total number of received *unique* original, retransmitted or recovered DATA packets
@@ -72,9 +87,10 @@ pub struct SocketStatistics {
     /// - arrived in time packets, but decrypted with errors (see [rx_decrypt_errors](#rx_decrypt_errors)
     ///   statistic), and, as a result, dropped by the TLPKTDROP mechanism (see [tx_dropped_data](#tx_dropped_data)
     ///   statistic).
-    //
-    //  TODO: Should we do this?
-    //   DATA packets recovered by the packet filter ([pktRcvFilterSupplyTotal](#pktRcvFilterSupplyTotal)) are taken into account if the `SRTO_PACKETFILTER` socket option is enabled (refer to [SRT API Socket Options](API-socket-options.md)). Do not mix up with the control packets received by the packet filter ([pktRcvFilterExtraTotal](#pktRcvFilterExtraTotal)).
+    ///
+    /// DATA packets recovered by the packet filter ([rx_filter_supply](#rx_filter_supply)) are
+    /// taken into account. Do not mix up with the control packets received by the packet filter
+    /// ([rx_filter_extra](#rx_filter_extra)).
     pub rx_unique_data: u64, // pktRecvUniqueTotal
 
     /// The total number of data packets considered or reported as lost at the sender side. Does not
@@ -185,37 +201,30 @@ pub struct SocketStatistics {
     /// The total number of packets that failed to be decrypted at the receiver side.
     pub rx_decrypt_errors: u64, // pktRcvUndecryptTotal
 
-    // The total number of packet filter control packets generated by the packet filter (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktSndFilterExtraTotal
+    /// The total number of packet filter control packets generated by the packet filter (refer to [SRT Packet Filtering & FEC](https://github.com/Haivision/srt/blob/master/docs/features/packet-filtering-and-fec.md)).
+    ///
+    /// Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is disabled, this statistic is equal to 0.
+    pub tx_filter_extra: u64, // pktSndFilterExtraTotal
 
-    // The total number of packet filter control packets received by the packet filter (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktRcvFilterExtraTotal
+    /// The total number of packet filter control packets received by the packet filter (refer to [SRT Packet Filtering & FEC](https://github.com/Haivision/srt/blob/master/docs/features/packet-filtering-and-fec.md)).
+    ///
+    /// Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is disabled, this statistic is equal to 0.
+    pub rx_filter_extra: u64, // pktRcvFilterExtraTotal
 
-    // The total number of lost DATA packets recovered by the packet filter at the receiver side (e.g., FEC rebuilt packets; refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktRcvFilterSupplyTotal
+    /// The total number of lost DATA packets recovered by the packet filter at the receiver side (e.g., FEC rebuilt packets).
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is disabled, this statistic is equal to 0.
+    pub rx_filter_supply: u64, // pktRcvFilterSupplyTotal
+
+    /// The total number of lost DATA packets **not** recovered by the packet filter at the receiver side.
+    ///
+    /// If the `SRTO_PACKETFILTER` socket option is disabled, this statistic is equal to 0.
+    pub rx_filter_loss: u64, // pktRcvFilterLossTotal
 
-    // The total number of lost DATA packets **not** recovered by the packet filter at the receiver side (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktRcvFilterLossTotal
     /// Same as [tx_data](#tx_data), but expressed in bytes, including payload and all the headers
     /// (20 bytes IPv4 + 8 bytes UDP + 16 bytes SRT).
     pub tx_bytes: u64, // byteSentTotal
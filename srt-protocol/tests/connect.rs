@@ -6,6 +6,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+use assert_matches::assert_matches;
 use log::debug;
 use rand::{prelude::StdRng, Rng, SeedableRng};
 use rand_distr::{Bernoulli, Normal};
@@ -14,7 +15,10 @@ use srt_protocol::{
     connection::{Connection, Input},
     packet::*,
     protocol::pending_connection::{
-        connect::Connect, listen::Listen, rendezvous::Rendezvous, ConnectionResult,
+        connect::Connect,
+        listen::Listen,
+        rendezvous::{CookieContest, Rendezvous, RendezvousDiagnostics, RendezvousPhase},
+        ConnectionResult,
     },
     settings::*,
 };
@@ -148,7 +152,6 @@ fn precise_ts0() {
     let send = ConnectEntity::PendingC(
         Connect::new(
             r_sa,
-            s_sa.ip(),
             ConnInitSettings {
                 local_sockid: s_sid,
                 send_latency: Duration::from_millis(2000),
@@ -233,7 +236,6 @@ fn do_lossy_connect(seed: u64) {
     let c = ConnectEntity::PendingC(
         Connect::new(
             l_sa,
-            c_sa.ip(),
             ConnInitSettings {
                 local_sockid: s_sid,
                 ..ConnInitSettings::default()
@@ -386,3 +388,63 @@ fn complete(mut conn: Conn, start: Instant) -> (Connection, Connection) {
         current_time = next_time;
     }
 }
+
+#[test]
+fn rendezvous_diagnostics() {
+    use ConnectionResult::*;
+
+    let a_sa: SocketAddr = ([127, 0, 0, 1], 2222).into();
+    let b_sa: SocketAddr = ([127, 0, 0, 1], 2224).into();
+    let now = Instant::now();
+
+    let rendezvous = |local, remote, sockid| {
+        Rendezvous::new(
+            local,
+            remote,
+            ConnInitSettings {
+                local_sockid: SocketId(sockid),
+                ..ConnInitSettings::default()
+            },
+            SeqNumber::new_truncate(0),
+        )
+    };
+    let mut a = rendezvous(a_sa, b_sa, 1234);
+    let mut b = rendezvous(b_sa, a_sa, 2234);
+
+    assert_eq!(
+        a.diagnostics(),
+        RendezvousDiagnostics {
+            phase: RendezvousPhase::Waving,
+            peer_seen: false,
+            cookie_contest: None,
+        }
+    );
+
+    // a packet from another host is not from the peer
+    let wave = assert_matches!(a.handle_tick(now), SendPacket((p, _)) => p);
+    let other: SocketAddr = ([127, 0, 0, 1], 2226).into();
+    assert_matches!(
+        b.handle_packet(Ok((wave.clone(), other)), now),
+        NotHandled(_)
+    );
+    assert!(!b.diagnostics().peer_seen);
+
+    // the wave moves b to the attention phase, and b's conclusion moves a to the fine phase
+    let conclusion =
+        assert_matches!(b.handle_packet(Ok((wave, a_sa)), now), SendPacket((p, _)) => p);
+    let b_diagnostics = b.diagnostics();
+    assert_eq!(b_diagnostics.phase, RendezvousPhase::Attention);
+    assert!(b_diagnostics.peer_seen);
+
+    assert_matches!(a.handle_packet(Ok((conclusion, b_sa)), now), SendPacket(_));
+    let a_diagnostics = a.diagnostics();
+    assert_eq!(a_diagnostics.phase, RendezvousPhase::Fine);
+    assert!(a_diagnostics.peer_seen);
+
+    // one of the peers won the cookie contest
+    use CookieContest::*;
+    assert_matches!(
+        (a_diagnostics.cookie_contest, b_diagnostics.cookie_contest),
+        (Some(Initiator), Some(Responder)) | (Some(Responder), Some(Initiator))
+    );
+}
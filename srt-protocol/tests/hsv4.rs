@@ -0,0 +1,283 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use assert_matches::assert_matches;
+
+use srt_protocol::{
+    connection::Connection,
+    options::*,
+    packet::*,
+    protocol::{
+        handshake::Handshake,
+        pending_connection::{
+            connect::Connect, listen::Listen, ConnectionReject, ConnectionResult,
+        },
+    },
+    settings::*,
+};
+
+fn caller_addr() -> SocketAddr {
+    ([127, 0, 0, 1], 2000).into()
+}
+
+fn listener_addr() -> SocketAddr {
+    ([127, 0, 0, 1], 2001).into()
+}
+
+// listeners before SRT 1.3.0 respond to the induction with the HSv4 handshake, this one only
+// differs in that, and then continues as any listener does with a HSv4 caller
+fn as_legacy_listener(packet: Packet) -> Packet {
+    match packet {
+        Packet::Control(ControlPacket {
+            timestamp,
+            dest_sockid,
+            control_type: ControlTypes::Handshake(shake),
+        }) if shake.shake_type == ShakeType::Induction => Packet::Control(ControlPacket {
+            timestamp,
+            dest_sockid,
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                info: HandshakeVsInfo::V4(SocketType::Datagram),
+                ..shake
+            }),
+        }),
+        packet => packet,
+    }
+}
+
+// returns the connections of the caller and the listener, or the first result that is neither
+#[allow(clippy::result_large_err)]
+fn handshake(
+    caller: ConnInitSettings,
+    listener: ConnInitSettings,
+) -> Result<(Connection, Connection), ConnectionResult> {
+    use ConnectionResult::*;
+
+    let now = Instant::now();
+    let mut connect = Connect::new(listener_addr(), caller, None, SeqNumber::new_truncate(0));
+    let mut listen = Listen::new(listener, false);
+    let mut listener = None;
+
+    let mut to_listener = Some(assert_matches!(connect.handle_tick(now), SendPacket((p, _)) => p));
+    for _ in 0..10 {
+        let to_caller = match (to_listener.take(), &listener) {
+            (None, _) => None,
+            (Some(packet), None) => match listen.handle_packet(now, Ok((packet, caller_addr()))) {
+                SendPacket((packet, _)) => Some(packet),
+                Connected(packet, connection) => {
+                    listener = Some(connection);
+                    packet.map(|(packet, _)| packet)
+                }
+                NoAction => None,
+                result => return Err(result),
+            },
+            // once connected, the listener answers the handshake requests the caller repeats
+            (
+                Some(Packet::Control(ControlPacket {
+                    timestamp,
+                    dest_sockid,
+                    control_type: ControlTypes::Srt(srt),
+                })),
+                Some(connection),
+            ) => connection
+                .handshake
+                .handle_srt_handshake(&srt)
+                .map(|control_type| {
+                    Packet::Control(ControlPacket {
+                        timestamp,
+                        dest_sockid,
+                        control_type,
+                    })
+                }),
+            (Some(packet), Some(_)) => panic!("unexpected packet {:?}", packet),
+        };
+
+        let result = match to_caller {
+            Some(packet) => {
+                connect.handle_packet(Ok((as_legacy_listener(packet), listener_addr())), now)
+            }
+            // the caller repeats its request once it is due
+            None => connect.handle_tick(connect.next_attempt(now)),
+        };
+        match result {
+            SendPacket((packet, _)) => to_listener = Some(packet),
+            Connected(_, caller) => return Ok((caller, listener.unwrap())),
+            result => return Err(result),
+        }
+    }
+    panic!("the handshake did not complete")
+}
+
+fn key_settings(passphrase: &str) -> Option<KeySettings> {
+    Some(KeySettings {
+        key_size: KeySize::AES192,
+        passphrase: passphrase.into(),
+    })
+}
+
+#[test]
+fn hsv4_handshake() {
+    let _ = pretty_env_logger::try_init();
+
+    let (caller, listener) = handshake(
+        ConnInitSettings {
+            send_latency: Duration::from_millis(200),
+            recv_latency: Duration::from_millis(20),
+            ..ConnInitSettings::default()
+        },
+        ConnInitSettings {
+            send_latency: Duration::from_millis(50),
+            recv_latency: Duration::from_millis(120),
+            ..ConnInitSettings::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(caller.handshake, Handshake::Connector);
+    assert_matches!(listener.handshake, Handshake::Hsv4Listener(_, _));
+
+    assert_eq!(
+        caller.settings.remote_sockid,
+        listener.settings.local_sockid
+    );
+    assert_eq!(
+        listener.settings.remote_sockid,
+        caller.settings.local_sockid
+    );
+    assert_eq!(caller.settings.init_seq_num, listener.settings.init_seq_num);
+
+    // the latencies are negotiated as with the HSv5 handshake
+    assert_eq!(
+        caller.settings.send_tsbpd_latency,
+        Duration::from_millis(200)
+    );
+    assert_eq!(
+        listener.settings.recv_tsbpd_latency,
+        Duration::from_millis(200)
+    );
+    assert_eq!(
+        caller.settings.recv_tsbpd_latency,
+        Duration::from_millis(50)
+    );
+    assert_eq!(
+        listener.settings.send_tsbpd_latency,
+        Duration::from_millis(50)
+    );
+
+    assert_eq!(
+        caller.settings.send_key_material_state,
+        KeyMaterialState::Unsecured
+    );
+    assert_eq!(
+        caller.settings.recv_key_material_state,
+        KeyMaterialState::Unsecured
+    );
+    assert_eq!(
+        listener.settings.send_key_material_state,
+        KeyMaterialState::Unsecured
+    );
+    assert_eq!(
+        listener.settings.recv_key_material_state,
+        KeyMaterialState::Unsecured
+    );
+}
+
+#[test]
+fn hsv4_encrypted_handshake() {
+    let _ = pretty_env_logger::try_init();
+
+    let (caller, listener) = handshake(
+        ConnInitSettings {
+            key_settings: key_settings("password123"),
+            ..ConnInitSettings::default()
+        },
+        ConnInitSettings {
+            key_settings: key_settings("password123"),
+            ..ConnInitSettings::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        caller.settings.send_key_material_state,
+        KeyMaterialState::Secured
+    );
+    assert_eq!(
+        caller.settings.recv_key_material_state,
+        KeyMaterialState::Secured
+    );
+    assert_eq!(
+        listener.settings.send_key_material_state,
+        KeyMaterialState::Secured
+    );
+    assert_eq!(
+        listener.settings.recv_key_material_state,
+        KeyMaterialState::Secured
+    );
+    assert!(caller.settings.cipher.is_some());
+    assert!(listener.settings.cipher.is_some());
+}
+
+#[test]
+fn hsv4_bad_passphrase() {
+    let _ = pretty_env_logger::try_init();
+
+    let result = handshake(
+        ConnInitSettings {
+            key_settings: key_settings("password123"),
+            ..ConnInitSettings::default()
+        },
+        ConnInitSettings {
+            key_settings: key_settings("password456"),
+            ..ConnInitSettings::default()
+        },
+    );
+
+    assert_matches!(
+        result,
+        Err(ConnectionResult::Reject(
+            _,
+            ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::BadSecret))
+        ))
+    );
+}
+
+#[test]
+fn hsv4_min_version() {
+    let _ = pretty_env_logger::try_init();
+
+    let min_version = SrtVersion::new(1, 3, 0);
+
+    // the caller refuses a legacy listener
+    let result = handshake(
+        ConnInitSettings {
+            min_version,
+            ..ConnInitSettings::default()
+        },
+        ConnInitSettings::default(),
+    );
+    assert_matches!(
+        result,
+        Err(ConnectionResult::Reject(
+            Some(_),
+            ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Version))
+        ))
+    );
+
+    // and the listener a legacy caller
+    let result = handshake(
+        ConnInitSettings::default(),
+        ConnInitSettings {
+            min_version,
+            ..ConnInitSettings::default()
+        },
+    );
+    assert_matches!(
+        result,
+        Err(ConnectionResult::Reject(
+            Some(_),
+            ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Version))
+        ))
+    );
+}
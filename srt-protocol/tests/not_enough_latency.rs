@@ -26,9 +26,51 @@ fn not_enough_latency() {
     }
 }
 
+#[test]
+fn not_enough_latency_without_too_late_packet_drop() {
+    // once failing seeds
+    do_not_enough_latency_without_too_late_packet_drop(2227381194603645596, 1000);
+
+    for _ in 0..10 {
+        do_not_enough_latency_without_too_late_packet_drop(rand::random(), 1000);
+    }
+}
+
 fn do_not_enough_latency(seed: u64, packets: usize) {
     println!("not_enough_latency seed is {}", seed);
 
+    let (total_recvd, total_dropped, last_data) = simulate(seed, packets, true);
+
+    assert_eq!(total_dropped + total_recvd + (packets - last_data), packets);
+    assert!(
+        total_recvd > packets * 2 / 3,
+        "received {} packtes, expected {}",
+        total_recvd,
+        packets * 2 / 3
+    );
+    assert!(
+        total_recvd <= packets,
+        "received all ({}) packets, expected < {}",
+        total_recvd,
+        packets
+    );
+}
+
+// everything is delivered, late
+fn do_not_enough_latency_without_too_late_packet_drop(seed: u64, packets: usize) {
+    println!(
+        "not_enough_latency_without_too_late_packet_drop seed is {}",
+        seed
+    );
+
+    let (total_recvd, total_dropped, last_data) = simulate(seed, packets, false);
+
+    assert_eq!(total_dropped, 0);
+    assert_eq!(total_recvd, packets);
+    assert_eq!(last_data, packets);
+}
+
+fn simulate(seed: u64, packets: usize, too_late_packet_drop: bool) -> (usize, usize, usize) {
     let _ = pretty_env_logger::try_init();
 
     const PACKET_SPACING: Duration = Duration::from_millis(10);
@@ -44,8 +86,16 @@ fn do_not_enough_latency(seed: u64, packets: usize) {
         drop_dist: Bernoulli::new(0.01).unwrap(),
     };
 
-    let (mut network, mut sender, mut receiver) =
-        simulation.build(start, Duration::from_secs(2), PacketCount(8192));
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(2),
+        PacketCount(8192),
+        |settings| {
+            settings.recv_too_late_packet_drop = too_late_packet_drop;
+            settings.send_too_late_packet_drop = too_late_packet_drop;
+            settings.linger = Some(Duration::from_secs(180));
+        },
+    );
 
     input_data_simulation(start, packets, PACKET_SPACING, &mut network.sender);
 
@@ -123,17 +173,5 @@ fn do_not_enough_latency(seed: u64, packets: usize) {
         now = next_time;
     }
 
-    assert_eq!(total_dropped + total_recvd + (packets - last_data), packets);
-    assert!(
-        total_recvd > packets * 2 / 3,
-        "received {} packtes, expected {}",
-        total_recvd,
-        packets * 2 / 3
-    );
-    assert!(
-        total_recvd <= packets,
-        "received all ({}) packets, expected < {}",
-        total_recvd,
-        packets
-    );
+    (total_recvd, total_dropped, last_data)
 }
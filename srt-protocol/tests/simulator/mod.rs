@@ -15,6 +15,7 @@ use srt_protocol::{
     options::*,
     packet::*,
     protocol::handshake::Handshake,
+    settings::KeyMaterialState,
 };
 
 #[derive(Eq, PartialEq)]
@@ -165,7 +166,19 @@ impl RandomLossSimulation {
         latency: Duration,
         recv_buffer_size: PacketCount,
     ) -> (NetworkSimulator, DuplexConnection, DuplexConnection) {
-        let sender = self.new_connection_settings(start, latency);
+        self.build_with(start, latency, recv_buffer_size, |_| {})
+    }
+
+    /// Like build, with changes to the settings of both connections
+    pub fn build_with(
+        &mut self,
+        start: Instant,
+        latency: Duration,
+        recv_buffer_size: PacketCount,
+        configure: impl Fn(&mut ConnectionSettings),
+    ) -> (NetworkSimulator, DuplexConnection, DuplexConnection) {
+        let mut sender = self.new_connection_settings(start, latency);
+        configure(&mut sender);
         let receiver = ConnectionSettings {
             remote: (sender.remote.ip(), sender.remote.port().wrapping_add(1)).into(),
             remote_sockid: sender.local_sockid,
@@ -209,11 +222,21 @@ impl RandomLossSimulation {
             send_tsbpd_latency: latency,
             recv_tsbpd_latency: latency,
             cipher: None,
+            send_key_material_state: KeyMaterialState::Unsecured,
+            recv_key_material_state: KeyMaterialState::Unsecured,
             stream_id: None,
             bandwidth: Default::default(),
             recv_buffer_size: PacketCount(8192),
             send_buffer_size: PacketCount(8192),
             statistics_interval: Duration::from_secs(1),
+            transmission_type: Default::default(),
+            message_api: true,
+            recv_tsbpd_mode: true,
+            recv_too_late_packet_drop: true,
+            send_too_late_packet_drop: true,
+            linger: None,
+            peer_group: None,
+            packet_filter: None,
         }
     }
 }
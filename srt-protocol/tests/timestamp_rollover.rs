@@ -13,6 +13,7 @@ use srt_protocol::{
     options::{PacketCount, PacketSize},
     packet::*,
     protocol::handshake::Handshake,
+    settings::KeyMaterialState,
 };
 
 #[test]
@@ -41,11 +42,21 @@ fn timestamp_rollover() {
         send_tsbpd_latency: Duration::from_millis(20),
         recv_tsbpd_latency: Duration::from_millis(20),
         cipher: None,
+        send_key_material_state: KeyMaterialState::Unsecured,
+        recv_key_material_state: KeyMaterialState::Unsecured,
         stream_id: None,
         bandwidth: Default::default(),
         recv_buffer_size: PacketCount(8192),
         send_buffer_size: PacketCount(8192),
         statistics_interval: Duration::from_secs(1),
+        transmission_type: Default::default(),
+        message_api: true,
+        recv_tsbpd_mode: true,
+        recv_too_late_packet_drop: true,
+        send_too_late_packet_drop: true,
+        linger: None,
+        peer_group: None,
+        packet_filter: None,
     };
 
     let s2 = ConnectionSettings {
@@ -61,11 +72,21 @@ fn timestamp_rollover() {
         send_tsbpd_latency: Duration::from_millis(20),
         recv_tsbpd_latency: Duration::from_millis(20),
         cipher: None,
+        send_key_material_state: KeyMaterialState::Unsecured,
+        recv_key_material_state: KeyMaterialState::Unsecured,
         stream_id: None,
         bandwidth: Default::default(),
         recv_buffer_size: PacketCount(8192),
         send_buffer_size: PacketCount(8192),
         statistics_interval: Duration::from_secs(1),
+        transmission_type: Default::default(),
+        message_api: true,
+        recv_tsbpd_mode: true,
+        recv_too_late_packet_drop: true,
+        send_too_late_packet_drop: true,
+        linger: None,
+        peer_group: None,
+        packet_filter: None,
     };
 
     const PACKET_RATE: u32 = 10; // 10 packet/s
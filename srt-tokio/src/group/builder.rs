@@ -0,0 +1,247 @@
+use std::{
+    convert::TryInto,
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use log::warn;
+use srt_protocol::{
+    group::SocketGroup,
+    packet::SeqNumber,
+    settings::{ConnInitSettings, GroupSettings, GroupType},
+};
+
+use crate::{
+    net::{bind_socket, lookup_remote_host, PacketSocket},
+    options::*,
+    socket::call,
+    ConnectError,
+};
+
+use super::{task::GroupTask, SrtGroup};
+
+struct MemberOptions {
+    local: Option<SocketAddr>,
+    remote: SocketAddress,
+    weight: u16,
+}
+
+/// Struct to build socket groups.
+///
+/// The caller side lists the member links, each connecting over its own socket, while the
+/// listener side accepts all members of the peer group on a single socket.
+///
+/// # Panics:
+/// * There is no tokio runtime
+pub struct SrtGroupBuilder {
+    options: SocketOptions,
+    group_type: GroupType,
+    min_stability_timeout: Duration,
+    members: Vec<MemberOptions>,
+}
+
+impl Default for SrtGroupBuilder {
+    fn default() -> Self {
+        Self {
+            options: SocketOptions::default(),
+            group_type: GroupType::MainBackup,
+            min_stability_timeout: SocketGroup::DEFAULT_MIN_STABILITY_TIMEOUT,
+            members: Vec::new(),
+        }
+    }
+}
+
+impl SrtGroupBuilder {
+    /// Set the group type, [`GroupType::MainBackup`] (the default) or [`GroupType::Broadcast`].
+    pub fn group_type(mut self, group_type: GroupType) -> Self {
+        self.group_type = group_type;
+        self
+    }
+
+    // SRTO_GROUPMINSTABLETIMEO
+    /// Set the minimum time a main/backup member may go without a response from the peer,
+    /// before it is considered unstable and a backup member is activated.
+    pub fn min_stability_timeout(mut self, timeout: Duration) -> Self {
+        self.min_stability_timeout = timeout;
+        self
+    }
+
+    // SRTO_LATENCY
+    /// Set the latency of the member connections. This sets both the send and receive latency
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.options.sender.peer_latency = latency;
+        self.options.receiver.latency = latency;
+        self
+    }
+
+    /// Add a member link to the remote address, for the caller side. Members with a higher weight
+    /// are preferred as the main link.
+    ///
+    /// # Panics:
+    /// * the remote address is invalid
+    pub fn member(mut self, remote: impl TryInto<SocketAddress>, weight: u16) -> Self {
+        let remote = remote
+            .try_into()
+            .map_err(|_| OptionsError::InvalidRemoteAddress)
+            .unwrap();
+        self.members.push(MemberOptions {
+            local: None,
+            remote,
+            weight,
+        });
+        self
+    }
+
+    /// Add a member link bound to a specific local address, for example to use a specific network
+    /// adapter, for the caller side.
+    ///
+    /// # Panics:
+    /// * the local or remote address is invalid
+    pub fn member_from(
+        self,
+        local: impl TryInto<SocketAddress>,
+        remote: impl TryInto<SocketAddress>,
+        weight: u16,
+    ) -> Self {
+        let local: SocketAddress = local
+            .try_into()
+            .map_err(|_| OptionsError::InvalidLocalAddress)
+            .unwrap();
+        let local = local
+            .try_into()
+            .map_err(|_| OptionsError::InvalidLocalAddress)
+            .unwrap();
+        let mut builder = self.member(remote, weight);
+        if let Some(member) = builder.members.last_mut() {
+            member.local = Some(local);
+        }
+        builder
+    }
+
+    pub fn with<O>(mut self, options: O) -> Self
+    where
+        SocketOptions: OptionsOf<O>,
+        O: Validation<Error = OptionsError>,
+    {
+        self.options.set_options(options);
+        self
+    }
+
+    pub fn set(mut self, set_fn: impl FnOnce(&mut SocketOptions)) -> Self {
+        set_fn(&mut self.options);
+        self
+    }
+
+    pub async fn listen_on(
+        self,
+        local: impl TryInto<SocketAddress>,
+    ) -> Result<SrtGroup, io::Error> {
+        let local: SocketAddress = local
+            .try_into()
+            .map_err(|_| OptionsError::InvalidLocalAddress)
+            .unwrap();
+        self.set(|options| {
+            options.connect.local = local
+                .try_into()
+                .map_err(|_| OptionsError::InvalidLocalAddress)
+                .unwrap()
+        })
+        .listen()
+        .await
+    }
+
+    /// Accept the members of a peer group, returning once the first member is connected
+    pub async fn listen(self) -> Result<SrtGroup, io::Error> {
+        let settings = GroupSettings::new(self.group_type, 0);
+        let group = self.new_group(settings)?;
+        let options = ListenerOptions {
+            socket: self.options,
+        }
+        .try_validate()?;
+
+        let socket = bind_socket(&options.socket).await?;
+        let socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024);
+
+        let mut init_settings: ConnInitSettings = options.socket.clone().into();
+        init_settings.group = Some(settings);
+
+        let mut task = GroupTask::new(group);
+        let connected = task.listen(socket, init_settings);
+        let group = task.spawn(settings);
+        connected.await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "group closed before a member connected",
+            )
+        })?;
+        Ok(group)
+    }
+
+    /// Connect all members, returning once they either connected or failed. At least one member
+    /// needs to connect.
+    pub async fn call(self, stream_id: Option<&str>) -> Result<SrtGroup, io::Error> {
+        // all members share the group id, and the initial sequence number, so the members are
+        // interchangeable from the first packet on
+        let settings = GroupSettings::new(self.group_type, 0);
+        let group = self.new_group(settings)?;
+        if self.members.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a group needs at least one member",
+            ));
+        }
+
+        let init_seq_num: SeqNumber = rand::random();
+
+        let connects = self.members.iter().map(|member| {
+            let mut options = self.options.clone();
+            if let Some(local) = member.local {
+                options.connect.local = local;
+            }
+            async move {
+                let options = CallerOptions::with(member.remote.clone(), stream_id, options)?;
+                let remote = lookup_remote_host(&options.remote).await?;
+                let socket = bind_socket(&options.socket).await?;
+                let mut socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024);
+
+                let mut init_settings: ConnInitSettings = options.socket.clone().into();
+                init_settings.group = Some(settings.with_weight(member.weight));
+                let connection = call::connect(
+                    &mut socket,
+                    remote,
+                    &options.socket,
+                    init_settings,
+                    stream_id.map(|s| s.to_string()),
+                    init_seq_num,
+                )
+                .await?;
+                Ok::<_, ConnectError>((socket, connection))
+            }
+        });
+
+        let mut task = GroupTask::new(group);
+        let mut error = None;
+        for result in join_all(connects).await {
+            match result {
+                Ok((socket, connection)) => task.add_member(Instant::now(), socket, connection),
+                Err(e) => {
+                    warn!("Group member failed to connect: {}", e);
+                    error = Some(e);
+                }
+            }
+        }
+
+        match error {
+            Some(error) if !task.is_open() => Err(error.into()),
+            _ => Ok(task.spawn(settings)),
+        }
+    }
+
+    fn new_group(&self, settings: GroupSettings) -> Result<SocketGroup, io::Error> {
+        SocketGroup::new(settings, self.min_stability_timeout)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+}
@@ -0,0 +1,118 @@
+mod builder;
+mod task;
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*, ready};
+use srt_protocol::settings::GroupSettings;
+use tokio::task::JoinHandle;
+
+use crate::watch;
+
+pub use builder::SrtGroupBuilder;
+pub use srt_protocol::{
+    group::MemberState,
+    settings::GroupType,
+    statistics::{GroupStatistics, MemberStatistics},
+};
+
+/// A socket group, bonding multiple SRT connections to the same peer into one redundant link,
+/// generally created with [`SrtGroupBuilder`].
+///
+/// In main/backup mode ([`GroupType::MainBackup`]), data is sent over a single member link.
+/// When that link becomes unstable, because the peer stopped responding within the stability
+/// timeout, the highest weight backup link is activated. It resends the data the main link did
+/// not get acknowledged, with the same sequence numbers, and the receiving group drops the
+/// duplicates, so the switch is seamless.
+///
+/// In broadcast mode ([`GroupType::Broadcast`]), data is sent over every member link, and the
+/// receiving group merges the members by sequence number into one stream, dropping the
+/// duplicates, so a packet only needs to arrive over one of the links.
+///
+/// Like [`SrtSocket`](crate::SrtSocket), groups implement `Stream + Sink` of `(Instant, Bytes)`.
+///
+/// # Examples:
+/// ```
+/// # use srt_tokio::SrtGroup;
+/// # use std::io;
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), io::Error> {
+/// let (a, b) = futures::try_join!(
+///     SrtGroup::builder().listen_on(":3334"),
+///     SrtGroup::builder()
+///         .member("127.0.0.1:3334", 10)
+///         .member("127.0.0.1:3334", 5)
+///         .call(None),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SrtGroup {
+    output_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+    input_data_sender: mpsc::Sender<(Instant, Bytes)>,
+    statistics_receiver: watch::Receiver<GroupStatistics>,
+    settings: GroupSettings,
+    task: JoinHandle<()>,
+}
+
+impl SrtGroup {
+    pub fn builder() -> SrtGroupBuilder {
+        SrtGroupBuilder::default()
+    }
+
+    pub fn settings(&self) -> &GroupSettings {
+        &self.settings
+    }
+
+    /// The aggregate statistics of the group, along with the statistics of each member
+    pub fn statistics(&mut self) -> &mut (impl Stream<Item = GroupStatistics> + Clone) {
+        &mut self.statistics_receiver
+    }
+
+    pub async fn close_and_finish(&mut self) -> Result<(), io::Error> {
+        self.close().await?;
+        (&mut self.task).await?;
+        Ok(())
+    }
+}
+
+impl Stream for SrtGroup {
+    type Item = Result<(Instant, Bytes), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Poll::Ready(ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx)).map(Ok))
+    }
+}
+
+impl Sink<(Instant, Bytes)> for SrtGroup {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(ready!(
+            Pin::new(&mut self.input_data_sender).poll_ready(cx)
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?))
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
+        self.input_data_sender
+            .start_send(item)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.input_data_sender)
+            .poll_flush(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.input_data_sender)
+            .poll_close(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashMap, net::SocketAddr, time::Instant};
+
+use bytes::Bytes;
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    select,
+    stream::FusedStream,
+};
+use log::{error, warn};
+use srt_protocol::{
+    connection::{Connection, DuplexConnection},
+    group::SocketGroup,
+    packet::*,
+    protocol::pending_connection::{listen::Listen, ConnectionResult},
+    settings::{ConnInitSettings, GroupSettings},
+    statistics::GroupStatistics,
+};
+use tokio::{task::JoinHandle, time::sleep_until};
+
+use crate::{net::PacketSocket, watch};
+
+use super::SrtGroup;
+
+#[derive(Clone, Copy, Debug)]
+enum PacketSource {
+    Member(SocketId),
+    Listener,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Input {
+    Timer,
+    Packet(Option<(PacketSource, ReceivePacketResult)>),
+    Data(Option<(Instant, Bytes)>),
+}
+
+// Accepts new members on a single socket, routing the packets of connected members by address
+struct GroupListener {
+    socket: PacketSocket,
+    init_settings: ConnInitSettings,
+    pending: HashMap<SocketAddr, Listen>,
+    routes: HashMap<SocketAddr, SocketId>,
+    peer_group_id: Option<SocketId>,
+    connected: Option<oneshot::Sender<()>>,
+}
+
+pub struct GroupTask {
+    group: SocketGroup,
+    sockets: HashMap<SocketId, PacketSocket>,
+    listener: Option<GroupListener>,
+    packet_sender: mpsc::Sender<(PacketSource, ReceivePacketResult)>,
+    packet_receiver: mpsc::Receiver<(PacketSource, ReceivePacketResult)>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl GroupTask {
+    pub fn new(group: SocketGroup) -> Self {
+        let (packet_sender, packet_receiver) = mpsc::channel(1024);
+        Self {
+            group,
+            sockets: HashMap::new(),
+            listener: None,
+            packet_sender,
+            packet_receiver,
+            readers: Vec::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.group.is_open()
+    }
+
+    /// Adds a member connected over its own socket
+    pub fn add_member(&mut self, now: Instant, socket: PacketSocket, connection: Connection) {
+        let id = connection.settings.local_sockid;
+        self.spawn_reader(PacketSource::Member(id), &socket);
+        self.sockets.insert(id, socket);
+        self.group
+            .add_member(now, DuplexConnection::new(connection));
+    }
+
+    /// Accepts members on the socket, the receiver completes once the first member is connected
+    pub fn listen(
+        &mut self,
+        socket: PacketSocket,
+        init_settings: ConnInitSettings,
+    ) -> oneshot::Receiver<()> {
+        let (connected_sender, connected_receiver) = oneshot::channel();
+        self.spawn_reader(PacketSource::Listener, &socket);
+        self.listener = Some(GroupListener {
+            socket,
+            init_settings,
+            pending: HashMap::new(),
+            routes: HashMap::new(),
+            peer_group_id: None,
+            connected: Some(connected_sender),
+        });
+        connected_receiver
+    }
+
+    pub fn spawn(self, settings: GroupSettings) -> SrtGroup {
+        let (output_data_sender, output_data_receiver) = mpsc::channel(128);
+        let (input_data_sender, input_data_receiver) = mpsc::channel(128);
+        let (statistics_sender, statistics_receiver) = watch::channel();
+        let task =
+            tokio::spawn(self.run_loop(input_data_receiver, output_data_sender, statistics_sender));
+        SrtGroup {
+            output_data_receiver,
+            input_data_sender,
+            statistics_receiver,
+            settings,
+            task,
+        }
+    }
+
+    fn spawn_reader(&mut self, source: PacketSource, socket: &PacketSocket) {
+        let mut socket = socket.clone_socket();
+        let mut packet_sender = self.packet_sender.clone();
+        self.readers.push(tokio::spawn(async move {
+            loop {
+                let packet = socket.receive().await;
+                let failed = matches!(packet, Err(ReceivePacketError::Io(_)));
+                if packet_sender.send((source, packet)).await.is_err() || failed {
+                    break;
+                }
+            }
+        }));
+    }
+
+    async fn run_loop(
+        mut self,
+        input_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+        mut output_data: mpsc::Sender<(Instant, Bytes)>,
+        statistics_sender: watch::Sender<GroupStatistics>,
+    ) {
+        let mut input_data = input_data_receiver.fuse();
+        loop {
+            if self.group.should_update_statistics(Instant::now()) {
+                let _ = statistics_sender.send(self.group.statistics());
+            }
+
+            while let Some((id, packet)) = self.group.next_packet(Instant::now()) {
+                if let Some(socket) = self.sockets.get_mut(&id) {
+                    if let Err(e) = socket.send(packet).await {
+                        error!("Error while sending packet: {:?}", e);
+                    }
+                }
+            }
+
+            while let Some(data) = self.group.next_data(Instant::now()) {
+                if output_data.is_closed() {
+                    continue;
+                }
+                if let Err(e) = output_data.send(data).await {
+                    error!("Error while releasing packet {:?}", e);
+                }
+            }
+
+            let timeout = self.group.check_timers(Instant::now());
+            self.remove_closed_members();
+
+            // a listening group waits for its first member, otherwise the group is done once
+            // all of its members are closed
+            let waiting = matches!(&self.listener, Some(l) if l.connected.is_some());
+            if !waiting && !self.group.is_open() {
+                break;
+            }
+
+            let can_accept_data =
+                self.group.is_open() && self.group.can_accept_data() && !input_data.is_terminated();
+            let input_data_fut = async {
+                if can_accept_data {
+                    input_data.next().await
+                } else {
+                    future::pending().await
+                }
+            };
+
+            let input = select! {
+                _ = sleep_until(timeout.into()).fuse() => Input::Timer,
+                packet = self.packet_receiver.next() => Input::Packet(packet),
+                data = input_data_fut.fuse() => Input::Data(data),
+            };
+
+            match input {
+                Input::Packet(Some((source, packet))) => self.handle_packet(source, packet).await,
+                Input::Data(data) => self.group.handle_data_input(Instant::now(), data),
+                _ => {}
+            }
+        }
+
+        for reader in &self.readers {
+            reader.abort();
+        }
+        if let Err(e) = output_data.close().await {
+            error!("Error while closing data output stream {:?}", e);
+        }
+    }
+
+    fn remove_closed_members(&mut self) {
+        let group = &self.group;
+        self.sockets.retain(|id, _| group.contains_member(*id));
+        if let Some(listener) = &mut self.listener {
+            listener.routes.retain(|_, id| group.contains_member(*id));
+        }
+    }
+
+    async fn handle_packet(&mut self, source: PacketSource, packet: ReceivePacketResult) {
+        let now = Instant::now();
+        let listener = match (source, &mut self.listener) {
+            (PacketSource::Member(id), _) => {
+                return self.group.handle_packet_input(now, id, packet)
+            }
+            (PacketSource::Listener, Some(listener)) => listener,
+            (PacketSource::Listener, None) => return,
+        };
+
+        let from = match &packet {
+            Ok((_, from)) => *from,
+            Err(e) => return warn!("Error while receiving on the group socket: {:?}", e),
+        };
+        if let Some(id) = listener.routes.get(&from) {
+            return self.group.handle_packet_input(now, *id, packet);
+        }
+
+        let init_settings = &listener.init_settings;
+        let listen = listener
+            .pending
+            .entry(from)
+            .or_insert_with(|| Listen::new(init_settings.copy_randomize(), false));
+
+        use ConnectionResult::*;
+        match listen.handle_packet(now, packet) {
+            SendPacket(packet) => listener.send(packet).await,
+            NotHandled(e) => warn!("{:?}", e),
+            Reject(packet, reason) => {
+                listener.pending.remove(&from);
+                if let Some(packet) = packet {
+                    listener.send(packet).await;
+                }
+                warn!("Rejected group member {}: {}", from, reason);
+            }
+            Connected(packet, connection) => {
+                listener.pending.remove(&from);
+                if let Some(packet) = packet {
+                    listener.send(packet).await;
+                }
+
+                // all members must belong to the same peer group
+                let peer_group_id = connection.settings.peer_group.map(|g| g.group_id);
+                if listener.peer_group_id.is_some() && listener.peer_group_id != peer_group_id {
+                    warn!("Closing group member {}, from another peer group", from);
+                    listener.shutdown(&connection).await;
+                    return;
+                }
+                listener.peer_group_id = peer_group_id;
+
+                let id = connection.settings.local_sockid;
+                listener.routes.insert(from, id);
+                self.sockets.insert(id, listener.socket.clone_socket());
+                self.group
+                    .add_member(now, DuplexConnection::new(connection));
+                if let Some(connected) = listener.connected.take() {
+                    let _ = connected.send(());
+                }
+            }
+            Failure(e) => {
+                listener.pending.remove(&from);
+                warn!("Group member {} failed to connect: {:?}", from, e);
+            }
+            NoAction | RequestAccess(_) => {}
+        }
+    }
+}
+
+impl GroupListener {
+    async fn send(&mut self, packet: (Packet, SocketAddr)) {
+        if let Err(e) = self.socket.send(packet).await {
+            error!("Error while sending packet: {:?}", e);
+        }
+    }
+
+    async fn shutdown(&mut self, connection: &Connection) {
+        let shutdown = ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: connection.settings.remote_sockid,
+            control_type: ControlTypes::Shutdown,
+        };
+        self.send((shutdown.into(), connection.settings.remote))
+            .await;
+    }
+}
@@ -47,6 +47,7 @@
 //! ```
 //!
 
+mod group;
 mod listener;
 mod net;
 mod socket;
@@ -56,6 +57,13 @@ pub use srt_protocol::access;
 pub use srt_protocol::options;
 
 pub use crate::{
-    listener::{ConnectionRequest, ListenerStatistics, SrtIncoming, SrtListener},
-    socket::{SocketStatistics, SrtSocket, SrtSocketBuilder},
+    group::{GroupStatistics, GroupType, MemberState, MemberStatistics, SrtGroup, SrtGroupBuilder},
+    listener::{
+        AsyncStreamAcceptor, ConnectionRequest, ListenerStatistics, SrtIncoming, SrtListener,
+    },
+    socket::{
+        ConnectError, ConnectionReject, CookieContest, Discontinuity, HandshakeStage,
+        MessageControl, MessageInfo, ReceiveEvent, RendezvousDiagnostics, RendezvousPhase,
+        SocketStatistics, SrtMessageSender, SrtSocket, SrtSocketBuilder,
+    },
 };
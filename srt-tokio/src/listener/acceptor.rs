@@ -0,0 +1,26 @@
+use futures::Future;
+use srt_protocol::access::{AcceptParameters, AccessControlRequest, Rejection};
+
+/// The asynchronous counterpart of [`StreamAcceptor`](srt_protocol::access::StreamAcceptor),
+/// for authorization that has to wait on I/O, e.g. looking up the stream id in a database.
+///
+/// The returned future is awaited without holding on to the acceptor, so that several
+/// connection requests can be authorized at the same time. Any closure taking the request and
+/// returning such a future is an acceptor.
+pub trait AsyncStreamAcceptor {
+    type Future: Future<Output = Result<AcceptParameters, Rejection>> + Send + 'static;
+
+    fn accept(&mut self, request: &AccessControlRequest) -> Self::Future;
+}
+
+impl<F, Fut> AsyncStreamAcceptor for F
+where
+    F: FnMut(&AccessControlRequest) -> Fut,
+    Fut: Future<Output = Result<AcceptParameters, Rejection>> + Send + 'static,
+{
+    type Future = Fut;
+
+    fn accept(&mut self, request: &AccessControlRequest) -> Self::Future {
+        self(request)
+    }
+}
@@ -1,17 +1,19 @@
+mod acceptor;
 mod builder;
 mod session;
 mod state;
 
-use std::{io, sync::Arc};
+use std::{io, sync::Arc, time::Duration};
 
 use futures::{channel::mpsc, prelude::*};
 use srt_protocol::settings::ConnInitSettings;
 use tokio::{net::UdpSocket, sync::oneshot, task::JoinHandle};
 
-use crate::net::bind_socket;
+use crate::{net::bind_socket, SrtSocket};
 
 use super::{net::PacketSocket, options::*, watch};
 
+pub use acceptor::AsyncStreamAcceptor;
 pub use builder::SrtListenerBuilder;
 pub use session::ConnectionRequest;
 pub use srt_protocol::statistics::ListenerStatistics;
@@ -88,9 +90,39 @@ impl SrtListener {
 }
 
 impl SrtIncoming {
+    // the most connection requests awaiting authorization at the same time
+    const MAX_PENDING_AUTHORIZATIONS: usize = 100;
+
     pub fn incoming(&mut self) -> &mut impl Stream<Item = ConnectionRequest> {
         &mut self.request_receiver
     }
+
+    /// The connections accepted by the acceptor, which authorizes several requests at the same
+    /// time. Requests the acceptor does not decide on within the timeout are rejected as
+    /// unauthorized.
+    pub fn authorized<'a>(
+        &'a mut self,
+        mut acceptor: impl AsyncStreamAcceptor + 'a,
+        timeout: Duration,
+    ) -> impl Stream<Item = SrtSocket> + 'a {
+        self.request_receiver
+            .by_ref()
+            .map(move |request| {
+                let authorization = acceptor.accept(request.request());
+                let remote = request.remote();
+                request
+                    .complete_authorization(authorization, timeout)
+                    .map(move |result| match result {
+                        Ok(socket) => socket,
+                        Err(e) => {
+                            log::warn!("failed to complete the connection from {}: {}", remote, e);
+                            None
+                        }
+                    })
+            })
+            .buffer_unordered(Self::MAX_PENDING_AUTHORIZATIONS)
+            .filter_map(future::ready)
+    }
 }
 
 impl Drop for SrtListener {
@@ -1,8 +1,8 @@
-use std::{io::ErrorKind, net::SocketAddr};
+use std::{io::ErrorKind, net::SocketAddr, time::Duration};
 
 use futures::{
     channel::{mpsc, oneshot},
-    SinkExt,
+    Future, SinkExt,
 };
 use srt_protocol::{
     connection::{Connection, ConnectionSettings},
@@ -19,6 +19,8 @@ use crate::{
     SrtSocket,
 };
 
+use super::AsyncStreamAcceptor;
+
 #[derive(Debug)]
 pub struct ConnectionRequest {
     response_sender: ResponseSender,
@@ -40,13 +42,34 @@ impl ConnectionRequest {
     pub fn stream_id(&self) -> Option<&StreamId> {
         self.request.stream_id.as_ref()
     }
+    pub fn peer_version(&self) -> SrtVersion {
+        self.request.peer_version
+    }
+    pub fn peer_send_latency(&self) -> Duration {
+        self.request.peer_send_latency
+    }
+    pub fn peer_recv_latency(&self) -> Duration {
+        self.request.peer_recv_latency
+    }
+    pub fn request(&self) -> &AccessControlRequest {
+        &self.request
+    }
 
     pub async fn accept(
         self,
         key_settings: Option<KeySettings>,
+    ) -> Result<SrtSocket, std::io::Error> {
+        let parameters = key_settings.map(AcceptParameters::from);
+        self.accept_with(parameters.unwrap_or_default()).await
+    }
+
+    /// Accept the connection with the settings chosen for it, e.g. by a StreamAcceptor
+    pub async fn accept_with(
+        self,
+        parameters: AcceptParameters,
     ) -> Result<SrtSocket, std::io::Error> {
         self.response_sender
-            .send(AccessControlResponse::Accepted(key_settings))
+            .send(AccessControlResponse::Accepted(parameters))
             .await?;
 
         let (settings, jh) = self
@@ -57,10 +80,46 @@ impl ConnectionRequest {
         Ok(self.socket_factory.create_socket(settings, jh))
     }
 
-    pub async fn reject(self, reason: RejectReason) -> Result<(), std::io::Error> {
+    /// Accept or reject the connection as decided by the acceptor, rejecting it as unauthorized
+    /// when the acceptor takes longer than the timeout. Returns None when it was rejected.
+    pub async fn authorize(
+        self,
+        acceptor: &mut impl AsyncStreamAcceptor,
+        timeout: Duration,
+    ) -> Result<Option<SrtSocket>, std::io::Error> {
+        let authorization = acceptor.accept(&self.request);
+        self.complete_authorization(authorization, timeout).await
+    }
+
+    pub(crate) async fn complete_authorization(
+        self,
+        authorization: impl Future<Output = Result<AcceptParameters, Rejection>>,
+        timeout: Duration,
+    ) -> Result<Option<SrtSocket>, std::io::Error> {
+        let result = tokio::time::timeout(timeout, authorization)
+            .await
+            .unwrap_or_else(|_| Err(ServerRejectReason::Unauthorized.into()));
+        match result {
+            Ok(parameters) => Ok(Some(self.accept_with(parameters).await?)),
+            Err(reason) => {
+                self.reject(reason).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reject the connection, the caller receives the reject reason, the message is only logged
+    pub async fn reject(self, rejection: impl Into<Rejection>) -> Result<(), std::io::Error> {
+        let rejection = rejection.into();
+        log::info!(
+            "rejecting the connection from {} for {:?}: {}",
+            self.request.remote,
+            self.request.stream_id,
+            rejection
+        );
         Ok(self
             .response_sender
-            .send(AccessControlResponse::Rejected(reason))
+            .send(AccessControlResponse::Rejected(rejection.reason()))
             .await?)
     }
 }
@@ -103,7 +162,7 @@ impl PendingConnection {
         connection: Connection,
     ) -> Result<OpenConnection, ()> {
         let (packet_sender, socket) = socket.clone_channel(100);
-        let (handle, settings) = self.task_factory.spawn_task(socket, connection);
+        let (handle, settings) = self.task_factory.spawn_task(socket, connection, None);
         let _ = self
             .settings_sender
             .send((settings, handle))
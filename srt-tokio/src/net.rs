@@ -3,7 +3,7 @@ use std::{
     error,
     fmt::{Debug, Display, Formatter},
     io::{self, Cursor, ErrorKind},
-    net::SocketAddr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     sync::Arc,
 };
 
@@ -33,6 +33,9 @@ pub async fn bind_socket(options: &SocketOptions) -> Result<UdpSocket, io::Error
     socket.set_nonblocking(true)?; // required for passing to tokio
     socket.set_recv_buffer_size(recv_buffer_size)?;
     socket.set_send_buffer_size(send_buffer_size)?;
+    if options.connect.local.is_ipv6() {
+        socket.set_only_v6(options.connect.ipv6_only)?;
+    }
     socket.bind(&options.connect.local.into())?;
 
     UdpSocket::from_std(socket.into())
@@ -56,21 +59,53 @@ pub async fn lookup_remote_host(remote: &SocketAddress) -> Result<SocketAddr, io
     Ok(remote_address)
 }
 
+/// The IPv4 address an IPv4-mapped IPv6 address stands for, as a dual-stack socket receives
+/// from IPv4 peers
+fn to_canonical(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V6(ip) => match ip.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                SocketAddr::new(Ipv4Addr::new(a, b, c, d).into(), address.port())
+            }
+            _ => address,
+        },
+        IpAddr::V4(_) => address,
+    }
+}
+
+/// The IPv4-mapped IPv6 address to reach an IPv4 peer from a dual-stack socket
+fn to_ipv6_mapped(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(ip.to_ipv6_mapped().into(), address.port()),
+        IpAddr::V6(_) => address,
+    }
+}
+
+/// Sends and receives packets, addressing IPv4 peers by their IPv4 addresses on dual-stack
+/// sockets too
 pub struct PacketSocket {
     socket: Arc<UdpSocket>,
     stream: Option<mpsc::Receiver<ReceivePacketResult>>,
     buffer: BytesMut,
+    is_ipv6: bool,
 }
 
 impl PacketSocket {
     pub fn from_socket(socket: Arc<UdpSocket>, buffer_capacity: usize) -> Self {
+        let is_ipv6 = matches!(socket.local_addr(), Ok(SocketAddr::V6(_)));
         Self {
             socket,
             stream: None,
             buffer: BytesMut::with_capacity(buffer_capacity),
+            is_ipv6,
         }
     }
 
+    /// A new handle to the same underlying socket, receiving from the socket directly
+    pub fn clone_socket(&self) -> Self {
+        Self::from_socket(self.socket.clone(), self.buffer.capacity())
+    }
+
     pub fn clone_channel(
         &self,
         channel_buffer: usize,
@@ -82,6 +117,7 @@ impl PacketSocket {
                 socket: self.socket.clone(),
                 stream: Some(packet_receiver),
                 buffer: BytesMut::with_capacity(self.buffer.capacity()),
+                is_ipv6: self.is_ipv6,
             },
         )
     }
@@ -89,7 +125,12 @@ impl PacketSocket {
     pub async fn send(&mut self, packet: (Packet, SocketAddr)) -> Result<usize, io::Error> {
         self.buffer.clear();
         packet.0.serialize(&mut self.buffer);
-        self.socket.send_to(&self.buffer, packet.1).await
+        let to = if self.is_ipv6 {
+            to_ipv6_mapped(packet.1)
+        } else {
+            packet.1
+        };
+        self.socket.send_to(&self.buffer, to).await
     }
 
     pub async fn receive(&mut self) -> ReceivePacketResult {
@@ -118,10 +159,10 @@ impl PacketSocket {
     }
 
     fn parse(&mut self, size: usize, from: SocketAddr) -> ReceivePacketResult {
-        let packet = Packet::parse(
-            &mut Cursor::new(&self.buffer[0..size]),
-            self.socket.local_addr()?.is_ipv6(),
-        )?;
+        // the handshake carries an IPv4 or IPv6 address depending on the peer's address family,
+        // not the socket's, as an IPv4 peer of a dual-stack socket sends IPv4 addresses
+        let from = to_canonical(from);
+        let packet = Packet::parse(&mut Cursor::new(&self.buffer[0..size]), from.is_ipv6())?;
         Ok((packet, from))
     }
 }
@@ -1,11 +1,11 @@
 use std::net::SocketAddr;
-use std::{convert::TryInto, io, net::IpAddr, time::Duration};
+use std::{convert::TryInto, net::IpAddr, time::Duration};
 
 use tokio::net::UdpSocket;
 
 use crate::options::*;
 
-use super::SrtSocket;
+use super::{ConnectError, SrtSocket};
 
 #[derive(Default)]
 pub struct SrtSocketBuilder(SocketOptions, Option<UdpSocket>);
@@ -121,6 +121,70 @@ impl SrtSocketBuilder {
         self
     }
 
+    // SRTO_TRANSTYPE
+    /// Set the transmission type, live (the default) or file. Both sides must use the same type.
+    pub fn transmission_type(mut self, transmission_type: TransmissionType) -> Self {
+        self.0.session.transmission_type = transmission_type;
+        self
+    }
+
+    // SRTO_TSBPDMODE
+    /// Set to false to deliver received messages as soon as they are complete and in order,
+    /// instead of one latency after they were sent. The peer does the same.
+    pub fn tsbpd_mode(mut self, tsbpd_mode: bool) -> Self {
+        self.0.session.tsbpd_mode = tsbpd_mode;
+        self
+    }
+
+    // SRTO_MESSAGEAPI
+    /// Set to false to use the stream API, where message boundaries are not preserved. Only
+    /// supported for file transmission, and both sides must use the same setting.
+    pub fn message_api(mut self, message_api: bool) -> Self {
+        self.0.session.message_api = message_api;
+        self
+    }
+
+    // SRTO_TLPKTDROP
+    /// Set to false to wait for lost packets to be retransmitted, delivering late but complete,
+    /// instead of dropping them once they are too late to play. The peer is told not to drop
+    /// packets it sends either.
+    pub fn too_late_packet_drop(mut self, too_late_packet_drop: bool) -> Self {
+        self.0.receiver.too_late_packet_drop = too_late_packet_drop;
+        self
+    }
+
+    // SRTO_PACKETFILTER
+    /// Set the packet filter configuration, e.g. "fec,cols:10,rows:5,layout:staircase,arq:onreq".
+    /// Only supported for live transmission.
+    ///
+    /// # Panics:
+    /// * the configuration can not be parsed
+    pub fn packet_filter(mut self, filter: &str) -> Self {
+        self.0.session.packet_filter = Some(filter.parse().unwrap());
+        self
+    }
+
+    /// Set the schedule of the handshake requests of a caller, see [`RetryPolicy`]
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.0.connect.retry = retry;
+        self
+    }
+
+    /// Set how often a rendezvous peer repeats its handshake until the other peer responds
+    pub fn waving_interval(mut self, interval: Duration) -> Self {
+        self.0.connect.waving_interval = interval;
+        self
+    }
+
+    /// Set to true for a caller to connect again when its connection is lost, until the socket
+    /// is closed, the listener rejects it, or the [`retry`](Self::retry) policy runs out of
+    /// attempts. Messages sent meanwhile are dropped, and the [`settings`](SrtSocket::settings)
+    /// remain those of the first connection.
+    pub fn autoreconnect(mut self, autoreconnect: bool) -> Self {
+        self.0.connect.autoreconnect = autoreconnect;
+        self
+    }
+
     pub fn socket(mut self, socket: UdpSocket) -> Self {
         self.1 = Some(socket);
         self
@@ -143,11 +207,11 @@ impl SrtSocketBuilder {
     pub async fn listen_on(
         self,
         local: impl TryInto<SocketAddress>,
-    ) -> Result<SrtSocket, io::Error> {
+    ) -> Result<SrtSocket, ConnectError> {
         self.local(local).listen().await
     }
 
-    pub async fn listen(self) -> Result<SrtSocket, io::Error> {
+    pub async fn listen(self) -> Result<SrtSocket, ConnectError> {
         Self::bind(
             ListenerOptions { socket: self.0 }.try_validate()?.into(),
             self.1,
@@ -159,7 +223,7 @@ impl SrtSocketBuilder {
         self,
         remote: impl TryInto<SocketAddress>,
         stream_id: Option<&str>,
-    ) -> Result<SrtSocket, io::Error> {
+    ) -> Result<SrtSocket, ConnectError> {
         let options = CallerOptions::with(remote, stream_id, self.0)?;
         Self::bind(options.into(), self.1).await
     }
@@ -167,12 +231,15 @@ impl SrtSocketBuilder {
     pub async fn rendezvous(
         self,
         remote: impl TryInto<SocketAddress>,
-    ) -> Result<SrtSocket, io::Error> {
+    ) -> Result<SrtSocket, ConnectError> {
         let options = RendezvousOptions::with(remote, self.0)?;
         Self::bind(options.into(), self.1).await
     }
 
-    async fn bind(options: BindOptions, socket: Option<UdpSocket>) -> Result<SrtSocket, io::Error> {
+    async fn bind(
+        options: BindOptions,
+        socket: Option<UdpSocket>,
+    ) -> Result<SrtSocket, ConnectError> {
         match socket {
             None => SrtSocket::bind(options).await,
             Some(socket) => SrtSocket::bind_with_socket(options, socket).await,
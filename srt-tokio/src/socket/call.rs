@@ -1,44 +1,72 @@
-use std::{
-    io,
-    time::{Duration, Instant},
-};
+use std::{net::SocketAddr, time::Instant};
 
 use futures::{prelude::*, select};
 use log::{debug, warn};
-use tokio::time::interval;
+use tokio::time::sleep_until;
 
 use srt_protocol::{
     connection::Connection,
     options::*,
-    protocol::pending_connection::{connect::Connect, ConnectionResult},
+    packet::{CoreRejectReason, RejectReason, SeqNumber},
+    protocol::pending_connection::{connect::Connect, ConnectionReject, ConnectionResult},
+    settings::ConnInitSettings,
 };
 
 use crate::net::{lookup_remote_host, PacketSocket};
 
+use super::ConnectError;
+
 pub async fn bind_with(
     mut socket: PacketSocket,
     options: Valid<CallerOptions>,
-) -> Result<(PacketSocket, Connection), io::Error> {
+) -> Result<(PacketSocket, Connection), ConnectError> {
+    let connection = call(&mut socket, &options).await?;
+    Ok((socket, connection))
+}
+
+/// Connect to the remote of the options, also used to reconnect the socket of a caller
+pub async fn call(
+    socket: &mut PacketSocket,
+    options: &CallerOptions,
+) -> Result<Connection, ConnectError> {
     let stream_id = options.stream_id.as_ref().map(|s| s.to_string());
     let remote = lookup_remote_host(&options.remote).await?;
 
-    let mut tick_interval = interval(Duration::from_millis(100));
-    let mut connect = Connect::new(
+    connect(
+        socket,
         remote,
-        options.socket.connect.local.ip(),
+        &options.socket,
         options.socket.clone().into(),
-        stream_id.clone(),
+        stream_id,
         rand::random(),
-    );
+    )
+    .await
+}
+
+pub async fn connect(
+    socket: &mut PacketSocket,
+    remote: SocketAddr,
+    options: &SocketOptions,
+    init_settings: ConnInitSettings,
+    stream_id: Option<String>,
+    init_seq_num: SeqNumber,
+) -> Result<Connection, ConnectError> {
+    let mut connect = Connect::new(remote, init_settings, stream_id.clone(), init_seq_num);
 
-    let start_time = Instant::now();
+    let deadline = Instant::now() + options.connect.timeout;
     loop {
-        if start_time.elapsed() > options.socket.connect.timeout {
-            return Err(io::Error::new(io::ErrorKind::TimedOut, ""));
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(ConnectError::Timeout {
+                remote,
+                stage: connect.stage(),
+            });
         }
 
+        // wake up for the next request the retry policy schedules, or the timeout
+        let wakeup = connect.next_attempt(now).min(deadline);
         let result = select! {
-            now = tick_interval.tick().fuse() => connect.handle_tick(now.into()),
+            _ = sleep_until(wakeup.into()).fuse() => connect.handle_tick(Instant::now()),
             packet = socket.receive().fuse() => connect.handle_packet(packet, Instant::now()),
         };
 
@@ -51,21 +79,35 @@ pub async fn bind_with(
             NotHandled(e) => {
                 warn!("{:?}", e);
             }
+            // the retry policy ran out of attempts
+            Reject(
+                None,
+                ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Timeout)),
+            ) => {
+                return Err(ConnectError::Timeout {
+                    remote,
+                    stage: connect.stage(),
+                });
+            }
             Reject(rp, rr) => {
                 if let Some(packet) = rp {
                     let _ = socket.send(packet).await?;
                 }
-                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, rr));
+                return Err(ConnectError::Rejected {
+                    remote,
+                    stage: connect.stage(),
+                    rejection: rr,
+                });
             }
             Connected(p, connection) => {
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
                 }
-                return Ok((socket, connection));
+                return Ok(connection);
             }
             NoAction => {}
             RequestAccess(_) => {}
-            Failure(error) => return Err(error),
+            Failure(error) => return Err(error.into()),
         }
     }
 }
@@ -77,15 +119,24 @@ mod test {
         time::{Duration, Instant},
     };
 
-    use crate::SrtSocket;
+    use crate::{ConnectError, SrtSocket};
     use assert_matches::assert_matches;
+    use srt_protocol::protocol::pending_connection::HandshakeStage;
 
     #[tokio::test]
     async fn conntimeo() {
         // default-3s
         let start = Instant::now();
         let ret = SrtSocket::builder().call("127.0.0.1:11111", None).await;
-        assert_matches!(ret, Err(e) if e.kind() == io::ErrorKind::TimedOut);
+        assert_matches!(&ret, Err(e) if e.kind() == io::ErrorKind::TimedOut);
+        // the peer never responded to the induction
+        assert_matches!(
+            ret,
+            Err(ConnectError::Timeout {
+                remote,
+                stage: HandshakeStage::Induction,
+            }) if remote.port() == 11111
+        );
         assert!(start.elapsed() > Duration::from_millis(3000));
         assert!(start.elapsed() < Duration::from_millis(3500));
 
@@ -0,0 +1,142 @@
+use std::{error::Error, fmt, io, net::SocketAddr};
+
+use srt_protocol::{
+    options::OptionsError,
+    packet::RejectReason,
+    protocol::pending_connection::{
+        rendezvous::RendezvousDiagnostics, ConnectionReject, HandshakeStage,
+    },
+};
+
+/// The reason a socket failed to connect
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ConnectError {
+    /// The connection was rejected, by the peer or by this side, e.g. for a bad stream id,
+    /// passphrase or version
+    Rejected {
+        remote: SocketAddr,
+        stage: HandshakeStage,
+        rejection: ConnectionReject,
+    },
+    /// The handshake did not complete within the connect timeout
+    Timeout {
+        remote: SocketAddr,
+        stage: HandshakeStage,
+    },
+    /// The rendezvous handshake did not complete within ten times the connect timeout
+    RendezvousTimeout {
+        remote: SocketAddr,
+        diagnostics: RendezvousDiagnostics,
+    },
+    /// Invalid options, a failed host lookup or a socket error
+    Io(io::Error),
+}
+
+impl ConnectError {
+    pub fn remote(&self) -> Option<SocketAddr> {
+        use ConnectError::*;
+        match self {
+            Rejected { remote, .. } | Timeout { remote, .. } | RendezvousTimeout { remote, .. } => {
+                Some(*remote)
+            }
+            Io(_) => None,
+        }
+    }
+
+    /// How far the handshake got before it failed
+    pub fn stage(&self) -> Option<HandshakeStage> {
+        use ConnectError::*;
+        match self {
+            Rejected { stage, .. } | Timeout { stage, .. } => Some(*stage),
+            RendezvousTimeout { .. } => Some(HandshakeStage::Rendezvous),
+            Io(_) => None,
+        }
+    }
+
+    pub fn rejection(&self) -> Option<&ConnectionReject> {
+        match self {
+            ConnectError::Rejected { rejection, .. } => Some(rejection),
+            _ => None,
+        }
+    }
+
+    /// How far the rendezvous handshake got, when it timed out
+    pub fn rendezvous_diagnostics(&self) -> Option<&RendezvousDiagnostics> {
+        match self {
+            ConnectError::RendezvousTimeout { diagnostics, .. } => Some(diagnostics),
+            _ => None,
+        }
+    }
+
+    /// The reject code, whether this side or the peer rejected the connection
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        self.rejection().map(ConnectionReject::reason)
+    }
+
+    /// The kind of the equivalent [`io::Error`]
+    pub fn kind(&self) -> io::ErrorKind {
+        use ConnectError::*;
+        match self {
+            Rejected { .. } => io::ErrorKind::ConnectionRefused,
+            Timeout { .. } | RendezvousTimeout { .. } => io::ErrorKind::TimedOut,
+            Io(error) => error.kind(),
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConnectError::*;
+        match self {
+            Rejected {
+                remote,
+                stage,
+                rejection,
+            } => write!(
+                f,
+                "connection to {} rejected during the {}: {}",
+                remote, stage, rejection
+            ),
+            Timeout { remote, stage } => {
+                write!(f, "connection to {} timed out during the {}", remote, stage)
+            }
+            RendezvousTimeout {
+                remote,
+                diagnostics,
+            } => write!(f, "rendezvous with {} timed out, {}", remote, diagnostics),
+            Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ConnectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConnectError::Rejected { rejection, .. } => Some(rejection),
+            ConnectError::Io(error) => Some(error),
+            ConnectError::Timeout { .. } | ConnectError::RendezvousTimeout { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConnectError {
+    fn from(error: io::Error) -> Self {
+        ConnectError::Io(error)
+    }
+}
+
+impl From<OptionsError> for ConnectError {
+    fn from(error: OptionsError) -> Self {
+        ConnectError::Io(error.into())
+    }
+}
+
+impl From<ConnectError> for io::Error {
+    fn from(error: ConnectError) -> Self {
+        match error {
+            ConnectError::Io(error) => error,
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
@@ -1,22 +1,42 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
-use futures::{channel::mpsc, prelude::*, select};
-use log::{error, trace};
+use futures::{
+    channel::mpsc,
+    pin_mut,
+    prelude::*,
+    select,
+    stream::{Fuse, FusedStream},
+};
+use log::{error, info, trace, warn};
+use rand::random;
 use srt_protocol::{
-    connection::{Action, Connection, ConnectionSettings, DuplexConnection, Input},
+    connection::{
+        Action, Connection, ConnectionSettings, DuplexConnection, Input, MessageControl,
+        SequencedMessage,
+    },
+    options::{CallerOptions, Valid},
     packet::TimeSpan,
 };
-use tokio::{task::JoinHandle, time::sleep_until};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, sleep_until},
+};
+
+use crate::{net::PacketSocket, watch, ConnectError, SocketStatistics, SrtSocket};
 
-use crate::{net::PacketSocket, watch, SocketStatistics, SrtSocket};
+use super::call;
+
+type InputDataReceiver = mpsc::Receiver<(MessageControl, (Instant, Bytes))>;
 
 struct SrtSocketState {
     socket: PacketSocket,
     connection: DuplexConnection,
     statistics_sender: watch::Sender<SocketStatistics>,
-    output_data_sender: mpsc::Sender<(Instant, Bytes)>,
-    input_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+    output_data_sender: mpsc::Sender<SequencedMessage>,
+    input_data_receiver: InputDataReceiver,
+    // the options of a caller that connects again when its connection breaks
+    reconnect: Option<Valid<CallerOptions>>,
 }
 
 impl SrtSocketState {
@@ -33,14 +53,56 @@ impl SrtSocketState {
     }
 
     async fn run_handler_loop(self) {
-        let local_sockid = self.connection.settings().local_sockid;
         let mut socket = self.socket;
         let mut input_data = self.input_data_receiver.fuse();
         let mut output_data = self.output_data_sender;
         let mut connection = self.connection;
         let statistics_sender = self.statistics_sender;
+        let reconnect = self.reconnect;
+        loop {
+            Self::run_connection(
+                &mut socket,
+                &mut connection,
+                &mut input_data,
+                &mut output_data,
+                &statistics_sender,
+            )
+            .await;
+
+            // reconnect when the connection was lost, not when either side shut it down or the
+            // socket was dropped
+            let options = match &reconnect {
+                Some(options)
+                    if connection.is_broken()
+                        && !input_data.is_terminated()
+                        && !output_data.is_closed() =>
+                {
+                    options
+                }
+                _ => break,
+            };
+            match Self::reconnect(&mut socket, options, &mut input_data).await {
+                Some(new_connection) => connection = DuplexConnection::new(new_connection),
+                None => break,
+            }
+        }
+        if let Err(e) = output_data.close().await {
+            error!("Error while closing data output stream {:?}", e);
+        }
+    }
+
+    async fn run_connection(
+        socket: &mut PacketSocket,
+        connection: &mut DuplexConnection,
+        input_data: &mut Fuse<InputDataReceiver>,
+        output_data: &mut mpsc::Sender<SequencedMessage>,
+        statistics_sender: &watch::Sender<SocketStatistics>,
+    ) {
+        let local_sockid = connection.settings().local_sockid;
         while connection.is_open() {
-            if connection.should_update_statistics(Instant::now()) {
+            let now = Instant::now();
+            if connection.should_update_statistics(now) {
+                connection.update_statistics(now);
                 let _ = statistics_sender.send(connection.statistics().clone());
             }
 
@@ -50,7 +112,7 @@ impl SrtSocketState {
                 }
             }
 
-            while let Some(data) = connection.next_data(Instant::now()) {
+            while let Some(data) = connection.next_sequenced_data(Instant::now()) {
                 if output_data.is_closed() {
                     continue;
                 }
@@ -70,6 +132,8 @@ impl SrtSocketState {
                 sleep_until(timeout.into()).await
             };
 
+            let input_data_fut = Self::next_input_data(connection, input_data);
+
             let input = select! {
                 // one of the entities requested wakeup
                 _ = timeout_fut.fuse() => Input::Timer,
@@ -77,19 +141,83 @@ impl SrtSocketState {
                 packet = socket.receive().fuse() =>
                     Input::Packet(packet),
                 // new packet queued
-                data = input_data.next() => {
-                    Input::Data(data)
+                data = input_data_fut.fuse() => match data {
+                    Some((control, data)) => {
+                        connection.handle_message_input(Instant::now(), control, data);
+                        Input::Timer
+                    }
+                    None => Input::Data(None),
                 }
             };
 
             match input {
                 Input::Packet(packet) => connection.handle_packet_input(Instant::now(), packet),
-                Input::Data(data) => connection.handle_data_input(Instant::now(), data),
+                Input::Data(None) => connection.handle_data_input(Instant::now(), None),
                 _ => {}
             }
         }
-        if let Err(e) = output_data.close().await {
-            error!("Error while closing data output stream {:?}", e);
+    }
+
+    // stop reading input while the send buffer is full, to apply backpressure, and once the
+    // input has ended, so the loop doesn't spin on a terminated stream
+    fn next_input_data<'a>(
+        connection: &DuplexConnection,
+        input_data: &'a mut Fuse<InputDataReceiver>,
+    ) -> impl Future<Output = Option<(MessageControl, (Instant, Bytes))>> + 'a {
+        let can_accept_data = connection.can_accept_data() && !input_data.is_terminated();
+        async move {
+            if can_accept_data {
+                input_data.next().await
+            } else {
+                future::pending().await
+            }
+        }
+    }
+
+    // connects again until connected, rejected or out of attempts by the retry policy, or until
+    // the input ends, dropping the input meanwhile as there is no connection to send it over
+    async fn reconnect(
+        socket: &mut PacketSocket,
+        options: &CallerOptions,
+        input_data: &mut Fuse<InputDataReceiver>,
+    ) -> Option<Connection> {
+        let retry = &options.socket.connect.retry;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            info!("reconnecting to {:?}, attempt {}", options.remote, attempt);
+            let delay = match attempt {
+                1 => Duration::ZERO,
+                _ => retry.jittered_interval(attempt - 1, random::<f64>() * 2.0 - 1.0),
+            };
+            let call = async {
+                sleep(delay).await;
+                call::call(socket, options).await
+            }
+            .fuse();
+            pin_mut!(call);
+
+            let result = loop {
+                select! {
+                    result = call => break result,
+                    data = input_data.next() => match data {
+                        Some(_) => trace!("dropping a message sent while reconnecting"),
+                        None => return None,
+                    },
+                }
+            };
+            match result {
+                Ok(connection) => return Some(connection),
+                Err(e @ ConnectError::Rejected { .. }) => {
+                    error!("Reconnection rejected: {}", e);
+                    return None;
+                }
+                Err(e) if retry.is_exhausted(attempt) => {
+                    error!("Failed to reconnect after {} attempts: {}", attempt, e);
+                    return None;
+                }
+                Err(e) => warn!("Failed to reconnect: {}", e),
+            }
         }
     }
 
@@ -124,12 +252,17 @@ impl SrtSocketState {
                 }
                 Action::WaitForData(wait) => {
                     let timeout = now + wait;
+                    let input_data_fut = Self::next_input_data(&connection, &mut input_data);
                     select! {
                         _ = sleep_until(timeout.into()).fuse() => Input::Timer,
                         packet = socket.receive().fuse() =>
                             Input::Packet(packet),
-                        res = input_data.next() => {
-                            Input::Data(res)
+                        res = input_data_fut.fuse() => match res {
+                            Some((control, data)) => {
+                                connection.handle_message_input(Instant::now(), control, data);
+                                Input::Timer
+                            }
+                            None => Input::Data(None),
                         }
                     }
                 }
@@ -143,8 +276,8 @@ impl SrtSocketState {
 
 #[derive(Debug)]
 pub struct SrtSocketFactory {
-    output_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
-    input_data_sender: mpsc::Sender<(Instant, Bytes)>,
+    output_data_receiver: mpsc::Receiver<SequencedMessage>,
+    input_data_sender: mpsc::Sender<(MessageControl, (Instant, Bytes))>,
     statistics_receiver: watch::Receiver<SocketStatistics>,
 }
 
@@ -153,6 +286,7 @@ impl SrtSocketFactory {
         SrtSocket {
             settings,
             output_data_receiver: self.output_data_receiver,
+            read_buffer: Bytes::new(),
             input_data_sender: self.input_data_sender,
             statistics_receiver: self.statistics_receiver,
             task,
@@ -162,8 +296,8 @@ impl SrtSocketFactory {
 
 #[derive(Debug)]
 pub struct SrtSocketTaskFactory {
-    output_data_sender: mpsc::Sender<(Instant, Bytes)>,
-    input_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+    output_data_sender: mpsc::Sender<SequencedMessage>,
+    input_data_receiver: mpsc::Receiver<(MessageControl, (Instant, Bytes))>,
     statistics_sender: watch::Sender<SocketStatistics>,
 }
 
@@ -172,15 +306,20 @@ impl SrtSocketTaskFactory {
         self,
         socket: PacketSocket,
         connection: Connection,
+        reconnect: Option<Valid<CallerOptions>>,
     ) -> (JoinHandle<()>, ConnectionSettings) {
         let settings = connection.settings.clone();
+        let connection = DuplexConnection::new(connection);
+        // the socket reports the state of the new connection until the first update
+        let _ = self.statistics_sender.send(connection.statistics().clone());
 
         let state = SrtSocketState {
             socket,
-            connection: DuplexConnection::new(connection),
+            connection,
             statistics_sender: self.statistics_sender,
             output_data_sender: self.output_data_sender,
             input_data_receiver: self.input_data_receiver,
+            reconnect,
         };
 
         let handle = tokio::spawn(async move { state.run_loop().await });
@@ -34,7 +34,13 @@ pub async fn bind_with(
             NotHandled(e) => {
                 warn!("{:?}", e);
             }
-            Reject(_, _) => todo!(),
+            Reject(rp, rr) => {
+                // keep listening for other callers after rejecting this one
+                warn!("{:?}:listen  - rejected connection: {}", socket_id, rr);
+                if let Some(packet) = rp {
+                    let _ = socket.send(packet).await?;
+                }
+            }
             Connected(p, connection) => {
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
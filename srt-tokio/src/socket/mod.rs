@@ -1,5 +1,6 @@
 mod builder;
-mod call;
+pub(crate) mod call;
+mod error;
 mod listen;
 mod rendezvous;
 
@@ -14,21 +15,30 @@ use std::{
 };
 
 use bytes::Bytes;
-use futures::{
-    channel::mpsc::{self, TrySendError},
-    prelude::*,
-    ready,
-};
+use futures::{channel::mpsc, prelude::*, ready};
 use srt_protocol::{
-    connection::ConnectionSettings,
+    connection::{ConnectionSettings, SequencedMessage},
     options::{OptionsError, OptionsOf, SocketOptions, Validation},
+    settings::KeyMaterialState,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::UdpSocket,
+    task::JoinHandle,
 };
-use tokio::{net::UdpSocket, task::JoinHandle};
 
 use super::{net::*, options::BindOptions, watch};
 
 pub use builder::SrtSocketBuilder;
-pub use srt_protocol::statistics::SocketStatistics;
+pub use error::ConnectError;
+pub use srt_protocol::{
+    connection::{Discontinuity, MessageControl, MessageInfo},
+    protocol::pending_connection::{
+        rendezvous::{CookieContest, RendezvousDiagnostics, RendezvousPhase},
+        ConnectionReject, HandshakeStage,
+    },
+    statistics::SocketStatistics,
+};
 
 /// Connected SRT connection, generally created with [`SrtSocketBuilder`](crate::SrtSocketBuilder).
 ///
@@ -37,10 +47,15 @@ pub use srt_protocol::statistics::SocketStatistics;
 ///
 /// The sockets yield and consume `(Instant, Bytes)`, representing the data and the origin instant. This instant
 /// defines when the packet will be released on the receiving side, at more or less one latency later.
+///
+/// For the stream API (file transmission with `message_api` disabled), the socket also implements
+/// `AsyncRead + AsyncWrite`, which reads and writes contiguous bytes without message boundaries.
 #[derive(Debug)]
 pub struct SrtSocket {
-    output_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
-    input_data_sender: mpsc::Sender<(Instant, Bytes)>,
+    output_data_receiver: mpsc::Receiver<SequencedMessage>,
+    // data released by the connection that has not been read yet, for AsyncRead
+    read_buffer: Bytes,
+    input_data_sender: mpsc::Sender<(MessageControl, (Instant, Bytes))>,
     statistics_receiver: watch::Receiver<SocketStatistics>,
     settings: ConnectionSettings,
     task: JoinHandle<()>,
@@ -52,9 +67,69 @@ impl SrtSocket {
     }
 
     pub fn try_send(&mut self, srctime: Instant, data: Bytes) -> Result<(), (Instant, Bytes)> {
+        self.try_send_message(MessageControl::default(), srctime, data)
+    }
+
+    /// A sink for messages with their own options, such as a time to live or a priority, for
+    /// example to send short-lived data alongside the regular stream. The connection stays open for sending as
+    /// long as one of the senders does, unless it is closed.
+    pub fn message_sender(&self) -> SrtMessageSender {
+        SrtMessageSender(self.input_data_sender.clone())
+    }
+
+    /// Like `try_send`, with options for this message only, such as a time to live
+    pub fn try_send_message(
+        &mut self,
+        control: MessageControl,
+        srctime: Instant,
+        data: Bytes,
+    ) -> Result<(), (Instant, Bytes)> {
         self.input_data_sender
-            .try_send((srctime, data))
-            .map_err(TrySendError::into_inner)
+            .try_send((control, (srctime, data)))
+            .map_err(|e| e.into_inner().1)
+    }
+
+    /// Like `poll_next` of the `Stream` implementation, along with where the message was in the
+    /// stream of the peer
+    pub fn poll_next_message(
+        &mut self,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<SequencedMessage, io::Error>>> {
+        Poll::Ready(ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx)).map(Ok))
+    }
+
+    /// The received messages along with their [`MessageInfo`], such as whether messages were
+    /// dropped before them, instead of just `(Instant, Bytes)` like the `Stream` implementation.
+    /// To send at the same time, use a [`message_sender`](SrtSocket::message_sender).
+    pub fn messages(
+        &mut self,
+    ) -> impl Stream<Item = Result<SequencedMessage, io::Error>> + Unpin + '_ {
+        stream::poll_fn(move |cx| self.poll_next_message(cx))
+    }
+
+    /// Like [`messages`](SrtSocket::messages), with an explicit [`ReceiveEvent::Discontinuity`]
+    /// before each message that follows dropped packets
+    pub fn receive_events(
+        &mut self,
+    ) -> impl Stream<Item = Result<ReceiveEvent, io::Error>> + Unpin + '_ {
+        let mut pending = None;
+        stream::poll_fn(move |cx| {
+            if let Some((info, message)) = pending.take() {
+                return Poll::Ready(Some(Ok(ReceiveEvent::Message(info, message))));
+            }
+            let (info, message) = match ready!(self.poll_next_message(cx)) {
+                Some(Ok(next)) => next,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+            Poll::Ready(Some(Ok(match info.gap.clone() {
+                Some(gap) => {
+                    pending = Some((info, message));
+                    ReceiveEvent::Discontinuity(gap)
+                }
+                None => ReceiveEvent::Message(info, message),
+            })))
+        })
     }
 
     pub fn with<O>(options: O) -> SrtSocketBuilder
@@ -65,7 +140,7 @@ impl SrtSocket {
         Self::builder().with(options)
     }
 
-    pub async fn bind(options: BindOptions) -> Result<Self, io::Error> {
+    pub async fn bind(options: BindOptions) -> Result<Self, ConnectError> {
         use BindOptions::*;
         let socket_options = match &options {
             Listen(options) => &options.socket,
@@ -76,18 +151,27 @@ impl SrtSocket {
         Self::bind_with_socket(options, socket).await
     }
 
-    async fn bind_with_socket(options: BindOptions, socket: UdpSocket) -> Result<Self, io::Error> {
+    async fn bind_with_socket(
+        options: BindOptions,
+        socket: UdpSocket,
+    ) -> Result<Self, ConnectError> {
         let socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024);
 
         use BindOptions::*;
+        let mut reconnect = None;
         let (socket, connection) = match options {
             Listen(options) => listen::bind_with(socket, options).await?,
-            Call(options) => call::bind_with(socket, options).await?,
+            Call(options) => {
+                if options.socket.connect.autoreconnect {
+                    reconnect = Some(options.clone());
+                }
+                call::bind_with(socket, options).await?
+            }
             Rendezvous(options) => rendezvous::bind_with(socket, options).await?,
         };
 
         let (new_socket, new_state) = factory::split_new();
-        let (task, settings) = new_state.spawn_task(socket, connection);
+        let (task, settings) = new_state.spawn_task(socket, connection, reconnect);
         let socket = new_socket.create_socket(settings, task);
 
         Ok(socket)
@@ -108,13 +192,28 @@ impl SrtSocket {
     pub fn statistics(&mut self) -> &mut (impl Stream<Item = SocketStatistics> + Clone) {
         &mut self.statistics_receiver
     }
+
+    /// The statistics as of the last update, which happens every statistics interval
+    pub fn last_statistics(&self) -> SocketStatistics {
+        self.statistics_receiver.borrow().clone()
+    }
+
+    /// Whether the peer can decrypt the sent data, as of the last statistics update
+    pub fn send_key_material_state(&self) -> KeyMaterialState {
+        self.statistics_receiver.borrow().tx_key_material_state
+    }
+
+    /// Whether the received data can be decrypted, as of the last statistics update
+    pub fn recv_key_material_state(&self) -> KeyMaterialState {
+        self.statistics_receiver.borrow().rx_key_material_state
+    }
 }
 
 impl Stream for SrtSocket {
     type Item = Result<(Instant, Bytes), io::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        Poll::Ready(ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx)).map(Ok))
+        Poll::Ready(ready!(self.poll_next_message(cx)).map(|message| Ok(message?.1)))
     }
 }
 
@@ -129,7 +228,7 @@ impl Sink<(Instant, Bytes)> for SrtSocket {
     }
     fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
         self.input_data_sender
-            .start_send(item)
+            .start_send((MessageControl::default(), item))
             .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
     }
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
@@ -143,3 +242,88 @@ impl Sink<(Instant, Bytes)> for SrtSocket {
             .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
     }
 }
+
+/// What was received by an [`SrtSocket`], see [`SrtSocket::receive_events`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReceiveEvent {
+    /// Packets were dropped before the next message, for example to reset a demuxer
+    Discontinuity(Discontinuity),
+    Message(MessageInfo, (Instant, Bytes)),
+}
+
+/// Sends messages over an [`SrtSocket`], each with its own [`MessageControl`], see
+/// [`SrtSocket::message_sender`]
+#[derive(Debug, Clone)]
+pub struct SrtMessageSender(mpsc::Sender<(MessageControl, (Instant, Bytes))>);
+
+impl Sink<(MessageControl, (Instant, Bytes))> for SrtMessageSender {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.0)
+            .poll_ready(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        item: (MessageControl, (Instant, Bytes)),
+    ) -> Result<(), Self::Error> {
+        self.0
+            .start_send(item)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.0)
+            .poll_flush(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.0)
+            .poll_close(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+}
+
+impl AsyncRead for SrtSocket {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.read_buffer.is_empty() {
+            match ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx)) {
+                Some((_, (_, data))) => self.read_buffer = data,
+                // end of stream, nothing is put in the buffer
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+
+        let len = self.read_buffer.len().min(buf.remaining());
+        buf.put_slice(&self.read_buffer.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for SrtSocket {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        // there is nothing to send, and an empty message would still go on the wire
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(Sink::poll_ready(self.as_mut(), cx))?;
+        self.start_send((Instant::now(), Bytes::copy_from_slice(buf)))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Sink::poll_flush(self, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Sink::poll_close(self, cx)
+    }
+}
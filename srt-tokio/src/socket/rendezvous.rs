@@ -1,37 +1,47 @@
-use std::{
-    io,
-    time::{Duration, Instant},
-};
+use std::time::Instant;
 
 use futures::{prelude::*, select};
 use log::{debug, warn};
-use tokio::time::interval;
+use tokio::time::{interval, sleep_until};
 
 use srt_protocol::{
     connection::Connection,
     options::*,
+    protocol::pending_connection::HandshakeStage,
     protocol::pending_connection::{rendezvous::Rendezvous, ConnectionResult},
     settings::*,
 };
 
 use crate::net::{lookup_remote_host, PacketSocket};
 
+use super::ConnectError;
+
 pub async fn bind_with(
     mut socket: PacketSocket,
     options: Valid<RendezvousOptions>,
-) -> Result<(PacketSocket, Connection), io::Error> {
+) -> Result<(PacketSocket, Connection), ConnectError> {
     let local_addr = options.socket.connect.local;
     let remote_public = lookup_remote_host(&options.remote).await?;
     let starting_seqno = rand::random();
     let init_settings: ConnInitSettings = options.socket.clone().into();
     let socket_id = init_settings.local_sockid;
 
-    let mut tick_interval = interval(Duration::from_millis(100));
+    // as with libsrt, the peers may start a while apart, so they wait longer than callers do
+    let deadline = Instant::now() + options.socket.connect.timeout * 10;
+    let mut tick_interval = interval(options.socket.connect.waving_interval);
     let mut rendezvous = Rendezvous::new(local_addr, remote_public, init_settings, starting_seqno);
     loop {
         let result = select! {
             now = tick_interval.tick().fuse() => rendezvous.handle_tick(now.into()),
             packet = socket.receive().fuse() => rendezvous.handle_packet(packet, Instant::now()),
+            _ = sleep_until(deadline.into()).fuse() => {
+                let diagnostics = rendezvous.diagnostics();
+                warn!("rendezvous {:?} timed out, {}", socket_id, diagnostics);
+                return Err(ConnectError::RendezvousTimeout {
+                    remote: remote_public,
+                    diagnostics,
+                });
+            }
         };
 
         debug!("{:?}:rendezvous - {:?}", socket_id, result);
@@ -43,7 +53,16 @@ pub async fn bind_with(
             NotHandled(e) => {
                 warn!("rendezvous {:?} error: {}", socket_id, e);
             }
-            Reject(_, _) => todo!(),
+            Reject(rp, rr) => {
+                if let Some(packet) = rp {
+                    let _ = socket.send(packet).await?;
+                }
+                return Err(ConnectError::Rejected {
+                    remote: remote_public,
+                    stage: HandshakeStage::Rendezvous,
+                    rejection: rr,
+                });
+            }
             Connected(p, connection) => {
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
@@ -52,7 +71,7 @@ pub async fn bind_with(
             }
             NoAction => {}
             RequestAccess(_) => {}
-            Failure(error) => return Err(error),
+            Failure(error) => return Err(error.into()),
         }
     }
 }
@@ -30,6 +30,13 @@ pub struct Receiver<T: 'static + Debug + Default + Clone + Send + Sync + Unpin>(
     WatchStream<T>,
 );
 
+impl<T: 'static + Debug + Default + Clone + Send + Sync + Unpin> Receiver<T> {
+    /// The most recently sent value
+    pub fn borrow(&self) -> watch::Ref<'_, T> {
+        self.0.borrow()
+    }
+}
+
 impl<T: 'static + Debug + Default + Clone + Send + Sync + Unpin> Clone for Receiver<T> {
     fn clone(&self) -> Self {
         let stream = WatchStream::new(self.0.clone());
@@ -1,21 +1,25 @@
 use std::{
     convert::{TryFrom, TryInto},
     io,
-    time::Instant,
+    net::SocketAddr,
+    time::{Duration, Instant},
 };
 
+use assert_matches::assert_matches;
 use bytes::Bytes;
 use futures::{future::try_join_all, stream, SinkExt, StreamExt};
 use log::info;
 
 use srt_protocol::{
-    access::*, packet::CoreRejectReason, protocol::pending_connection::ConnectionReject,
-    settings::KeySettings,
+    access::*,
+    packet::CoreRejectReason,
+    protocol::pending_connection::AccessControlRequest,
+    settings::{KeyMaterialRefreshSettings, KeySettings},
 };
 
 use srt_tokio::{
-    options::{KeySize, StreamId},
-    SrtListener, SrtSocket,
+    options::{KeySize, PacketSize, SrtVersion, StreamId},
+    ConnectError, ConnectionReject, ConnectionRequest, HandshakeStage, SrtListener, SrtSocket,
 };
 
 fn accept(streamid: Option<&StreamId>) -> Result<AcceptParameters, RejectReason> {
@@ -93,10 +97,10 @@ async fn streamid() -> io::Result<()> {
                 let err = recvr.unwrap_err();
                 assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
                 assert_eq!(
-                    err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>()),
-                    Some(Some(&ConnectionReject::Rejected(
+                    err.rejection(),
+                    Some(&ConnectionReject::Rejected(
                         ServerRejectReason::BadRequest.into()
-                    )))
+                    ))
                 );
                 return;
             }
@@ -177,12 +181,301 @@ async fn set_password() {
         .unwrap_err();
     assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
     assert_eq!(
-        err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>()),
-        Some(Some(&ConnectionReject::Rejected(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(
             CoreRejectReason::BadSecret.into()
-        )))
+        ))
     );
 
     server.close().await;
     listener.await.unwrap();
 }
+
+// a per tenant policy, taking the handshake of the caller into account
+struct TenantAcceptor;
+
+impl StreamAcceptor for TenantAcceptor {
+    fn accept(&mut self, request: &AccessControlRequest) -> Result<AcceptParameters, Rejection> {
+        assert_eq!(request.peer_version, SrtVersion::CURRENT);
+        assert_eq!(request.peer_send_latency, Duration::from_millis(300));
+
+        let mut parameters = AcceptParameters::new();
+        match request.stream_id.as_ref().map(|s| s.as_str()) {
+            Some("premium") => {
+                parameters
+                    .set_key_settings("premium-secret", 32)
+                    .set_key_refresh(KeyMaterialRefreshSettings::new(1 << 20, 1 << 12).unwrap())
+                    .set_latency(Duration::from_millis(500))
+                    .set_max_payload_size(PacketSize(1000));
+            }
+            Some("basic") => {
+                parameters.set_latency(Duration::from_millis(50));
+            }
+            _ => return Err(ServerRejectReason::Unauthorized.into()),
+        }
+        Ok(parameters)
+    }
+}
+
+#[tokio::test]
+async fn per_connection_settings() {
+    let (mut server, mut incoming) = SrtListener::builder().bind(6250).await.unwrap();
+
+    let listener = tokio::spawn(async move {
+        let mut acceptor = TenantAcceptor;
+        while let Some(request) = incoming.incoming().next().await {
+            let parameters = acceptor.accept(request.request());
+            let socket = match parameters {
+                Ok(parameters) => request.accept_with(parameters).await.unwrap(),
+                Err(reason) => {
+                    request.reject(reason).await.unwrap();
+                    continue;
+                }
+            };
+            let settings = socket.settings();
+            match settings.stream_id.as_deref() {
+                Some("premium") => {
+                    assert_eq!(settings.recv_tsbpd_latency, Duration::from_millis(500));
+                    assert_eq!(settings.max_packet_size, PacketSize(1000));
+                    assert!(settings.cipher.is_some());
+                }
+                _ => {
+                    assert_eq!(settings.recv_tsbpd_latency, Duration::from_millis(300));
+                    assert!(settings.cipher.is_none());
+                }
+            }
+        }
+    });
+
+    let socket = SrtSocket::builder()
+        .latency(Duration::from_millis(300))
+        .encryption(32, "premium-secret")
+        .call("127.0.0.1:6250", Some("premium"))
+        .await
+        .unwrap();
+    assert_eq!(
+        socket.settings().send_tsbpd_latency,
+        Duration::from_millis(500)
+    );
+    assert_eq!(socket.settings().max_packet_size, PacketSize(1000));
+
+    let socket = SrtSocket::builder()
+        .latency(Duration::from_millis(300))
+        .call("127.0.0.1:6250", Some("basic"))
+        .await
+        .unwrap();
+    assert_eq!(
+        socket.settings().send_tsbpd_latency,
+        Duration::from_millis(300)
+    );
+
+    let err = SrtSocket::builder()
+        .latency(Duration::from_millis(300))
+        .call("127.0.0.1:6250", Some("unknown"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(
+            ServerRejectReason::Unauthorized.into()
+        ))
+    );
+
+    server.close().await;
+    listener.await.unwrap();
+}
+
+// stands in for a lookup of the stream id in a database
+async fn authorize(stream_id: Option<StreamId>) -> Result<AcceptParameters, Rejection> {
+    match stream_id.as_ref().map(|s| s.as_str()) {
+        Some("slow") => {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            Ok(AcceptParameters::new())
+        }
+        Some("known") => {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(AcceptParameters::new())
+        }
+        _ => Err(ServerRejectReason::Forbidden.into()),
+    }
+}
+
+#[tokio::test]
+async fn async_authorization() {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut server, mut incoming) = SrtListener::builder().bind(6251).await.unwrap();
+    let listener = tokio::spawn(async move {
+        let acceptor = |request: &AccessControlRequest| authorize(request.stream_id.clone());
+        let mut sockets = incoming.authorized(acceptor, Duration::from_millis(500));
+        while let Some(mut socket) = sockets.next().await {
+            assert_eq!(socket.settings().stream_id.as_deref(), Some("known"));
+            socket.close().await.unwrap();
+        }
+    });
+
+    let call = |stream_id: &'static str| async move {
+        let start = Instant::now();
+        let result = SrtSocket::builder()
+            .call("127.0.0.1:6251", Some(stream_id))
+            .await;
+        (result, start.elapsed())
+    };
+
+    // the slow authorization does not hold up the others
+    let ((slow, slow_elapsed), (known, known_elapsed), (unknown, _)) =
+        futures::join!(call("slow"), call("known"), call("unknown"));
+
+    assert!(known_elapsed < Duration::from_millis(500));
+    known.unwrap().close().await.unwrap();
+
+    let err = unknown.unwrap_err();
+    assert_eq!(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(
+            ServerRejectReason::Forbidden.into()
+        ))
+    );
+
+    let err = slow.unwrap_err();
+    assert_eq!(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(
+            ServerRejectReason::Unauthorized.into()
+        ))
+    );
+    assert!(slow_elapsed < Duration::from_secs(2));
+
+    server.close().await;
+    listener.await.unwrap();
+}
+
+#[tokio::test]
+async fn authorize_request() {
+    let (mut server, mut incoming) = SrtListener::builder().bind(6252).await.unwrap();
+    let listener = tokio::spawn(async move {
+        let mut acceptor = |request: &AccessControlRequest| authorize(request.stream_id.clone());
+        let request: ConnectionRequest = incoming.incoming().next().await.unwrap();
+        let socket = request
+            .authorize(&mut acceptor, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert!(socket.is_some());
+
+        let request = incoming.incoming().next().await.unwrap();
+        let socket = request
+            .authorize(&mut acceptor, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert!(socket.is_none());
+    });
+
+    SrtSocket::builder()
+        .call("127.0.0.1:6252", Some("known"))
+        .await
+        .unwrap()
+        .close()
+        .await
+        .unwrap();
+
+    let err = SrtSocket::builder()
+        .call("127.0.0.1:6252", Some("slow"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(
+            ServerRejectReason::Unauthorized.into()
+        ))
+    );
+
+    listener.await.unwrap();
+    server.close().await;
+}
+
+// a user defined reject code, with a message that stays on the listener
+struct UserCodeAcceptor;
+
+impl StreamAcceptor for UserCodeAcceptor {
+    fn accept(&mut self, request: &AccessControlRequest) -> Result<AcceptParameters, Rejection> {
+        match request.stream_id.as_ref().map(|s| s.as_str()) {
+            Some("live") => Ok(AcceptParameters::new()),
+            Some(stream_id) => {
+                Err(Rejection::user(3404, format!("no stream {}", stream_id)).unwrap())
+            }
+            None => Err(ServerRejectReason::BadRequest.into()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn user_reject_code() {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut server, mut incoming) = SrtListener::builder().bind(6260).await.unwrap();
+    let listener = tokio::spawn(async move {
+        let mut acceptor = UserCodeAcceptor;
+        while let Some(request) = incoming.incoming().next().await {
+            match acceptor.accept(request.request()) {
+                Ok(parameters) => {
+                    request.accept_with(parameters).await.unwrap();
+                }
+                Err(rejection) => request.reject(rejection).await.unwrap(),
+            }
+        }
+    });
+
+    let err = SrtSocket::builder()
+        .call("127.0.0.1:6260", Some("unknown"))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    assert_eq!(err.reject_reason(), Some(RejectReason::User(3404)));
+    assert_eq!(
+        err.rejection(),
+        Some(&ConnectionReject::Rejected(RejectReason::User(3404)))
+    );
+    assert_eq!(err.remote(), Some(SocketAddr::from(([127, 0, 0, 1], 6260))));
+    // the listener only decides once it has the stream id of the conclusion
+    assert_eq!(err.stage(), Some(HandshakeStage::Conclusion));
+
+    let err = SrtSocket::builder()
+        .call("127.0.0.1:6260", None)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ConnectError::Rejected {
+            rejection: ConnectionReject::Rejected(RejectReason::Server(
+                ServerRejectReason::BadRequest
+            )),
+            ..
+        }
+    );
+
+    SrtSocket::builder()
+        .call("127.0.0.1:6260", Some("live"))
+        .await
+        .unwrap()
+        .close()
+        .await
+        .unwrap();
+
+    server.close().await;
+    listener.await.unwrap();
+}
+
+#[test]
+fn rejection_message() {
+    let rejection = Rejection::user(3404, "no stream unknown").unwrap();
+    assert_eq!(rejection.reason(), RejectReason::User(3404));
+    assert_eq!(rejection.message(), Some("no stream unknown"));
+    assert_eq!(rejection.to_string(), "User error: 3404: no stream unknown");
+
+    // the codes below are reserved for the core and server reject reasons
+    assert_eq!(Rejection::user(2999, "not a user code"), None);
+
+    let rejection = Rejection::from(ServerRejectReason::Forbidden);
+    assert_eq!(rejection.reason(), ServerRejectReason::Forbidden.into());
+    assert_eq!(rejection.message(), None);
+}
@@ -1,9 +1,17 @@
-use std::time::{Duration, Instant};
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
-use srt_tokio::SrtSocket;
+use srt_protocol::{
+    packet::{CipherType, CoreRejectReason},
+    settings::KeyMaterialState,
+};
+use srt_tokio::{options::CryptoMode, ConnectError, ConnectionReject, SrtSocket};
 
 use bytes::Bytes;
-use futures::{SinkExt, TryStreamExt};
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use log::info;
 
 use tokio::{spawn, time::sleep};
@@ -49,5 +57,177 @@ async fn crypto_exchange() {
     test_crypto(32).await;
 }
 
-// TODO: bad password
-// TODO: mismatch
+#[tokio::test]
+async fn crypto_mode_gcm() {
+    let _ = pretty_env_logger::try_init();
+
+    // the listener accepts the cipher of the caller
+    let listener = SrtSocket::builder()
+        .encryption(16, "password123")
+        .listen_on(":6240");
+
+    let caller = SrtSocket::builder()
+        .encryption(16, "password123")
+        .set(|options| options.encryption.crypto_mode = CryptoMode::AesGcm)
+        .call("127.0.0.1:6240", None);
+
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+    for socket in [&listener, &caller] {
+        let cipher = socket.settings().cipher.as_ref().unwrap().cipher();
+        assert_eq!(cipher, CipherType::Gcm);
+    }
+
+    caller
+        .send((Instant::now(), Bytes::from("Hello")))
+        .await
+        .unwrap();
+    let (_, by) = listener.try_next().await.unwrap().unwrap();
+    assert_eq!(&by[..], b"Hello");
+
+    caller.close().await.unwrap();
+    listener.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn crypto_mode_mismatch() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = spawn(
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .set(|options| options.encryption.crypto_mode = CryptoMode::AesCtr)
+            .listen_on(":6241"),
+    );
+
+    let result = SrtSocket::builder()
+        .encryption(16, "password123")
+        .set(|options| options.encryption.crypto_mode = CryptoMode::AesGcm)
+        .call("127.0.0.1:6241", None)
+        .await;
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+
+    listener.abort();
+}
+
+fn reject_reason(error: ConnectError) -> Option<ConnectionReject> {
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+    let reject = error.rejection()?;
+    Some(match reject {
+        ConnectionReject::Rejecting(r) => ConnectionReject::Rejecting(*r),
+        ConnectionReject::Rejected(r) => ConnectionReject::Rejected(*r),
+    })
+}
+
+#[tokio::test]
+async fn enforced_encryption() {
+    let _ = pretty_env_logger::try_init();
+
+    // the listener keeps listening after rejecting a caller
+    let listener = spawn(
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .listen_on(":6242"),
+    );
+
+    // the listener rejects a wrong passphrase
+    let error = SrtSocket::builder()
+        .encryption(16, "password456")
+        .call("127.0.0.1:6242", None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        reject_reason(error),
+        Some(ConnectionReject::Rejected(
+            CoreRejectReason::BadSecret.into()
+        ))
+    );
+
+    // ...and a missing one
+    let error = SrtSocket::builder()
+        .call("127.0.0.1:6242", None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        reject_reason(error),
+        Some(ConnectionReject::Rejected(
+            CoreRejectReason::Unsecure.into()
+        ))
+    );
+
+    listener.abort();
+
+    // a caller enforcing encryption rejects the response of a lenient listener
+    let listener = spawn(
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .set(|options| options.encryption.enforced_encryption = false)
+            .listen_on(":6243"),
+    );
+    let error = SrtSocket::builder()
+        .encryption(16, "password456")
+        .call("127.0.0.1:6243", None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        reject_reason(error),
+        Some(ConnectionReject::Rejecting(
+            CoreRejectReason::BadSecret.into()
+        ))
+    );
+    listener.abort();
+}
+
+#[tokio::test]
+async fn unenforced_encryption() {
+    let _ = pretty_env_logger::try_init();
+
+    let connect = |listener_passphrase: Option<&'static str>,
+                   caller_passphrase: Option<&'static str>,
+                   port: u16| async move {
+        let mut listener =
+            SrtSocket::builder().set(|options| options.encryption.enforced_encryption = false);
+        if let Some(passphrase) = listener_passphrase {
+            listener = listener.encryption(16, passphrase);
+        }
+        let mut caller =
+            SrtSocket::builder().set(|options| options.encryption.enforced_encryption = false);
+        if let Some(passphrase) = caller_passphrase {
+            caller = caller.encryption(16, passphrase);
+        }
+        futures::try_join!(
+            listener.listen_on(port),
+            caller.call(SocketAddr::from(([127, 0, 0, 1], port)), None)
+        )
+        .unwrap()
+    };
+
+    // (send, receive) key material states
+    let states = |socket: &SrtSocket| {
+        (
+            socket.send_key_material_state(),
+            socket.recv_key_material_state(),
+        )
+    };
+    use KeyMaterialState::*;
+
+    // the caller encrypts with keys the listener could not unwrap, the listener does not encrypt
+    let (listener, caller) = connect(Some("password123"), Some("password456"), 6244).await;
+    assert_eq!(states(&listener), (Unsecured, BadSecret));
+    assert_eq!(states(&caller), (BadSecret, Unsecured));
+
+    let (listener, mut caller) = connect(Some("password123"), None, 6245).await;
+    assert_eq!(states(&listener), (Unsecured, Unsecured));
+    assert_eq!(states(&caller), (Unsecured, Unsecured));
+    caller.close().await.unwrap();
+
+    let (mut listener, mut caller) = connect(Some("password123"), Some("password123"), 6246).await;
+    assert_eq!(states(&listener), (Secured, Secured));
+    assert_eq!(states(&caller), (Secured, Secured));
+
+    let statistics = listener.statistics().next().await.unwrap();
+    assert_eq!(statistics.tx_key_material_state, Secured);
+    assert_eq!(statistics.rx_key_material_state, Secured);
+
+    caller.close().await.unwrap();
+    listener.close().await.unwrap();
+}
@@ -0,0 +1,84 @@
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use assert_matches::assert_matches;
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use srt_tokio::{
+    options::{Connect, RetryPolicy},
+    ConnectError, SrtListener, SrtSocket,
+};
+
+#[tokio::test]
+async fn dual_stack_listener() {
+    let _ = pretty_env_logger::try_init();
+
+    let (_server, mut incoming) = SrtListener::builder().bind("[::]:6263").await.unwrap();
+
+    let listener = tokio::spawn(async move {
+        let mut remotes = vec![];
+        for _ in 0..2 {
+            let request = incoming.incoming().next().await.unwrap();
+            remotes.push(request.remote().ip());
+            let mut socket = request.accept(None).await.unwrap();
+            socket.send((Instant::now(), "hello".into())).await.unwrap();
+            socket.close().await.unwrap();
+        }
+        remotes
+    });
+
+    for remote in ["127.0.0.1:6263", "[::1]:6263"] {
+        let mut caller = SrtSocket::builder().call(remote, None).await.unwrap();
+        let (_, message) = caller.try_next().await.unwrap().unwrap();
+        assert_eq!(message, "hello");
+    }
+
+    // IPv4 callers are reported at their IPv4 addresses, not the IPv4-mapped ones
+    let remotes = listener.await.unwrap();
+    assert_eq!(
+        remotes,
+        [
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "::1".parse::<IpAddr>().unwrap()
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ipv6_only_listener() {
+    let _ = pretty_env_logger::try_init();
+
+    let (_server, mut incoming) = SrtListener::builder()
+        .with(Connect {
+            ipv6_only: true,
+            ..Default::default()
+        })
+        .bind("[::]:6264")
+        .await
+        .unwrap();
+
+    let listener = tokio::spawn(async move {
+        let request = incoming.incoming().next().await.unwrap();
+        let remote = request.remote().ip();
+        request.accept(None).await.unwrap();
+        remote
+    });
+
+    let error = SrtSocket::builder()
+        .retry(RetryPolicy {
+            max_attempts: Some(3),
+            ..Default::default()
+        })
+        .call("127.0.0.1:6264", None)
+        .await
+        .unwrap_err();
+    assert_matches!(error, ConnectError::Timeout { .. });
+
+    SrtSocket::builder()
+        .latency(Duration::from_millis(20))
+        .call("[::1]:6264", None)
+        .await
+        .unwrap();
+    assert_eq!(listener.await.unwrap(), "::1".parse::<IpAddr>().unwrap());
+}
@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::{stream, SinkExt, StreamExt, TryStreamExt};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::timeout,
+};
+
+use srt_protocol::packet::CoreRejectReason;
+use srt_tokio::{options::*, SrtSocket};
+
+const MESSAGE_COUNT: usize = 10_000;
+
+#[tokio::test]
+async fn file_transmission() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let sender_fut = async {
+        let mut tx = SrtSocket::builder()
+            .transmission_type(TransmissionType::File)
+            .set(|options| {
+                options.sender.buffer_size = ByteCount(8192 * 1500);
+                options.connect.udp_send_buffer_size = ByteCount(5_000_000);
+            })
+            .listen_on(":6100")
+            .await?;
+
+        // send as fast as possible, file transmission applies backpressure instead of dropping
+        let mut messages = stream::iter(0..MESSAGE_COUNT)
+            .map(|i| Ok((Instant::now(), Bytes::from(vec![(i % 256) as u8; 1316]))));
+        tx.send_all(&mut messages).await?;
+        tx.close().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        let mut rx = SrtSocket::builder()
+            .transmission_type(TransmissionType::File)
+            .set(|options| options.connect.udp_recv_buffer_size = ByteCount(5_000_000))
+            .call("127.0.0.1:6100", None)
+            .await?;
+
+        assert_eq!(rx.settings().transmission_type, TransmissionType::File);
+
+        let mut count = 0;
+        while let Some((_, data)) = rx.try_next().await? {
+            assert_eq!(data, Bytes::from(vec![(count % 256) as u8; 1316]));
+            count += 1;
+        }
+        assert_eq!(count, MESSAGE_COUNT);
+
+        Ok::<_, Error>(())
+    };
+
+    timeout(Duration::from_secs(30), async {
+        futures::try_join!(sender_fut, receiver_fut)
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn transmission_type_mismatch() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = SrtSocket::builder()
+        .transmission_type(TransmissionType::File)
+        .listen_on(":6101");
+
+    let caller = async {
+        SrtSocket::builder()
+            .set(|options| options.connect.timeout = Duration::from_secs(2))
+            .call("127.0.0.1:6101", None)
+            .await
+    };
+
+    let (_, caller) = futures::join!(
+        timeout(Duration::from_secs(3), listener),
+        timeout(Duration::from_secs(3), caller)
+    );
+    assert_eq!(
+        caller.unwrap().unwrap_err().reject_reason(),
+        Some(CoreRejectReason::Congestion.into())
+    );
+}
+
+#[tokio::test]
+async fn stream_api() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let data: Vec<u8> = (0..5_000_000).map(|i| (i % 251) as u8).collect();
+
+    let sender_fut = async {
+        let mut tx = SrtSocket::builder()
+            .transmission_type(TransmissionType::File)
+            .message_api(false)
+            .set(|options| {
+                options.sender.buffer_size = ByteCount(8192 * 1500);
+                options.connect.udp_send_buffer_size = ByteCount(5_000_000);
+            })
+            .listen_on(":6102")
+            .await?;
+
+        tokio::io::copy(&mut &data[..], &mut tx).await?;
+        tx.shutdown().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        let mut rx = SrtSocket::builder()
+            .transmission_type(TransmissionType::File)
+            .message_api(false)
+            .set(|options| options.connect.udp_recv_buffer_size = ByteCount(5_000_000))
+            .call("127.0.0.1:6102", None)
+            .await?;
+
+        assert!(!rx.settings().message_api);
+
+        let mut received = Vec::new();
+        rx.read_to_end(&mut received).await?;
+
+        Ok::<_, Error>(received)
+    };
+
+    let ((), received) = timeout(Duration::from_secs(30), async {
+        futures::try_join!(sender_fut, receiver_fut)
+    })
+    .await??;
+    assert!(received == data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn message_api_mismatch() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = SrtSocket::builder()
+        .transmission_type(TransmissionType::File)
+        .message_api(false)
+        .listen_on(":6103");
+
+    let caller = async {
+        SrtSocket::builder()
+            .transmission_type(TransmissionType::File)
+            .set(|options| options.connect.timeout = Duration::from_secs(2))
+            .call("127.0.0.1:6103", None)
+            .await
+    };
+
+    let (_, caller) = futures::join!(
+        timeout(Duration::from_secs(3), listener),
+        timeout(Duration::from_secs(3), caller)
+    );
+    assert_eq!(
+        caller.unwrap().unwrap_err().reject_reason(),
+        Some(CoreRejectReason::MessageApi.into())
+    );
+}
@@ -0,0 +1,191 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::{net::UdpSocket, task::JoinHandle, time::sleep};
+
+use srt_tokio::{GroupType, SrtGroup, SrtSocket};
+
+const MESSAGE_COUNT: u32 = 300;
+
+// Forwards packets between a single client and the server, unless the link is cut
+async fn relay(local: u16, server: u16, link: Arc<AtomicBool>) -> Result<JoinHandle<()>, Error> {
+    let front = UdpSocket::bind(("127.0.0.1", local)).await?;
+    let back = UdpSocket::bind("127.0.0.1:0").await?;
+    let server: SocketAddr = ([127, 0, 0, 1], server).into();
+    Ok(tokio::spawn(async move {
+        let mut client = None;
+        let mut front_buf = [0; 2048];
+        let mut back_buf = [0; 2048];
+        loop {
+            tokio::select! {
+                Ok((size, from)) = front.recv_from(&mut front_buf) => {
+                    client = Some(from);
+                    if link.load(Ordering::SeqCst) {
+                        let _ = back.send_to(&front_buf[..size], server).await;
+                    }
+                }
+                Ok((size, _)) = back.recv_from(&mut back_buf) => {
+                    if let (Some(client), true) = (client, link.load(Ordering::SeqCst)) {
+                        let _ = front.send_to(&back_buf[..size], client).await;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[tokio::test]
+async fn main_backup_failover() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let main_link = Arc::new(AtomicBool::new(true));
+    let relays = [
+        relay(6201, 6200, main_link.clone()).await?,
+        relay(6202, 6200, Arc::new(AtomicBool::new(true))).await?,
+    ];
+
+    let sender_fut = async {
+        let mut tx = SrtGroup::builder()
+            .latency(Duration::from_millis(500))
+            .set(|options| options.session.peer_idle_timeout = Duration::from_secs(2))
+            .member("127.0.0.1:6201", 10)
+            .member("127.0.0.1:6202", 5)
+            .call(None)
+            .await?;
+
+        for i in 0..MESSAGE_COUNT {
+            if i == MESSAGE_COUNT / 3 {
+                main_link.store(false, Ordering::SeqCst);
+            }
+            tx.send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(5)).await;
+        }
+        tx.close().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        let rx = SrtGroup::builder()
+            .latency(Duration::from_millis(500))
+            .set(|options| options.session.peer_idle_timeout = Duration::from_secs(2))
+            .listen_on(":6200")
+            .await?;
+
+        let received: Vec<_> = rx.map_ok(|(_, data)| data).try_collect().await?;
+        let expected: Vec<_> = (0..MESSAGE_COUNT)
+            .map(|i| Bytes::from(i.to_string()))
+            .collect();
+        assert_eq!(received, expected);
+
+        Ok::<_, Error>(())
+    };
+
+    futures::try_join!(sender_fut, receiver_fut)?;
+    for relay in relays {
+        relay.abort();
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn broadcast() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let links = [
+        Arc::new(AtomicBool::new(true)),
+        Arc::new(AtomicBool::new(true)),
+    ];
+    let relays = [
+        relay(6221, 6220, links[0].clone()).await?,
+        relay(6222, 6220, links[1].clone()).await?,
+    ];
+
+    let sender_fut = async {
+        let mut tx = SrtGroup::builder()
+            .group_type(GroupType::Broadcast)
+            .latency(Duration::from_millis(500))
+            .set(|options| options.session.peer_idle_timeout = Duration::from_secs(2))
+            .member("127.0.0.1:6221", 0)
+            .member("127.0.0.1:6222", 0)
+            .call(None)
+            .await?;
+
+        // cut each link for a while, there is always one link left
+        for i in 0..MESSAGE_COUNT {
+            if i == MESSAGE_COUNT / 3 {
+                links[0].store(false, Ordering::SeqCst);
+            }
+            if i == MESSAGE_COUNT / 2 {
+                links[0].store(true, Ordering::SeqCst);
+            }
+            if i == 2 * MESSAGE_COUNT / 3 {
+                links[1].store(false, Ordering::SeqCst);
+            }
+            tx.send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(5)).await;
+        }
+        links[1].store(true, Ordering::SeqCst);
+        tx.close().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        let mut rx = SrtGroup::builder()
+            .group_type(GroupType::Broadcast)
+            .latency(Duration::from_millis(500))
+            .set(|options| options.session.peer_idle_timeout = Duration::from_secs(2))
+            .listen_on(":6220")
+            .await?;
+        let mut statistics = rx.statistics().clone();
+
+        let received: Vec<_> = rx.map_ok(|(_, data)| data).try_collect().await?;
+        let expected: Vec<_> = (0..MESSAGE_COUNT)
+            .map(|i| Bytes::from(i.to_string()))
+            .collect();
+        assert_eq!(received, expected);
+
+        // both members received data, the duplicates are only counted once by the group
+        let statistics = statistics.next().await.unwrap();
+        assert_eq!(statistics.members.len(), 2);
+        assert!(statistics.members.iter().all(|m| m.statistics.rx_data > 0));
+        assert!(statistics.aggregate.rx_data > statistics.aggregate.rx_unique_data);
+
+        Ok::<_, Error>(())
+    };
+
+    futures::try_join!(sender_fut, receiver_fut)?;
+    for relay in relays {
+        relay.abort();
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn group_member_rejected_by_socket() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(SrtSocket::builder().listen_on(":6210"));
+
+    let result = SrtGroup::builder()
+        .member("127.0.0.1:6210", 1)
+        .call(None)
+        .await;
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+
+    listener.abort();
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::{net::UdpSocket, task::JoinHandle, time::sleep};
+
+use srt_tokio::{options::*, MessageControl, ReceiveEvent, SrtSocket};
+
+const MESSAGE_COUNT: u32 = 100;
+
+// Forwards packets between a single client and the server, never delivering data packets from
+// the client whose payload starts with `prefix`, not even retransmissions
+async fn blackhole_relay(
+    local: u16,
+    server: u16,
+    prefix: &'static [u8],
+) -> Result<JoinHandle<()>, Error> {
+    let front = UdpSocket::bind(("127.0.0.1", local)).await?;
+    let back = UdpSocket::bind("127.0.0.1:0").await?;
+    let server: SocketAddr = ([127, 0, 0, 1], server).into();
+    Ok(tokio::spawn(async move {
+        let mut client = None;
+        let mut front_buf = [0; 2048];
+        let mut back_buf = [0; 2048];
+        loop {
+            tokio::select! {
+                Ok((size, from)) = front.recv_from(&mut front_buf) => {
+                    client = Some(from);
+                    let is_data = front_buf[0] & 0x80 == 0;
+                    if !(is_data && front_buf[16..size].starts_with(prefix)) {
+                        let _ = back.send_to(&front_buf[..size], server).await;
+                    }
+                }
+                Ok((size, _)) = back.recv_from(&mut back_buf) => {
+                    if let Some(client) = client {
+                        let _ = front.send_to(&back_buf[..size], client).await;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[tokio::test]
+async fn expired_messages_are_dropped() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let relay = blackhole_relay(6255, 6254, b"telemetry").await?;
+
+    let sender_fut = async {
+        let mut tx = SrtSocket::builder()
+            .latency(Duration::from_secs(2))
+            .set(|options| {
+                // large enough that nothing is dropped for lack of room
+                options.sender.buffer_size = ByteCount(8192 * 1500);
+                options.session.statistics_interval = Duration::from_millis(200);
+            })
+            .call("127.0.0.1:6255", None)
+            .await?;
+
+        // the telemetry never makes it, it has to be dropped long before the latency is up
+        let mut telemetry = tx.message_sender();
+        let control = MessageControl::with_ttl(Duration::from_millis(20));
+        for i in 0..MESSAGE_COUNT {
+            tx.send((Instant::now(), Bytes::from(format!("video {}", i))))
+                .await?;
+            telemetry
+                .send((
+                    control,
+                    (Instant::now(), Bytes::from(format!("telemetry {}", i))),
+                ))
+                .await?;
+            sleep(Duration::from_millis(2)).await;
+        }
+        drop(telemetry);
+
+        sleep(Duration::from_millis(500)).await;
+        let statistics = tx.last_statistics();
+        assert!(statistics.tx_dropped_data >= u64::from(MESSAGE_COUNT));
+        tx.close().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        let mut rx = SrtSocket::builder()
+            .latency(Duration::from_secs(2))
+            .listen_on(":6254")
+            .await?;
+
+        let mut events = rx.receive_events();
+        for i in 0..MESSAGE_COUNT {
+            // the telemetry in between was dropped
+            if i > 0 {
+                let gap = match events.try_next().await? {
+                    Some(ReceiveEvent::Discontinuity(gap)) => gap,
+                    other => panic!("expected a discontinuity, got {:?}", other),
+                };
+                assert_eq!(gap.seq_numbers.end - gap.seq_numbers.start, 1);
+                assert_eq!(gap.messages, 1);
+            }
+
+            let (info, data) = match events.try_next().await? {
+                Some(ReceiveEvent::Message(info, (_, data))) => (info, data),
+                other => panic!("expected a message, got {:?}", other),
+            };
+            assert_eq!(data, format!("video {}", i));
+            assert_eq!(info.gap.is_some(), i > 0);
+            assert_eq!(info.seq_number, info.last_seq_number);
+        }
+        assert_eq!(events.try_next().await?, None);
+
+        Ok::<_, Error>(())
+    };
+
+    futures::try_join!(sender_fut, receiver_fut)?;
+    relay.abort();
+    Ok(())
+}
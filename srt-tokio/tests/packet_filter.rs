@@ -0,0 +1,122 @@
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::{net::UdpSocket, task::JoinHandle, time::sleep};
+
+use srt_tokio::SrtSocket;
+
+const MESSAGE_COUNT: u32 = 300;
+
+// Forwards packets between a single client and the server, dropping every 7th data packet
+// from the client, so that no row of 5 packets loses more than one
+async fn lossy_relay(local: u16, server: u16) -> Result<JoinHandle<()>, Error> {
+    let front = UdpSocket::bind(("127.0.0.1", local)).await?;
+    let back = UdpSocket::bind("127.0.0.1:0").await?;
+    let server: SocketAddr = ([127, 0, 0, 1], server).into();
+    Ok(tokio::spawn(async move {
+        let mut client = None;
+        let mut front_buf = [0; 2048];
+        let mut back_buf = [0; 2048];
+        loop {
+            tokio::select! {
+                Ok((size, from)) = front.recv_from(&mut front_buf) => {
+                    client = Some(from);
+                    let data_seq_number = (front_buf[0] & 0x80 == 0)
+                        .then(|| u32::from_be_bytes([front_buf[0], front_buf[1], front_buf[2], front_buf[3]]));
+                    // never drop retransmissions, flagged in the second word
+                    let retransmitted = front_buf[4] & 0b0000_0100 != 0;
+                    if !matches!(data_seq_number, Some(n) if n % 7 == 3 && !retransmitted) {
+                        let _ = back.send_to(&front_buf[..size], server).await;
+                    }
+                }
+                Ok((size, _)) = back.recv_from(&mut back_buf) => {
+                    if let Some(client) = client {
+                        let _ = front.send_to(&back_buf[..size], client).await;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[tokio::test]
+async fn fec_recovers_lost_packets() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let relay = lossy_relay(6231, 6230).await?;
+
+    let sender_fut = async {
+        let mut tx = SrtSocket::builder()
+            .latency(Duration::from_millis(500))
+            .packet_filter("fec,cols:5,rows:2,arq:never")
+            .call("127.0.0.1:6231", None)
+            .await?;
+
+        for i in 0..MESSAGE_COUNT {
+            tx.send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(2)).await;
+        }
+        tx.close().await?;
+
+        Ok::<_, Error>(())
+    };
+
+    let receiver_fut = async {
+        // the receiver adopts the configuration of the caller
+        let mut rx = SrtSocket::builder()
+            .latency(Duration::from_millis(500))
+            .set(|options| options.session.statistics_interval = Duration::from_millis(200))
+            .listen_on(":6230")
+            .await?;
+        assert_eq!(
+            rx.settings().packet_filter.as_ref().map(|f| f.to_string()),
+            Some("fec,arq:never,cols:5,rows:2".to_string())
+        );
+        let mut statistics = rx.statistics().clone();
+
+        let received: Vec<_> = rx.map_ok(|(_, data)| data).try_collect().await?;
+        let expected: Vec<_> = (0..MESSAGE_COUNT)
+            .map(|i| Bytes::from(i.to_string()))
+            .collect();
+        assert_eq!(received, expected);
+
+        let statistics = statistics.next().await.unwrap();
+        assert!(statistics.rx_filter_extra > 0);
+        assert!(statistics.rx_filter_supply > 0);
+        assert_eq!(statistics.rx_filter_loss, 0);
+        assert_eq!(statistics.tx_nak, 0);
+
+        Ok::<_, Error>(())
+    };
+
+    futures::try_join!(sender_fut, receiver_fut)?;
+    relay.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn conflicting_packet_filters_rejected() -> Result<(), Error> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(
+        SrtSocket::builder()
+            .packet_filter("fec,cols:10")
+            .listen_on(":6232"),
+    );
+
+    let result = SrtSocket::builder()
+        .packet_filter("fec,cols:5")
+        .call("127.0.0.1:6232", None)
+        .await;
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+
+    listener.abort();
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::{channel::oneshot, prelude::*};
+use srt_tokio::{options::RetryPolicy, ConnectError, HandshakeStage, SrtSocket, SrtSocketBuilder};
+use tokio::{
+    runtime::Runtime,
+    time::{sleep, timeout},
+};
+
+// a listener in a runtime of its own, which goes away without shutting its connection down, like
+// a crashed process, once told to
+fn crashing_listener(
+    local: &'static str,
+) -> (
+    oneshot::Receiver<Bytes>,
+    oneshot::Sender<()>,
+    thread::JoinHandle<()>,
+) {
+    let (received_sender, received) = oneshot::channel();
+    let (crash, crashed) = oneshot::channel::<()>();
+    let thread = thread::spawn(move || {
+        let runtime = Runtime::new().unwrap();
+        let listener = runtime.block_on(async move {
+            let mut listener = SrtSocket::builder().listen_on(local).await.unwrap();
+            let (_, message) = listener.try_next().await.unwrap().unwrap();
+            received_sender.send(message).unwrap();
+            let _ = crashed.await;
+            listener
+        });
+        // stop the connection task first, dropping the socket would shut the connection down
+        drop(runtime);
+        drop(listener);
+    });
+    (received, crash, thread)
+}
+
+fn reconnecting_caller() -> SrtSocketBuilder {
+    SrtSocket::builder()
+        .autoreconnect(true)
+        .set(|options| options.session.peer_idle_timeout = Duration::from_secs(1))
+}
+
+#[tokio::test]
+async fn autoreconnect() {
+    let _ = pretty_env_logger::try_init();
+
+    let (received, crash, listener) = crashing_listener(":6261");
+    let caller = reconnecting_caller()
+        .call("127.0.0.1:6261", None)
+        .await
+        .unwrap();
+
+    // the caller keeps sending until told to stop
+    let (stop, stopped) = oneshot::channel::<()>();
+    let sender = tokio::spawn(async move {
+        let mut caller = caller;
+        let mut stopped = stopped.fuse();
+        loop {
+            caller
+                .send((Instant::now(), Bytes::from("hello")))
+                .await
+                .unwrap();
+            futures::select! {
+                _ = sleep(Duration::from_millis(50)).fuse() => {}
+                _ = stopped => break,
+            }
+        }
+        caller.close_and_finish().await.unwrap();
+    });
+
+    assert_eq!(received.await.unwrap(), "hello");
+
+    // the listener goes away, and a new one takes its place
+    crash.send(()).unwrap();
+    listener.join().unwrap();
+    let mut listener = SrtSocket::builder().listen_on(":6261").await.unwrap();
+
+    let (_, message) = timeout(Duration::from_secs(20), listener.try_next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(message, "hello");
+
+    // closing the caller ends the connection for good
+    stop.send(()).unwrap();
+    sender.await.unwrap();
+    let remaining = listener.try_collect::<Vec<_>>();
+    timeout(Duration::from_secs(5), remaining)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn no_reconnect_after_shutdown() {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut listener, mut caller) = futures::try_join!(
+        SrtSocket::builder().listen_on(":6263"),
+        reconnecting_caller().call("127.0.0.1:6263", None),
+    )
+    .unwrap();
+
+    // the listener shutting the connection down ends it, rather than breaking it
+    listener.close_and_finish().await.unwrap();
+    let remaining = timeout(Duration::from_secs(5), caller.try_next()).await;
+    assert_matches!(remaining, Ok(Ok(None)));
+}
+
+#[tokio::test]
+async fn reconnect_backoff() {
+    let _ = pretty_env_logger::try_init();
+
+    let (received, crash, listener) = crashing_listener(":6264");
+    let mut caller = reconnecting_caller()
+        .retry(RetryPolicy {
+            initial_interval: Duration::from_millis(300),
+            max_interval: Duration::from_secs(10),
+            multiplier: 3.0,
+            max_attempts: Some(2),
+            ..Default::default()
+        })
+        .call("127.0.0.1:6264", None)
+        .await
+        .unwrap();
+
+    caller
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    assert_eq!(received.await.unwrap(), "hello");
+
+    crash.send(()).unwrap();
+    listener.join().unwrap();
+    let crashed = Instant::now();
+
+    // once the peer idle timeout (about 5s) passed, two reconnection attempts 300ms apart, each
+    // giving up after handshake requests 300ms and 900ms apart, rather than retrying every 10s
+    // without end
+    let remaining = timeout(Duration::from_secs(15), caller.try_next()).await;
+    assert_matches!(remaining, Ok(Ok(None)));
+    assert!(crashed.elapsed() >= Duration::from_millis(3_700));
+}
+
+#[tokio::test]
+async fn retry_attempts() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let error = SrtSocket::builder()
+        .retry(RetryPolicy {
+            initial_interval: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_attempts: Some(3),
+            ..Default::default()
+        })
+        .call("127.0.0.1:6262", None)
+        .await
+        .unwrap_err();
+
+    // requests at 0ms, 100ms and 300ms, giving up at 700ms rather than after the 3s timeout
+    assert_matches!(
+        error,
+        ConnectError::Timeout {
+            stage: HandshakeStage::Induction,
+            ..
+        }
+    );
+    assert!(start.elapsed() >= Duration::from_millis(700));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
@@ -1,8 +1,11 @@
 use std::time::{Duration, Instant};
 
+use assert_matches::assert_matches;
 use bytes::Bytes;
 use futures::{join, prelude::*};
-use srt_tokio::SrtSocket;
+use srt_tokio::{
+    options::Connect, ConnectError, RendezvousDiagnostics, RendezvousPhase, SrtSocket,
+};
 use tokio::time::sleep;
 
 #[tokio::test]
@@ -39,3 +42,38 @@ async fn rendezvous() {
         }
     );
 }
+
+#[tokio::test]
+async fn rendezvous_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let error = SrtSocket::builder()
+        .with(Connect {
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .waving_interval(Duration::from_millis(20))
+        .local_port(5002)
+        .rendezvous("127.0.0.1:5003")
+        .await
+        .unwrap_err();
+
+    // rendezvous waits ten times the connect timeout, and nothing ever answered the waving
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_matches!(
+        error,
+        ConnectError::RendezvousTimeout {
+            diagnostics: RendezvousDiagnostics {
+                phase: RendezvousPhase::Waving,
+                peer_seen: false,
+                cookie_contest: None,
+            },
+            ..
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "rendezvous with 127.0.0.1:5003 timed out, reached the waving phase, no packet arrived from the peer"
+    );
+}
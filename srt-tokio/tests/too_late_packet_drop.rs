@@ -0,0 +1,50 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use srt_tokio::SrtSocket;
+
+use futures::prelude::*;
+
+// returns whether the caller and then the listener drop received and sent packets
+async fn exchange(port: u16, caller: bool, listener: bool) -> Result<[(bool, bool); 2]> {
+    let caller = SrtSocket::builder()
+        .too_late_packet_drop(caller)
+        .call(SocketAddr::from(([127, 0, 0, 1], port)), None);
+    let listener = SrtSocket::builder()
+        .too_late_packet_drop(listener)
+        .listen_on(port);
+
+    let drops = |socket: &SrtSocket| {
+        let settings = socket.settings();
+        (
+            settings.recv_too_late_packet_drop,
+            settings.send_too_late_packet_drop,
+        )
+    };
+    let (mut caller, mut listener) = futures::try_join!(caller, listener)?;
+    let result = [drops(&caller), drops(&listener)];
+    futures::try_join!(caller.close(), listener.close())?;
+
+    Ok(result)
+}
+
+#[tokio::test]
+async fn too_late_packet_drop_exchange() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    assert_eq!(
+        exchange(6256, true, true).await?,
+        [(true, true), (true, true)]
+    );
+    // a sender only drops packets if the receiving peer would
+    assert_eq!(
+        exchange(6257, false, true).await?,
+        [(false, false), (true, false)]
+    );
+    assert_eq!(
+        exchange(6258, true, false).await?,
+        [(true, false), (false, false)]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::sleep;
+
+use srt_tokio::SrtSocket;
+
+const MESSAGE_COUNT: usize = 10;
+
+#[tokio::test]
+async fn tsbpd_mode_disabled() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let latency = Duration::from_secs(2);
+
+    // disabling timestamp based packet delivery on the sender disables it on the receiver too
+    let sender = async {
+        let mut tx = SrtSocket::builder()
+            .latency(latency)
+            .tsbpd_mode(false)
+            .call("127.0.0.1:6259", None)
+            .await?;
+        assert!(!tx.settings().recv_tsbpd_mode);
+
+        for i in 0..MESSAGE_COUNT {
+            tx.send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(10)).await;
+        }
+        tx.close().await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let receiver = async {
+        let mut rx = SrtSocket::builder()
+            .latency(latency)
+            .listen_on(":6259")
+            .await?;
+        assert!(!rx.settings().recv_tsbpd_mode);
+
+        for i in 0..MESSAGE_COUNT {
+            let (sent, data) = rx.try_next().await?.unwrap();
+            assert_eq!(data, i.to_string());
+            // delivered as soon as it arrives, long before the latency is up
+            assert!(sent.elapsed() < latency / 2, "{:?}", sent.elapsed());
+        }
+        assert_eq!(rx.try_next().await?, None);
+        Ok::<_, anyhow::Error>(())
+    };
+
+    futures::try_join!(sender, receiver)?;
+    Ok(())
+}
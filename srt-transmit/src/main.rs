@@ -22,7 +22,7 @@ use futures::{
     future,
     prelude::*,
     ready,
-    stream::{self, once, unfold, BoxStream},
+    stream::{self, once, BoxStream},
     try_join,
 };
 use tokio::{
@@ -111,7 +111,8 @@ where
                 options.encryption.key_size = size.try_into()?;
                 key = true;
             }
-            "rendezvous" | "multiplex" | "autoreconnect" => (),
+            "autoreconnect" => options.connect.autoreconnect = true,
+            "rendezvous" | "multiplex" => (),
             unrecog => bail!("Unrecgonized parameter '{}' for srt", unrecog),
         }
     }
@@ -206,6 +207,7 @@ fn parse_socket_options(
     input_local_port: u16,
 ) -> Result<BindOptions, Error> {
     let socket_options = parse_srt_args(input_url.query_pairs())?;
+    let autoreconnect = socket_options.connect.autoreconnect;
 
     let rendezvous_v = parse_rendezvous(input_url);
 
@@ -228,6 +230,11 @@ fn parse_socket_options(
         (_, Some(unex)) => bail!("Unexpected value for rendezvous: {}, expected empty", unex),
     };
 
+    // the socket reconnects by itself, which only callers do
+    if autoreconnect && !matches!(bind_options, BindOptions::Call(_)) {
+        bail!("The autoreconnect option is only supported for caller connections")
+    }
+
     Ok(bind_options)
 }
 
@@ -284,23 +291,7 @@ fn resolve_input<'a>(
                     .boxed())
                 })
                 .boxed(),
-                "srt" => {
-                    if input_url.query_pairs().any(|(k, _)| k == "autoreconnect") {
-                        unfold(
-                            (input_addr, input_url, input_local_port),
-                            move |(input_addr, input_url, input_local_port)| async move {
-                                Some((
-                                    make_srt_input(input_url.clone(), input_addr, input_local_port)
-                                        .await,
-                                    (input_addr, input_url, input_local_port),
-                                ))
-                            },
-                        )
-                        .boxed()
-                    } else {
-                        once(make_srt_input(input_url, input_addr, input_local_port)).boxed()
-                    }
-                }
+                "srt" => once(make_srt_input(input_url, input_addr, input_local_port)).boxed(),
                 "tcp" => {
                     if let Some(input) = input_addr {
                         once(async move {
@@ -415,27 +406,7 @@ fn resolve_output(output_url: DataType) -> Result<SinkStream, Error> {
                     .boxed_sink())
                 })
                 .boxed(),
-                "srt" => {
-                    if output_url.query_pairs().any(|(k, _)| k == "autoreconnect") {
-                        unfold(
-                            (output_addr, output_url, output_local_port),
-                            |(output_addr, output_url, output_local_port)| async move {
-                                Some((
-                                    make_srt_ouput(
-                                        output_addr,
-                                        output_url.clone(),
-                                        output_local_port,
-                                    )
-                                    .await,
-                                    (output_addr, output_url, output_local_port),
-                                ))
-                            },
-                        )
-                        .boxed()
-                    } else {
-                        once(make_srt_ouput(output_addr, output_url, output_local_port)).boxed()
-                    }
-                }
+                "srt" => once(make_srt_ouput(output_addr, output_url, output_local_port)).boxed(),
                 "tcp" => {
                     if let Some(output) = output_addr {
                         once(async move {